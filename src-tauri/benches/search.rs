@@ -0,0 +1,121 @@
+// src-tauri/benches/search.rs
+//! Stock search over 100k batches, on each store and through the fuzzy
+//! search index: `cargo bench --bench search`. With `BENCH_MONGODB_URI` set
+//! it also times MongoDB, using a scratch `caton_bench` database that is
+//! dropped afterwards.
+use caton_lib::commands::Medicine;
+use caton_lib::money::Money;
+use caton_lib::repository::memory::MemoryStore;
+use caton_lib::repository::{MedicineQuery, MedicineRepository, MedicineSort, Page};
+use caton_lib::search::MedicineIndex;
+use caton_lib::sqlite::SqliteStore;
+use chrono::{Days, NaiveDate};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mongodb::Collection;
+use tokio::runtime::Runtime;
+
+const BATCHES: usize = 100_000;
+const NAMES: &[&str] =
+    &["Paracetamol", "Amoxicillin", "Cetirizine", "Azithromycin", "Pantoprazole", "Metformin", "Dolo", "Montelukast"];
+
+fn batches() -> Vec<Medicine> {
+    let first_expiry = NaiveDate::from_ymd_opt(2027, 1, 1).unwrap();
+    (0..BATCHES)
+        .map(|i| Medicine {
+            id: None,
+            user_id: "bench".to_string(),
+            name: format!("{} {}", NAMES[i % NAMES.len()], 100 + i % 900),
+            batch_number: format!("B{}", i),
+            expiry_date: first_expiry + Days::new((i % 1000) as u64),
+            quantity: (i % 500) as u32,
+            purchase_price: Money::from_paise(800 + (i % 400) as i64),
+            selling_price: Money::from_paise(1000 + (i % 500) as i64),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        })
+        .collect()
+}
+
+fn searches() -> Vec<(&'static str, MedicineQuery)> {
+    let page = Page { skip: 0, limit: Some(50) };
+    let text = |text: &str| Some(text.to_string());
+    vec![
+        ("prefix", MedicineQuery { text: text("para"), page, ..Default::default() }),
+        ("contains", MedicineQuery { text: text("mycin 1"), page, ..Default::default() }),
+        ("by expiry", MedicineQuery { sort: MedicineSort::Expiry, page, ..Default::default() }),
+        ("page 1000", MedicineQuery { page: Page { skip: 49_950, limit: Some(50) }, ..Default::default() }),
+    ]
+}
+
+fn bench_store(c: &mut Criterion, runtime: &Runtime, store: &str, repo: &dyn MedicineRepository) {
+    let mut group = c.benchmark_group(format!("stock search/{}", store));
+    for (search, query) in searches() {
+        group.bench_with_input(BenchmarkId::from_parameter(search), &query, |b, query| {
+            b.iter(|| {
+                let found = runtime.block_on(repo.list_medicines("bench", query)).unwrap();
+                assert_eq!(found.len(), 50);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn stock_search(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let medicines = batches();
+
+    let memory = MemoryStore::default();
+    memory.medicines.lock().unwrap().extend(medicines.iter().cloned());
+    bench_store(c, &runtime, "memory", &memory);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bench.db");
+    let sqlite = runtime.block_on(async {
+        let sqlite = SqliteStore::open(path.to_str().unwrap()).await.unwrap();
+        for medicine in &medicines {
+            sqlite.insert_medicine(medicine.clone()).await.unwrap();
+        }
+        sqlite
+    });
+    bench_store(c, &runtime, "sqlite", &sqlite);
+
+    let Ok(uri) = std::env::var("BENCH_MONGODB_URI") else { return };
+    let db = runtime.block_on(async {
+        let db = mongodb::Client::with_uri_str(&uri).await.unwrap().database("caton_bench");
+        db.drop(None).await.unwrap();
+        caton_lib::migration::run_pending(&db, false).await.unwrap();
+        db.collection::<Medicine>("medicines").insert_many(&medicines, None).await.unwrap();
+        db
+    });
+    let collection: Collection<Medicine> = db.collection("medicines");
+    bench_store(c, &runtime, "mongodb", &collection);
+    runtime.block_on(db.drop(None)).unwrap();
+}
+
+fn search_index(c: &mut Criterion) {
+    let medicines = batches();
+    c.bench_function("search index/build", |b| b.iter(|| MedicineIndex::build(&medicines)));
+
+    let index = MedicineIndex::build(&medicines);
+    let mut group = c.benchmark_group("search index");
+    for text in ["paracetmol", "amoxcilin 1"] {
+        group.bench_with_input(BenchmarkId::from_parameter(text), text, |b, text| {
+            b.iter(|| assert!(!index.search(text).is_empty()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = stock_search, search_index
+}
+criterion_main!(benches);
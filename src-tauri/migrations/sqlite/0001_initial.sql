@@ -0,0 +1,51 @@
+-- Local storage for offline pharmacies. IDs are ObjectId hex strings so
+-- records keep the same IDs as on the MongoDB backend.
+
+CREATE TABLE medicines (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    batch_number TEXT NOT NULL,
+    expiry_date TEXT NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity >= 0),
+    purchase_price REAL NOT NULL,
+    selling_price REAL NOT NULL,
+    wholesaler_name TEXT NOT NULL,
+    purchase_date TEXT NOT NULL,
+    hsn_code TEXT,
+    gst_rate REAL,
+    tax_inclusive INTEGER NOT NULL DEFAULT 1
+);
+CREATE INDEX medicines_user_name ON medicines (user_id, name);
+
+CREATE TABLE appointments (
+    id TEXT PRIMARY KEY NOT NULL,
+    hospital_id TEXT NOT NULL,
+    date_created TEXT NOT NULL,
+    follow_up_date TEXT,
+    has_clinical INTEGER NOT NULL DEFAULT 0,
+    data TEXT NOT NULL
+);
+CREATE INDEX appointments_hospital_created ON appointments (hospital_id, date_created);
+
+CREATE TABLE users (
+    id TEXT PRIMARY KEY NOT NULL,
+    username TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL UNIQUE,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE invoices (
+    id TEXT PRIMARY KEY NOT NULL,
+    hospital_id TEXT NOT NULL,
+    invoice_number TEXT NOT NULL,
+    date_created TEXT NOT NULL,
+    data TEXT NOT NULL,
+    UNIQUE (hospital_id, invoice_number)
+);
+CREATE INDEX invoices_hospital_created ON invoices (hospital_id, date_created);
+
+CREATE TABLE counters (
+    id TEXT PRIMARY KEY NOT NULL,
+    seq INTEGER NOT NULL
+);
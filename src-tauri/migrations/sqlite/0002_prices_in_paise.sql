@@ -0,0 +1,12 @@
+-- Prices are kept in whole paise, as on the MongoDB backend. The columns
+-- keep their names; only the unit changes.
+
+ALTER TABLE medicines ADD COLUMN purchase_paise INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE medicines ADD COLUMN selling_paise INTEGER NOT NULL DEFAULT 0;
+UPDATE medicines
+SET purchase_paise = CAST(ROUND(purchase_price * 100) AS INTEGER),
+    selling_paise = CAST(ROUND(selling_price * 100) AS INTEGER);
+ALTER TABLE medicines DROP COLUMN purchase_price;
+ALTER TABLE medicines DROP COLUMN selling_price;
+ALTER TABLE medicines RENAME COLUMN purchase_paise TO purchase_price;
+ALTER TABLE medicines RENAME COLUMN selling_paise TO selling_price;
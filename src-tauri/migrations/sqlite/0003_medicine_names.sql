@@ -0,0 +1,7 @@
+-- Other names a medicine is searched by, and the barcode on its pack.
+-- brand_names holds a JSON array of strings.
+
+ALTER TABLE medicines ADD COLUMN generic_name TEXT;
+ALTER TABLE medicines ADD COLUMN brand_names TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE medicines ADD COLUMN composition TEXT;
+ALTER TABLE medicines ADD COLUMN barcode TEXT;
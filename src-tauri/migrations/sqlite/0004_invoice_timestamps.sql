@@ -0,0 +1,6 @@
+-- Invoice times in the same fixed-width UTC form as appointments
+-- (2026-10-19T05:00:00.000Z), so date ranges compare correctly as text.
+-- The copy inside `data` is read either way and is left as it is.
+
+UPDATE invoices SET date_created = strftime('%Y-%m-%dT%H:%M:%fZ', date_created)
+WHERE strftime('%Y-%m-%dT%H:%M:%fZ', date_created) IS NOT NULL;
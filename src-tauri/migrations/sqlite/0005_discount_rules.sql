@@ -0,0 +1,10 @@
+-- Automatic discounts, stored as JSON like appointments and invoices.
+
+CREATE TABLE discount_rules (
+    id TEXT PRIMARY KEY NOT NULL,
+    hospital_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    active INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX discount_rules_hospital_name ON discount_rules (hospital_id, name);
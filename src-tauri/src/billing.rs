@@ -0,0 +1,972 @@
+// src-tauri/src/billing.rs
+use crate::commands::{medicines_collection, Medicine, MedicineDetail};
+use crate::model::User;
+use crate::credit::{post_to_account, LedgerKind, Posting};
+use crate::dates;
+use crate::db::DbState;
+use crate::discount::{authorize_below_cost, price_bill, Discount};
+use crate::draft::{drafts_collection, held_by_others};
+use crate::money::Money;
+use crate::payment::{settle_payments, Payment, PaymentMode, Tender};
+use crate::repository::InvoiceRepository;
+use crate::shift::touch_open_shift;
+use crate::tax::{
+    seller_state, state_code_from_gstin, supply_type, SupplyType, TaxSummaryRow, TaxedLine,
+};
+use crate::user::find_hospital;
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use mongodb::bson;
+use futures::future::BoxFuture;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::{Client, ClientSession, Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{command, State};
+
+pub(crate) const DEFAULT_INVOICE_PREFIX: &str = "INV";
+const MAX_TRANSACTION_ATTEMPTS: usize = 5;
+
+/// Indian financial year containing `date`, e.g. "2026-27" for any date
+/// from 1 April 2026 to 31 March 2027.
+pub fn financial_year(date: NaiveDate) -> String {
+    let start = if date.month() >= 4 { date.year() } else { date.year() - 1 };
+    format!("{}-{:02}", start, (start + 1) % 100)
+}
+
+pub fn format_document_number(prefix: &str, financial_year: &str, sequence: i64) -> String {
+    format!("{}/{}/{:06}", prefix, financial_year, sequence)
+}
+
+/// Why a transaction body gave up. Rejections are business rules and are
+/// returned as-is; database errors may be retried.
+pub(crate) enum TxnError {
+    Rejected(String),
+    Db(mongodb::error::Error),
+}
+
+impl From<mongodb::error::Error> for TxnError {
+    fn from(error: mongodb::error::Error) -> Self {
+        TxnError::Db(error)
+    }
+}
+
+/// Runs `body` inside a MongoDB transaction, retrying it when the server
+/// reports a transient conflict (e.g. two counters billing at once).
+/// Transactions need a replica set; Atlas clusters always are one.
+pub(crate) async fn run_transaction<T, F>(client: &Client, mut body: F) -> Result<T, String>
+where
+    F: for<'s> FnMut(&'s mut ClientSession) -> BoxFuture<'s, Result<T, TxnError>>,
+{
+    let mut session = client.start_session(None).await.map_err(|e| e.to_string())?;
+
+    for _ in 0..MAX_TRANSACTION_ATTEMPTS {
+        session.start_transaction(None).await.map_err(|e| e.to_string())?;
+
+        let value = match body(&mut session).await {
+            Ok(value) => value,
+            Err(TxnError::Rejected(message)) => {
+                let _ = session.abort_transaction().await;
+                return Err(message);
+            }
+            Err(TxnError::Db(e)) => {
+                let _ = session.abort_transaction().await;
+                if e.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+                    continue;
+                }
+                return Err(format!("Database error: {}", e));
+            }
+        };
+
+        let mut commit = session.commit_transaction().await;
+        for _ in 0..MAX_TRANSACTION_ATTEMPTS {
+            match &commit {
+                Err(e) if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => {
+                    commit = session.commit_transaction().await;
+                }
+                _ => break,
+            }
+        }
+        match commit {
+            Ok(()) => return Ok(value),
+            Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) => continue,
+            Err(e) => return Err(format!("Database error: {}", e)),
+        }
+    }
+
+    Err("The database is busy, please try again.".to_string())
+}
+
+/// Takes the next number in a hospital's series for the current financial
+/// year. Runs inside the caller's transaction, so a number is only used up
+/// if the document carrying it is saved too, which keeps the series gap-free.
+/// The counter `_id` is derived from the series, so there is exactly one
+/// counter per series and year no matter how many tills are billing.
+pub(crate) async fn next_document_number(
+    db: &Database,
+    session: &mut ClientSession,
+    hospital_id: &str,
+    series: &str,
+    prefix: &str,
+    date: NaiveDate,
+) -> Result<String, TxnError> {
+    let year = financial_year(date);
+    let counter_id = format!("{}:{}:{}", hospital_id, series, year);
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+
+    let counter = db
+        .collection::<Document>("counters")
+        .find_one_and_update_with_session(
+            doc! { "_id": counter_id },
+            doc! { "$inc": { "seq": 1_i64 } },
+            options,
+            session,
+        )
+        .await?
+        .ok_or_else(|| TxnError::Rejected("Failed to allocate a document number.".to_string()))?;
+    let sequence = counter
+        .get_i64("seq")
+        .map_err(|e| TxnError::Rejected(e.to_string()))?;
+
+    Ok(format_document_number(prefix, &year, sequence))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub quantity: u32,
+    pub unit_price: Money,
+    #[serde(default)]
+    pub discount: Money, // line discount plus its share of the bill discount
+    pub taxable_value: Money,
+    pub cgst: Money,
+    pub sgst: Money,
+    pub igst: Money,
+    pub total: Money,
+    #[serde(default)]
+    pub returned_quantity: u32,
+}
+
+impl InvoiceLine {
+    fn from_taxed(medicine: &Medicine, line: TaxedLine) -> Self {
+        InvoiceLine {
+            medicine_id: medicine.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: line.description,
+            batch_number: medicine.batch_number.clone(),
+            hsn_code: line.hsn_code,
+            gst_rate: line.gst_rate,
+            quantity: line.quantity,
+            unit_price: Money::from_rupees(line.unit_price),
+            discount: Money::from_rupees(line.discount),
+            taxable_value: Money::from_rupees(line.taxable_value),
+            cgst: Money::from_rupees(line.cgst),
+            sgst: Money::from_rupees(line.sgst),
+            igst: Money::from_rupees(line.igst),
+            total: Money::from_rupees(line.total),
+            returned_quantity: 0,
+        }
+    }
+}
+
+/// A row of the HSN / rate summary as kept on the invoice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceTaxRow {
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub taxable_value: Money,
+    pub cgst: Money,
+    pub sgst: Money,
+    pub igst: Money,
+}
+
+impl From<TaxSummaryRow> for InvoiceTaxRow {
+    fn from(row: TaxSummaryRow) -> Self {
+        InvoiceTaxRow {
+            hsn_code: row.hsn_code,
+            gst_rate: row.gst_rate,
+            taxable_value: Money::from_rupees(row.taxable_value),
+            cgst: Money::from_rupees(row.cgst),
+            sgst: Money::from_rupees(row.sgst),
+            igst: Money::from_rupees(row.igst),
+        }
+    }
+}
+
+impl From<&InvoiceTaxRow> for TaxSummaryRow {
+    fn from(row: &InvoiceTaxRow) -> Self {
+        TaxSummaryRow {
+            hsn_code: row.hsn_code.clone(),
+            gst_rate: row.gst_rate,
+            taxable_value: row.taxable_value.rupees(),
+            cgst: row.cgst.rupees(),
+            sgst: row.sgst.rupees(),
+            igst: row.igst.rupees(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    #[default]
+    Issued,
+    PartiallyReturned,
+    Returned,
+    Voided,
+}
+
+/// The hospital's letterhead as it stood when the invoice was issued, so a
+/// reprint shows the same details after the profile is edited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SellerDetails {
+    pub name: String,
+    pub address: String,
+    pub mobile: String,
+    pub email: String,
+    pub gstin: Option<String>,
+    pub registration_number: Option<String>,
+    pub letterhead: Option<String>,
+    pub logo_path: Option<String>,
+}
+
+impl From<&User> for SellerDetails {
+    fn from(hospital: &User) -> Self {
+        SellerDetails {
+            name: hospital.hospital.clone(),
+            address: hospital.address.clone(),
+            mobile: hospital.mobile.clone(),
+            email: hospital.email.clone(),
+            gstin: hospital.gstin.clone(),
+            registration_number: hospital.registration_number.clone(),
+            letterhead: hospital.letterhead.clone(),
+            logo_path: hospital.logo_path.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invoice {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub invoice_number: String,
+    pub financial_year: String,
+    pub customer_name: String,
+    pub customer_mobile: Option<String>,
+    pub appointment_id: Option<String>,
+    pub place_of_supply: Option<String>,
+    pub supply_type: SupplyType,
+    pub lines: Vec<InvoiceLine>,
+    pub tax_summary: Vec<InvoiceTaxRow>,
+    #[serde(default)]
+    pub customer_category: Option<String>,
+    #[serde(default)]
+    pub discount_total: Money,
+    #[serde(default)]
+    pub applied_rules: Vec<String>, // automatic discount rules, by name
+    #[serde(default)]
+    pub price_override: bool, // admin allowed a sale below purchase price
+    pub taxable_total: Money,
+    pub cgst_total: Money,
+    pub sgst_total: Money,
+    pub igst_total: Money,
+    pub round_off: Money,
+    pub grand_total: Money,
+    #[serde(default)]
+    pub payments: Vec<Payment>,
+    #[serde(default)]
+    pub change_due: Money, // cash handed back
+    #[serde(default)]
+    pub credit_customer_id: Option<String>,
+    #[serde(default)]
+    pub over_credit_limit: bool, // sold past the limit of a customer set to warn
+    #[serde(default)]
+    pub shift_id: Option<String>,
+    #[serde(default)]
+    pub seller: Option<SellerDetails>, // missing on invoices issued before letterheads were stored
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+    #[serde(default)]
+    pub status: InvoiceStatus,
+}
+
+/// Builds invoices for tests: a walk-in bill within the state, issued at
+/// 10:30 IST on 19 October 2026 and paid in cash unless told otherwise.
+/// Line prices include GST and are taxed, totalled and summarised by HSN
+/// the way billing does it.
+#[cfg(test)]
+pub(crate) struct TestInvoice(Invoice);
+
+#[cfg(test)]
+impl TestInvoice {
+    pub fn new(invoice_number: &str) -> Self {
+        TestInvoice(Invoice {
+            id: None,
+            hospital_id: "h1".to_string(),
+            invoice_number: invoice_number.to_string(),
+            financial_year: "2026-27".to_string(),
+            customer_name: "Walk-in".to_string(),
+            customer_mobile: None,
+            appointment_id: None,
+            place_of_supply: None,
+            supply_type: SupplyType::IntraState,
+            lines: Vec::new(),
+            tax_summary: Vec::new(),
+            customer_category: None,
+            discount_total: Money::default(),
+            applied_rules: Vec::new(),
+            price_override: false,
+            taxable_total: Money::default(),
+            cgst_total: Money::default(),
+            sgst_total: Money::default(),
+            igst_total: Money::default(),
+            round_off: Money::default(),
+            grand_total: Money::default(),
+            payments: Vec::new(),
+            change_due: Money::default(),
+            credit_customer_id: None,
+            over_credit_limit: false,
+            shift_id: None,
+            seller: None,
+            date_created: bson::DateTime::from_millis(1_792_386_000_000),
+            status: InvoiceStatus::Issued,
+        })
+    }
+
+    pub fn hospital(mut self, hospital_id: &str) -> Self {
+        self.0.hospital_id = hospital_id.to_string();
+        self
+    }
+
+    pub fn customer(mut self, name: &str, mobile: Option<&str>) -> Self {
+        self.0.customer_name = name.to_string();
+        self.0.customer_mobile = mobile.map(str::to_string);
+        self
+    }
+
+    /// Sold to another state: IGST instead of CGST and SGST.
+    pub fn inter_state(mut self, place_of_supply: &str) -> Self {
+        self.0.supply_type = SupplyType::InterState;
+        self.0.place_of_supply = Some(place_of_supply.to_string());
+        self
+    }
+
+    /// `quantity` at `price` rupees each, GST included.
+    pub fn line(mut self, medicine_id: &str, name: &str, hsn: &str, gst_rate: f64, quantity: u32, price: f64) -> Self {
+        let unit_price = Money::from_rupees(price);
+        self.0.lines.push(InvoiceLine {
+            medicine_id: medicine_id.to_string(),
+            name: name.to_string(),
+            batch_number: "B1".to_string(),
+            hsn_code: hsn.to_string(),
+            gst_rate,
+            quantity,
+            unit_price,
+            discount: Money::default(),
+            taxable_value: Money::default(),
+            cgst: Money::default(),
+            sgst: Money::default(),
+            igst: Money::default(),
+            total: Money::from_paise(unit_price.paise() * quantity as i64),
+            returned_quantity: 0,
+        });
+        self
+    }
+
+    pub fn pay(mut self, mode: PaymentMode, amount: f64) -> Self {
+        self.0.payments.push(Payment { mode, amount: Money::from_rupees(amount), reference: None });
+        self
+    }
+
+    pub fn payments(mut self, payments: &[Payment]) -> Self {
+        self.0.payments.extend_from_slice(payments);
+        self
+    }
+
+    pub fn shift(mut self, shift_id: &str) -> Self {
+        self.0.shift_id = Some(shift_id.to_string());
+        self
+    }
+
+    pub fn issued_at(mut self, at: chrono::DateTime<Utc>) -> Self {
+        self.0.date_created = dates::from_utc(at);
+        self
+    }
+
+    pub fn build(self) -> Invoice {
+        use crate::tax::{div_round, to_basis_points};
+
+        let mut invoice = self.0;
+        let inter_state = invoice.supply_type == SupplyType::InterState;
+        for line in &mut invoice.lines {
+            let total = line.total.paise();
+            let taxable = div_round(total * 10_000, 10_000 + to_basis_points(line.gst_rate));
+            let tax = total - taxable;
+            // The odd paisa goes to CGST
+            let (cgst, sgst, igst) = if inter_state { (0, 0, tax) } else { (tax - tax / 2, tax / 2, 0) };
+            line.taxable_value = Money::from_paise(taxable);
+            line.cgst = Money::from_paise(cgst);
+            line.sgst = Money::from_paise(sgst);
+            line.igst = Money::from_paise(igst);
+
+            let summary = &mut invoice.tax_summary;
+            match summary.iter_mut().find(|r| r.hsn_code == line.hsn_code && r.gst_rate == line.gst_rate) {
+                Some(row) => {
+                    row.taxable_value = row.taxable_value + line.taxable_value;
+                    (row.cgst, row.sgst, row.igst) = (row.cgst + line.cgst, row.sgst + line.sgst, row.igst + line.igst);
+                }
+                None => summary.push(InvoiceTaxRow {
+                    hsn_code: line.hsn_code.clone(),
+                    gst_rate: line.gst_rate,
+                    taxable_value: line.taxable_value,
+                    cgst: line.cgst,
+                    sgst: line.sgst,
+                    igst: line.igst,
+                }),
+            }
+        }
+        invoice.taxable_total = invoice.lines.iter().map(|l| l.taxable_value).sum();
+        invoice.cgst_total = invoice.lines.iter().map(|l| l.cgst).sum();
+        invoice.sgst_total = invoice.lines.iter().map(|l| l.sgst).sum();
+        invoice.igst_total = invoice.lines.iter().map(|l| l.igst).sum();
+        invoice.grand_total = invoice.lines.iter().map(|l| l.total).sum();
+
+        if invoice.payments.is_empty() {
+            invoice.payments.push(Payment { mode: PaymentMode::Cash, amount: invoice.grand_total, reference: None });
+        }
+        let paid: Money = invoice.payments.iter().map(|p| p.amount).sum();
+        invoice.change_due = Money::from_paise((paid - invoice.grand_total).paise().max(0));
+        invoice
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceLineResponse {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub quantity: u32,
+    pub unit_price: f64,
+    pub discount: f64,
+    pub taxable_value: f64,
+    pub cgst: f64,
+    pub sgst: f64,
+    pub igst: f64,
+    pub total: f64,
+    pub returned_quantity: u32,
+}
+
+impl From<&InvoiceLine> for InvoiceLineResponse {
+    fn from(line: &InvoiceLine) -> Self {
+        InvoiceLineResponse {
+            medicine_id: line.medicine_id.clone(),
+            name: line.name.clone(),
+            batch_number: line.batch_number.clone(),
+            hsn_code: line.hsn_code.clone(),
+            gst_rate: line.gst_rate,
+            quantity: line.quantity,
+            unit_price: line.unit_price.rupees(),
+            discount: line.discount.rupees(),
+            taxable_value: line.taxable_value.rupees(),
+            cgst: line.cgst.rupees(),
+            sgst: line.sgst.rupees(),
+            igst: line.igst.rupees(),
+            total: line.total.rupees(),
+            returned_quantity: line.returned_quantity,
+        }
+    }
+}
+
+/// An invoice as the billing screens see it: amounts in rupees and the
+/// issue time as RFC 3339.
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub invoice_number: String,
+    pub financial_year: String,
+    pub customer_name: String,
+    pub customer_mobile: Option<String>,
+    pub appointment_id: Option<String>,
+    pub place_of_supply: Option<String>,
+    pub supply_type: SupplyType,
+    pub lines: Vec<InvoiceLineResponse>,
+    pub tax_summary: Vec<TaxSummaryRow>,
+    pub customer_category: Option<String>,
+    pub discount_total: f64,
+    pub applied_rules: Vec<String>,
+    pub price_override: bool,
+    pub taxable_total: f64,
+    pub cgst_total: f64,
+    pub sgst_total: f64,
+    pub igst_total: f64,
+    pub round_off: f64,
+    pub grand_total: f64,
+    pub payments: Vec<Tender>,
+    pub change_due: f64,
+    pub credit_customer_id: Option<String>,
+    pub over_credit_limit: bool,
+    pub shift_id: Option<String>,
+    pub seller: Option<SellerDetails>,
+    pub date_created: chrono::DateTime<Utc>,
+    pub status: InvoiceStatus,
+}
+
+impl From<Invoice> for InvoiceResponse {
+    fn from(invoice: Invoice) -> Self {
+        InvoiceResponse {
+            id: invoice.id,
+            hospital_id: invoice.hospital_id,
+            invoice_number: invoice.invoice_number,
+            financial_year: invoice.financial_year,
+            customer_name: invoice.customer_name,
+            customer_mobile: invoice.customer_mobile,
+            appointment_id: invoice.appointment_id,
+            place_of_supply: invoice.place_of_supply,
+            supply_type: invoice.supply_type,
+            lines: invoice.lines.iter().map(InvoiceLineResponse::from).collect(),
+            tax_summary: invoice.tax_summary.iter().map(TaxSummaryRow::from).collect(),
+            customer_category: invoice.customer_category,
+            discount_total: invoice.discount_total.rupees(),
+            applied_rules: invoice.applied_rules,
+            price_override: invoice.price_override,
+            taxable_total: invoice.taxable_total.rupees(),
+            cgst_total: invoice.cgst_total.rupees(),
+            sgst_total: invoice.sgst_total.rupees(),
+            igst_total: invoice.igst_total.rupees(),
+            round_off: invoice.round_off.rupees(),
+            grand_total: invoice.grand_total.rupees(),
+            payments: invoice.payments.iter().map(Tender::from).collect(),
+            change_due: invoice.change_due.rupees(),
+            credit_customer_id: invoice.credit_customer_id,
+            over_credit_limit: invoice.over_credit_limit,
+            shift_id: invoice.shift_id,
+            seller: invoice.seller,
+            date_created: dates::to_utc(invoice.date_created),
+            status: invoice.status,
+        }
+    }
+}
+
+pub(crate) fn invoices_collection(db: &Database) -> Collection<Invoice> {
+    db.collection("invoices")
+}
+
+/// Adds up repeated lines for the same batch so stock is checked once.
+pub(crate) fn merge_items(items: Vec<MedicineDetail>) -> Result<Vec<MedicineDetail>, String> {
+    let mut merged: Vec<MedicineDetail> = Vec::new();
+    for item in items {
+        if item.quantity == 0 {
+            return Err("Quantity must be at least 1.".to_string());
+        }
+        match merged.iter_mut().find(|m| m.id == item.id) {
+            Some(existing) => {
+                if existing.discount.is_some() && item.discount.is_some() {
+                    return Err("Give one discount per item.".to_string());
+                }
+                existing.quantity += item.quantity;
+                existing.discount = existing.discount.or(item.discount);
+            }
+            None => merged.push(item),
+        }
+    }
+    if merged.is_empty() {
+        return Err("Bill has no items.".to_string());
+    }
+    Ok(merged)
+}
+
+/// Takes the billed quantity off each batch, refusing to dip into what
+/// other drafts hold (`held`, by medicine ID) or to go below zero.
+pub(crate) async fn deduct_stock(
+    db: &Database,
+    session: &mut ClientSession,
+    hospital_id: &str,
+    lines: &[InvoiceLine],
+    held: &HashMap<String, u32>,
+) -> Result<(), TxnError> {
+    let medicines = medicines_collection(db);
+    for line in lines {
+        let object_id = ObjectId::parse_str(&line.medicine_id)
+            .map_err(|_| TxnError::Rejected("Invalid medicine ID".to_string()))?;
+        let held = held.get(&line.medicine_id).copied().unwrap_or(0);
+        let result = medicines
+            .update_one_with_session(
+                doc! { "_id": object_id, "user_id": hospital_id, "quantity": { "$gte": line.quantity + held } },
+                doc! { "$inc": { "quantity": -(line.quantity as i64) } },
+                None,
+                session,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            let note = if held > 0 { format!("; {} held for other bills", held) } else { String::new() };
+            return Err(TxnError::Rejected(format!(
+                "Insufficient stock for {} (batch {}){}.",
+                line.name, line.batch_number, note
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Saves a bill: reduces stock on each batch, assigns the next invoice
+/// number and stores the invoice with its tax breakdown, all or nothing.
+/// Discounts that take an item below purchase price need `admin_password`.
+/// Without `payments` the whole bill is taken as paid in cash, or put on
+/// the account of `credit_customer_id` when one is given. Billing a draft
+/// (`draft_id`) may use the stock it holds, and removes the draft.
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_invoice(
+    items: Vec<MedicineDetail>,
+    customer_name: String,
+    customer_mobile: Option<String>,
+    appointment_id: Option<String>,
+    place_of_supply: Option<String>,
+    bill_discount: Option<Discount>,
+    customer_category: Option<String>,
+    admin_password: Option<String>,
+    payments: Option<Vec<Tender>>,
+    credit_customer_id: Option<String>,
+    shift_id: Option<String>,
+    draft_id: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<InvoiceResponse, String> {
+    if customer_name.trim().is_empty() {
+        return Err("Customer name is required.".to_string());
+    }
+    let items = merge_items(items)?;
+    let draft_id = match draft_id.filter(|id| !id.trim().is_empty()) {
+        Some(id) => Some(ObjectId::parse_str(&id).map_err(|_| "Invalid draft ID".to_string())?),
+        None => None,
+    };
+
+    let hospital = find_hospital(&*db_state.users(), &hospital_id).await?;
+
+    let place_of_supply = place_of_supply
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| state_code_from_gstin(&p).unwrap_or(p));
+    let supply = supply_type(seller_state(&hospital).as_deref(), place_of_supply.as_deref());
+    let customer_category = customer_category.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+
+    let priced = price_bill(&db_state, &items, bill_discount, customer_category.as_deref(), supply, &hospital_id).await?;
+    let price_override = authorize_below_cost(&priced.below_cost, &hospital, admin_password.as_deref())?;
+    let (medicines, tax) = (priced.medicines, priced.tax);
+
+    let credit_customer_id = credit_customer_id.filter(|id| !id.trim().is_empty());
+    let payments = payments.unwrap_or_else(|| {
+        let mode = if credit_customer_id.is_some() { PaymentMode::Credit } else { PaymentMode::Cash };
+        vec![Tender { mode, amount: tax.grand_total, reference: None }]
+    });
+    let (payments, change_due) = settle_payments(payments, Money::from_rupees(tax.grand_total))?;
+    let on_credit: i64 = payments
+        .iter()
+        .filter(|p| p.mode == PaymentMode::Credit)
+        .map(|p| p.amount.paise())
+        .sum();
+    if on_credit > 0 && credit_customer_id.is_none() {
+        return Err("Choose the credit customer for a credit sale.".to_string());
+    }
+
+    let today = Local::now().date_naive();
+    let prefix = hospital
+        .invoice_prefix
+        .clone()
+        .unwrap_or_else(|| DEFAULT_INVOICE_PREFIX.to_string());
+    let invoice = Invoice {
+        id: None,
+        hospital_id: hospital_id.clone(),
+        invoice_number: String::new(),
+        financial_year: financial_year(today),
+        customer_name: customer_name.trim().to_string(),
+        customer_mobile: customer_mobile.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        appointment_id,
+        place_of_supply,
+        supply_type: supply,
+        lines: medicines
+            .iter()
+            .zip(tax.lines)
+            .map(|(medicine, line)| InvoiceLine::from_taxed(medicine, line))
+            .collect(),
+        tax_summary: tax.summary.into_iter().map(InvoiceTaxRow::from).collect(),
+        customer_category,
+        discount_total: Money::from_rupees(tax.discount_total),
+        applied_rules: priced.applied_rules,
+        price_override,
+        taxable_total: Money::from_rupees(tax.taxable_total),
+        cgst_total: Money::from_rupees(tax.cgst_total),
+        sgst_total: Money::from_rupees(tax.sgst_total),
+        igst_total: Money::from_rupees(tax.igst_total),
+        round_off: Money::from_rupees(tax.round_off),
+        grand_total: Money::from_rupees(tax.grand_total),
+        payments,
+        change_due,
+        credit_customer_id,
+        over_credit_limit: false,
+        shift_id,
+        seller: Some(SellerDetails::from(&hospital)),
+        date_created: bson::DateTime::now(),
+        status: InvoiceStatus::Issued,
+    };
+    let mongo = match db_state.inner() {
+        DbState::Mongo(mongo) if mongo.offline.is_online() => mongo,
+        DbState::Mongo(mongo) => {
+            // Offline invoices keep their shift; sync redraws its Z-report if it closed meanwhile
+            if draft_id.is_some() || on_credit > 0 {
+                return Err("Drafts and credit sales need the connection to MongoDB; they are not available offline.".to_string());
+            }
+            return mongo.offline.save_invoice(invoice, &prefix, today).await.map(InvoiceResponse::from);
+        }
+        DbState::Sqlite(store) => {
+            if invoice.shift_id.is_some() || draft_id.is_some() || on_credit > 0 {
+                return Err("Not available on the SQLite backend yet: shifts, drafts and credit sales.".to_string());
+            }
+            return store.save_invoice(invoice, &prefix, today).await.map(InvoiceResponse::from);
+        }
+    };
+    run_transaction(&mongo.client, |session| {
+        let db = mongo.db.clone();
+        let prefix = prefix.clone();
+        let mut invoice = invoice.clone();
+        Box::pin(async move {
+            if let Some(shift_id) = &invoice.shift_id {
+                touch_open_shift(&db, session, shift_id, &invoice.hospital_id).await?;
+            }
+            let ids: Vec<String> = invoice.lines.iter().map(|l| l.medicine_id.clone()).collect();
+            let held = held_by_others(&db, session, &invoice.hospital_id, &ids, draft_id).await?;
+            deduct_stock(&db, session, &invoice.hospital_id, &invoice.lines, &held).await?;
+            invoice.invoice_number =
+                next_document_number(&db, session, &invoice.hospital_id, "invoice", &prefix, today).await?;
+            if let (Some(customer_id), true) = (&invoice.credit_customer_id, on_credit > 0) {
+                let posting = Posting {
+                    customer_id,
+                    kind: LedgerKind::Sale,
+                    reference: &invoice.invoice_number,
+                    amount: on_credit,
+                    mode: None,
+                    payment_reference: None,
+                    shift_id: invoice.shift_id.clone(),
+                };
+                let (_, over_limit) = post_to_account(&db, session, &invoice.hospital_id, posting).await?;
+                invoice.over_credit_limit = over_limit;
+            }
+            let result = invoices_collection(&db)
+                .insert_one_with_session(&invoice, None, session)
+                .await?;
+            invoice.id = result.inserted_id.as_object_id();
+            if let Some(draft_id) = draft_id {
+                drafts_collection(&db)
+                    .delete_one_with_session(doc! { "_id": draft_id, "hospital_id": &invoice.hospital_id }, None, session)
+                    .await?;
+            }
+            Ok(invoice)
+        })
+    })
+    .await
+    .map(InvoiceResponse::from)
+}
+
+/// Looks up one of a hospital's invoices by its number.
+pub(crate) async fn find_invoice(
+    repo: &dyn InvoiceRepository,
+    hospital_id: &str,
+    invoice_number: &str,
+) -> Result<Invoice, String> {
+    repo.find_invoice(hospital_id, invoice_number.trim())
+        .await?
+        .ok_or_else(|| "Invoice not found".to_string())
+}
+
+#[command]
+pub async fn get_invoice(
+    invoice_number: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<InvoiceResponse, String> {
+    find_invoice(&*db_state.invoices(), &hospital_id, &invoice_number).await.map(InvoiceResponse::from)
+}
+
+/// Invoices between two dates (inclusive, `YYYY-MM-DD`), newest first.
+#[command]
+pub async fn get_invoices(
+    from_date: String,
+    to_date: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<InvoiceResponse>, String> {
+    let (from, to) = utc_day_range(&from_date, &to_date)?;
+    let invoices = db_state.invoices().list_invoices(&hospital_id, from, to).await?;
+    Ok(invoices.into_iter().map(InvoiceResponse::from).collect())
+}
+
+/// Turns local calendar dates into the bounds to match `date_created`
+/// against: start of `from` up to the start of the day after `to`.
+pub(crate) fn utc_day_range(from_date: &str, to_date: &str) -> Result<(bson::DateTime, bson::DateTime), String> {
+    let parse = |value: &str| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", value))
+    };
+    let (from, to) = (parse(from_date)?, parse(to_date)?);
+    if to < from {
+        return Err("End date must not be before start date.".to_string());
+    }
+
+    let bound = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(Local).earliest())
+            .map(|dt| dates::from_utc(dt.with_timezone(&Utc)))
+            .ok_or_else(|| format!("Invalid date: {}", date))
+    };
+    Ok((bound(from)?, bound(to + chrono::Duration::days(1))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discount::{price_items, DiscountKind, DiscountRule};
+    use crate::money::Money;
+    use crate::repository::memory::MemoryStore;
+    use crate::repository::MedicineRepository;
+    use crate::tax::load_bill_medicines;
+
+    fn medicine(hospital_id: &str, name: &str, purchase_price: f64, selling_price: f64) -> Medicine {
+        Medicine {
+            id: None,
+            user_id: hospital_id.to_string(),
+            name: name.to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity: 50,
+            purchase_price: Money::from_rupees(purchase_price),
+            selling_price: Money::from_rupees(selling_price),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: Some("3004".to_string()),
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        }
+    }
+
+    fn item(id: ObjectId, quantity: u32, discount: Option<Discount>) -> MedicineDetail {
+        MedicineDetail { id: id.to_hex(), quantity, dosage: None, discount }
+    }
+
+    #[tokio::test]
+    async fn bill_medicines_load_in_item_order_within_the_hospital() {
+        let store = MemoryStore::default();
+        let a = store.insert_medicine(medicine("h1", "Amoxicillin", 60.0, 100.0)).await.unwrap();
+        let b = store.insert_medicine(medicine("h1", "Bromhexine", 30.0, 50.0)).await.unwrap();
+        let other = store.insert_medicine(medicine("h2", "Cefixime", 10.0, 20.0)).await.unwrap();
+
+        let loaded = load_bill_medicines(&store, &[item(b, 1, None), item(a, 1, None)], "h1").await.unwrap();
+        let names: Vec<&str> = loaded.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Bromhexine", "Amoxicillin"]);
+
+        let error = load_bill_medicines(&store, &[item(a, 1, None), item(other, 1, None)], "h1").await.unwrap_err();
+        assert_eq!(error, format!("Medicine {} not found", other.to_hex()));
+    }
+
+    #[tokio::test]
+    async fn pricing_applies_rules_and_flags_counter_discounts_below_cost() {
+        let store = MemoryStore::default();
+        let a = store.insert_medicine(medicine("h1", "Amoxicillin", 60.0, 100.0)).await.unwrap();
+        let b = store.insert_medicine(medicine("h1", "Bromhexine", 30.0, 50.0)).await.unwrap();
+        let senior = DiscountRule {
+            id: None,
+            hospital_id: "h1".to_string(),
+            name: "Senior citizens".to_string(),
+            customer_category: Some("senior_citizen".to_string()),
+            min_bill_value: None,
+            medicine_name: None,
+            discount: Discount { kind: DiscountKind::Percent, value: 10.0 },
+            active: true,
+            date_created: bson::DateTime::now(),
+        };
+
+        let items = [item(a, 1, None), item(b, 2, None)];
+        let medicines = load_bill_medicines(&store, &items, "h1").await.unwrap();
+        let priced = price_items(medicines, &items, None, &[senior], Some("senior_citizen"), SupplyType::IntraState);
+        assert_eq!(priced.applied_rules, ["Senior citizens"]);
+        assert_eq!(priced.tax.discount_total, 20.0);
+        assert_eq!(priced.tax.grand_total, 180.0);
+        assert!(priced.below_cost.is_empty());
+
+        let flat = Some(Discount { kind: DiscountKind::Flat, value: 50.0 });
+        let items = [item(a, 1, flat), item(b, 2, None)];
+        let medicines = load_bill_medicines(&store, &items, "h1").await.unwrap();
+        let priced = price_items(medicines, &items, None, &[], None, SupplyType::IntraState);
+        assert_eq!(priced.below_cost, ["Amoxicillin"]);
+    }
+
+    #[test]
+    fn the_financial_year_turns_over_on_1_april() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(financial_year(day(2026, 3, 31)), "2025-26");
+        assert_eq!(financial_year(day(2026, 4, 1)), "2026-27");
+        assert_eq!(financial_year(day(2027, 3, 31)), "2026-27");
+        assert_eq!(financial_year(day(2027, 4, 1)), "2027-28");
+        assert_eq!(financial_year(day(2099, 4, 1)), "2099-00");
+
+        assert_eq!(format_document_number("INV", &financial_year(day(2027, 3, 31)), 42), "INV/2026-27/000042");
+        assert_eq!(format_document_number("INV", &financial_year(day(2027, 4, 1)), 1), "INV/2027-28/000001");
+        assert_eq!(format_document_number("CN", "2026-27", 1_234_567), "CN/2026-27/1234567");
+    }
+
+    /// Needs a replica set in `TEST_MONGODB_URI`:
+    /// `cargo test concurrent_numbering -- --ignored`. Uses a scratch
+    /// `caton_test` database that is dropped afterwards.
+    #[tokio::test]
+    #[ignore]
+    async fn concurrent_numbering_never_skips_or_repeats() {
+        let uri = std::env::var("TEST_MONGODB_URI").expect("TEST_MONGODB_URI");
+        let client = Client::with_uri_str(&uri).await.unwrap();
+        let db = client.database("caton_test");
+        db.drop(None).await.unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+
+        let bills = (0..24).map(|i| {
+            let (client, db) = (client.clone(), db.clone());
+            tokio::spawn(async move {
+                run_transaction(&client, |session| {
+                    let db = db.clone();
+                    Box::pin(async move {
+                        let number = next_document_number(&db, session, "h1", "invoice", "INV", date).await?;
+                        // A bill given up after taking its number hands the number back
+                        if i % 4 == 3 {
+                            return Err(TxnError::Rejected("Out of stock".to_string()));
+                        }
+                        Ok(number)
+                    })
+                })
+                .await
+            })
+        });
+        let mut numbers: Vec<String> = futures::future::join_all(bills)
+            .await
+            .into_iter()
+            .filter_map(|joined| joined.unwrap().ok()) // some may give up as busy
+            .collect();
+        db.drop(None).await.unwrap();
+
+        numbers.sort();
+        assert!(!numbers.is_empty());
+        let expected: Vec<String> =
+            (1..=numbers.len() as i64).map(|n| format_document_number("INV", "2026-27", n)).collect();
+        assert_eq!(numbers, expected);
+    }
+}
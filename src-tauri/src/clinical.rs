@@ -0,0 +1,322 @@
+// src-tauri/src/clinical.rs
+use crate::dates;
+use crate::db::DbState;
+use crate::error::AppError;
+use crate::repository::AppointmentRepository;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+/// Optional measurements taken during a visit. BMI is derived from weight
+/// and height when both are present and is stored alongside for trends.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Vitals {
+    pub bp_systolic: Option<u32>,  // mmHg
+    pub bp_diastolic: Option<u32>, // mmHg
+    pub pulse: Option<u32>,        // beats per minute
+    pub temperature_c: Option<f64>,
+    pub spo2: Option<u32>, // percent
+    pub weight_kg: Option<f64>,
+    pub height_cm: Option<f64>,
+    pub blood_sugar_mg_dl: Option<f64>,
+    #[serde(default)]
+    pub bmi: Option<f64>,
+}
+
+impl Vitals {
+    pub fn compute_bmi(&self) -> Option<f64> {
+        match (self.weight_kg, self.height_cm) {
+            (Some(weight), Some(height)) if height > 0.0 => {
+                let metres = height / 100.0;
+                Some((weight / (metres * metres) * 10.0).round() / 10.0)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rejects values outside what a living patient could plausibly show,
+    /// which in practice catches typos like 1200 for 120.
+    pub fn validate(&self) -> Result<(), String> {
+        fn check<T: PartialOrd + Copy + std::fmt::Display>(
+            errors: &mut Vec<String>,
+            label: &str,
+            value: Option<T>,
+            min: T,
+            max: T,
+        ) {
+            if let Some(value) = value {
+                if value < min || value > max {
+                    errors.push(format!("{} must be between {} and {} (got {})", label, min, max, value));
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        check(&mut errors, "Systolic BP", self.bp_systolic, 50, 260);
+        check(&mut errors, "Diastolic BP", self.bp_diastolic, 30, 160);
+        check(&mut errors, "Pulse", self.pulse, 20, 250);
+        check(&mut errors, "Temperature", self.temperature_c, 30.0, 45.0);
+        check(&mut errors, "SpO2", self.spo2, 50, 100);
+        check(&mut errors, "Weight", self.weight_kg, 0.5, 350.0);
+        check(&mut errors, "Height", self.height_cm, 30.0, 250.0);
+        check(&mut errors, "Blood sugar", self.blood_sugar_mg_dl, 20.0, 800.0);
+
+        match (self.bp_systolic, self.bp_diastolic) {
+            (Some(systolic), Some(diastolic)) if systolic <= diastolic => {
+                errors.push("Systolic BP must be higher than diastolic BP".to_string())
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                errors.push("Blood pressure needs both systolic and diastolic values".to_string())
+            }
+            _ => {}
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Validates and fills in the derived BMI, ready to be stored.
+    pub fn prepare(mut self) -> Result<Self, String> {
+        self.validate()?;
+        self.bmi = self.compute_bmi();
+        Ok(self)
+    }
+}
+
+/// SOAP-style consultation notes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClinicalNotes {
+    pub subjective: Option<String>,
+    pub objective: Option<String>,
+    pub assessment: Option<String>,
+    pub plan: Option<String>,
+}
+
+impl ClinicalNotes {
+    pub fn is_empty(&self) -> bool {
+        [&self.subjective, &self.objective, &self.assessment, &self.plan]
+            .iter()
+            .all(|field| field.as_deref().is_none_or(|s| s.trim().is_empty()))
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct VitalsTrendPoint {
+    pub appointment_id: String,
+    pub date_created: String,
+    pub vitals: Vitals,
+}
+
+/// Records the vitals and notes taken during a visit.
+pub async fn record_clinical(
+    appointments: &dyn AppointmentRepository,
+    appointment_id: &str,
+    hospital_id: &str,
+    vitals: Option<Vitals>,
+    notes: Option<ClinicalNotes>,
+) -> Result<(), AppError> {
+    let id = ObjectId::parse_str(appointment_id)
+        .map_err(|_| AppError::invalid("appointment_id", "Invalid appointment ID"))?;
+    let vitals = vitals.map(Vitals::prepare).transpose().map_err(|e| AppError::invalid("vitals", &e))?;
+    let notes = notes.filter(|n| !n.is_empty());
+    if vitals.is_none() && notes.is_none() {
+        return Err(AppError::validation("No fields to update."));
+    }
+
+    if !appointments.update_clinical(id, hospital_id, vitals.as_ref(), notes.as_ref()).await? {
+        return Err(AppError::not_found("Appointment not found"));
+    }
+    Ok(())
+}
+
+/// Vitals from a patient's most recent visits, oldest first so they can be
+/// charted directly.
+pub async fn vitals_trend(
+    appointments: &dyn AppointmentRepository,
+    mobile: &str,
+    limit: usize,
+    hospital_id: &str,
+) -> Result<Vec<VitalsTrendPoint>, AppError> {
+    let history = appointments.vitals_history(hospital_id, mobile.trim(), limit).await?;
+    Ok(history
+        .into_iter()
+        .rev()
+        .filter_map(|appointment| {
+            Some(VitalsTrendPoint {
+                appointment_id: appointment.id.to_hex(),
+                date_created: dates::to_utc(appointment.date_created).to_rfc3339(),
+                vitals: appointment.vitals?,
+            })
+        })
+        .collect())
+}
+
+#[command]
+pub async fn update_visit_clinical(
+    appointment_id: String,
+    vitals: Option<Vitals>,
+    notes: Option<ClinicalNotes>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
+    record_clinical(&*db_state.appointments(), &appointment_id, &hospital_id, vitals, notes).await?;
+    Ok("Visit details updated successfully.".to_string())
+}
+
+/// Vitals from a patient's last `limit` visits (default 10).
+#[command]
+pub async fn get_vitals_trend(
+    mobile: String,
+    limit: Option<i64>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<VitalsTrendPoint>, AppError> {
+    let limit = match limit {
+        None => 10,
+        Some(limit @ 1..=100) => limit as usize,
+        Some(_) => return Err(AppError::invalid("limit", "Limit must be between 1 and 100.")),
+    };
+    vitals_trend(&*db_state.appointments(), &mobile, limit, &hospital_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Appointment;
+    use crate::repository::memory::MemoryStore;
+    use crate::repository::AppointmentQuery;
+    use crate::sqlite::SqliteStore;
+    use chrono::{Duration, NaiveDate, Utc};
+
+    fn vitals() -> Vitals {
+        Vitals {
+            bp_systolic: Some(120),
+            bp_diastolic: Some(80),
+            pulse: Some(72),
+            temperature_c: Some(36.8),
+            spo2: Some(98),
+            weight_kg: Some(70.0),
+            height_cm: Some(175.0),
+            blood_sugar_mg_dl: Some(110.0),
+            bmi: None,
+        }
+    }
+
+    #[test]
+    fn vitals_outside_living_ranges_are_rejected() {
+        assert!(vitals().validate().is_ok());
+        assert!(Vitals::default().validate().is_ok());
+
+        let typo = Vitals { bp_systolic: Some(1200), ..vitals() };
+        assert!(typo.validate().unwrap_err().starts_with("Systolic BP must be between 50 and 260"));
+        let edges = Vitals { pulse: Some(20), spo2: Some(100), temperature_c: Some(45.0), ..vitals() };
+        assert!(edges.validate().is_ok());
+        let cold = Vitals { temperature_c: Some(29.9), spo2: Some(49), ..vitals() };
+        assert_eq!(cold.validate().unwrap_err().split("; ").count(), 2);
+
+        let inverted = Vitals { bp_systolic: Some(80), bp_diastolic: Some(80), ..vitals() };
+        assert_eq!(inverted.validate().unwrap_err(), "Systolic BP must be higher than diastolic BP");
+        let half = Vitals { bp_diastolic: None, ..vitals() };
+        assert_eq!(half.validate().unwrap_err(), "Blood pressure needs both systolic and diastolic values");
+    }
+
+    #[test]
+    fn bmi_is_derived_from_weight_and_height() {
+        assert_eq!(vitals().compute_bmi(), Some(22.9)); // 70 / 1.75^2 = 22.857
+        assert_eq!(Vitals { height_cm: None, ..vitals() }.compute_bmi(), None);
+        assert_eq!(Vitals { height_cm: Some(0.0), ..vitals() }.compute_bmi(), None);
+        assert_eq!(vitals().prepare().unwrap().bmi, Some(22.9));
+        assert!(Vitals { weight_kg: Some(400.0), ..vitals() }.prepare().is_err());
+    }
+
+    fn visit(mobile: &str, days_ago: i64) -> Appointment {
+        Appointment {
+            id: ObjectId::new(),
+            patient_name: "Meera Iyer".to_string(),
+            mobile: mobile.to_string(),
+            disease: "Fever".to_string(),
+            precautions: String::new(),
+            medicines: Vec::new(),
+            hospital_id: "h1".to_string(),
+            date_created: dates::from_utc(Utc::now() - Duration::days(days_ago)),
+            email: None,
+            follow_up_date: None,
+            vitals: None,
+            notes: None,
+            doctor_name: None,
+        }
+    }
+
+    async fn clinical_records_are_kept(store: &dyn AppointmentRepository) {
+        let visits = [visit("9123456780", 3), visit("9123456780", 2), visit("9123456780", 1), visit("9000000000", 1)];
+        for visit in &visits {
+            store.insert_appointment(visit.clone()).await.unwrap();
+        }
+        let id = |i: usize| visits[i].id.to_hex();
+
+        for (i, weight) in [(0, 72.0), (2, 70.0), (3, 90.0)] {
+            let vitals = Vitals { weight_kg: Some(weight), ..vitals() };
+            record_clinical(store, &id(i), "h1", Some(vitals), None).await.unwrap();
+        }
+        let notes = ClinicalNotes { assessment: Some("Viral fever".to_string()), ..Default::default() };
+        record_clinical(store, &id(1), "h1", None, Some(notes)).await.unwrap();
+
+        let empty = record_clinical(store, &id(1), "h1", None, Some(ClinicalNotes::default())).await;
+        assert!(matches!(empty, Err(AppError::Validation { .. })));
+        let bad = record_clinical(store, &id(1), "h1", Some(Vitals { pulse: Some(400), ..vitals() }), None).await;
+        assert!(matches!(bad, Err(AppError::Validation { fields, .. }) if fields[0].field == "vitals"));
+        let elsewhere = record_clinical(store, &id(1), "h2", Some(vitals()), None).await;
+        assert!(matches!(elsewhere, Err(AppError::NotFound { .. })));
+
+        // Oldest first, only this patient's visits with vitals
+        let trend = vitals_trend(store, " 9123456780 ", 10, "h1").await.unwrap();
+        let points: Vec<_> = trend.iter().map(|p| (p.appointment_id.clone(), p.vitals.weight_kg)).collect();
+        assert_eq!(points, [(id(0), Some(72.0)), (id(2), Some(70.0))]);
+        assert_eq!(trend[1].vitals.bmi, Some(22.9));
+        let latest = vitals_trend(store, "9123456780", 1, "h1").await.unwrap();
+        assert_eq!(latest[0].appointment_id, id(2));
+    }
+
+    /// Retention compares follow-ups as dates, so one due today is kept.
+    async fn stale_visits_are_removed(store: &dyn AppointmentRepository) {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let follow_up = |days: i64| Appointment {
+            follow_up_date: Some(today + Duration::days(days)),
+            ..visit("", 30)
+        };
+        let visits = [
+            visit("plain", 30),
+            Appointment { mobile: "reviewed".to_string(), ..follow_up(-1) },
+            Appointment { mobile: "due today".to_string(), ..follow_up(0) },
+            Appointment { mobile: "due next year".to_string(), ..follow_up(400) },
+            Appointment { mobile: "with vitals".to_string(), vitals: Some(vitals()), ..visit("", 30) },
+            visit("recent", 1),
+        ];
+        for visit in &visits {
+            store.insert_appointment(visit.clone()).await.unwrap();
+        }
+
+        let cutoff = dates::from_utc(Utc::now() - Duration::days(7));
+        assert_eq!(store.delete_stale_appointments("h2", cutoff, today).await.unwrap(), 0);
+        assert_eq!(store.delete_stale_appointments("h1", cutoff, today).await.unwrap(), 2);
+        let left = store.list_appointments("h1", &AppointmentQuery::default()).await.unwrap();
+        let mut mobiles: Vec<String> = left.into_iter().map(|a| a.mobile).collect();
+        mobiles.sort();
+        assert_eq!(mobiles, ["due next year", "due today", "recent", "with vitals"]);
+    }
+
+    #[tokio::test]
+    async fn clinical_records_are_kept_in_memory() {
+        clinical_records_are_kept(&MemoryStore::default()).await;
+        stale_visits_are_removed(&MemoryStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn clinical_records_are_kept_on_sqlite() {
+        clinical_records_are_kept(&SqliteStore::open_in_memory().await.unwrap()).await;
+        stale_visits_are_removed(&SqliteStore::open_in_memory().await.unwrap()).await;
+    }
+}
@@ -1,9 +1,8 @@
 use tauri::State;
-use mongodb::Collection;
-use crate::user::{signup_user, login_user, send_otp, validate_otp};
-use crate::model::User;
+use crate::user::{self, signup_user, login_user, send_otp, validate_otp, ProfileChanges};
 use crate::db::DbState; // Import your DbState struct
-use mongodb::bson::doc;
+use crate::error::AppError;
+use crate::sync;
 use chrono::Utc;
 use std::sync::Mutex;
 
@@ -14,6 +13,7 @@ pub struct SessionState {
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn signup(
     username: String,
     name: String,
@@ -24,24 +24,19 @@ pub async fn signup(
     password_pharma: String,
     email: String,
     db: State<'_, DbState>,
-) -> Result<(), String> {
-    let user_collection: &Collection<User> = &db.db.collection("users");
+) -> Result<(), AppError> {
+    let user_collection = db.users();
 
     // Check if email is already registered
-    let existing_email = user_collection
-        .find_one(doc! { "email": &email }, None)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?;
-
-    if existing_email.is_some() {
-        return Err("Email already in use".to_string());
+    if user_collection.find_user_by_email(&email).await?.is_some() {
+        return Err(AppError::conflict("Email already in use"));
     }
 
     // Send OTP for email verification
-    send_otp(user_collection, &email).await?;
+    send_otp(&*user_collection, &email).await?;
 
     signup_user(
-        user_collection,
+        &*user_collection,
         &username,
         &name,
         &mobile,
@@ -55,6 +50,7 @@ pub async fn signup(
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn verify_signup(
     username: String,
     name: String,
@@ -66,15 +62,15 @@ pub async fn verify_signup(
     email: String,
     otp: String,
     db: State<'_, DbState>,
-) -> Result<(), String> {
-    let user_collection: &Collection<User> = &db.db.collection("users");
+) -> Result<(), AppError> {
+    let user_collection = db.users();
 
     // Validate OTP
-    validate_otp(user_collection, &email, &otp).await?;
+    validate_otp(&*user_collection, &email, &otp).await?;
 
     // Proceed with user signup
     signup_user(
-        user_collection,
+        &*user_collection,
         &username,
         &name,
         &mobile,
@@ -90,21 +86,16 @@ pub async fn verify_signup(
 
 
 #[tauri::command]
-pub async fn forgot_password(email: String, db: State<'_, DbState>) -> Result<(), String> {
-    let user_collection: &Collection<User> = &db.db.collection("users");
+pub async fn forgot_password(email: String, db: State<'_, DbState>) -> Result<(), AppError> {
+    let user_collection = db.users();
 
     // Check if email exists
-    let existing_email = user_collection
-        .find_one(doc! { "email": &email }, None)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?;
-
-    if existing_email.is_none() {
-        return Err("Email not found".to_string());
+    if user_collection.find_user_by_email(&email).await?.is_none() {
+        return Err(AppError::not_found("Email not found"));
     }
 
     // Send OTP for password reset
-    send_otp(user_collection, &email).await
+    send_otp(&*user_collection, &email).await
 }
 
 #[tauri::command]
@@ -114,31 +105,10 @@ pub async fn reset_password(
     new_password: String,
     role: String,
     db: State<'_, DbState>,
-) -> Result<(), String> {
-    let user_collection: &Collection<User> = &db.db.collection("users");
-
-    // Validate OTP
-    validate_otp(user_collection, &email, &otp).await?;
-
-    // Update the user's password
-    let password_hash = bcrypt::hash(&new_password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())?;
-
-    let update_field = if role == "Doctor" {
-        "password_hash_doc"
-    } else {
-        "password_hash_pharma"
-    };
+) -> Result<(), AppError> {
+    let user_collection = db.users();
 
-    user_collection
-        .update_one(
-            doc! { "email": &email },
-            doc! { "$set": { update_field: password_hash } },
-            None,
-        )
-        .await
-        .map_err(|e| format!("Failed to update password: {}", e))?;
-
-    Ok(())
+    user::reset_password(&*user_collection, &email, &otp, &new_password, &role).await
 }
 
 #[tauri::command]
@@ -147,15 +117,60 @@ pub async fn login(
     username: String,
     password: String,
     db: State<'_, DbState>,
-) -> Result<String, String> {
-    let user_collection: &Collection<User> = &db.db.collection("users");
+) -> Result<String, AppError> {
+    let user_collection = db.users();
     
     // Call the login function and return the result
-    login_user(user_collection, &username, &password, &role).await
+    let response = login_user(&*user_collection, &username, &password, &role).await?;
+
+    // Keep this hospital's stock on the counter in case the connection drops
+    let hospital_id = serde_json::from_str::<serde_json::Value>(&response)
+        .ok()
+        .and_then(|v| v["userId"].as_str().map(str::to_string));
+    if let Some(hospital_id) = hospital_id {
+        if let Err(error) = sync::track_hospital(&db, &hospital_id).await {
+            eprintln!("Failed to keep an offline copy for {}: {}", hospital_id, error);
+        }
+    }
+    Ok(response)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_hospital_profile(
+    hospital_id: String,
+    name: Option<String>,
+    mobile: Option<String>,
+    address: Option<String>,
+    hospital: Option<String>,
+    registration_number: Option<String>,
+    gstin: Option<String>,
+    state_code: Option<String>,
+    invoice_prefix: Option<String>,
+    upi_id: Option<String>,
+    logo_path: Option<String>,
+    letterhead: Option<String>,
+    db: State<'_, DbState>,
+) -> Result<(), AppError> {
+    let user_collection = db.users();
+    let changes = ProfileChanges {
+        name,
+        mobile,
+        address,
+        hospital,
+        registration_number,
+        gstin,
+        state_code,
+        invoice_prefix,
+        upi_id,
+        logo_path,
+        letterhead,
+    };
+    user::update_profile(&*user_collection, &hospital_id, changes).await
 }
 
 #[tauri::command]
-pub async fn is_logged_in(state: State<'_, SessionState>) -> Result<bool, String> {
+pub async fn is_logged_in(state: State<'_, SessionState>) -> Result<bool, AppError> {
     let is_logged_in = if let Some(expiry) = *state.expiry.lock().unwrap() {
         Utc::now().timestamp() < expiry
     } else {
@@ -165,7 +180,7 @@ pub async fn is_logged_in(state: State<'_, SessionState>) -> Result<bool, String
 }
 
 #[tauri::command]
-pub async fn logout(state: State<'_, SessionState>) -> Result<(), String> {
+pub async fn logout(state: State<'_, SessionState>) -> Result<(), AppError> {
     *state.token.lock().unwrap() = None;
     *state.expiry.lock().unwrap() = None;
     Ok(())
@@ -1,43 +1,119 @@
-use crate::database::get_db_connection;
+use crate::clinical::{ClinicalNotes, Vitals};
+use crate::dates::{self, date_field};
+use crate::discount::Discount;
+use crate::error::AppError;
+use crate::money::Money;
+use crate::repository::{AppointmentQuery, AppointmentSort, MedicineChanges, MedicineQuery, MedicineSort, Page};
+use crate::search::{self, SearchIndexes};
+use crate::stock;
+use crate::user::find_hospital;
 use serde::{Deserialize, Serialize};
 use tauri::{command, State};
 use crate::db::DbState;
-use futures::stream::StreamExt;
-use futures::TryStreamExt;
 use mongodb::bson;
-use mongodb::options::FindOptions;
 use regex::Regex;
-use chrono::Utc;
-use mongodb::error::Error;
-use mongodb::{Collection, Cursor};
-use mongodb::bson::{to_bson,doc, Document, Bson , oid::ObjectId};
+use chrono::{Local, NaiveDate};
+use mongodb::{Collection, Database};
+use mongodb::bson::{doc, oid::ObjectId};
 // use mongodb::bson::oid::ObjectId;
 
 
 
 #[command]
-pub async fn initialize_db() -> Result<String, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
+pub async fn initialize_db(db_state: State<'_, DbState>) -> Result<String, AppError> {
+    let _medicines = db_state.medicines();
     Ok("Medicines collection initialized successfully.".to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Medicine {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub user_id: String,
     pub name: String,
     pub batch_number: String,
-    pub expiry_date: String,
+    #[serde(with = "crate::dates::calendar")]
+    pub expiry_date: NaiveDate,
+    pub quantity: u32,
+    pub purchase_price: Money,
+    pub selling_price: Money,
+    pub wholesaler_name: String,
+    #[serde(with = "crate::dates::calendar")]
+    pub purchase_date: NaiveDate,
+    #[serde(default)]
+    pub hsn_code: Option<String>,
+    #[serde(default)]
+    pub gst_rate: Option<f64>, // percent
+    #[serde(default = "default_tax_inclusive")]
+    pub tax_inclusive: bool, // MRP in India already includes GST
+    #[serde(default)]
+    pub generic_name: Option<String>, // e.g. "Paracetamol" for stock filed as "Dolo 650"
+    #[serde(default)]
+    pub brand_names: Vec<String>, // other names customers ask for the same product by
+    #[serde(default)]
+    pub composition: Option<String>, // e.g. "Amoxicillin 500 mg + Clavulanic acid 125 mg"
+    #[serde(default)]
+    pub barcode: Option<String>, // EAN/GTIN printed on the pack
+}
+
+/// A medicine as the stock screens see it: prices in rupees and dates as
+/// `YYYY-MM-DD`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MedicineResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub expiry_date: NaiveDate,
     pub quantity: u32,
     pub purchase_price: f64,
     pub selling_price: f64,
     pub wholesaler_name: String,
-    pub purchase_date: String,
+    pub purchase_date: NaiveDate,
+    pub hsn_code: Option<String>,
+    pub gst_rate: Option<f64>,
+    pub tax_inclusive: bool,
+    pub generic_name: Option<String>,
+    pub brand_names: Vec<String>,
+    pub composition: Option<String>,
+    pub barcode: Option<String>,
+}
+
+impl From<Medicine> for MedicineResponse {
+    fn from(medicine: Medicine) -> Self {
+        MedicineResponse {
+            id: medicine.id,
+            user_id: medicine.user_id,
+            name: medicine.name,
+            batch_number: medicine.batch_number,
+            expiry_date: medicine.expiry_date,
+            quantity: medicine.quantity,
+            purchase_price: medicine.purchase_price.rupees(),
+            selling_price: medicine.selling_price.rupees(),
+            wholesaler_name: medicine.wholesaler_name,
+            purchase_date: medicine.purchase_date,
+            hsn_code: medicine.hsn_code,
+            gst_rate: medicine.gst_rate,
+            tax_inclusive: medicine.tax_inclusive,
+            generic_name: medicine.generic_name,
+            brand_names: medicine.brand_names,
+            composition: medicine.composition,
+            barcode: medicine.barcode,
+        }
+    }
+}
+
+fn default_tax_inclusive() -> bool {
+    true
+}
+
+pub(crate) fn medicines_collection(db: &Database) -> Collection<Medicine> {
+    db.collection("medicines")
 }
 
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_medicine(
     name: String,
     batch_number: String,
@@ -48,158 +124,130 @@ pub async fn insert_medicine(
     wholesaler_name: String,
     purchase_date: String,
     hospital_id: String,
-) -> Result<String, String> {
-    // println!("1");
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
+    hsn_code: Option<String>,
+    gst_rate: Option<f64>,
+    tax_inclusive: Option<bool>,
+    generic_name: Option<String>,
+    brand_names: Option<Vec<String>>,
+    composition: Option<String>,
+    barcode: Option<String>,
+    db_state: State<'_, DbState>,
+    indexes: State<'_, SearchIndexes>,
+) -> Result<String, AppError> {
+    // Prices follow the hospital's tax mode unless this batch says otherwise
+    let tax_inclusive = match tax_inclusive {
+        Some(inclusive) => inclusive,
+        None => find_hospital(&*db_state.users(), &hospital_id).await?.settings.tax_mode.is_inclusive(),
+    };
     let new_medicine = Medicine {
         id: None,
         user_id: hospital_id,
         name,
         batch_number,
-        expiry_date,
+        expiry_date: date_field("expiry_date", &expiry_date)?,
         quantity,
-        purchase_price,
-        selling_price,
+        purchase_price: Money::parse_rupees("purchase_price", purchase_price)?,
+        selling_price: Money::parse_rupees("selling_price", selling_price)?,
         wholesaler_name,
-        purchase_date,
+        purchase_date: date_field("purchase_date", &purchase_date)?,
+        hsn_code,
+        gst_rate,
+        tax_inclusive,
+        generic_name,
+        brand_names: brand_names.unwrap_or_default(),
+        composition,
+        barcode,
     };
 
-    collection.insert_one(new_medicine, None).await.map_err(|e| e.to_string())?;
+    let hospital_id = new_medicine.user_id.clone();
+    stock::add_medicine(&*db_state.medicines(), new_medicine).await?;
+    indexes.invalidate(&hospital_id);
     Ok("Medicine inserted successfully.".to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 pub struct Wholesaler {
     pub wholesaler_id: String,
     pub wholesaler_name: String,
-    pub purchase_date: String,
-    pub medicines: Vec<Medicine>,
+    pub purchase_date: NaiveDate,
+    pub medicines: Vec<MedicineResponse>,
 }
 #[tauri::command]
-pub async fn get_stock(hospital_id: &str) -> Result<Vec<Wholesaler>, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Filter documents by hospital_id (user_id)
-    let filter = doc! { "user_id": hospital_id };
-
-    // Fetch and transform the documents
-    let mut cursor = collection.find(filter, None).await.map_err(|e| e.to_string())?;
-    let mut medicines: Vec<Medicine> = Vec::new();
-
-    while let Some(medicine) = cursor.try_next().await.map_err(|e| e.to_string())? {
-        medicines.push(medicine);
-    }
-
-    // Group medicines by wholesaler_name and purchase_date
-    let mut wholesalers_map: std::collections::HashMap<(String, String), Vec<Medicine>> = std::collections::HashMap::new();
-    for mut medicine in medicines {
-        // Ensure id is converted to string for frontend (not modifying the medicine.id directly)
-        if let Some(ref id) = medicine.id {
-            // Optionally, you could convert the id to a string for display purposes:
-            let id_string = id.to_string();
-            // You can now send id_string to the frontend, but keep the original ObjectId intact.
-        }
-        let key = (medicine.wholesaler_name.clone(), medicine.purchase_date.clone());
-        wholesalers_map.entry(key).or_default().push(medicine);
-    }
-
-    // Convert grouped medicines into Wholesaler structs
-    let stock: Vec<Wholesaler> = wholesalers_map
-        .into_iter()
-        .map(|((wholesaler_name, purchase_date), medicines)| Wholesaler {
-            wholesaler_id: hospital_id.to_string(),  // Set wholesaler_id to hospital_id (user_id)
-            wholesaler_name,
-            purchase_date,
-            medicines,
-        })
-        .collect();
-        
-    Ok(stock)
+pub async fn get_stock(
+    hospital_id: &str,
+    query: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<Wholesaler>, AppError> {
+    stock::stock_by_wholesaler(&*db_state.medicines(), hospital_id, query.as_deref()).await
 }
 #[tauri::command]
 pub async fn reduce_batch(
     id: String,
     batch_number: String,
     quantity: u32,
-) -> Result<String, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Convert the string ID to ObjectId
-    let object_id = ObjectId::parse_str(&id).map_err(|_| "Invalid ID format")?;
-
-    // Filter by ID and batch_number
-    let filter = doc! {
-        "_id": object_id,
-        "batch_number": batch_number,
-    };
-
-    // Reduce the quantity
-    let update = doc! {
-        "$inc": { "quantity": -(quantity as i32) }
-    };
-
-    match collection.update_one(filter, update, None).await {
-        Ok(result) => {
-            if result.matched_count > 0 {
-                Ok("Medicine quantity updated successfully.".to_string())
-            } else {
-                Err("No matching medicine found.".into())
-            }
-        }
-        Err(e) => Err(format!("Database update error: {}", e)),
-    }
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
+    stock::reduce_batch(&*db_state.medicines(), &id, &batch_number, quantity).await?;
+    Ok("Medicine quantity updated successfully.".to_string())
 }
 
 #[command]
-pub async fn delete_medicine(medicine_id: &str, hospital_id: &str) -> Result<String, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Filter to find the specific medicine by ID and user ID
-    let filter = doc! {
-        "_id": ObjectId::parse_str(medicine_id).map_err(|_| "Invalid medicine ID".to_string())?,
-        "user_id": hospital_id
-    };
+pub async fn delete_medicine(
+    medicine_id: &str,
+    hospital_id: &str,
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
+    stock::delete_medicine(&*db_state.medicines(), medicine_id, None, hospital_id).await?;
+    Ok("Medicine deleted successfully.".to_string())
+}
 
-    // Delete the medicine document
-    let result = collection.delete_one(filter, None).await.map_err(|e| e.to_string())?;
-    if result.deleted_count > 0 {
-        Ok("Medicine deleted successfully.".to_string())
-    } else {
-        Err("No matching medicine found.".to_string())
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// One page of a list. `page` counts from 1; with no page size the list
+/// is cut at `default_size`, or not at all when that is `None`.
+fn page_of(page: Option<u32>, page_size: Option<u32>, default_size: Option<u32>) -> Result<Page, AppError> {
+    let page = page.unwrap_or(1);
+    if page == 0 {
+        return Err(AppError::invalid("page", "Pages are numbered from 1."));
     }
+    let size = match page_size.or(default_size) {
+        Some(size) if size == 0 || size > MAX_PAGE_SIZE => {
+            return Err(AppError::invalid(
+                "page_size",
+                &format!("Page size must be between 1 and {}.", MAX_PAGE_SIZE),
+            ))
+        }
+        Some(size) => size,
+        None if page > 1 => DEFAULT_PAGE_SIZE,
+        None => return Ok(Page::default()),
+    };
+    Ok(Page { skip: (page as u64 - 1) * size as u64, limit: Some(size as u64) })
 }
 
 #[command]
-pub async fn fetch_medicine(hospital_id: &str) -> Result<Vec<Medicine>, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Filter to match the specific hospital_id
-    let filter = doc! { "user_id": hospital_id };
-
-    // Fetch all medicines matching the filter
-    let cursor = collection
-        .find(filter, None)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    // Collect the results into a Vec<Medicine>
-    let medicines: Vec<Medicine> = cursor
-        .try_collect()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(medicines)
+pub async fn fetch_medicine(
+    hospital_id: &str,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    sort: Option<MedicineSort>,
+    descending: Option<bool>,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<MedicineResponse>, AppError> {
+    let query = MedicineQuery {
+        text: None,
+        sort: sort.unwrap_or_default(),
+        descending: descending.unwrap_or(false),
+        page: page_of(page, page_size, None)?,
+    };
+    let medicines = db_state.medicines().list_medicines(hospital_id, &query).await?;
+    Ok(medicines.into_iter().map(MedicineResponse::from).collect())
 }
 
 
 
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_stock(
     medicine_id: String,
     quantity: Option<u32>,
@@ -208,79 +256,40 @@ pub async fn update_stock(
     batch_number: Option<String>,
     expiry_date: Option<String>,
     hospital_id: String,
-) -> Result<String, String> {
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Create filter
-    let filter = doc! {
-        "_id": ObjectId::parse_str(&medicine_id).map_err(|_| "Invalid medicine ID".to_string())?,
-        "user_id": hospital_id,
+    hsn_code: Option<String>,
+    gst_rate: Option<f64>,
+    tax_inclusive: Option<bool>,
+    generic_name: Option<String>,
+    brand_names: Option<Vec<String>>,
+    composition: Option<String>,
+    barcode: Option<String>,
+    db_state: State<'_, DbState>,
+    indexes: State<'_, SearchIndexes>,
+) -> Result<String, AppError> {
+    let changes = MedicineChanges {
+        quantity,
+        purchase_price: purchase_price.map(|p| Money::parse_rupees("purchase_price", p)).transpose()?,
+        selling_price: selling_price.map(|p| Money::parse_rupees("selling_price", p)).transpose()?,
+        batch_number,
+        expiry_date: expiry_date.map(|d| date_field("expiry_date", &d)).transpose()?,
+        hsn_code,
+        gst_rate,
+        tax_inclusive,
+        generic_name,
+        brand_names,
+        composition,
+        barcode,
+        ..Default::default()
     };
-    println!("Filter: {:?}", filter);
-
-    // Check if the document exists
-    let existing_doc = collection.find_one(filter.clone(), None).await.map_err(|e| {
-        println!("Error finding document: {:?}", e);
-        "Error finding document.".to_string()
-    })?;
-    println!("Existing Document: {:?}", existing_doc);
-
-    if existing_doc.is_none() {
-        return Err("No matching document found.".to_string());
-    } else {
-        println!("Found document, proceeding with update.");
-    }
-
-    // Construct the update document
-    let mut update_doc = doc! {};
-    if let Some(qty) = quantity {
-        update_doc.insert("quantity", qty);
-    }
-    if let Some(pp) = purchase_price {
-        update_doc.insert("purchase_price", pp);
-    }
-    println!("Selling price: {:?}", selling_price);  // Log selling price
-    if let Some(sp) = selling_price {
-        update_doc.insert("selling_price", sp);
-    }
-    if let Some(batch) = batch_number {
-        update_doc.insert("batch_number", batch);
-    }
-    if let Some(expiry) = expiry_date {
-        update_doc.insert("expiry_date", expiry);
-    }
-    println!("Update Document: {:?}", update_doc);
-
-    if update_doc.is_empty() {
-        return Err("No fields to update.".to_string());
-    }
-
-    // Perform the update
-    let update = doc! { "$set": update_doc };
-    let result = collection.update_one(filter, update, None).await;
-
-    match result {
-        Ok(res) => {
-            println!("Matched Count: {}", res.matched_count);
-            println!("Modified Count: {}", res.modified_count);
-
-            if res.modified_count == 0 {
-                return Err("No changes were made (value may be the same).".to_string());
-            }
-        },
-        Err(e) => {
-            println!("Error during update: {:?}", e);
-            return Err("Error during update.".to_string());
-        }
-    }
-
+    stock::update_stock(&*db_state.medicines(), &medicine_id, &hospital_id, changes).await?;
+    indexes.invalidate(&hospital_id);
     Ok("Stock updated successfully.".to_string())
 }
 
 
 
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_batch(
     medicine_id: String,
     batch_number: String,
@@ -291,96 +300,63 @@ pub async fn update_batch(
     wholesaler_name: Option<String>,
     purchase_date: Option<String>,
     hospital_id: String,
-) -> Result<String, String> {
-    // let user_id = get_user_id(session.user_id.clone()).await?;
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    let filter = doc! {
-        "_id": ObjectId::parse_str(&medicine_id).map_err(|_| "Invalid medicine ID".to_string())?,
-        "user_id": hospital_id,
-        "batch_number": batch_number.clone(),
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
+    let changes = MedicineChanges {
+        quantity,
+        purchase_price: purchase_price.map(|p| Money::parse_rupees("purchase_price", p)).transpose()?,
+        selling_price: selling_price.map(|p| Money::parse_rupees("selling_price", p)).transpose()?,
+        expiry_date: expiry_date.map(|d| date_field("expiry_date", &d)).transpose()?,
+        wholesaler_name,
+        purchase_date: purchase_date.map(|d| date_field("purchase_date", &d)).transpose()?,
+        ..Default::default()
     };
-
-    let mut update_doc = doc! {};
-    if let Some(qty) = quantity {
-        update_doc.insert("quantity", qty);
-    }
-    if let Some(pp) = purchase_price {
-        update_doc.insert("purchase_price", pp);
-    }
-    if let Some(sp) = selling_price {
-        update_doc.insert("selling_price", sp);
-    }
-
-    if update_doc.is_empty() {
-        return Err("No fields to update.".to_string());
-    }
-
-    let update = doc! { "$set": update_doc };
-
-    collection.update_one(filter, update, None).await.map_err(|e| e.to_string())?;
+    stock::update_batch(&*db_state.medicines(), &medicine_id, &batch_number, &hospital_id, changes).await?;
     Ok("Batch updated successfully.".to_string())
 }
 
+/// Each batch is its own medicine document, so this removes the document
+/// for that batch.
 #[command]
 pub async fn delete_batch(
     medicine_id: String,
     batch_number: String, // Specify the batch to delete
     hospital_id: String,
-) -> Result<String, String> {
-    // let user_id = get_user_id(session.user_id.clone()).await?;
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Filter to find the medicine by ID and user ID
-    let filter = doc! {
-        "_id": ObjectId::parse_str(&medicine_id).map_err(|_| "Invalid medicine ID".to_string())?,
-        "user_id": hospital_id,
-    };
-
-    // Update to pull the specific batch from the batches array
-    let update = doc! {
-        "$pull": { "batches": { "batch_number": batch_number } }
-    };
-
-    // Apply the update
-    let result = collection.update_one(filter, update, None).await.map_err(|e| e.to_string())?;
-
-    // If no batches remain, delete the entire medicine document
-    if result.matched_count > 0 {
-        let remaining_batches_filter = doc! {
-            "_id": ObjectId::parse_str(&medicine_id).unwrap(),
-            "batches": { "$size": 0 }
-        };
-
-        collection.delete_one(remaining_batches_filter, None).await.map_err(|e| e.to_string())?;
-    }
-
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
+    stock::delete_medicine(&*db_state.medicines(), &medicine_id, Some(&batch_number), &hospital_id).await?;
     Ok("Batch deleted successfully.".to_string())
 }
 
+/// Batches matching the search text, typo-tolerant and across brand,
+/// generic and composition names or a barcode (see `search.rs`). Blank
+/// text lists the hospital's stock.
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_medicines(
     query: String,
     hospital_id: String,
-) -> Result<Vec<Medicine>, String> {
-    // let user_id = get_user_id(session.user_id.clone()).await?;
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-    let filter = doc! {
-        "user_id": hospital_id,
-        "name": { "$regex": &query, "$options": "i" }, // Case-insensitive search
+    page: Option<u32>,
+    page_size: Option<u32>,
+    sort: Option<MedicineSort>,
+    descending: Option<bool>,
+    db_state: State<'_, DbState>,
+    indexes: State<'_, SearchIndexes>,
+) -> Result<Vec<MedicineResponse>, AppError> {
+    let query = MedicineQuery {
+        text: Some(query.trim().to_string()).filter(|text| !text.is_empty()),
+        sort: sort.unwrap_or_default(),
+        descending: descending.unwrap_or(false),
+        page: page_of(page, page_size, Some(DEFAULT_PAGE_SIZE))?,
     };
-
-    let mut cursor = collection.find(filter, None).await.map_err(|e| e.to_string())?;
-    let mut medicines = Vec::new();
-
-    while let Some(doc) = cursor.try_next().await.map_err(|e| e.to_string())? {
-        println!("Fetched document: {:?}", doc); // Log the document
-        medicines.push(doc);
-    }
-    Ok(medicines)
+    let repo = db_state.medicines();
+    let medicines = if query.text.is_some() {
+        let index = indexes.get(&*repo, &hospital_id).await?;
+        search::search_batches(&*repo, &index, &hospital_id, &query).await?
+    } else {
+        stock::search(&*repo, query, &hospital_id).await?
+    };
+    Ok(medicines.into_iter().map(MedicineResponse::from).collect())
 }
 
 
@@ -390,13 +366,17 @@ pub async fn search_medicines(
 //     pub name: String,
 //     pub quantity: u32,
 // }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MedicineDetail {
     pub id: String, // Medicine ID
     pub quantity: u32,
+    #[serde(default)]
+    pub dosage: Option<String>, // e.g. "1-0-1 after food x 5 days"
+    #[serde(default)]
+    pub discount: Option<Discount>, // counter discount on this line when billing
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Appointment {
     #[serde(rename = "_id")]
     pub id: ObjectId,
@@ -406,10 +386,35 @@ pub struct Appointment {
     pub precautions: String,
     pub medicines: Vec<MedicineDetail>, // Updated to store only IDs and quantities
     pub hospital_id: String,
-    pub date_created: String,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default, with = "crate::dates::optional_calendar")]
+    pub follow_up_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub vitals: Option<Vitals>,
+    #[serde(default)]
+    pub notes: Option<ClinicalNotes>,
+    #[serde(default)]
+    pub doctor_name: Option<String>, // who saw the patient; signs the prescription
+}
+
+/// Picks up notes like "review after 7 days" or "come back after 2 weeks".
+fn follow_up_from_precautions(precautions: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let re = Regex::new(r"(?i)after\s+(\d+)\s*(day|week|month)").ok()?;
+    let caps = re.captures(precautions)?;
+    let count: i64 = caps[1].parse().ok()?;
+    let days = match caps[2].to_lowercase().as_str() {
+        "week" => count * 7,
+        "month" => count * 30,
+        _ => count,
+    };
+    today.checked_add_signed(chrono::Duration::days(days))
 }
 
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn save_appointment(
     patient_name: String,
     mobile: String,
@@ -417,16 +422,33 @@ pub async fn save_appointment(
     precautions: String,
     medicines: Vec<MedicineDetail>, // Adjusted to accept only medicine IDs and quantities
     hospital_id: String,
-) -> Result<String, String> {
+    email: Option<String>,
+    follow_up_date: Option<String>,
+    vitals: Option<Vitals>,
+    notes: Option<ClinicalNotes>,
+    doctor_name: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<String, AppError> {
     // Validate required fields
-    if patient_name.trim().is_empty() || mobile.trim().is_empty() {
-        return Err("Patient name and mobile number are required.".to_string());
+    if patient_name.trim().is_empty() {
+        return Err(AppError::invalid("patient_name", "Patient name and mobile number are required."));
+    }
+    if mobile.trim().is_empty() {
+        return Err(AppError::invalid("mobile", "Patient name and mobile number are required."));
     }
 
-    // Prepare the database connection
-    let db = get_db_connection().await;
-
-    let collection: Collection<Appointment> = db.collection("appointments");
+    // An explicit follow-up date wins over one read from the precautions
+    let follow_up_date = match follow_up_date.filter(|d| !d.trim().is_empty()) {
+        Some(date) => Some(
+            NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| AppError::invalid("follow_up_date", &format!("Invalid follow-up date: {}", date)))?,
+        ),
+        None => follow_up_from_precautions(&precautions, Local::now().date_naive()),
+    };
+    let email = email.map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+    let vitals = vitals.map(Vitals::prepare).transpose().map_err(|e| AppError::invalid("vitals", &e))?;
+    let notes = notes.filter(|n| !n.is_empty());
+    let doctor_name = doctor_name.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
 
     // Create the new appointment object
     let new_appointment = Appointment {
@@ -437,22 +459,23 @@ pub async fn save_appointment(
         precautions,
         medicines, // Only medicine IDs and quantities are stored
         hospital_id,
-        date_created: Utc::now().to_rfc3339(), // Generate current timestamp
+        date_created: bson::DateTime::now(), // Generate current timestamp
+        email,
+        follow_up_date,
+        vitals,
+        notes,
+        doctor_name,
     };
 
     // Insert the appointment into the database
-    collection
-        .insert_one(new_appointment, None)
-        .await
-        .map_err(|e| format!("Database insert error: {}", e))?;
+    db_state.appointments().insert_appointment(new_appointment).await?;
 
     // Return success message
     Ok("Appointment saved successfully.".to_string())
 }
 
-async fn get_appointments_collection() -> Result<Collection<Appointment>, mongodb::error::Error> {
-    let db = get_db_connection().await; // Replace with your database connection logic
-    Ok(db.collection::<Appointment>("appointments"))
+pub(crate) fn appointments_collection(db: &Database) -> Collection<Appointment> {
+    db.collection::<Appointment>("appointments")
 }
 
 // Fetch all appointments from the database
@@ -466,97 +489,73 @@ pub struct AppointmentResponse {
     pub precautions: String,
     pub medicines: Vec<MedicineDetail>,
     pub date_created: Option<chrono::DateTime<chrono::Utc>>,
+    pub follow_up_date: Option<NaiveDate>,
+    pub vitals: Option<Vitals>,
+    pub notes: Option<ClinicalNotes>,
+    pub doctor_name: Option<String>,
 }
 
 #[command]
-pub async fn get_all_appointments(hospital_id: &str) -> Result<Vec<AppointmentResponse>, String> {
-    let collection = get_appointments_collection().await.map_err(|e| e.to_string())?;
-
-    // Create a filter to fetch only the appointments that match the given hospital_id
-    let filter = doc! { "hospital_id": hospital_id };
-
-    // Retrieve documents from the appointments collection with the filter and sort by latest (date_created)
-    let find_options = FindOptions::builder().sort(doc! { "date_created": -1 }).build();
-    let cursor = collection
-        .find(filter, find_options)
-        .await
-        .map_err(|e| format!("Database query error: {}", e))?;
-
-    // Map MongoDB's ObjectId to a String and collect into a vector
-    let appointments: Vec<AppointmentResponse> = cursor
-        .map(|result| {
-            result.map(|appointment: Appointment| AppointmentResponse {
-                id: appointment.id.to_hex(),
-                hospital_id: appointment.hospital_id,
-                patient_name: appointment.patient_name,
-                disease: appointment.disease,
-                precautions: appointment.precautions,
-                medicines: appointment
-                    .medicines
-                    .iter()
-                    .map(|m| MedicineDetail {
-                        id: m.id.clone(),
-                        quantity: m.quantity,
-                    })
-                    .collect(),
-                date_created: appointment
-                .date_created
-                .parse::<chrono::DateTime<chrono::Utc>>()
-                .ok(),
-            })
+pub async fn get_all_appointments(
+    hospital_id: &str,
+    query: Option<String>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    sort: Option<AppointmentSort>,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<AppointmentResponse>, AppError> {
+    let query = AppointmentQuery {
+        text: query.map(|text| text.trim().to_string()).filter(|text| !text.is_empty()),
+        sort: sort.unwrap_or_default(),
+        page: page_of(page, page_size, None)?,
+    };
+    let appointments = db_state.appointments().list_appointments(hospital_id, &query).await?;
+
+    let appointments = appointments
+        .into_iter()
+        .map(|appointment| AppointmentResponse {
+            id: appointment.id.to_hex(),
+            hospital_id: appointment.hospital_id,
+            patient_name: appointment.patient_name,
+            disease: appointment.disease,
+            precautions: appointment.precautions,
+            medicines: appointment.medicines,
+            date_created: Some(dates::to_utc(appointment.date_created)),
+            follow_up_date: appointment.follow_up_date,
+            vitals: appointment.vitals,
+            notes: appointment.notes,
+            doctor_name: appointment.doctor_name,
         })
-        .try_collect()
-        .await
-        .map_err(|e| format!("Error parsing appointments: {}", e))?;
+        .collect();
 
     Ok(appointments)
 }
 
+/// Removes visits past each hospital's retention period, keeping clinical
+/// records (needed for vitals trends) and any whose follow-up is still ahead.
 #[command]
-pub async fn delete_appointments_older_than_one_hour() -> Result<String, String> {
-    let collection = get_appointments_collection().await.map_err(|e| e.to_string())?;
-
-    // Calculate the timestamp for 1 hour ago
-    let cutoff_date = chrono::Utc::now() - chrono::Duration::hours(1);
-
-    // Delete appointments older than the cutoff date
-    let filter = doc! {
-        "date_created": {
-            "$lt": cutoff_date.to_rfc3339()
-        }
-    };
-
-    let delete_result = collection
-        .delete_many(filter, None)
-        .await
-        .map_err(|e| format!("Error deleting old appointments: {}", e))?;
+pub async fn apply_visit_retention(db_state: State<'_, DbState>) -> Result<String, AppError> {
+    let today = Local::now().date_naive();
+    let appointments = db_state.appointments();
+    let mut deleted = 0;
+    for hospital in db_state.users().list_users().await? {
+        let Some(hospital_id) = hospital.id else { continue };
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(hospital.settings.retention.visit_hours);
+        deleted += appointments
+            .delete_stale_appointments(&hospital_id.to_hex(), dates::from_utc(cutoff), today)
+            .await?;
+    }
 
-    Ok(format!(
-        "Deleted {} appointments older than 1 hour.",
-        delete_result.deleted_count
-    ))
+    Ok(format!("Deleted {} appointments past their retention period.", deleted))
 }
 
 
 
+
 #[command]
-pub async fn get_medicine_by_id(medicine_id: String) -> Result<Medicine, String> {
-    // println!("0");
-    // Step 1: Establish a database connection
-    let db = get_db_connection().await;
-    let collection: Collection<Medicine> = db.collection("medicines");
-
-    // Step 2: Parse the `medicine_id` into an ObjectId
-    let object_id = ObjectId::parse_str(&medicine_id).map_err(|e| e.to_string())?;
-
-    // Step 3: Query the database for the medicine
-    let filter = doc! { "_id": object_id };
-    let medicine = collection
-        .find_one(filter, None)
-        .await
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Medicine not found".to_string())?;
-
-    // Step 4: Return the retrieved medicine
-    Ok(medicine)
+pub async fn get_medicine_by_id(
+    medicine_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<MedicineResponse, AppError> {
+    Ok(stock::get_medicine(&*db_state.medicines(), &medicine_id).await?.into())
 }
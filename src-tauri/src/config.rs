@@ -0,0 +1,504 @@
+// src-tauri/src/config.rs
+//! Settings for this installation, loaded once at startup from
+//! `caton.toml` in the app data directory and then overridden by
+//! environment variables (including `.env`) under the names the app has
+//! always read, so existing setups keep working. Everything is checked
+//! before the database is opened; per-hospital settings live in the
+//! database instead (see `settings.rs`).
+//!
+//! ```toml
+//! [database]
+//! backend = "mongodb"          # DB_BACKEND: mongodb or sqlite
+//! mongodb_url = "mongodb://localhost:27017/caton"   # MONGODB_URL
+//!
+//! [smtp]
+//! server = "smtp.example.com"  # SMTP_SERVER
+//! port = 587                   # SMTP_PORT
+//! user = "clinic@example.com"  # SMTP_USER
+//! password = "..."             # SMTP_PASSWORD
+//!
+//! [output]
+//! invoices = "D:/Bills"        # INVOICE_DIR
+//! ```
+use crate::pdf::PageSize;
+use crate::receipt::{PaperWidth, ReceiptTarget};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+pub const CONFIG_FILE: &str = "caton.toml";
+const APP_DIR: &str = "com.caton.desktop"; // the identifier in tauri.conf.json
+
+static CONFIG: OnceLock<AppConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DbBackend {
+    #[default]
+    #[serde(alias = "mongo")]
+    Mongodb,
+    Sqlite,
+}
+
+impl FromStr for DbBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "mongodb" | "mongo" => Ok(DbBackend::Mongodb),
+            "sqlite" => Ok(DbBackend::Sqlite),
+            other => Err(format!("unknown backend '{}': use mongodb or sqlite", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub backend: DbBackend,
+    pub mongodb_url: Option<String>,
+    pub mongodb_db: Option<String>, // falls back to the database in the URL, then "caton"
+    pub connect_attempts: u32,
+    pub timeout_secs: u64,
+    pub sqlite_path: String,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            backend: DbBackend::Mongodb,
+            mongodb_url: None,
+            mongodb_db: None,
+            connect_attempts: 5,
+            timeout_secs: 5,
+            sqlite_path: "caton.db".to_string(),
+        }
+    }
+}
+
+/// The offline store kept while MongoDB is unreachable (see `offline.rs`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OfflineSettings {
+    pub store_path: String,
+    pub series: Option<String>, // offline invoice series; unset, each counter makes up its own
+    pub sync_interval_secs: u64,
+}
+
+impl Default for OfflineSettings {
+    fn default() -> Self {
+        OfflineSettings {
+            store_path: "caton-offline.json".to_string(),
+            series: None,
+            sync_interval_secs: 30,
+        }
+    }
+}
+
+/// The account OTPs and reminders are mailed from. Optional; without it
+/// sending mail fails with a message saying what to set.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpSettings {
+    pub server: Option<String>,
+    pub port: u16,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings { server: None, port: 587, user: None, password: None }
+    }
+}
+
+impl fmt::Debug for SmtpSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpSettings")
+            .field("server", &self.server)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// A complete SMTP account.
+pub struct SmtpAccount<'a> {
+    pub server: &'a str,
+    pub port: u16,
+    pub user: &'a str,
+    pub password: &'a str,
+}
+
+impl SmtpSettings {
+    pub fn account(&self) -> Result<SmtpAccount<'_>, String> {
+        match (&self.server, &self.user, &self.password) {
+            (Some(server), Some(user), Some(password)) => Ok(SmtpAccount { server, port: self.port, user, password }),
+            _ => Err("Email is not set up: set [smtp] in caton.toml, or SMTP_SERVER, SMTP_USER and SMTP_PASSWORD."
+                .to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmsSettings {
+    pub provider: String,
+    pub outbox_path: String,
+}
+
+impl Default for SmsSettings {
+    fn default() -> Self {
+        SmsSettings { provider: "file".to_string(), outbox_path: "sms_outbox.log".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DraftSettings {
+    pub hold_minutes: i64, // how long a parked bill reserves its stock
+}
+
+impl Default for DraftSettings {
+    fn default() -> Self {
+        DraftSettings { hold_minutes: 30 }
+    }
+}
+
+/// Printing defaults for hospitals that have not chosen their own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrintingSettings {
+    pub receipt_printer: Option<String>,
+    pub receipt_width_mm: u32,
+    pub invoice_page_size: String,
+}
+
+impl Default for PrintingSettings {
+    fn default() -> Self {
+        PrintingSettings { receipt_printer: None, receipt_width_mm: 80, invoice_page_size: "A4".to_string() }
+    }
+}
+
+/// Where generated documents are saved. Unset means the matching folder
+/// in the app data directory: prescriptions, invoices, statements or
+/// z-reports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    pub prescriptions: Option<String>,
+    pub invoices: Option<String>,
+    pub statements: Option<String>,
+    pub z_reports: Option<String>,
+}
+
+impl OutputSettings {
+    fn dirs(&self) -> [(&'static str, &Option<String>); 4] {
+        [
+            ("prescriptions", &self.prescriptions),
+            ("invoices", &self.invoices),
+            ("statements", &self.statements),
+            ("z_reports", &self.z_reports),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub database: DatabaseSettings,
+    pub offline: OfflineSettings,
+    pub smtp: SmtpSettings,
+    pub sms: SmsSettings,
+    pub drafts: DraftSettings,
+    pub printing: PrintingSettings,
+    pub output: OutputSettings,
+}
+
+/// Reads environment overrides, collecting every value that does not parse.
+struct Overrides<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    errors: Vec<String>,
+}
+
+impl Overrides<'_> {
+    fn text(&self, key: &str) -> Option<String> {
+        (self.var)(key).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+    }
+
+    fn parsed<T: FromStr>(&mut self, key: &str, expected: &str) -> Option<T> {
+        let text = self.text(key)?;
+        match text.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.errors.push(format!("{} must be {} (got '{}')", key, expected, text));
+                None
+            }
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Applies the environment variables the app read before it had a
+    /// config file. They win over the file.
+    fn apply_env(&mut self, var: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+        let mut env = Overrides { var, errors: Vec::new() };
+        if let Some(backend) = env.text("DB_BACKEND") {
+            match backend.parse() {
+                Ok(backend) => self.database.backend = backend,
+                Err(e) => env.errors.push(format!("DB_BACKEND: {}", e)),
+            }
+        }
+        let database = &mut self.database;
+        database.mongodb_url = env.text("MONGODB_URL").or(database.mongodb_url.take());
+        database.mongodb_db = env.text("MONGODB_DB").or(database.mongodb_db.take());
+        if let Some(attempts) = env.parsed("MONGODB_CONNECT_ATTEMPTS", "a whole number") {
+            database.connect_attempts = attempts;
+        }
+        if let Some(secs) = env.parsed("MONGODB_TIMEOUT_SECS", "a whole number of seconds") {
+            database.timeout_secs = secs;
+        }
+        if let Some(path) = env.text("SQLITE_PATH") {
+            database.sqlite_path = path;
+        }
+
+        let offline = &mut self.offline;
+        if let Some(path) = env.text("OFFLINE_STORE_PATH") {
+            offline.store_path = path;
+        }
+        if let Some(series) = env.text("OFFLINE_SERIES") {
+            offline.series = Some(series);
+        }
+        if let Some(secs) = env.parsed("SYNC_INTERVAL_SECS", "a whole number of seconds") {
+            offline.sync_interval_secs = secs;
+        }
+
+        let smtp = &mut self.smtp;
+        smtp.server = env.text("SMTP_SERVER").or(smtp.server.take());
+        smtp.user = env.text("SMTP_USER").or(smtp.user.take());
+        smtp.password = env.text("SMTP_PASSWORD").or(smtp.password.take());
+        if let Some(port) = env.parsed("SMTP_PORT", "a port number") {
+            smtp.port = port;
+        }
+
+        if let Some(provider) = env.text("SMS_PROVIDER") {
+            self.sms.provider = provider;
+        }
+        if let Some(path) = env.text("SMS_OUTBOX_PATH") {
+            self.sms.outbox_path = path;
+        }
+        if let Some(minutes) = env.parsed("DRAFT_HOLD_MINUTES", "a whole number of minutes") {
+            self.drafts.hold_minutes = minutes;
+        }
+
+        let printing = &mut self.printing;
+        printing.receipt_printer = env.text("RECEIPT_PRINTER").or(printing.receipt_printer.take());
+        if let Some(width) = env.parsed("RECEIPT_WIDTH", "58 or 80") {
+            printing.receipt_width_mm = width;
+        }
+        if let Some(size) = env.text("INVOICE_PAGE_SIZE") {
+            printing.invoice_page_size = size;
+        }
+
+        let output = &mut self.output;
+        output.prescriptions = env.text("PRESCRIPTION_DIR").or(output.prescriptions.take());
+        output.invoices = env.text("INVOICE_DIR").or(output.invoices.take());
+        output.statements = env.text("STATEMENT_DIR").or(output.statements.take());
+        output.z_reports = env.text("Z_REPORT_DIR").or(output.z_reports.take());
+        env.errors
+    }
+
+    /// Every problem with the settings, worded for whoever edits the file.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let database = &self.database;
+        match (database.backend, database.mongodb_url.as_deref()) {
+            (DbBackend::Mongodb, None) => {
+                errors.push("database.mongodb_url (MONGODB_URL) is required for the MongoDB backend".to_string())
+            }
+            (DbBackend::Mongodb, Some(url)) if !url.starts_with("mongodb://") && !url.starts_with("mongodb+srv://") => {
+                errors.push("database.mongodb_url must start with mongodb:// or mongodb+srv://".to_string())
+            }
+            (DbBackend::Sqlite, _) if database.sqlite_path.trim().is_empty() => {
+                errors.push("database.sqlite_path (SQLITE_PATH) must not be empty".to_string())
+            }
+            _ => {}
+        }
+        if database.connect_attempts == 0 {
+            errors.push("database.connect_attempts must be at least 1".to_string());
+        }
+        if database.timeout_secs == 0 {
+            errors.push("database.timeout_secs must be at least 1".to_string());
+        }
+
+        if let Some(series) = &self.offline.series {
+            if series.is_empty() || series.len() > 12 || !series.chars().all(|c| c.is_ascii_alphanumeric()) {
+                errors.push("offline.series (OFFLINE_SERIES) must be 1-12 letters or digits".to_string());
+            }
+        }
+        if self.offline.sync_interval_secs == 0 {
+            errors.push("offline.sync_interval_secs must be at least 1".to_string());
+        }
+
+        let smtp = &self.smtp;
+        let given = [&smtp.server, &smtp.user, &smtp.password].iter().filter(|v| v.is_some()).count();
+        if given > 0 && smtp.account().is_err() {
+            errors.push("smtp needs server, user and password together".to_string());
+        }
+        if smtp.user.as_deref().is_some_and(|user| !user.contains('@')) {
+            errors.push("smtp.user (SMTP_USER) must be the sender's email address".to_string());
+        }
+        if smtp.port == 0 {
+            errors.push("smtp.port must be a port number".to_string());
+        }
+
+        if self.sms.provider != "file" {
+            errors.push(format!("sms.provider '{}' is not supported: use file", self.sms.provider));
+        }
+        if self.drafts.hold_minutes <= 0 {
+            errors.push("drafts.hold_minutes must be at least 1".to_string());
+        }
+
+        let printing = &self.printing;
+        if let Some(Err(e)) = printing.receipt_printer.as_deref().map(ReceiptTarget::parse) {
+            errors.push(format!("printing.receipt_printer: {}", e));
+        }
+        if let Err(e) = PaperWidth::from_mm(printing.receipt_width_mm) {
+            errors.push(format!("printing.receipt_width_mm: {}", e));
+        }
+        if let Err(e) = PageSize::parse(&printing.invoice_page_size) {
+            errors.push(format!("printing.invoice_page_size: {}", e));
+        }
+
+        for (name, dir) in self.output.dirs() {
+            match dir.as_deref().map(str::trim) {
+                Some("") => errors.push(format!("output.{} must not be empty; leave it out for the default", name)),
+                Some(dir) if PathBuf::from(dir).is_file() => {
+                    errors.push(format!("output.{} ({}) is a file, not a directory", name, dir))
+                }
+                _ => {}
+            }
+        }
+        errors
+    }
+}
+
+/// `CATON_CONFIG` if set, otherwise `caton.toml` in the app data directory.
+pub fn config_path() -> PathBuf {
+    if let Some(path) = env::var_os("CATON_CONFIG").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    let var = |key: &str| env::var_os(key).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let data_dir = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    };
+    data_dir.unwrap_or_default().join(APP_DIR).join(CONFIG_FILE)
+}
+
+/// Reads and checks the settings; a missing file means all defaults.
+pub fn load() -> Result<AppConfig, String> {
+    dotenv::dotenv().ok();
+    let path = config_path();
+    let mut config = match std::fs::read_to_string(&path) {
+        Ok(text) => AppConfig::from_toml(&text).map_err(|e| format!("Invalid settings in {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => AppConfig::default(),
+        Err(e) => return Err(format!("Failed to read settings from {}: {}", path.display(), e)),
+    };
+
+    let mut errors = config.apply_env(&|key| env::var(key).ok());
+    errors.extend(config.validate());
+    if !errors.is_empty() {
+        return Err(format!("Invalid settings ({} and the environment):\n  - {}", path.display(), errors.join("\n  - ")));
+    }
+    config.offline.series = config.offline.series.map(|series| series.to_uppercase());
+    Ok(config)
+}
+
+/// Loads the settings once at startup.
+pub fn init() -> Result<&'static AppConfig, String> {
+    let config = load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The settings loaded by `init`; defaults before that, as in tests.
+pub fn get() -> &'static AppConfig {
+    CONFIG.get_or_init(AppConfig::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn with_env(config: &mut AppConfig, vars: &[(&str, &str)]) -> Vec<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        config.apply_env(&|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn file_settings_are_overridden_by_the_environment() {
+        let mut config = AppConfig::from_toml(
+            r#"
+            [database]
+            backend = "sqlite"
+            sqlite_path = "/data/caton.db"
+
+            [smtp]
+            server = "smtp.example.com"
+            user = "clinic@example.com"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.database.backend, DbBackend::Sqlite);
+        assert_eq!(config.offline.sync_interval_secs, 30);
+        assert_eq!(config.validate(), ["smtp needs server, user and password together"]);
+
+        let errors = with_env(&mut config, &[("SMTP_PASSWORD", "secret"), ("SMTP_PORT", "465"), ("SQLITE_PATH", " ")]);
+        assert!(errors.is_empty());
+        assert!(config.validate().is_empty());
+        assert_eq!((config.smtp.port, config.database.sqlite_path.as_str()), (465, "/data/caton.db"));
+        assert!(!format!("{:?}", config.smtp).contains("secret"));
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        assert!(AppConfig::from_toml("[database]\nbackend = \"mysql\"").is_err());
+        assert!(AppConfig::from_toml("[smtp]\nhost = \"smtp.example.com\"").is_err());
+
+        let mut config = AppConfig::default();
+        let errors = with_env(&mut config, &[("SMTP_PORT", "smtp"), ("DB_BACKEND", "mysql")]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].starts_with("SMTP_PORT must be a port number"));
+
+        with_env(&mut config, &[("MONGODB_URL", "localhost:27017"), ("RECEIPT_WIDTH", "72"), ("OFFLINE_SERIES", "C-1")]);
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("database.mongodb_url must start with"));
+    }
+
+    #[test]
+    fn output_directories_come_from_the_file_or_the_environment() {
+        let mut config = AppConfig::from_toml("[output]\ninvoices = \"/srv/bills\"\nstatements = \"\"").unwrap();
+        assert_eq!(config.validate().len(), 2); // MongoDB URL, empty statements directory
+
+        with_env(&mut config, &[("STATEMENT_DIR", "/srv/statements"), ("Z_REPORT_DIR", "/srv/z")]);
+        assert_eq!(config.output.invoices.as_deref(), Some("/srv/bills"));
+        assert_eq!(config.output.statements.as_deref(), Some("/srv/statements"));
+        assert_eq!((config.output.z_reports.as_deref(), config.output.prescriptions.as_deref()), (Some("/srv/z"), None));
+
+        config.output.invoices = Some(file!().to_string());
+        assert!(config.validate().iter().any(|e| e.contains("is a file")));
+    }
+}
@@ -0,0 +1,724 @@
+// src-tauri/src/credit.rs
+use crate::billing::{next_document_number, run_transaction, utc_day_range, TxnError, DEFAULT_INVOICE_PREFIX};
+use crate::config;
+use crate::dates;
+use crate::db::DbState;
+use crate::model::User;
+use crate::money::Money;
+use crate::pdf::{output_dir, safe_file_name, PageSize, PdfCanvas};
+use crate::payment::PaymentMode;
+use crate::shift::touch_open_shift;
+use crate::tax::{to_paise, to_rupees};
+use crate::user::{find_hospital, users_collection};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, to_bson};
+use mongodb::options::FindOptions;
+use mongodb::{ClientSession, Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{command, AppHandle, State};
+
+/// What happens when a sale would take a customer past their limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditLimitPolicy {
+    Block,
+    Warn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditCustomer {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub name: String,
+    pub mobile: String,
+    pub address: Option<String>,
+    pub credit_limit: Money,
+    pub limit_policy: CreditLimitPolicy,
+    pub balance: Money, // outstanding; only changed together with a ledger entry
+    pub active: bool,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CreditCustomerResponse {
+    pub id: String,
+    pub name: String,
+    pub mobile: String,
+    pub address: Option<String>,
+    pub credit_limit: f64,
+    pub limit_policy: CreditLimitPolicy,
+    pub balance: f64,
+    pub active: bool,
+}
+
+impl From<CreditCustomer> for CreditCustomerResponse {
+    fn from(customer: CreditCustomer) -> Self {
+        CreditCustomerResponse {
+            id: customer.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: customer.name,
+            mobile: customer.mobile,
+            address: customer.address,
+            credit_limit: customer.credit_limit.rupees(),
+            limit_policy: customer.limit_policy,
+            balance: customer.balance.rupees(),
+            active: customer.active,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerKind {
+    Sale,
+    Receipt,
+    CreditNote,
+}
+
+/// One movement on a customer's account. Positive amounts are owed by the
+/// customer (sales), negative ones settle it (receipts, credit notes).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub customer_id: String,
+    pub kind: LedgerKind,
+    pub reference: String, // invoice, receipt or credit note number
+    pub amount: Money,
+    pub balance_after: Money,
+    pub mode: Option<PaymentMode>, // receipts only
+    pub payment_reference: Option<String>,
+    #[serde(default)]
+    pub shift_id: Option<String>,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LedgerEntryResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub customer_id: String,
+    pub kind: LedgerKind,
+    pub reference: String,
+    pub amount: f64,
+    pub balance_after: f64,
+    pub mode: Option<PaymentMode>,
+    pub payment_reference: Option<String>,
+    pub shift_id: Option<String>,
+    pub date_created: DateTime<Utc>,
+}
+
+impl From<LedgerEntry> for LedgerEntryResponse {
+    fn from(entry: LedgerEntry) -> Self {
+        LedgerEntryResponse {
+            id: entry.id,
+            customer_id: entry.customer_id,
+            kind: entry.kind,
+            reference: entry.reference,
+            amount: entry.amount.rupees(),
+            balance_after: entry.balance_after.rupees(),
+            mode: entry.mode,
+            payment_reference: entry.payment_reference,
+            shift_id: entry.shift_id,
+            date_created: dates::to_utc(entry.date_created),
+        }
+    }
+}
+
+fn customers_collection(db: &Database) -> Collection<CreditCustomer> {
+    db.collection("credit_customers")
+}
+
+pub(crate) fn ledger_collection(db: &Database) -> Collection<LedgerEntry> {
+    db.collection("credit_ledger")
+}
+
+pub(crate) async fn find_credit_customer(
+    db: &Database,
+    session: &mut ClientSession,
+    customer_id: &str,
+    hospital_id: &str,
+) -> Result<CreditCustomer, TxnError> {
+    let object_id =
+        ObjectId::parse_str(customer_id).map_err(|_| TxnError::Rejected("Invalid customer ID".to_string()))?;
+    customers_collection(db)
+        .find_one_with_session(doc! { "_id": object_id, "hospital_id": hospital_id }, None, session)
+        .await?
+        .ok_or_else(|| TxnError::Rejected("Credit customer not found".to_string()))
+}
+
+/// A movement to post to a customer's account. `amount` is in paise.
+pub(crate) struct Posting<'a> {
+    pub customer_id: &'a str,
+    pub kind: LedgerKind,
+    pub reference: &'a str,
+    pub amount: i64,
+    pub mode: Option<PaymentMode>,
+    pub payment_reference: Option<String>,
+    pub shift_id: Option<String>,
+}
+
+/// Posts to a customer's account inside the caller's transaction and
+/// records it in the ledger. Sales are checked against the credit limit:
+/// refused for `Block` customers, and reported back as `true` for `Warn`
+/// customers so the counter can be told.
+pub(crate) async fn post_to_account(
+    db: &Database,
+    session: &mut ClientSession,
+    hospital_id: &str,
+    posting: Posting<'_>,
+) -> Result<(LedgerEntry, bool), TxnError> {
+    let Posting { customer_id, kind, reference, amount, mode, payment_reference, shift_id } = posting;
+    let customer = find_credit_customer(db, session, customer_id, hospital_id).await?;
+    let balance = customer.balance.paise() + amount;
+    let over_limit = check_credit_limit(&customer, kind, balance).map_err(TxnError::Rejected)?;
+
+    // Balances are rewritten from paise rather than $inc'd so they don't drift
+    customers_collection(db)
+        .update_one_with_session(
+            doc! { "_id": customer.id, "hospital_id": hospital_id },
+            doc! { "$set": { "balance": balance } },
+            None,
+            session,
+        )
+        .await?;
+
+    let mut entry = LedgerEntry {
+        id: None,
+        hospital_id: hospital_id.to_string(),
+        customer_id: customer_id.to_string(),
+        kind,
+        reference: reference.to_string(),
+        amount: Money::from_paise(amount),
+        balance_after: Money::from_paise(balance),
+        mode,
+        payment_reference,
+        shift_id,
+        date_created: bson::DateTime::now(),
+    };
+    let result = ledger_collection(db)
+        .insert_one_with_session(&entry, None, session)
+        .await?;
+    entry.id = result.inserted_id.as_object_id();
+    Ok((entry, over_limit))
+}
+
+/// Whether a posting of `kind` that leaves the customer owing `balance`
+/// paise is allowed, and if so whether it goes over their limit. Only sales
+/// are checked; money coming back is always taken.
+fn check_credit_limit(customer: &CreditCustomer, kind: LedgerKind, balance: i64) -> Result<bool, String> {
+    if kind != LedgerKind::Sale {
+        return Ok(false);
+    }
+    if !customer.active {
+        return Err(format!("Credit for {} has been stopped.", customer.name));
+    }
+    if balance <= customer.credit_limit.paise() {
+        return Ok(false);
+    }
+    match customer.limit_policy {
+        CreditLimitPolicy::Block => Err(format!(
+            "This sale takes {} to {:.2}, over their credit limit of {:.2}.",
+            customer.name,
+            to_rupees(balance),
+            customer.credit_limit.rupees()
+        )),
+        CreditLimitPolicy::Warn => Ok(true),
+    }
+}
+
+/// Outstanding balance split by age: 0-30, 31-60, 61-90 and over 90 days.
+/// Receipts and credit notes settle the oldest sales first; money paid in
+/// advance shows as a negative amount in the first bucket.
+pub fn age_balance(entries: &[(NaiveDate, i64)], today: NaiveDate) -> [i64; 4] {
+    let mut open: Vec<(NaiveDate, i64)> = Vec::new();
+    let mut advance = 0;
+    for &(date, amount) in entries {
+        if amount > 0 {
+            let settled = advance.min(amount);
+            advance -= settled;
+            if amount > settled {
+                open.push((date, amount - settled));
+            }
+            continue;
+        }
+        let mut credit = -amount;
+        for sale in open.iter_mut() {
+            let settled = credit.min(sale.1);
+            sale.1 -= settled;
+            credit -= settled;
+            if credit == 0 {
+                break;
+            }
+        }
+        open.retain(|sale| sale.1 > 0);
+        advance += credit;
+    }
+
+    let mut buckets = [0; 4];
+    for (date, amount) in open {
+        let bucket = match (today - date).num_days() {
+            ..=30 => 0,
+            31..=60 => 1,
+            61..=90 => 2,
+            _ => 3,
+        };
+        buckets[bucket] += amount;
+    }
+    buckets[0] -= advance;
+    buckets
+}
+
+fn local_date(date: bson::DateTime) -> NaiveDate {
+    dates::to_utc(date).with_timezone(&Local).date_naive()
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AgeingRow {
+    pub customer_id: String,
+    pub name: String,
+    pub mobile: String,
+    pub balance: f64,
+    pub days_0_30: f64,
+    pub days_31_60: f64,
+    pub days_61_90: f64,
+    pub days_over_90: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CreditStatement {
+    pub customer: CreditCustomerResponse,
+    pub from_date: String,
+    pub to_date: String,
+    pub opening_balance: f64,
+    pub entries: Vec<LedgerEntryResponse>,
+    pub closing_balance: f64,
+}
+
+/// Creates or, when `customer_id` is given, updates a credit customer. The
+/// balance only moves through sales, receipts and credit notes.
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_credit_customer(
+    customer_id: Option<String>,
+    name: String,
+    mobile: String,
+    address: Option<String>,
+    credit_limit: f64,
+    limit_policy: CreditLimitPolicy,
+    active: bool,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    if name.trim().is_empty() {
+        return Err("Customer name is required.".to_string());
+    }
+    if !credit_limit.is_finite() || credit_limit < 0.0 {
+        return Err("Credit limit cannot be negative.".to_string());
+    }
+    let address = address.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    let collection = customers_collection(&db);
+    match customer_id {
+        Some(customer_id) => {
+            let object_id = ObjectId::parse_str(&customer_id).map_err(|_| "Invalid customer ID".to_string())?;
+            let update = doc! {
+                "$set": {
+                    "name": name.trim(),
+                    "mobile": mobile.trim(),
+                    "address": address,
+                    "credit_limit": Money::from_rupees(credit_limit).paise(),
+                    "limit_policy": to_bson(&limit_policy).map_err(|e| e.to_string())?,
+                    "active": active,
+                }
+            };
+            let result = collection
+                .update_one(doc! { "_id": object_id, "hospital_id": &hospital_id }, update, None)
+                .await
+                .map_err(|e| format!("Database update error: {}", e))?;
+            if result.matched_count == 0 {
+                return Err("Credit customer not found".to_string());
+            }
+            Ok(customer_id)
+        }
+        None => {
+            let customer = CreditCustomer {
+                id: None,
+                hospital_id,
+                name: name.trim().to_string(),
+                mobile: mobile.trim().to_string(),
+                address,
+                credit_limit: Money::from_rupees(credit_limit),
+                limit_policy,
+                balance: Money::default(),
+                active,
+                date_created: bson::DateTime::now(),
+            };
+            let result = collection
+                .insert_one(customer, None)
+                .await
+                .map_err(|e| format!("Database insert error: {}", e))?;
+            Ok(result.inserted_id.as_object_id().map(|id| id.to_hex()).unwrap_or_default())
+        }
+    }
+}
+
+#[command]
+pub async fn get_credit_customers(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<CreditCustomerResponse>, String> {
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "name": 1 }).build();
+    let customers: Vec<CreditCustomer> = customers_collection(&db)
+        .find(doc! { "hospital_id": hospital_id }, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing credit customers: {}", e))?;
+
+    Ok(customers.into_iter().map(CreditCustomerResponse::from).collect())
+}
+
+/// Records money received against a customer's account and returns the
+/// ledger entry with its receipt number.
+#[command]
+pub async fn record_credit_receipt(
+    customer_id: String,
+    amount: f64,
+    mode: PaymentMode,
+    reference: Option<String>,
+    shift_id: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<LedgerEntryResponse, String> {
+    if !amount.is_finite() || to_paise(amount) <= 0 {
+        return Err("Receipt amount must be more than zero.".to_string());
+    }
+    if mode == PaymentMode::Credit {
+        return Err("A receipt has to be paid in cash, UPI or card.".to_string());
+    }
+    let reference = reference.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    let hospital = find_hospital(&users_collection(&db), &hospital_id).await?;
+    let prefix = format!(
+        "{}-RC",
+        hospital.invoice_prefix.unwrap_or_else(|| DEFAULT_INVOICE_PREFIX.to_string())
+    );
+
+    run_transaction(&db_state.mongo("credit accounts")?.client, |session| {
+        let db = db.clone();
+        let (customer_id, hospital_id, prefix, reference, shift_id) =
+            (customer_id.clone(), hospital_id.clone(), prefix.clone(), reference.clone(), shift_id.clone());
+        Box::pin(async move {
+            let number =
+                next_document_number(&db, session, &hospital_id, "receipt", &prefix, Local::now().date_naive())
+                    .await?;
+            if let Some(shift_id) = &shift_id {
+                touch_open_shift(&db, session, shift_id, &hospital_id).await?;
+            }
+            let posting = Posting {
+                customer_id: &customer_id,
+                kind: LedgerKind::Receipt,
+                reference: &number,
+                amount: -to_paise(amount),
+                mode: Some(mode),
+                payment_reference: reference,
+                shift_id,
+            };
+            let (entry, _) = post_to_account(&db, session, &hospital_id, posting).await?;
+            Ok(entry)
+        })
+    })
+    .await
+    .map(LedgerEntryResponse::from)
+}
+
+/// Outstanding balances by age for every customer who owes something.
+#[command]
+pub async fn get_credit_ageing(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<AgeingRow>, String> {
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    let customers: Vec<CreditCustomer> = customers_collection(&db)
+        .find(doc! { "hospital_id": &hospital_id, "balance": { "$ne": 0_i64 } }, None)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing credit customers: {}", e))?;
+
+    let find_options = FindOptions::builder().sort(doc! { "date_created": 1 }).build();
+    let entries: Vec<LedgerEntry> = ledger_collection(&db)
+        .find(doc! { "hospital_id": &hospital_id }, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing ledger: {}", e))?;
+
+    let mut by_customer: HashMap<&str, Vec<(NaiveDate, i64)>> = HashMap::new();
+    for entry in &entries {
+        by_customer
+            .entry(entry.customer_id.as_str())
+            .or_default()
+            .push((local_date(entry.date_created), entry.amount.paise()));
+    }
+
+    let today = Local::now().date_naive();
+    let mut rows: Vec<AgeingRow> = customers
+        .into_iter()
+        .map(|customer| {
+            let id = customer.id.map(|id| id.to_hex()).unwrap_or_default();
+            let buckets = by_customer
+                .get(id.as_str())
+                .map(|entries| age_balance(entries, today))
+                .unwrap_or_default();
+            AgeingRow {
+                customer_id: id,
+                name: customer.name,
+                mobile: customer.mobile,
+                balance: customer.balance.rupees(),
+                days_0_30: to_rupees(buckets[0]),
+                days_31_60: to_rupees(buckets[1]),
+                days_61_90: to_rupees(buckets[2]),
+                days_over_90: to_rupees(buckets[3]),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.balance.total_cmp(&a.balance));
+    Ok(rows)
+}
+
+async fn load_statement(
+    db: &Database,
+    customer_id: &str,
+    from_date: &str,
+    to_date: &str,
+    hospital_id: &str,
+) -> Result<CreditStatement, String> {
+    let (from, to) = utc_day_range(from_date, to_date)?;
+    let object_id = ObjectId::parse_str(customer_id).map_err(|_| "Invalid customer ID".to_string())?;
+    let customer = customers_collection(db)
+        .find_one(doc! { "_id": object_id, "hospital_id": hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Credit customer not found".to_string())?;
+
+    let find_options = FindOptions::builder().sort(doc! { "date_created": 1 }).build();
+    let entries: Vec<LedgerEntry> = ledger_collection(db)
+        .find(
+            doc! { "hospital_id": hospital_id, "customer_id": customer_id, "date_created": { "$lt": &to } },
+            find_options,
+        )
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing ledger: {}", e))?;
+
+    let (before, entries): (Vec<LedgerEntry>, Vec<LedgerEntry>) =
+        entries.into_iter().partition(|e| e.date_created < from);
+    let opening: Money = before.iter().map(|e| e.amount).sum();
+    let closing = opening + entries.iter().map(|e| e.amount).sum();
+
+    Ok(CreditStatement {
+        customer: CreditCustomerResponse::from(customer),
+        from_date: from_date.to_string(),
+        to_date: to_date.to_string(),
+        opening_balance: opening.rupees(),
+        entries: entries.into_iter().map(LedgerEntryResponse::from).collect(),
+        closing_balance: closing.rupees(),
+    })
+}
+
+/// A customer's account movements between two dates (inclusive,
+/// `YYYY-MM-DD`) with opening and closing balances.
+#[command]
+pub async fn get_credit_statement(
+    customer_id: String,
+    from_date: String,
+    to_date: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<CreditStatement, String> {
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    load_statement(&db, &customer_id, &from_date, &to_date, &hospital_id).await
+}
+
+fn render_statement(statement: &CreditStatement, hospital: &User) -> Result<PdfCanvas, String> {
+    let mut pdf = PdfCanvas::new("Statement of Account", PageSize::A4)?;
+    let date = |value: &DateTime<Utc>| value.with_timezone(&Local).format("%d %b %Y").to_string();
+
+    pdf.text_centered(&hospital.hospital, 16.0, true);
+    pdf.text_centered(&hospital.address, 10.0, false);
+    if let Some(gstin) = &hospital.gstin {
+        pdf.text_centered(&format!("GSTIN: {}", gstin), 10.0, false);
+    }
+    pdf.gap(2.0);
+    pdf.rule();
+    pdf.text_centered("Statement of Account", 13.0, true);
+    pdf.gap(2.0);
+
+    let customer = &statement.customer;
+    pdf.text_at(&customer.name, 11.0, pdf.left(), true);
+    pdf.text_right(
+        &format!("{} to {}", statement.from_date, statement.to_date),
+        10.0,
+        pdf.right(),
+        false,
+    );
+    pdf.newline(11.0);
+    pdf.text(&format!("Mobile: {}", customer.mobile), 10.0, false);
+    if let Some(address) = &customer.address {
+        pdf.paragraph(address, 10.0, 0.0);
+    }
+    pdf.gap(2.0);
+    pdf.rule();
+
+    let (left, right) = (pdf.left(), pdf.right());
+    let columns = [left, left + 28.0, left + 62.0];
+    let heading = |pdf: &mut PdfCanvas| {
+        pdf.ensure_space(8.0);
+        pdf.text_at("Date", 10.0, columns[0], true);
+        pdf.text_at("Particulars", 10.0, columns[1], true);
+        pdf.text_at("Reference", 10.0, columns[2], true);
+        pdf.text_right("Debit", 10.0, right - 60.0, true);
+        pdf.text_right("Credit", 10.0, right - 30.0, true);
+        pdf.text_right("Balance", 10.0, right, true);
+        pdf.newline(10.0);
+    };
+    heading(&mut pdf);
+
+    pdf.text_at("Opening balance", 10.0, columns[1], false);
+    pdf.text_right(&format!("{:.2}", statement.opening_balance), 10.0, right, false);
+    pdf.newline(10.0);
+
+    let mut balance = to_paise(statement.opening_balance);
+    for entry in &statement.entries {
+        balance += to_paise(entry.amount);
+        let particulars = match entry.kind {
+            LedgerKind::Sale => "Sale",
+            LedgerKind::Receipt => "Receipt",
+            LedgerKind::CreditNote => "Credit note",
+        };
+        pdf.ensure_space(6.0);
+        pdf.text_at(&date(&entry.date_created), 10.0, columns[0], false);
+        pdf.text_at(particulars, 10.0, columns[1], false);
+        pdf.text_at(&entry.reference, 10.0, columns[2], false);
+        let column = if entry.amount >= 0.0 { right - 60.0 } else { right - 30.0 };
+        pdf.text_right(&format!("{:.2}", entry.amount.abs()), 10.0, column, false);
+        pdf.text_right(&format!("{:.2}", to_rupees(balance)), 10.0, right, false);
+        pdf.newline(10.0);
+    }
+
+    pdf.rule();
+    pdf.text_at("Closing balance", 11.0, columns[1], true);
+    pdf.text_right(&format!("{:.2}", statement.closing_balance), 11.0, right, true);
+    pdf.newline(11.0);
+
+    Ok(pdf)
+}
+
+/// Writes the statement as a PDF and returns its path.
+#[command]
+pub async fn export_credit_statement(
+    customer_id: String,
+    from_date: String,
+    to_date: String,
+    hospital_id: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let db = db_state.mongo("credit accounts")?.db.clone();
+    let statement = load_statement(&db, &customer_id, &from_date, &to_date, &hospital_id).await?;
+    let hospital = find_hospital(&users_collection(&db), &hospital_id).await?;
+
+    let pdf = render_statement(&statement, &hospital)?;
+    let path = output_dir(&app, &config::get().output.statements, "statements")?.join(format!(
+        "statement_{}_{}_{}.pdf",
+        safe_file_name(&statement.customer.name),
+        from_date,
+        to_date
+    ));
+    pdf.save(&path)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn customer(credit_limit: f64, balance: f64, limit_policy: CreditLimitPolicy) -> CreditCustomer {
+        CreditCustomer {
+            id: Some(ObjectId::new()),
+            hospital_id: "h1".to_string(),
+            name: "Ramesh Kumar".to_string(),
+            mobile: "9876543210".to_string(),
+            address: None,
+            credit_limit: Money::from_rupees(credit_limit),
+            limit_policy,
+            balance: Money::from_rupees(balance),
+            active: true,
+            date_created: bson::DateTime::now(),
+        }
+    }
+
+    #[test]
+    fn balances_are_bucketed_by_the_age_of_open_sales() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let ago = |days| today - Duration::days(days);
+        let entries = [(ago(120), 40000), (ago(91), 10000), (ago(61), 30000), (ago(31), 20000), (ago(0), 5000)];
+        assert_eq!(age_balance(&entries, today), [5000, 20000, 30000, 50000]);
+
+        // The edges: 30, 60 and 90 days old still fall in the younger bucket
+        let edges = [(ago(30), 100), (ago(60), 200), (ago(90), 300)];
+        assert_eq!(age_balance(&edges, today), [100, 200, 300, 0]);
+    }
+
+    #[test]
+    fn receipts_settle_the_oldest_sales_first() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let ago = |days| today - Duration::days(days);
+        let entries = [(ago(95), 40000), (ago(65), 30000), (ago(35), 20000), (ago(10), -45000), (ago(2), 5000)];
+        assert_eq!(age_balance(&entries, today), [5000, 20000, 25000, 0]);
+
+        // Paying more than is owed leaves an advance, which the next sale uses up first
+        let advance = [(ago(40), 10000), (ago(20), -15000)];
+        assert_eq!(age_balance(&advance, today), [-5000, 0, 0, 0]);
+        let used = [(ago(40), 10000), (ago(20), -15000), (ago(1), 8000)];
+        assert_eq!(age_balance(&used, today), [3000, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sales_over_the_limit_are_blocked_or_flagged_by_policy() {
+        let blocked = customer(1000.0, 900.0, CreditLimitPolicy::Block);
+        assert_eq!(check_credit_limit(&blocked, LedgerKind::Sale, 100000), Ok(false)); // exactly at the limit
+        assert_eq!(
+            check_credit_limit(&blocked, LedgerKind::Sale, 100001),
+            Err("This sale takes Ramesh Kumar to 1000.01, over their credit limit of 1000.00.".to_string())
+        );
+        // Money coming back is taken whatever the balance
+        assert_eq!(check_credit_limit(&blocked, LedgerKind::Receipt, 150000), Ok(false));
+
+        let warned = customer(1000.0, 900.0, CreditLimitPolicy::Warn);
+        assert_eq!(check_credit_limit(&warned, LedgerKind::Sale, 125000), Ok(true));
+
+        let stopped = CreditCustomer { active: false, ..customer(1000.0, 0.0, CreditLimitPolicy::Warn) };
+        assert_eq!(
+            check_credit_limit(&stopped, LedgerKind::Sale, 1000),
+            Err("Credit for Ramesh Kumar has been stopped.".to_string())
+        );
+    }
+}
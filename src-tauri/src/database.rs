@@ -0,0 +1,151 @@
+// src-tauri/src/database.rs
+use crate::config::DatabaseSettings;
+use mongodb::bson::{doc, Document};
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Database};
+use std::time::{Duration, Instant};
+
+const DEFAULT_DATABASE: &str = "caton";
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+
+/// Where and how to reach MongoDB, from the `[database]` settings.
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub uri: String,
+    pub name: Option<String>, // falls back to the database in the URI, then "caton"
+    pub connect_attempts: u32,
+    pub server_selection_timeout: Duration,
+}
+
+impl DatabaseConfig {
+    pub fn from_settings(settings: &DatabaseSettings) -> Result<Self, String> {
+        let uri = settings
+            .mongodb_url
+            .clone()
+            .ok_or_else(|| "MONGODB_URL must be set in caton.toml or .env".to_string())?;
+        Ok(DatabaseConfig {
+            uri,
+            name: settings.mongodb_db.clone().filter(|n| !n.trim().is_empty()),
+            connect_attempts: settings.connect_attempts.max(1),
+            server_selection_timeout: Duration::from_secs(settings.timeout_secs.max(1)),
+        })
+    }
+}
+
+/// The host part of a connection string, for messages that must not show
+/// the password.
+pub fn redacted_host(uri: &str) -> &str {
+    let rest = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+/// Round-trips a `ping` to the server and returns how long it took.
+pub async fn ping(db: &Database) -> Result<Duration, String> {
+    let started = Instant::now();
+    db.run_command(doc! { "ping": 1 }, None)
+        .await
+        .map_err(|e| format!("MongoDB did not answer: {}", e))?;
+    Ok(started.elapsed())
+}
+
+/// Builds the client without contacting the server; the driver connects
+/// on first use.
+pub async fn client(config: &DatabaseConfig) -> Result<(Client, Database), String> {
+    let host = redacted_host(&config.uri);
+    let mut options = ClientOptions::parse(&config.uri)
+        .await
+        .map_err(|e| format!("Invalid MONGODB_URL for {}: {}", host, e))?;
+    options.app_name = Some("caton".to_string());
+    options.server_selection_timeout = Some(config.server_selection_timeout);
+
+    let name = config
+        .name
+        .clone()
+        .or_else(|| options.default_database.clone())
+        .unwrap_or_else(|| DEFAULT_DATABASE.to_string());
+    let client = Client::with_options(options).map_err(|e| format!("Failed to create MongoDB client: {}", e))?;
+    let db = client.database(&name);
+    Ok((client, db))
+}
+
+/// Builds the one client the app uses and checks the server answers,
+/// retrying with a doubling delay. The driver pools connections, so the
+/// client is shared by every command rather than opened per call.
+pub async fn connect(config: &DatabaseConfig) -> Result<(Client, Database), String> {
+    let host = redacted_host(&config.uri);
+    let (client, db) = client(config).await?;
+
+    let mut delay = Duration::from_secs(1);
+    let mut last_error = String::new();
+    for attempt in 1..=config.connect_attempts {
+        match ping(&db).await {
+            Ok(_) => return Ok((client, db)),
+            Err(error) => {
+                eprintln!("MongoDB at {} unreachable (attempt {}/{}): {}", host, attempt, config.connect_attempts, error);
+                last_error = error;
+            }
+        }
+        if attempt < config.connect_attempts {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+
+    Err(format!(
+        "Could not reach MongoDB at {} after {} attempts. Check MONGODB_URL in .env and the network connection. Last error: {}",
+        host, config.connect_attempts, last_error
+    ))
+}
+
+/// Billing, returns, credit accounts and shifts write several documents in
+/// one transaction, which MongoDB only runs on a replica set or through
+/// mongos. Fails with what to do about it on a standalone server.
+pub async fn check_transactions(config: &DatabaseConfig, db: &Database) -> Result<(), String> {
+    let hello = db
+        .run_command(doc! { "hello": 1 }, None)
+        .await
+        .map_err(|e| format!("MongoDB did not answer: {}", e))?;
+    transactions_supported(&hello, redacted_host(&config.uri))
+}
+
+fn transactions_supported(hello: &Document, host: &str) -> Result<(), String> {
+    let replica_set = hello.get_str("setName").is_ok();
+    let mongos = hello.get_str("msg") == Ok("isdbgrid");
+    if replica_set || mongos {
+        return Ok(());
+    }
+    Err(format!(
+        "MongoDB at {} is a standalone server, but billing needs transactions, which only a replica set \
+         has. Restart mongod with --replSet rs0 and run rs.initiate() once in mongosh; a single-node \
+         replica set is enough.",
+        host
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standalone_servers_are_refused() {
+        let standalone = doc! { "isWritablePrimary": true, "maxWireVersion": 21 };
+        let error = transactions_supported(&standalone, "localhost:27017").unwrap_err();
+        assert!(error.starts_with("MongoDB at localhost:27017 is a standalone server"), "{}", error);
+        assert!(error.contains("--replSet"));
+
+        let member = doc! { "isWritablePrimary": true, "setName": "rs0", "hosts": ["localhost:27017"] };
+        assert_eq!(transactions_supported(&member, "localhost:27017"), Ok(()));
+        let mongos = doc! { "isWritablePrimary": true, "msg": "isdbgrid" };
+        assert_eq!(transactions_supported(&mongos, "router:27017"), Ok(()));
+    }
+
+    #[test]
+    fn host_hides_credentials() {
+        assert_eq!(
+            redacted_host("mongodb+srv://user:p@ss@cluster0.abcd.mongodb.net/?retryWrites=true"),
+            "cluster0.abcd.mongodb.net"
+        );
+        assert_eq!(redacted_host("mongodb://localhost:27017/caton"), "localhost:27017");
+    }
+}
@@ -0,0 +1,160 @@
+// src-tauri/src/dates.rs
+//! BSON dates on stored documents. Medicines, appointments, accounts and
+//! the patient queue used to keep dates as free-form strings, so reading
+//! also accepts the old string forms until the typed-field migrations have
+//! rewritten them.
+use crate::error::AppError;
+use chrono::{DateTime, NaiveDate, Utc};
+use mongodb::bson::{self, Bson};
+use serde::{Deserialize, Deserializer};
+
+pub fn to_utc(date: bson::DateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(date.timestamp_millis()).unwrap_or_default()
+}
+
+pub fn from_utc(date: DateTime<Utc>) -> bson::DateTime {
+    bson::DateTime::from_millis(date.timestamp_millis())
+}
+
+/// A calendar date as stored: midnight UTC.
+pub fn midnight(date: NaiveDate) -> bson::DateTime {
+    from_utc(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+/// Reads a date the way the stock screens used to save it: `YYYY-MM-DD`,
+/// a full RFC 3339 timestamp, or `DD-MM-YYYY` / `DD/MM/YYYY`.
+pub fn parse_legacy_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    ["%Y-%m-%d", "%d-%m-%Y", "%d/%m/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .or_else(|| DateTime::parse_from_rfc3339(text).ok().map(|d| d.date_naive()))
+}
+
+pub fn parse_legacy_timestamp(text: &str) -> Option<bson::DateTime> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|d| from_utc(d.with_timezone(&Utc)))
+}
+
+/// Validates a date entered at the command boundary.
+pub fn date_field(field: &str, text: &str) -> Result<NaiveDate, AppError> {
+    parse_legacy_date(text)
+        .ok_or_else(|| AppError::invalid(field, &format!("Invalid {}: {}", field.replace('_', " "), text.trim())))
+}
+
+fn bson_to_timestamp<E: serde::de::Error>(value: Bson) -> Result<bson::DateTime, E> {
+    match value {
+        Bson::DateTime(date) => Ok(date),
+        Bson::String(text) => {
+            parse_legacy_timestamp(&text).ok_or_else(|| E::custom(format!("invalid timestamp: {}", text)))
+        }
+        other => Err(E::custom(format!("expected a date, found {}", other))),
+    }
+}
+
+/// `#[serde(deserialize_with)]` for a `bson::DateTime` that may still be an
+/// RFC 3339 string.
+pub fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bson::DateTime, D::Error> {
+    bson_to_timestamp(Bson::deserialize(deserializer)?)
+}
+
+pub fn optional_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bson::DateTime>, D::Error> {
+    match Option::<Bson>::deserialize(deserializer)? {
+        None | Some(Bson::Null) => Ok(None),
+        Some(value) => bson_to_timestamp(value).map(Some),
+    }
+}
+
+/// `#[serde(with)]` for a `NaiveDate` stored as a BSON date.
+pub mod calendar {
+    use super::*;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&midnight(*date), serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        bson_to_date(Bson::deserialize(deserializer)?)
+    }
+}
+
+/// `#[serde(with)]` for an optional `NaiveDate` stored as a BSON date.
+pub mod optional_calendar {
+    use super::*;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(date: &Option<NaiveDate>, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&date.map(midnight), serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+        match Option::<Bson>::deserialize(deserializer)? {
+            None | Some(Bson::Null) => Ok(None),
+            Some(value) => bson_to_date(value).map(Some),
+        }
+    }
+}
+
+fn bson_to_date<E: serde::de::Error>(value: Bson) -> Result<NaiveDate, E> {
+    match value {
+        Bson::DateTime(date) => Ok(to_utc(date).date_naive()),
+        Bson::String(text) => parse_legacy_date(&text).ok_or_else(|| E::custom(format!("invalid date: {}", text))),
+        other => Err(E::custom(format!("expected a date, found {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use serde::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Batch {
+        #[serde(with = "calendar")]
+        expiry_date: NaiveDate,
+        #[serde(deserialize_with = "timestamp")]
+        date_created: bson::DateTime,
+    }
+
+    #[test]
+    fn dates_are_stored_as_bson_dates_and_legacy_strings_still_read() {
+        let expiry = NaiveDate::from_ymd_opt(2027, 3, 31).unwrap();
+        let created = parse_legacy_timestamp("2026-10-19T10:30:00+05:30").unwrap();
+        let batch = Batch { expiry_date: expiry, date_created: created };
+
+        let stored = bson::to_document(&batch).unwrap();
+        assert_eq!(stored.get_datetime("expiry_date").unwrap(), &midnight(expiry));
+        assert_eq!(bson::from_document::<Batch>(stored).unwrap(), batch);
+        let json = serde_json::to_string(&batch).unwrap();
+        assert_eq!(serde_json::from_str::<Batch>(&json).unwrap(), batch);
+
+        let legacy = doc! { "expiry_date": "31/03/2027", "date_created": "2026-10-19T05:00:00+00:00" };
+        assert_eq!(bson::from_document::<Batch>(legacy).unwrap(), batch);
+        assert!(bson::from_document::<Batch>(doc! { "expiry_date": "soon", "date_created": "" }).is_err());
+    }
+
+    #[test]
+    fn optional_dates_read_missing_null_typed_and_legacy_values() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Visit {
+            #[serde(default, with = "optional_calendar")]
+            follow_up_date: Option<NaiveDate>,
+        }
+        let review = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
+
+        let stored = bson::to_document(&Visit { follow_up_date: Some(review) }).unwrap();
+        assert_eq!(stored.get_datetime("follow_up_date").unwrap(), &midnight(review));
+        let read = |document| bson::from_document::<Visit>(document).map(|v| v.follow_up_date);
+        assert_eq!(read(stored).unwrap(), Some(review));
+        assert_eq!(read(doc! { "follow_up_date": "2026-10-26" }).unwrap(), Some(review));
+        assert_eq!(read(doc! { "follow_up_date": Bson::Null }).unwrap(), None);
+        assert_eq!(read(doc! {}).unwrap(), None);
+        assert!(read(doc! { "follow_up_date": "next week" }).is_err());
+
+        let json = serde_json::to_string(&Visit { follow_up_date: Some(review) }).unwrap();
+        assert_eq!(serde_json::from_str::<Visit>(&json).unwrap().follow_up_date, Some(review));
+    }
+}
@@ -0,0 +1,189 @@
+// src-tauri/src/db.rs
+use crate::billing::invoices_collection;
+use crate::config::{self, DbBackend};
+use crate::commands::{appointments_collection, medicines_collection};
+use crate::database::{check_transactions, client, connect, ping, DatabaseConfig};
+use crate::offline::OfflineStore;
+use crate::discount::rules_collection;
+use crate::repository::{
+    AppointmentRepository, DiscountRuleRepository, InvoiceRepository, MedicineRepository, UserRepository,
+};
+use crate::sqlite::SqliteStore;
+use crate::user::users_collection;
+use mongodb::{Client, Database};
+use serde::Serialize;
+use tauri::{command, State};
+
+
+/// A MongoDB deployment. `client` is there for transactions, which need a
+/// session started from it; `offline` takes over stock, billing and
+/// appointments while the server cannot be reached.
+#[derive(Clone)]
+pub struct MongoDb {
+    pub client: Client,
+    pub db: Database,
+    pub offline: OfflineStore,
+}
+
+/// Features the SQLite backend does not have yet. They are still to be
+/// ported; until then `check_db_health` lists them so the UI can hide them.
+pub const NOT_ON_SQLITE: &[&str] = &[
+    "shifts",
+    "returns",
+    "credit accounts",
+    "draft bills",
+    "appointment scheduling",
+    "the patient queue",
+    "reminders",
+    "prescriptions",
+];
+
+/// The shared database handle, held in Tauri managed state. `DB_BACKEND`
+/// picks MongoDB (the default) or a local SQLite file for pharmacies that
+/// run offline on a single counter. SQLite covers stock, visits and their
+/// clinical notes, accounts, settings, discount rules, invoices paid in
+/// cash, UPI or card and the payment summary, but not [`NOT_ON_SQLITE`]
+/// (see [`DbState::mongo`]).
+#[derive(Clone)]
+pub enum DbState {
+    Mongo(MongoDb),
+    Sqlite(SqliteStore),
+}
+
+impl DbState {
+    /// For features only the MongoDB backend has ([`NOT_ON_SQLITE`]), and
+    /// for those that need the server while it is up. Fails fast, naming
+    /// `feature`, on SQLite or while the server is unreachable.
+    pub fn mongo(&self, feature: &str) -> Result<&MongoDb, String> {
+        match self {
+            DbState::Mongo(mongo) if mongo.offline.is_online() => Ok(mongo),
+            DbState::Mongo(_) => {
+                Err(format!("Unavailable until the connection to MongoDB is restored: {}.", feature))
+            }
+            DbState::Sqlite(_) => Err(format!("Not available on the SQLite backend yet: {}.", feature)),
+        }
+    }
+
+    /// The MongoDB backend whether or not it is reachable right now.
+    pub fn mongo_state(&self) -> Result<&MongoDb, String> {
+        match self {
+            DbState::Mongo(mongo) => Ok(mongo),
+            DbState::Sqlite(_) => Err("Not supported on the SQLite backend: offline sync.".to_string()),
+        }
+    }
+
+    /// The offline store while MongoDB is configured but unreachable.
+    fn offline(&self) -> Option<&OfflineStore> {
+        match self {
+            DbState::Mongo(mongo) if !mongo.offline.is_online() => Some(&mongo.offline),
+            _ => None,
+        }
+    }
+
+    pub fn medicines(&self) -> Box<dyn MedicineRepository> {
+        if let Some(offline) = self.offline() {
+            return Box::new(offline.clone());
+        }
+        match self {
+            DbState::Mongo(mongo) => Box::new(medicines_collection(&mongo.db)),
+            DbState::Sqlite(store) => Box::new(store.clone()),
+        }
+    }
+
+    pub fn appointments(&self) -> Box<dyn AppointmentRepository> {
+        if let Some(offline) = self.offline() {
+            return Box::new(offline.clone());
+        }
+        match self {
+            DbState::Mongo(mongo) => Box::new(appointments_collection(&mongo.db)),
+            DbState::Sqlite(store) => Box::new(store.clone()),
+        }
+    }
+
+    pub fn users(&self) -> Box<dyn UserRepository> {
+        if let Some(offline) = self.offline() {
+            return Box::new(offline.clone());
+        }
+        match self {
+            DbState::Mongo(mongo) => Box::new(users_collection(&mongo.db)),
+            DbState::Sqlite(store) => Box::new(store.clone()),
+        }
+    }
+
+    pub fn invoices(&self) -> Box<dyn InvoiceRepository> {
+        if let Some(offline) = self.offline() {
+            return Box::new(offline.clone());
+        }
+        match self {
+            DbState::Mongo(mongo) => Box::new(invoices_collection(&mongo.db)),
+            DbState::Sqlite(store) => Box::new(store.clone()),
+        }
+    }
+
+    /// Discount rules; on MongoDB they are edited online only, offline
+    /// bills use the copy taken at the last sync.
+    pub fn discount_rules(&self) -> Result<Box<dyn DiscountRuleRepository>, String> {
+        match self {
+            DbState::Sqlite(store) => Ok(Box::new(store.clone())),
+            DbState::Mongo(_) => Ok(Box::new(rules_collection(&self.mongo("discount rules")?.db))),
+        }
+    }
+}
+
+/// Connects once at startup, retrying while MongoDB comes up, or opens the
+/// SQLite file (`database.sqlite_path`) and migrates it. If MongoDB cannot
+/// be reached but the offline store has a replica, the app starts offline
+/// and syncs once the server answers. A standalone MongoDB server is
+/// refused, since billing needs transactions.
+pub async fn init_db() -> Result<DbState, String> {
+    let settings = config::get();
+    match settings.database.backend {
+        DbBackend::Mongodb => {
+            let config = DatabaseConfig::from_settings(&settings.database)?;
+            let offline = OfflineStore::from_settings(&settings.offline)?;
+            let (client, db) = match connect(&config).await {
+                Ok((client, db)) => {
+                    check_transactions(&config, &db).await?;
+                    (client, db)
+                }
+                Err(error) if offline.has_replica() => {
+                    eprintln!("{} Starting offline.", error);
+                    offline.set_online(false);
+                    client(&config).await?
+                }
+                Err(error) => return Err(error),
+            };
+            Ok(DbState::Mongo(MongoDb { client, db, offline }))
+        }
+        DbBackend::Sqlite => Ok(DbState::Sqlite(SqliteStore::open(&settings.database.sqlite_path).await?)),
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DbHealth {
+    pub ok: bool,
+    pub database: String,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    pub unsupported: Vec<&'static str>, // features this backend does not have
+}
+
+/// Pings the server (or the SQLite file) so the UI can show whether the
+/// database is reachable.
+#[command]
+pub async fn check_db_health(db_state: State<'_, DbState>) -> Result<DbHealth, String> {
+    let (database, result, unsupported) = match db_state.inner() {
+        DbState::Mongo(mongo) => {
+            let result = ping(&mongo.db).await;
+            mongo.offline.set_online(result.is_ok());
+            (mongo.db.name().to_string(), result, Vec::new())
+        }
+        DbState::Sqlite(store) => (store.path().to_string(), store.ping().await, NOT_ON_SQLITE.to_vec()),
+    };
+    Ok(match result {
+        Ok(latency) => {
+            DbHealth { ok: true, database, latency_ms: Some(latency.as_millis() as u64), error: None, unsupported }
+        }
+        Err(error) => DbHealth { ok: false, database, latency_ms: None, error: Some(error), unsupported },
+    })
+}
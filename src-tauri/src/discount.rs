@@ -0,0 +1,477 @@
+// src-tauri/src/discount.rs
+use crate::commands::{Medicine, MedicineDetail};
+use crate::db::DbState;
+use crate::model::User;
+use crate::money::Money;
+use crate::tax::{compute_invoice_tax, div_round, load_bill_medicines, tax_line_for, to_basis_points, to_paise, to_rupees};
+use crate::tax::{InvoiceTax, SupplyType};
+use crate::user::verify_admin_password;
+use mongodb::bson::{self, oid::ObjectId};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscountKind {
+    Percent,
+    Flat, // rupees off the line or bill
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Discount {
+    pub kind: DiscountKind,
+    pub value: f64,
+}
+
+impl Discount {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.value.is_finite() || self.value < 0.0 {
+            return Err("Discount cannot be negative.".to_string());
+        }
+        if self.kind == DiscountKind::Percent && self.value > 100.0 {
+            return Err("Discount cannot be more than 100%.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Paise off an amount of `gross` paise, never more than the amount.
+    pub fn amount_on(&self, gross: i64) -> i64 {
+        let amount = match self.kind {
+            DiscountKind::Percent => div_round(gross * to_basis_points(self.value), 10_000),
+            DiscountKind::Flat => to_paise(self.value),
+        };
+        amount.clamp(0, gross.max(0))
+    }
+}
+
+/// An automatic discount. Every condition that is set has to hold; a rule
+/// naming a medicine is a product scheme and applies to that item's lines,
+/// any other rule applies to the whole bill.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscountRule {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub name: String,
+    pub customer_category: Option<String>, // e.g. "senior_citizen"
+    pub min_bill_value: Option<Money>,
+    pub medicine_name: Option<String>,
+    pub discount: Discount,
+    pub active: bool,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DiscountRuleResponse {
+    pub id: String,
+    pub name: String,
+    pub customer_category: Option<String>,
+    pub min_bill_value: Option<f64>,
+    pub medicine_name: Option<String>,
+    pub discount: Discount,
+    pub active: bool,
+}
+
+impl From<DiscountRule> for DiscountRuleResponse {
+    fn from(rule: DiscountRule) -> Self {
+        DiscountRuleResponse {
+            id: rule.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: rule.name,
+            customer_category: rule.customer_category,
+            min_bill_value: rule.min_bill_value.map(Money::rupees),
+            medicine_name: rule.medicine_name,
+            discount: rule.discount,
+            active: rule.active,
+        }
+    }
+}
+
+pub(crate) fn rules_collection(db: &Database) -> Collection<DiscountRule> {
+    db.collection("discount_rules")
+}
+
+impl DiscountRule {
+    fn applies(&self, customer_category: Option<&str>, bill_gross: i64) -> bool {
+        let category_ok = match (&self.customer_category, customer_category) {
+            (None, _) => true,
+            (Some(wanted), Some(given)) => wanted.eq_ignore_ascii_case(given.trim()),
+            (Some(_), None) => false,
+        };
+        let minimum_ok = self.min_bill_value.is_none_or(|min| bill_gross >= min.paise());
+        self.active && category_ok && minimum_ok
+    }
+
+    fn is_scheme_for(&self, medicine_name: &str) -> bool {
+        self.medicine_name
+            .as_deref()
+            .is_some_and(|name| name.trim().eq_ignore_ascii_case(medicine_name.trim()))
+    }
+}
+
+/// The rule giving the most off `amount`, if any gives anything.
+fn best_rule<'r>(rules: impl Iterator<Item = &'r &'r DiscountRule>, amount: i64) -> Option<(i64, &'r DiscountRule)> {
+    rules
+        .map(|rule| (rule.discount.amount_on(amount), *rule))
+        .filter(|(off, _)| *off > 0)
+        .max_by_key(|(off, _)| *off)
+}
+
+/// One bill line as far as discounting is concerned. Amounts in rupees.
+pub struct DiscountLine {
+    pub name: String,
+    pub unit_price: f64,
+    pub purchase_price: f64,
+    pub quantity: u32,
+    pub discount: Option<Discount>, // entered at the counter
+}
+
+#[derive(Debug, Clone)]
+pub struct AppliedDiscounts {
+    pub line_discounts: Vec<i64>, // paise per line, including its share of the bill discount
+    pub applied_rules: Vec<String>,
+    pub below_cost: Vec<String>, // items a counter discount takes below purchase price
+}
+
+/// Works out the discount on each line.
+///
+/// Discounts typed in at the counter win over automatic rules; among rules
+/// the largest one applies. The bill discount is taken off what is left
+/// after line discounts and shared out over the lines in proportion, so the
+/// GST on each line is charged on what was actually paid for it.
+///
+/// No line may end up below its purchase price. Automatic rules are quietly
+/// held back at that floor; counter discounts that cross it are reported in
+/// `below_cost` for the caller to refuse or allow with an admin override.
+pub fn apply_discounts(
+    lines: &[DiscountLine],
+    bill_discount: Option<Discount>,
+    rules: &[DiscountRule],
+    customer_category: Option<&str>,
+) -> AppliedDiscounts {
+    let gross: Vec<i64> = lines.iter().map(|l| to_paise(l.unit_price) * l.quantity as i64).collect();
+    let bill_gross: i64 = gross.iter().sum();
+    let matching: Vec<&DiscountRule> = rules.iter().filter(|r| r.applies(customer_category, bill_gross)).collect();
+    let mut applied_rules: Vec<String> = Vec::new();
+
+    // Line discounts: counter entry, else the best product scheme
+    let mut manual = vec![false; lines.len()];
+    let mut line_off = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let off = match line.discount {
+            Some(discount) => {
+                manual[i] = true;
+                discount.amount_on(gross[i])
+            }
+            None => match best_rule(matching.iter().filter(|r| r.is_scheme_for(&line.name)), gross[i]) {
+                Some((off, rule)) => {
+                    if !applied_rules.contains(&rule.name) {
+                        applied_rules.push(rule.name.clone());
+                    }
+                    off
+                }
+                None => 0,
+            },
+        };
+        line_off.push(off);
+    }
+
+    // Bill discount: counter entry, else the best bill-wide rule
+    let subtotal: i64 = gross.iter().zip(&line_off).map(|(g, d)| g - d).sum();
+    let (bill_off, bill_manual) = match bill_discount {
+        Some(discount) => (discount.amount_on(subtotal), true),
+        None => match best_rule(matching.iter().filter(|r| r.medicine_name.is_none()), subtotal) {
+            Some((off, rule)) => {
+                applied_rules.push(rule.name.clone());
+                (off, false)
+            }
+            None => (0, false),
+        },
+    };
+
+    // Share the bill discount out by cumulative rounding so it adds up exactly
+    let mut running = 0;
+    let mut below_cost = Vec::new();
+    let mut line_discounts = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let net = gross[i] - line_off[i];
+        let share = if subtotal > 0 {
+            div_round(bill_off * (running + net), subtotal) - div_round(bill_off * running, subtotal)
+        } else {
+            0
+        };
+        running += net;
+
+        let mut off = line_off[i] + share;
+        let floor = to_paise(line.purchase_price) * line.quantity as i64;
+        if off > 0 && gross[i] - off < floor {
+            if manual[i] || (bill_manual && share > 0) {
+                below_cost.push(line.name.clone());
+            } else {
+                off = (gross[i] - floor).max(0);
+            }
+        }
+        line_discounts.push(off);
+    }
+
+    AppliedDiscounts { line_discounts, applied_rules, below_cost }
+}
+
+/// Lets a bill through with counter discounts below purchase price only on
+/// the admin password. `true` when an override was needed.
+pub(crate) fn authorize_below_cost(
+    below_cost: &[String],
+    hospital: &User,
+    admin_password: Option<&str>,
+) -> Result<bool, String> {
+    if below_cost.is_empty() {
+        return Ok(false);
+    }
+    verify_admin_password(hospital, admin_password)
+        .map_err(|e| format!("Discount takes {} below purchase price. {}", below_cost.join(", "), e))?;
+    Ok(true)
+}
+
+/// A priced bill: medicines in item order with their tax breakdown.
+pub(crate) struct PricedBill {
+    pub medicines: Vec<Medicine>,
+    pub tax: InvoiceTax,
+    pub applied_rules: Vec<String>,
+    pub below_cost: Vec<String>,
+}
+
+/// Prices a bill with discounts and tax; shared by the preview and by
+/// invoice creation so both always agree.
+pub(crate) async fn price_bill(
+    db_state: &DbState,
+    items: &[MedicineDetail],
+    bill_discount: Option<Discount>,
+    customer_category: Option<&str>,
+    supply: SupplyType,
+    hospital_id: &str,
+) -> Result<PricedBill, String> {
+    for discount in items.iter().filter_map(|i| i.discount.as_ref()).chain(bill_discount.as_ref()) {
+        discount.validate()?;
+    }
+
+    let medicines = load_bill_medicines(&*db_state.medicines(), items, hospital_id).await?;
+    // Offline bills use the rules copied at the last sync
+    let rules = match db_state {
+        DbState::Mongo(mongo) if !mongo.offline.is_online() => mongo.offline.discount_rules(hospital_id),
+        _ => db_state.discount_rules()?.list_rules(hospital_id, true).await?,
+    };
+
+    Ok(price_items(medicines, items, bill_discount, &rules, customer_category, supply))
+}
+
+/// Applies discounts and tax to loaded medicines, one per item.
+pub(crate) fn price_items(
+    medicines: Vec<Medicine>,
+    items: &[MedicineDetail],
+    bill_discount: Option<Discount>,
+    rules: &[DiscountRule],
+    customer_category: Option<&str>,
+    supply: SupplyType,
+) -> PricedBill {
+    let lines: Vec<DiscountLine> = medicines
+        .iter()
+        .zip(items)
+        .map(|(medicine, item)| DiscountLine {
+            name: medicine.name.clone(),
+            unit_price: medicine.selling_price.rupees(),
+            purchase_price: medicine.purchase_price.rupees(),
+            quantity: item.quantity,
+            discount: item.discount,
+        })
+        .collect();
+    let applied = apply_discounts(&lines, bill_discount, rules, customer_category);
+
+    let tax_lines: Vec<_> = medicines
+        .iter()
+        .zip(items)
+        .zip(&applied.line_discounts)
+        .map(|((medicine, item), off)| tax_line_for(medicine, item.quantity, to_rupees(*off)))
+        .collect();
+
+    PricedBill {
+        medicines,
+        tax: compute_invoice_tax(&tax_lines, supply),
+        applied_rules: applied.applied_rules,
+        below_cost: applied.below_cost,
+    }
+}
+
+/// Creates or, when `rule_id` is given, replaces a discount rule.
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_discount_rule(
+    rule_id: Option<String>,
+    name: String,
+    customer_category: Option<String>,
+    min_bill_value: Option<f64>,
+    medicine_name: Option<String>,
+    discount: Discount,
+    active: bool,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    if name.trim().is_empty() {
+        return Err("Rule name is required.".to_string());
+    }
+    discount.validate()?;
+    if min_bill_value.is_some_and(|v| v < 0.0) {
+        return Err("Minimum bill value cannot be negative.".to_string());
+    }
+
+    let clean = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let rule = DiscountRule {
+        id: None,
+        hospital_id: hospital_id.clone(),
+        name: name.trim().to_string(),
+        customer_category: clean(customer_category),
+        min_bill_value: min_bill_value.map(Money::from_rupees),
+        medicine_name: clean(medicine_name),
+        discount,
+        active,
+        date_created: bson::DateTime::now(),
+    };
+
+    let rules = db_state.discount_rules()?;
+    match rule_id {
+        Some(rule_id) => {
+            let object_id = ObjectId::parse_str(&rule_id).map_err(|_| "Invalid rule ID".to_string())?;
+            if !rules.replace_rule(&DiscountRule { id: Some(object_id), ..rule }).await? {
+                return Err("Discount rule not found".to_string());
+            }
+            Ok(rule_id)
+        }
+        None => rules.insert_rule(rule).await.map(|id| id.to_hex()),
+    }
+}
+
+#[command]
+pub async fn get_discount_rules(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<DiscountRuleResponse>, String> {
+    let rules = db_state.discount_rules()?.list_rules(&hospital_id, false).await?;
+    Ok(rules.into_iter().map(DiscountRuleResponse::from).collect())
+}
+
+#[command]
+pub async fn delete_discount_rule(
+    rule_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let object_id = ObjectId::parse_str(&rule_id).map_err(|_| "Invalid rule ID".to_string())?;
+
+    if !db_state.discount_rules()?.delete_rule(object_id, &hospital_id).await? {
+        return Err("Discount rule not found".to_string());
+    }
+    Ok("Discount rule deleted".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(name: &str, price: f64, cost: f64, quantity: u32, discount: Option<Discount>) -> DiscountLine {
+        DiscountLine { name: name.to_string(), unit_price: price, purchase_price: cost, quantity, discount }
+    }
+
+    fn percent(value: f64) -> Discount {
+        Discount { kind: DiscountKind::Percent, value }
+    }
+
+    fn flat(value: f64) -> Discount {
+        Discount { kind: DiscountKind::Flat, value }
+    }
+
+    fn rule(name: &str, medicine_name: Option<&str>, discount: Discount) -> DiscountRule {
+        DiscountRule {
+            id: None,
+            hospital_id: "h1".to_string(),
+            name: name.to_string(),
+            customer_category: None,
+            min_bill_value: None,
+            medicine_name: medicine_name.map(str::to_string),
+            discount,
+            active: true,
+            date_created: bson::DateTime::now(),
+        }
+    }
+
+    #[test]
+    fn a_counter_discount_wins_over_the_product_scheme() {
+        let rules = [
+            rule("Amoxicillin scheme", Some("amoxicillin "), percent(20.0)),
+            rule("Bromhexine scheme", Some("Bromhexine"), percent(10.0)),
+        ];
+        let lines = [line("Amoxicillin", 100.0, 60.0, 1, Some(percent(5.0))), line("Bromhexine", 50.0, 30.0, 2, None)];
+
+        let applied = apply_discounts(&lines, None, &rules, None);
+        assert_eq!(applied.line_discounts, [500, 1000]);
+        assert_eq!(applied.applied_rules, ["Bromhexine scheme"]);
+        assert!(applied.below_cost.is_empty());
+    }
+
+    #[test]
+    fn rules_stop_at_the_purchase_price() {
+        let lines = [line("Insulin", 100.0, 90.0, 2, None)];
+
+        let scheme = apply_discounts(&lines, None, &[rule("Insulin scheme", Some("Insulin"), percent(25.0))], None);
+        assert_eq!(scheme.line_discounts, [2000]);
+        assert_eq!(scheme.applied_rules, ["Insulin scheme"]);
+        assert!(scheme.below_cost.is_empty());
+
+        let bill_wide = apply_discounts(&lines, None, &[rule("Flat 25", None, flat(25.0))], None);
+        assert_eq!(bill_wide.line_discounts, [2000]);
+        assert!(bill_wide.below_cost.is_empty());
+
+        let mut big_bills = rule("Big bills", None, percent(50.0));
+        big_bills.min_bill_value = Some(Money::from_rupees(200.01));
+        assert_eq!(apply_discounts(&lines, None, &[big_bills], None).line_discounts, [0]);
+    }
+
+    #[test]
+    fn counter_discounts_below_cost_need_the_admin_password() {
+        let lines = [line("Insulin", 100.0, 90.0, 2, Some(flat(30.0))), line("Cotton", 20.0, 5.0, 1, None)];
+        let applied = apply_discounts(&lines, None, &[], None);
+        assert_eq!(applied.line_discounts, [3000, 0]);
+        assert_eq!(applied.below_cost, ["Insulin"]);
+
+        let by_bill = apply_discounts(&lines[..1], Some(flat(25.0)), &[], None);
+        assert_eq!(by_bill.below_cost, ["Insulin"]);
+
+        let hospital: User = serde_json::from_value(serde_json::json!({
+            "username": "city", "name": "Dr Rao", "mobile": "9800000000", "hospital": "City Clinic",
+            "address": "MG Road", "password_hash_doc": bcrypt::hash("doc-pass", 4).unwrap(),
+            "password_hash_pharma": "", "email": "rao@example.com",
+        }))
+        .unwrap();
+        let refused = authorize_below_cost(&applied.below_cost, &hospital, None).unwrap_err();
+        assert_eq!(refused, "Discount takes Insulin below purchase price. Admin password is required.");
+        assert!(authorize_below_cost(&applied.below_cost, &hospital, Some("pharma-pass")).is_err());
+        assert_eq!(authorize_below_cost(&applied.below_cost, &hospital, Some("doc-pass")), Ok(true));
+        assert_eq!(authorize_below_cost(&[], &hospital, None), Ok(false));
+    }
+
+    #[test]
+    fn a_bill_discount_is_shared_over_the_lines_to_the_paisa() {
+        let lines = [
+            line("Paracetamol", 10.0, 1.0, 1, None),
+            line("Cetirizine", 10.0, 1.0, 1, None),
+            line("ORS", 10.0, 1.0, 1, None),
+        ];
+        let applied = apply_discounts(&lines, Some(flat(10.0)), &[], None);
+        assert_eq!(applied.line_discounts, [333, 334, 333]);
+
+        // Taken off what is left after the line discounts
+        let lines = [line("Paracetamol", 10.0, 1.0, 3, Some(flat(10.0))), line("ORS", 10.0, 1.0, 1, None)];
+        let applied = apply_discounts(&lines, Some(percent(10.0)), &[], None);
+        assert_eq!(applied.line_discounts, [1000 + 200, 100]);
+        assert!(applied.below_cost.is_empty());
+    }
+}
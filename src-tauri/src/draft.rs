@@ -0,0 +1,393 @@
+// src-tauri/src/draft.rs
+use crate::billing::{merge_items, run_transaction, TxnError};
+use crate::commands::{Medicine, MedicineDetail};
+use crate::config;
+use crate::db::DbState;
+use crate::discount::Discount;
+use chrono::{Duration, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use mongodb::{ClientSession, Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{command, State};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftStatus {
+    Open,
+    Parked, // set aside while another customer is served
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DraftLine {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub quantity: u32,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+}
+
+/// A bill in progress, kept server-side so it survives navigation and
+/// several carts can be open at once. With `reserve` set, its quantities
+/// are held against other bills until `reserved_until`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub label: String,
+    pub customer_name: Option<String>,
+    pub customer_mobile: Option<String>,
+    pub appointment_id: Option<String>,
+    pub status: DraftStatus,
+    pub lines: Vec<DraftLine>,
+    pub reserve: bool,
+    pub reserved_until: Option<String>,
+    pub date_created: String,
+    pub last_updated: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DraftResponse {
+    pub id: String,
+    pub label: String,
+    pub customer_name: Option<String>,
+    pub customer_mobile: Option<String>,
+    pub appointment_id: Option<String>,
+    pub status: DraftStatus,
+    pub lines: Vec<DraftLine>,
+    pub reserve: bool,
+    pub reserved_until: Option<String>,
+    pub date_created: String,
+    pub last_updated: String,
+    pub short_items: Vec<String>, // lines that could not be held; the draft is then not reserved
+}
+
+impl DraftResponse {
+    fn new(draft: Draft, short_items: Vec<String>) -> Self {
+        DraftResponse {
+            id: draft.id.map(|id| id.to_hex()).unwrap_or_default(),
+            label: draft.label,
+            customer_name: draft.customer_name,
+            customer_mobile: draft.customer_mobile,
+            appointment_id: draft.appointment_id,
+            status: draft.status,
+            lines: draft.lines,
+            reserve: draft.reserve,
+            reserved_until: draft.reserved_until,
+            date_created: draft.date_created,
+            last_updated: draft.last_updated,
+            short_items,
+        }
+    }
+}
+
+pub(crate) fn drafts_collection(db: &Database) -> Collection<Draft> {
+    db.collection("drafts")
+}
+
+/// How long a reservation lasts after the draft was last touched
+/// (`drafts.hold_minutes`, default 30).
+fn hold_minutes() -> i64 {
+    config::get().drafts.hold_minutes
+}
+
+async fn find_draft(db: &Database, draft_id: &str, hospital_id: &str) -> Result<Draft, String> {
+    let object_id = ObjectId::parse_str(draft_id).map_err(|_| "Invalid draft ID".to_string())?;
+    drafts_collection(db)
+        .find_one(doc! { "_id": object_id, "hospital_id": hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Draft not found".to_string())
+}
+
+/// Quantities of each batch held by unexpired reservations on drafts other
+/// than `except`, keyed by medicine ID.
+pub(crate) async fn held_by_others(
+    db: &Database,
+    session: &mut ClientSession,
+    hospital_id: &str,
+    medicine_ids: &[String],
+    except: Option<ObjectId>,
+) -> Result<HashMap<String, u32>, TxnError> {
+    let mut filter = doc! {
+        "hospital_id": hospital_id,
+        "reserved_until": { "$gt": Utc::now().to_rfc3339() },
+        "lines.medicine_id": { "$in": medicine_ids },
+    };
+    if let Some(except) = except {
+        filter.insert("_id", doc! { "$ne": except });
+    }
+    let mut cursor = drafts_collection(db).find_with_session(filter, None, session).await?;
+    let drafts: Vec<Draft> = cursor.stream(session).try_collect().await?;
+
+    let mut held = HashMap::new();
+    for line in drafts.iter().flat_map(|d| &d.lines) {
+        if medicine_ids.contains(&line.medicine_id) {
+            *held.entry(line.medicine_id.clone()).or_insert(0) += line.quantity;
+        }
+    }
+    Ok(held)
+}
+
+/// Checks each line against stock less what other drafts hold, and returns
+/// the lines that don't fit. Bumps every batch it looks at so a concurrent
+/// reservation or sale of the same batch conflicts and is retried rather
+/// than both going through on the same units.
+async fn check_reservation(
+    db: &Database,
+    session: &mut ClientSession,
+    draft: &Draft,
+) -> Result<Vec<String>, TxnError> {
+    let ids: Vec<String> = draft.lines.iter().map(|l| l.medicine_id.clone()).collect();
+    let held = held_by_others(db, session, &draft.hospital_id, &ids, draft.id).await?;
+    let medicines = db.collection::<Medicine>("medicines");
+
+    let mut short = Vec::new();
+    for line in &draft.lines {
+        let object_id = ObjectId::parse_str(&line.medicine_id)
+            .map_err(|_| TxnError::Rejected("Invalid medicine ID".to_string()))?;
+        let filter = doc! { "_id": object_id, "user_id": &draft.hospital_id };
+        let medicine = medicines
+            .find_one_with_session(filter.clone(), None, session)
+            .await?
+            .ok_or_else(|| TxnError::Rejected(format!("{} is no longer in stock.", line.name)))?;
+        medicines
+            .update_one_with_session(filter, doc! { "$inc": { "reservation_seq": 1 } }, None, session)
+            .await?;
+
+        let free = medicine.quantity.saturating_sub(held.get(&line.medicine_id).copied().unwrap_or(0));
+        if line.quantity > free {
+            short.push(format!("{} (batch {}): {} free", line.name, line.batch_number, free));
+        }
+    }
+    Ok(short)
+}
+
+/// Stores `draft`, renewing its reservation when it holds stock and is
+/// open. A draft whose lines can't all be held is saved unreserved.
+async fn save_draft(db_state: &DbState, mut draft: Draft) -> Result<(Draft, Vec<String>), String> {
+    let now = Utc::now();
+    draft.last_updated = now.to_rfc3339();
+    let hold_until = (now + Duration::minutes(hold_minutes())).to_rfc3339();
+
+    let mongo = db_state.mongo("draft bills")?;
+    run_transaction(&mongo.client, |session| {
+        let db = mongo.db.clone();
+        let mut draft = draft.clone();
+        let hold_until = hold_until.clone();
+        Box::pin(async move {
+            let mut short = Vec::new();
+            if draft.reserve && draft.status == DraftStatus::Open && !draft.lines.is_empty() {
+                short = check_reservation(&db, session, &draft).await?;
+                draft.reserved_until = short.is_empty().then_some(hold_until);
+            } else if !draft.reserve || draft.lines.is_empty() {
+                draft.reserved_until = None;
+            }
+
+            let collection = drafts_collection(&db);
+            match draft.id {
+                Some(id) => {
+                    let result = collection
+                        .replace_one_with_session(
+                            doc! { "_id": id, "hospital_id": &draft.hospital_id },
+                            &draft,
+                            None,
+                            session,
+                        )
+                        .await?;
+                    if result.matched_count == 0 {
+                        return Err(TxnError::Rejected("Draft not found".to_string()));
+                    }
+                }
+                None => {
+                    let result = collection.insert_one_with_session(&draft, None, session).await?;
+                    draft.id = result.inserted_id.as_object_id();
+                }
+            }
+            Ok((draft, short))
+        })
+    })
+    .await
+}
+
+/// Opens a new cart. `reserve` holds its items against other bills for
+/// `DRAFT_HOLD_MINUTES` after each change.
+#[command]
+pub async fn create_draft(
+    label: Option<String>,
+    customer_name: Option<String>,
+    customer_mobile: Option<String>,
+    appointment_id: Option<String>,
+    reserve: bool,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<DraftResponse, String> {
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let label = match label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty()) {
+        Some(label) => label,
+        None => {
+            let open = drafts_collection(&db)
+                .count_documents(doc! { "hospital_id": &hospital_id }, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            format!("Bill {}", open + 1)
+        }
+    };
+
+    let now = Utc::now().to_rfc3339();
+    let draft = Draft {
+        id: None,
+        hospital_id,
+        label,
+        customer_name: customer_name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()),
+        customer_mobile: customer_mobile.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        appointment_id,
+        status: DraftStatus::Open,
+        lines: Vec::new(),
+        reserve,
+        reserved_until: None,
+        date_created: now.clone(),
+        last_updated: now,
+    };
+    let (draft, short) = save_draft(&db_state, draft).await?;
+    Ok(DraftResponse::new(draft, short))
+}
+
+/// Replaces a draft's lines and customer details. Lines may be empty while
+/// the cart is being built.
+#[command]
+pub async fn update_draft(
+    draft_id: String,
+    items: Vec<MedicineDetail>,
+    customer_name: Option<String>,
+    customer_mobile: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<DraftResponse, String> {
+    let items = if items.is_empty() { items } else { merge_items(items)? };
+
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let mut draft = find_draft(&db, &draft_id, &hospital_id).await?;
+
+    let ids: Vec<ObjectId> = items
+        .iter()
+        .map(|item| ObjectId::parse_str(&item.id).map_err(|_| "Invalid medicine ID".to_string()))
+        .collect::<Result<_, _>>()?;
+    let medicines: Vec<Medicine> = db
+        .collection::<Medicine>("medicines")
+        .find(doc! { "_id": { "$in": ids }, "user_id": &hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut lines = Vec::with_capacity(items.len());
+    for item in items {
+        let medicine = medicines
+            .iter()
+            .find(|m| m.id.map(|id| id.to_hex()).as_deref() == Some(item.id.as_str()))
+            .ok_or_else(|| format!("Medicine {} not found", item.id))?;
+        if let Some(discount) = &item.discount {
+            discount.validate()?;
+        }
+        lines.push(DraftLine {
+            medicine_id: item.id,
+            name: medicine.name.clone(),
+            batch_number: medicine.batch_number.clone(),
+            quantity: item.quantity,
+            discount: item.discount,
+        });
+    }
+
+    draft.lines = lines;
+    if let Some(name) = customer_name {
+        draft.customer_name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+    }
+    if let Some(mobile) = customer_mobile {
+        draft.customer_mobile = Some(mobile.trim().to_string()).filter(|m| !m.is_empty());
+    }
+    let (draft, short) = save_draft(&db_state, draft).await?;
+    Ok(DraftResponse::new(draft, short))
+}
+
+/// Sets a cart aside. Its reservation runs on until it expires.
+#[command]
+pub async fn park_draft(
+    draft_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<DraftResponse, String> {
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let mut draft = find_draft(&db, &draft_id, &hospital_id).await?;
+    draft.status = DraftStatus::Parked;
+    draft.last_updated = Utc::now().to_rfc3339();
+
+    drafts_collection(&db)
+        .update_one(
+            doc! { "_id": draft.id, "hospital_id": &hospital_id },
+            doc! { "$set": { "status": "parked", "last_updated": &draft.last_updated } },
+            None,
+        )
+        .await
+        .map_err(|e| format!("Failed to park draft: {}", e))?;
+    Ok(DraftResponse::new(draft, Vec::new()))
+}
+
+/// Brings a parked cart back and renews its reservation. If stock has gone
+/// in the meantime, `short_items` says which lines no longer fit.
+#[command]
+pub async fn resume_draft(
+    draft_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<DraftResponse, String> {
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let mut draft = find_draft(&db, &draft_id, &hospital_id).await?;
+    draft.status = DraftStatus::Open;
+    let (draft, short) = save_draft(&db_state, draft).await?;
+    Ok(DraftResponse::new(draft, short))
+}
+
+/// Throws a cart away and releases anything it held.
+#[command]
+pub async fn discard_draft(
+    draft_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<(), String> {
+    let object_id = ObjectId::parse_str(&draft_id).map_err(|_| "Invalid draft ID".to_string())?;
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let result = drafts_collection(&db)
+        .delete_one(doc! { "_id": object_id, "hospital_id": hospital_id }, None)
+        .await
+        .map_err(|e| format!("Failed to discard draft: {}", e))?;
+    if result.deleted_count == 0 {
+        return Err("Draft not found".to_string());
+    }
+    Ok(())
+}
+
+/// Open and parked carts, most recently touched first.
+#[command]
+pub async fn get_drafts(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<DraftResponse>, String> {
+    let db = db_state.mongo("draft bills")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "last_updated": -1 }).build();
+    let drafts: Vec<Draft> = drafts_collection(&db)
+        .find(doc! { "hospital_id": hospital_id }, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing drafts: {}", e))?;
+
+    Ok(drafts.into_iter().map(|d| DraftResponse::new(d, Vec::new())).collect())
+}
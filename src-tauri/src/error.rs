@@ -0,0 +1,151 @@
+// src-tauri/src/error.rs
+//! Errors returned to the frontend. Each serializes as an object with a
+//! stable `code` the UI can switch on and a `message` safe to show, e.g.
+//! `{"code": "not_found", "message": "Medicine not found"}`. Driver and SMTP
+//! errors are logged and replaced with a generic message.
+//!
+//! Storage and the modules not yet moved over still use `String` errors: a
+//! `String` converts into `Database` and an `AppError` back into its message.
+use serde::Serialize;
+use std::fmt;
+
+/// A rejected field and why, for highlighting it in a form.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum AppError {
+    NotFound { message: String },
+    Validation { message: String, fields: Vec<FieldError> },
+    Conflict { message: String }, // e.g. username taken, nothing left to change
+    Unauthorized { message: String }, // wrong login or OTP
+    Forbidden { message: String }, // needs the admin password
+    InsufficientStock { message: String, name: String, batch_number: String, available: u32 },
+    Database { message: String },
+    Email { message: String },
+    Internal { message: String },
+}
+
+impl AppError {
+    pub fn not_found(message: &str) -> Self {
+        AppError::NotFound { message: message.to_string() }
+    }
+
+    /// A request that is wrong as a whole rather than in one field.
+    pub fn validation(message: &str) -> Self {
+        AppError::Validation { message: message.to_string(), fields: Vec::new() }
+    }
+
+    pub fn invalid(field: &str, message: &str) -> Self {
+        AppError::Validation {
+            message: message.to_string(),
+            fields: vec![FieldError { field: field.to_string(), message: message.to_string() }],
+        }
+    }
+
+    pub fn conflict(message: &str) -> Self {
+        AppError::Conflict { message: message.to_string() }
+    }
+
+    pub fn unauthorized(message: &str) -> Self {
+        AppError::Unauthorized { message: message.to_string() }
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        AppError::Forbidden { message: message.to_string() }
+    }
+
+    pub fn insufficient_stock(name: &str, batch_number: &str, available: u32) -> Self {
+        AppError::InsufficientStock {
+            message: format!("Insufficient stock for {} (batch {}): {} left.", name, batch_number, available),
+            name: name.to_string(),
+            batch_number: batch_number.to_string(),
+            available,
+        }
+    }
+
+    pub fn database(detail: impl fmt::Display) -> Self {
+        eprintln!("Database error: {}", detail);
+        AppError::Database { message: "The database could not complete the request. Please try again.".to_string() }
+    }
+
+    pub fn email(detail: impl fmt::Display) -> Self {
+        eprintln!("Email error: {}", detail);
+        AppError::Email { message: "The email could not be sent. Check the address and try again.".to_string() }
+    }
+
+    pub fn internal(detail: impl fmt::Display) -> Self {
+        eprintln!("Internal error: {}", detail);
+        AppError::Internal { message: "Something went wrong. Please try again.".to_string() }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound { .. } => "not_found",
+            AppError::Validation { .. } => "validation",
+            AppError::Conflict { .. } => "conflict",
+            AppError::Unauthorized { .. } => "unauthorized",
+            AppError::Forbidden { .. } => "forbidden",
+            AppError::InsufficientStock { .. } => "insufficient_stock",
+            AppError::Database { .. } => "database",
+            AppError::Email { .. } => "email",
+            AppError::Internal { .. } => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound { message }
+            | AppError::Validation { message, .. }
+            | AppError::Conflict { message }
+            | AppError::Unauthorized { message }
+            | AppError::Forbidden { message }
+            | AppError::InsufficientStock { message, .. }
+            | AppError::Database { message }
+            | AppError::Email { message }
+            | AppError::Internal { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Repository calls fail with the driver's message.
+impl From<String> for AppError {
+    fn from(detail: String) -> Self {
+        AppError::database(detail)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(error: AppError) -> Self {
+        error.message().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_serialize_with_a_stable_code() {
+        let error = serde_json::to_value(AppError::invalid("mobile", "Mobile number is required.")).unwrap();
+        assert_eq!(error["code"], "validation");
+        assert_eq!(error["fields"][0]["field"], "mobile");
+
+        let error = AppError::from("connection refused at 10.0.0.5:27017".to_string());
+        assert_eq!(error.code(), "database");
+        assert!(!error.message().contains("10.0.0.5"));
+        assert_eq!(serde_json::to_value(&error).unwrap()["code"], error.code());
+    }
+}
@@ -0,0 +1,413 @@
+// src-tauri/src/invoice_pdf.rs
+use crate::billing::{find_invoice, Invoice, SellerDetails};
+use crate::config;
+use crate::dates;
+use crate::db::DbState;
+use crate::money::Money;
+use crate::pdf::{output_dir, safe_file_name, JpegImage, PageSize, PdfCanvas};
+use crate::tax::{to_paise, SupplyType};
+use crate::user::find_hospital;
+use chrono::Local;
+use std::path::Path;
+use tauri::{command, AppHandle, State};
+
+const ONES: [&str; 20] = [
+    "", "One", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten", "Eleven", "Twelve",
+    "Thirteen", "Fourteen", "Fifteen", "Sixteen", "Seventeen", "Eighteen", "Nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "Twenty", "Thirty", "Forty", "Fifty", "Sixty", "Seventy", "Eighty", "Ninety",
+];
+
+fn below_hundred(n: u64) -> String {
+    let (tens, ones) = ((n / 10) as usize, (n % 10) as usize);
+    match n {
+        0..=19 => ONES[n as usize].to_string(),
+        _ if ones == 0 => TENS[tens].to_string(),
+        _ => format!("{} {}", TENS[tens], ONES[ones]),
+    }
+}
+
+/// Spells out `n` with the Indian grouping of crore, lakh and thousand.
+fn number_in_words(n: u64) -> String {
+    let mut parts = Vec::new();
+    let crore = n / 10_000_000;
+    if crore > 0 {
+        parts.push(format!("{} Crore", number_in_words(crore)));
+    }
+    for (value, unit) in [((n / 100_000) % 100, "Lakh"), ((n / 1_000) % 100, "Thousand"), ((n / 100) % 10, "Hundred")] {
+        if value > 0 {
+            parts.push(format!("{} {}", below_hundred(value), unit));
+        }
+    }
+    let rest = n % 100;
+    if rest > 0 {
+        parts.push(below_hundred(rest));
+    }
+    parts.join(" ")
+}
+
+/// The invoice total as printed under the totals, e.g. "Rupees One Lakh
+/// Five Hundred and Fifty Paise Only".
+pub fn amount_in_words(amount: f64) -> String {
+    let paise = to_paise(amount).unsigned_abs();
+    let (rupees, paise) = (paise / 100, paise % 100);
+    let rupees = if rupees == 0 { "Zero".to_string() } else { number_in_words(rupees) };
+    if paise == 0 {
+        format!("Rupees {} Only", rupees)
+    } else {
+        format!("Rupees {} and {} Paise Only", rupees, below_hundred(paise))
+    }
+}
+
+/// Cuts `text` to `max` characters, marking the cut.
+fn fit(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max.saturating_sub(2)).collect();
+    format!("{}..", kept)
+}
+
+/// Lays out a tax invoice from the stored document alone, so a reprint
+/// matches the original. Quantities are as sold; returns are on their
+/// credit notes.
+/// The hospital's name, address, contacts, GSTIN and extra letterhead
+/// lines, with the logo beside them when there is one, then a rule.
+pub(crate) fn draw_letterhead(pdf: &mut PdfCanvas, seller: &SellerDetails, compact: bool) {
+    let body = if compact { 8.0 } else { 9.0 };
+    let logo = seller
+        .logo_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .and_then(|p| JpegImage::load(Path::new(p)).ok());
+    let logo_height = match &logo {
+        Some(logo) => {
+            let box_size = if compact { 18.0 } else { 24.0 };
+            pdf.image_at(logo, pdf.left(), box_size, box_size).1
+        }
+        None => 0.0,
+    };
+    let letterhead_top = pdf.y();
+    pdf.text_centered(&seller.name, if compact { 14.0 } else { 18.0 }, true);
+    pdf.text_centered(&seller.address, body, false);
+    pdf.text_centered(&format!("Phone: {}  |  Email: {}", seller.mobile, seller.email), body, false);
+    if let Some(gstin) = &seller.gstin {
+        pdf.text_centered(&format!("GSTIN: {}", gstin), body, true);
+    }
+    for line in seller.letterhead.iter().flat_map(|l| l.lines()).filter(|l| !l.trim().is_empty()) {
+        pdf.text_centered(line.trim(), body - 1.0, false);
+    }
+    let used = letterhead_top - pdf.y();
+    if logo_height > used {
+        pdf.gap(logo_height - used);
+    }
+    pdf.gap(1.0);
+    pdf.rule();
+}
+
+fn render_invoice(invoice: &Invoice, seller: &SellerDetails, size: PageSize) -> Result<PdfCanvas, String> {
+    let mut pdf = PdfCanvas::new(&format!("Tax Invoice {}", invoice.invoice_number), size)?;
+    let (left, right) = (pdf.left(), pdf.right());
+    let width = right - left;
+    let compact = matches!(size, PageSize::A5);
+    let body = if compact { 8.0 } else { 9.0 };
+    let col = |fraction: f32| left + width * fraction;
+
+    draw_letterhead(&mut pdf, seller, compact);
+
+    pdf.text_centered("TAX INVOICE", body + 3.0, true);
+    pdf.gap(1.0);
+
+    // Invoice and customer details
+    let issued = dates::to_utc(invoice.date_created).with_timezone(&Local).format("%d %b %Y %H:%M");
+    pdf.text_at(&format!("Invoice No: {}", invoice.invoice_number), body + 1.0, left, true);
+    pdf.text_right(&format!("Date: {}", issued), body + 1.0, right, false);
+    pdf.newline(body + 1.0);
+    pdf.text_at(&format!("Bill to: {}", invoice.customer_name), body, left, false);
+    if let Some(mobile) = &invoice.customer_mobile {
+        pdf.text_right(&format!("Mobile: {}", mobile), body, right, false);
+    }
+    pdf.newline(body);
+    let supply = match invoice.supply_type {
+        SupplyType::IntraState => "Intra-state",
+        SupplyType::InterState => "Inter-state",
+    };
+    let place = invoice.place_of_supply.as_deref().unwrap_or("Same state");
+    pdf.text(&format!("Place of supply: {} ({})", place, supply), body, false);
+    pdf.gap(1.0);
+    pdf.rule();
+
+    // Items
+    let name_chars = (width * 0.37 / PdfCanvas::text_width("M", body)) as usize;
+    let header = |pdf: &mut PdfCanvas| {
+        pdf.ensure_space(12.0);
+        pdf.text_at("#", body, left, true);
+        pdf.text_at("Item", body, col(0.05), true);
+        pdf.text_at("HSN", body, col(0.43), true);
+        for (label, edge) in [("Qty", 0.54), ("Rate", 0.64), ("Disc", 0.73), ("Taxable", 0.84), ("GST%", 0.91)] {
+            pdf.text_right(label, body, col(edge), true);
+        }
+        pdf.text_right("Amount", body, right, true);
+        pdf.newline(body);
+        pdf.rule();
+    };
+    header(&mut pdf);
+    for (index, line) in invoice.lines.iter().enumerate() {
+        if pdf.ensure_space(12.0) {
+            header(&mut pdf);
+        }
+        pdf.text_at(&(index + 1).to_string(), body, left, false);
+        pdf.text_at(&fit(&line.name, name_chars), body, col(0.05), false);
+        pdf.text_at(&line.hsn_code, body, col(0.43), false);
+        for (value, edge) in [
+            (line.quantity.to_string(), 0.54),
+            (format!("{:.2}", line.unit_price.rupees()), 0.64),
+            (format!("{:.2}", line.discount.rupees()), 0.73),
+            (format!("{:.2}", line.taxable_value.rupees()), 0.84),
+            (format!("{}", line.gst_rate), 0.91),
+        ] {
+            pdf.text_right(&value, body, col(edge), false);
+        }
+        pdf.text_right(&format!("{:.2}", line.total.rupees()), body, right, false);
+        pdf.newline(body);
+        if !line.batch_number.is_empty() {
+            pdf.text_at(&format!("Batch: {}", line.batch_number), body - 1.5, col(0.05), false);
+            pdf.newline(body - 1.5);
+        }
+    }
+    pdf.rule();
+
+    // Totals
+    let mut totals: Vec<(&str, Money)> = Vec::new();
+    if invoice.discount_total > Money::default() {
+        totals.push(("Discount", invoice.discount_total));
+    }
+    totals.push(("Taxable value", invoice.taxable_total));
+    match invoice.supply_type {
+        SupplyType::IntraState => {
+            totals.push(("CGST", invoice.cgst_total));
+            totals.push(("SGST", invoice.sgst_total));
+        }
+        SupplyType::InterState => totals.push(("IGST", invoice.igst_total)),
+    }
+    totals.push(("Round off", invoice.round_off));
+    for (label, amount) in totals {
+        pdf.ensure_space(6.0);
+        pdf.text_at(label, body, col(0.6), false);
+        pdf.text_right(&format!("{:.2}", amount.rupees()), body, right, false);
+        pdf.newline(body);
+    }
+    pdf.text_at("Grand total", body + 2.0, col(0.6), true);
+    pdf.text_right(&format!("{:.2}", invoice.grand_total.rupees()), body + 2.0, right, true);
+    pdf.newline(body + 2.0);
+    pdf.gap(1.0);
+    pdf.paragraph(&amount_in_words(invoice.grand_total.rupees()), body, 0.0);
+    pdf.gap(1.0);
+    pdf.rule();
+
+    // HSN-wise tax summary
+    pdf.ensure_space(20.0);
+    pdf.text("Tax summary", body + 1.0, true);
+    let edges = [0.30, 0.50, 0.65, 0.80];
+    pdf.text_at("HSN", body, left, true);
+    pdf.text_at("GST%", body, col(0.18), true);
+    for (label, edge) in ["Taxable", "CGST", "SGST", "IGST"].into_iter().zip(edges) {
+        pdf.text_right(label, body, col(edge), true);
+    }
+    pdf.text_right("Total tax", body, right, true);
+    pdf.newline(body);
+    for row in &invoice.tax_summary {
+        pdf.ensure_space(6.0);
+        pdf.text_at(&row.hsn_code, body, left, false);
+        pdf.text_at(&format!("{}", row.gst_rate), body, col(0.18), false);
+        for (value, edge) in [row.taxable_value, row.cgst, row.sgst, row.igst].into_iter().zip(edges) {
+            pdf.text_right(&format!("{:.2}", value.rupees()), body, col(edge), false);
+        }
+        pdf.text_right(&format!("{:.2}", (row.cgst + row.sgst + row.igst).rupees()), body, right, false);
+        pdf.newline(body);
+    }
+    pdf.rule();
+
+    // Payments
+    let paid: Vec<String> = invoice
+        .payments
+        .iter()
+        .map(|p| match &p.reference {
+            Some(reference) => format!("{} {:.2} ({})", p.mode.label(), p.amount.rupees(), reference),
+            None => format!("{} {:.2}", p.mode.label(), p.amount.rupees()),
+        })
+        .collect();
+    if !paid.is_empty() {
+        let mut line = format!("Paid by: {}", paid.join(", "));
+        if invoice.change_due > Money::default() {
+            line.push_str(&format!("; change returned {:.2}", invoice.change_due.rupees()));
+        }
+        pdf.paragraph(&line, body, 0.0);
+    }
+
+    // Signature block
+    pdf.ensure_space(28.0);
+    pdf.gap(16.0);
+    let sign_width = if compact { 45.0 } else { 60.0 };
+    let sign_from = right - sign_width;
+    let sign_chars = (sign_width / PdfCanvas::text_width("M", body)) as usize;
+    pdf.text_at(&fit(&format!("For {}", seller.name), sign_chars), body, sign_from, true);
+    pdf.newline(body);
+    pdf.gap(10.0);
+    pdf.rule_between(sign_from, right);
+    pdf.text_at("Authorised Signatory", body, sign_from, false);
+    pdf.newline(body);
+    pdf.gap(2.0);
+    pdf.text_centered("This is a computer generated invoice.", body - 1.0, false);
+
+    Ok(pdf)
+}
+
+/// Renders a stored invoice as an A4 or A5 PDF and returns its path.
+/// `page_size` falls back to the hospital's settings, then to `[printing]`
+/// (default A4). Invoices from before letterheads were stored use the
+/// current hospital profile.
+#[command]
+pub async fn generate_invoice_pdf(
+    invoice_number: String,
+    page_size: Option<String>,
+    hospital_id: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let invoice = find_invoice(&*db_state.invoices(), &hospital_id, &invoice_number).await?;
+    let hospital = find_hospital(&*db_state.users(), &hospital_id).await?;
+    let size = match page_size.filter(|s| !s.trim().is_empty()) {
+        Some(size) => PageSize::parse(&size)?,
+        None => hospital.settings.printer.invoice_page_size()?,
+    };
+    let seller = match &invoice.seller {
+        Some(seller) => seller.clone(),
+        None => SellerDetails::from(&hospital),
+    };
+
+    let pdf = render_invoice(&invoice, &seller, size)?;
+    let path = output_dir(&app, &config::get().output.invoices, "invoices")?.join(format!(
+        "{}_{}.pdf",
+        safe_file_name(&invoice.invoice_number),
+        if matches!(size, PageSize::A5) { "a5" } else { "a4" }
+    ));
+    pdf.save(&path)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+    use crate::payment::PaymentMode;
+    use crate::pdf::shown_text;
+
+    fn seller() -> SellerDetails {
+        SellerDetails {
+            name: "Sri Sai Pharmacy".to_string(),
+            address: "12 MG Road, Bengaluru".to_string(),
+            mobile: "9876543210".to_string(),
+            email: "billing@srisai.in".to_string(),
+            gstin: Some("29ABCDE1234F1Z5".to_string()),
+            registration_number: None,
+            letterhead: Some("DL No. KA-B-123/20".to_string()),
+            logo_path: None,
+        }
+    }
+
+    // Two strips at 12% and a toothpaste at 18%, prices including tax
+    fn invoice(supply_type: SupplyType) -> Invoice {
+        let sold = TestInvoice::new("INV/2026-27/000042")
+            .customer("Anita Rao", Some("9000000001"))
+            .line("m1", "Azithromycin 500mg", "3004", 12.0, 2, 120.0)
+            .line("m2", "Herbal Toothpaste", "3306", 18.0, 1, 90.0)
+            .pay(PaymentMode::Cash, 500.0);
+        match supply_type {
+            SupplyType::IntraState => sold.build(),
+            SupplyType::InterState => sold.inter_state("Tamil Nadu").build(),
+        }
+    }
+
+    fn render_to(dir: &Path, supply_type: SupplyType, size: PageSize) -> Vec<String> {
+        let name = format!("{:?}_{:?}.pdf", supply_type, size);
+        let path = dir.join(name);
+        render_invoice(&invoice(supply_type), &seller(), size).unwrap().save(&path).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        shown_text(&path)
+    }
+
+    /// Whether `label` is drawn with `value` right after it, as in a totals row.
+    fn shows(text: &[String], label: &str, value: &str) -> bool {
+        text.windows(2).any(|pair| pair[0] == label && pair[1] == value)
+    }
+
+    /// The rest of the tax summary row for `hsn`, which comes after the items.
+    fn tax_row<'a>(text: &'a [String], hsn: &str) -> &'a [String] {
+        let start = text.iter().rposition(|t| t == hsn).unwrap();
+        &text[start + 1..start + 7]
+    }
+
+    #[test]
+    fn intra_state_invoice_splits_tax_into_cgst_and_sgst() {
+        let dir = tempfile::tempdir().unwrap();
+        for size in [PageSize::A4, PageSize::A5] {
+            let text = render_to(dir.path(), SupplyType::IntraState, size);
+            assert!(text.iter().any(|t| t == "Place of supply: Same state (Intra-state)"));
+            assert!(shows(&text, "Taxable value", "290.56"));
+            assert!(shows(&text, "CGST", "19.73") && shows(&text, "SGST", "19.71"));
+            assert!(!shows(&text, "IGST", "0.00"));
+            assert!(shows(&text, "Grand total", "330.00"));
+            assert!(text.iter().any(|t| t == "Rupees Three Hundred Thirty Only"));
+            // Rate, taxable value, CGST, SGST, IGST and total tax
+            assert_eq!(tax_row(&text, "3004"), ["12", "214.29", "12.86", "12.85", "0.00", "25.71"]);
+            assert_eq!(tax_row(&text, "3306"), ["18", "76.27", "6.87", "6.86", "0.00", "13.73"]);
+            assert!(text.iter().any(|t| t == "Paid by: Cash 500.00; change returned 170.00"));
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn inter_state_invoice_charges_igst() {
+        let dir = tempfile::tempdir().unwrap();
+        for size in [PageSize::A4, PageSize::A5] {
+            let text = render_to(dir.path(), SupplyType::InterState, size);
+            assert!(text.iter().any(|t| t == "Place of supply: Tamil Nadu (Inter-state)"));
+            assert!(shows(&text, "IGST", "39.44"));
+            assert!(!shows(&text, "CGST", "0.00") && !shows(&text, "SGST", "0.00"));
+            assert!(shows(&text, "Grand total", "330.00"));
+            assert_eq!(tax_row(&text, "3004"), ["12", "214.29", "0.00", "0.00", "25.71", "25.71"]);
+            assert_eq!(tax_row(&text, "3306"), ["18", "76.27", "0.00", "0.00", "13.73", "13.73"]);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn spells_indian_grouping() {
+        assert_eq!(amount_in_words(0.0), "Rupees Zero Only");
+        assert_eq!(amount_in_words(79.0), "Rupees Seventy Nine Only");
+        assert_eq!(amount_in_words(1_000.0), "Rupees One Thousand Only");
+        assert_eq!(
+            amount_in_words(123_456.0),
+            "Rupees One Lakh Twenty Three Thousand Four Hundred Fifty Six Only"
+        );
+        assert_eq!(amount_in_words(20_005_010.0), "Rupees Two Crore Five Thousand Ten Only");
+    }
+
+    #[test]
+    fn spells_paise() {
+        assert_eq!(amount_in_words(1_250.5), "Rupees One Thousand Two Hundred Fifty and Fifty Paise Only");
+        assert_eq!(amount_in_words(0.07), "Rupees Zero and Seven Paise Only");
+    }
+
+    #[test]
+    fn reads_jpeg_frame_size() {
+        // SOI, an APP0 segment to skip, then a baseline frame header for 40x30 RGB
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        data.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x1E, 0x00, 0x28, 0x03]);
+        assert!(JpegImage::parse(data).is_ok());
+        assert!(JpegImage::parse(b"\x89PNG\r\n".to_vec()).is_err());
+    }
+}
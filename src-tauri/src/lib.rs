@@ -0,0 +1,258 @@
+mod config;
+mod database;
+mod error;
+pub mod money;
+mod dates;
+mod db;
+mod cmd;
+mod user;
+mod model;
+pub mod commands;
+mod utils;
+mod schedule;
+mod queue;
+mod reminder;
+mod clinical;
+mod pdf;
+mod prescription;
+mod tax;
+mod billing;
+mod returns;
+mod discount;
+mod payment;
+mod credit;
+mod shift;
+mod receipt;
+mod invoice_pdf;
+mod draft;
+pub mod repository;
+pub mod sqlite;
+mod stock;
+pub mod search;
+mod offline;
+mod sync;
+pub mod migration;
+mod settings;
+use crate::db::{init_db, check_db_health, DbState};
+use commands::{
+    initialize_db,reduce_batch, insert_medicine, update_batch, delete_batch, search_medicines,
+    save_appointment,fetch_medicine,get_all_appointments,get_stock,delete_medicine,update_stock,get_medicine_by_id,
+    apply_visit_retention
+};
+use schedule::{
+    save_doctor_schedule, get_doctor_schedules, book_appointment, update_appointment_status,
+    get_day_schedule, get_available_slots
+};
+use queue::{
+    issue_token, call_next_token, skip_current_token, get_queue_state, expire_stale_tokens
+};
+use reminder::{get_due_followups, dispatch_followup_reminders, get_reminder_log};
+use clinical::{update_visit_clinical, get_vitals_trend};
+use prescription::generate_prescription;
+use tax::compute_bill_tax;
+use billing::{create_invoice, get_invoice, get_invoices};
+use returns::{void_invoice, create_sales_return, get_credit_notes};
+use discount::{save_discount_rule, get_discount_rules, delete_discount_rule};
+use payment::get_payment_summary;
+use credit::{
+    save_credit_customer, get_credit_customers, record_credit_receipt, get_credit_ageing, get_credit_statement,
+    export_credit_statement
+};
+use shift::{open_shift, get_open_shifts, close_shift, get_shift_report, print_z_report};
+use receipt::print_receipt;
+use invoice_pdf::generate_invoice_pdf;
+use draft::{create_draft, update_draft, park_draft, resume_draft, discard_draft, get_drafts};
+use sync::{get_sync_status, sync_now, get_sync_conflicts, resolve_sync_conflict, sync_once};
+use migration::{get_migrations, run_migrations};
+use search::{search_products, SearchIndexes};
+use settings::{get_hospital_settings, update_hospital_settings};
+use crate::cmd::{SessionState, login, signup, logout, is_logged_in, verify_signup, forgot_password, reset_password, update_hospital_profile};
+use tauri::{Builder, Manager, generate_handler};
+use tokio::time::{interval, Duration};
+
+/// Loads settings, opens the database and runs the app until its window
+/// closes.
+pub async fn run() {
+    // Settings come first so a bad caton.toml is reported before anything starts
+    let app_config = match config::init() {
+        Ok(app_config) => app_config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // Initialize session state
+    let session_state = SessionState::default();
+
+    // Connect once; commands share the client through managed state
+    let db_state = match init_db().await {
+        Ok(db_state) => db_state,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // Bring the collections and indexes up to date before serving commands
+    if let DbState::Mongo(mongo) = &db_state {
+        if mongo.offline.is_online() {
+            match migration::run_pending(&mongo.db, false).await {
+                Ok(steps) => {
+                    for step in steps {
+                        println!("Applied migration {}: {}", step.id, step.outcome.changes.join("; "));
+                        for failure in step.outcome.failures {
+                            eprintln!(
+                                "Could not convert {} {} {}: {:?}",
+                                failure.collection, failure.id, failure.field, failure.value
+                            );
+                        }
+                    }
+                }
+                Err(error) => eprintln!("Migrations failed: {:?}", error),
+            }
+        }
+    }
+
+    // Run the Tauri application
+    Builder::default()
+        .manage(db_state) // Register the database state
+        .manage(session_state) // Register the session state
+        .manage(SearchIndexes::default())
+        .setup(|app| {
+            // Schedule the housekeeping tasks to run every hour
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut task_interval = interval(Duration::from_secs(60 * 60)); // 1 hour in seconds
+                loop {
+                    task_interval.tick().await;
+                    match apply_visit_retention(handle.state()).await {
+                        Ok(message) => println!("{}", message),
+                        Err(error) => eprintln!("Failed to delete old appointments: {}", error),
+                    }
+                    // Also runs on the first tick, so yesterday's queue is cleared at startup
+                    // The queue and reminders are kept in MongoDB only
+                    if handle.state::<DbState>().mongo("the patient queue").is_err() {
+                        continue;
+                    }
+                    match expire_stale_tokens(handle.state()).await {
+                        Ok(message) => println!("{}", message),
+                        Err(error) => eprintln!("Failed to expire old queue tokens: {}", error),
+                    }
+                    match dispatch_followup_reminders(None, handle.state()).await {
+                        Ok(message) => println!("{}", message),
+                        Err(error) => eprintln!("Failed to send follow-up reminders: {}", error),
+                    }
+                }
+            });
+
+            // Watch the MongoDB link and push what was done offline once it is back
+            let handle = app.handle().clone();
+            let sync_secs = app_config.offline.sync_interval_secs;
+            tauri::async_runtime::spawn(async move {
+                let mut sync_interval = interval(Duration::from_secs(sync_secs));
+                loop {
+                    sync_interval.tick().await;
+                    let db_state = handle.state::<DbState>();
+                    if !matches!(db_state.inner(), DbState::Mongo(_)) {
+                        break;
+                    }
+                    match sync_once(&db_state).await {
+                        Ok(report) if report.applied > 0 || report.error.is_some() => println!(
+                            "Synced {} offline changes ({} conflicts, {} pending){}",
+                            report.applied,
+                            report.conflicts,
+                            report.pending,
+                            report.error.map(|e| format!(": {}", e)).unwrap_or_default()
+                        ),
+                        Ok(_) => {}
+                        Err(error) => eprintln!("Offline sync failed: {}", error),
+                    }
+                }
+            });
+            Ok(())
+        })
+        .invoke_handler(generate_handler![
+            check_db_health,
+            initialize_db,
+            insert_medicine,
+            reduce_batch,
+            update_batch,
+            delete_batch,
+            search_medicines,
+            search_products,
+            signup,
+            login,
+            logout,
+            is_logged_in,
+            save_appointment,
+            get_all_appointments,
+            get_stock,
+            delete_medicine,
+            update_stock,
+            get_medicine_by_id,
+            fetch_medicine,
+            verify_signup, 
+            forgot_password, 
+            reset_password,
+            apply_visit_retention, // Add command to invoke manually if needed
+            save_doctor_schedule,
+            get_doctor_schedules,
+            book_appointment,
+            update_appointment_status,
+            get_day_schedule,
+            get_available_slots,
+            issue_token,
+            call_next_token,
+            skip_current_token,
+            get_queue_state,
+            expire_stale_tokens,
+            get_due_followups,
+            dispatch_followup_reminders,
+            get_reminder_log,
+            update_visit_clinical,
+            get_vitals_trend,
+            generate_prescription,
+            update_hospital_profile,
+            get_hospital_settings,
+            update_hospital_settings,
+            compute_bill_tax,
+            create_invoice,
+            get_invoice,
+            get_invoices,
+            void_invoice,
+            create_sales_return,
+            get_credit_notes,
+            save_discount_rule,
+            get_discount_rules,
+            delete_discount_rule,
+            get_payment_summary,
+            save_credit_customer,
+            get_credit_customers,
+            record_credit_receipt,
+            get_credit_ageing,
+            get_credit_statement,
+            export_credit_statement,
+            open_shift,
+            get_open_shifts,
+            close_shift,
+            get_shift_report,
+            print_z_report,
+            print_receipt,
+            generate_invoice_pdf,
+            create_draft,
+            update_draft,
+            park_draft,
+            resume_draft,
+            discard_draft,
+            get_drafts,
+            get_sync_status,
+            sync_now,
+            get_sync_conflicts,
+            resolve_sync_conflict,
+            get_migrations,
+            run_migrations,
+        ])
+        .run(tauri::generate_context!())
+        .expect("Error while running Tauri application");
+}
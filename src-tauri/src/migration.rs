@@ -0,0 +1,713 @@
+// src-tauri/src/migration.rs
+//! Versioned changes to the MongoDB collections. Each migration runs once,
+//! in order, and is recorded in `_migrations`; pending ones run at startup
+//! and through `run_migrations`, which can also preview them as a dry run.
+//! Every step is safe to repeat, so two counters starting together at most
+//! do the same work twice.
+//!
+//! The `typed` migrations (0002, 0004, 0005 and 0006) rewrite documents
+//! saved before amounts were kept in paise and dates as BSON dates (see
+//! `money.rs` and `dates.rs`). Reading already accepts the old forms;
+//! converting them makes date ranges compare as dates rather than as
+//! strings. Values that cannot be read are left untouched and reported, so
+//! they can be fixed by hand.
+use crate::billing::Invoice;
+use crate::commands::{Appointment, Medicine};
+use crate::credit::{CreditCustomer, LedgerEntry};
+use crate::dates::{self, midnight, parse_legacy_date, parse_legacy_timestamp};
+use crate::db::DbState;
+use crate::discount::DiscountRule;
+use crate::error::AppError;
+use crate::model::User;
+use crate::queue::QueueToken;
+use crate::returns::CreditNote;
+use crate::schedule::ScheduledAppointment;
+use crate::shift::Shift;
+use crate::tax::to_paise;
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::error::ErrorKind;
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+const MIGRATIONS_COLLECTION: &str = "_migrations";
+
+struct Migration {
+    id: &'static str,
+    description: &'static str,
+    step: Step,
+}
+
+enum Step {
+    Indexes(&'static [IndexSpec]),
+    TypedFields(&'static [(&'static str, &'static [(&'static str, FieldKind)])]), // fields by collection
+}
+
+struct IndexSpec {
+    collection: &'static str,
+    keys: &'static [(&'static str, i32)],
+    unique: bool,
+}
+
+/// In the order they run; append new ones at the end.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        id: "0001_indexes",
+        description: "Indexes for stock search, account lookup, visits and invoice numbers",
+        step: Step::Indexes(&[
+            IndexSpec { collection: "medicines", keys: &[("user_id", 1), ("name", 1)], unique: false },
+            IndexSpec { collection: "users", keys: &[("username", 1)], unique: true },
+            IndexSpec { collection: "users", keys: &[("email", 1)], unique: true },
+            IndexSpec { collection: "appointments", keys: &[("hospital_id", 1), ("date_created", -1)], unique: false },
+            // Backstop for the counter: even a bug elsewhere can't store
+            // the same number twice for a hospital
+            IndexSpec { collection: "invoices", keys: &[("hospital_id", 1), ("invoice_number", 1)], unique: true },
+        ]),
+    },
+    Migration {
+        id: "0002_typed_fields",
+        description: "Prices in paise and dates as BSON dates on medicines, visits and accounts",
+        step: Step::TypedFields(&[
+            ("medicines", MEDICINE_FIELDS),
+            ("appointments", APPOINTMENT_FIELDS),
+            ("users", USER_FIELDS),
+        ]),
+    },
+    Migration {
+        id: "0003_search_indexes",
+        description: "Indexes for stock sorted by expiry and visits searched by patient",
+        step: Step::Indexes(&[
+            IndexSpec { collection: "medicines", keys: &[("user_id", 1), ("expiry_date", 1)], unique: false },
+            IndexSpec { collection: "appointments", keys: &[("hospital_id", 1), ("patient_name", 1)], unique: false },
+        ]),
+    },
+    Migration {
+        id: "0004_typed_billing",
+        description: "Amounts in paise and dates as BSON dates on invoices, credit accounts, credit notes and shifts",
+        step: Step::TypedFields(&[
+            ("invoices", INVOICE_FIELDS),
+            ("credit_customers", CREDIT_CUSTOMER_FIELDS),
+            ("credit_ledger", LEDGER_FIELDS),
+            ("credit_notes", CREDIT_NOTE_FIELDS),
+            ("shifts", SHIFT_FIELDS),
+        ]),
+    },
+    Migration {
+        id: "0005_typed_discount_rules",
+        description: "Minimum bill value in paise and creation date as a BSON date on discount rules",
+        step: Step::TypedFields(&[("discount_rules", DISCOUNT_RULE_FIELDS)]),
+    },
+    Migration {
+        id: "0006_typed_visit_dates",
+        description: "Follow-up dates, booking times and queue dates as BSON dates",
+        step: Step::TypedFields(&[
+            ("appointments", FOLLOW_UP_FIELDS),
+            ("scheduled_appointments", SCHEDULED_APPOINTMENT_FIELDS),
+            ("queue_tokens", QUEUE_TOKEN_FIELDS),
+        ]),
+    },
+];
+
+#[derive(Debug, Clone, Copy)]
+enum FieldKind {
+    Money,
+    CalendarDate,
+    Timestamp,
+}
+
+const MEDICINE_FIELDS: &[(&str, FieldKind)] = &[
+    ("purchase_price", FieldKind::Money),
+    ("selling_price", FieldKind::Money),
+    ("expiry_date", FieldKind::CalendarDate),
+    ("purchase_date", FieldKind::CalendarDate),
+];
+const APPOINTMENT_FIELDS: &[(&str, FieldKind)] = &[("date_created", FieldKind::Timestamp)];
+const USER_FIELDS: &[(&str, FieldKind)] = &[("otp_expiry", FieldKind::Timestamp)];
+
+// A dotted path goes into embedded documents and through arrays, so
+// `lines.total` is the total of every line
+const INVOICE_FIELDS: &[(&str, FieldKind)] = &[
+    ("lines.unit_price", FieldKind::Money),
+    ("lines.discount", FieldKind::Money),
+    ("lines.taxable_value", FieldKind::Money),
+    ("lines.cgst", FieldKind::Money),
+    ("lines.sgst", FieldKind::Money),
+    ("lines.igst", FieldKind::Money),
+    ("lines.total", FieldKind::Money),
+    ("tax_summary.taxable_value", FieldKind::Money),
+    ("tax_summary.cgst", FieldKind::Money),
+    ("tax_summary.sgst", FieldKind::Money),
+    ("tax_summary.igst", FieldKind::Money),
+    ("discount_total", FieldKind::Money),
+    ("taxable_total", FieldKind::Money),
+    ("cgst_total", FieldKind::Money),
+    ("sgst_total", FieldKind::Money),
+    ("igst_total", FieldKind::Money),
+    ("round_off", FieldKind::Money),
+    ("grand_total", FieldKind::Money),
+    ("payments.amount", FieldKind::Money),
+    ("change_due", FieldKind::Money),
+    ("date_created", FieldKind::Timestamp),
+];
+const CREDIT_CUSTOMER_FIELDS: &[(&str, FieldKind)] = &[
+    ("credit_limit", FieldKind::Money),
+    ("balance", FieldKind::Money),
+    ("date_created", FieldKind::Timestamp),
+];
+const LEDGER_FIELDS: &[(&str, FieldKind)] = &[
+    ("amount", FieldKind::Money),
+    ("balance_after", FieldKind::Money),
+    ("date_created", FieldKind::Timestamp),
+];
+const CREDIT_NOTE_FIELDS: &[(&str, FieldKind)] = &[
+    ("lines.unit_price", FieldKind::Money),
+    ("lines.taxable_value", FieldKind::Money),
+    ("lines.cgst", FieldKind::Money),
+    ("lines.sgst", FieldKind::Money),
+    ("lines.igst", FieldKind::Money),
+    ("lines.total", FieldKind::Money),
+    ("taxable_total", FieldKind::Money),
+    ("cgst_total", FieldKind::Money),
+    ("sgst_total", FieldKind::Money),
+    ("igst_total", FieldKind::Money),
+    ("round_off", FieldKind::Money),
+    ("total", FieldKind::Money),
+    ("account_credit", FieldKind::Money),
+    ("date_created", FieldKind::Timestamp),
+];
+const SHIFT_FIELDS: &[(&str, FieldKind)] = &[
+    ("opening_float", FieldKind::Money),
+    ("opened_at", FieldKind::Timestamp),
+    ("closed_at", FieldKind::Timestamp),
+    ("last_activity_at", FieldKind::Timestamp),
+    ("amended_at", FieldKind::Timestamp),
+    ("z_report.gross_sales", FieldKind::Money),
+    ("z_report.discount_total", FieldKind::Money),
+    ("z_report.taxable_total", FieldKind::Money),
+    ("z_report.tax_total", FieldKind::Money),
+    ("z_report.returns_total", FieldKind::Money),
+    ("z_report.payments.by_mode.amount", FieldKind::Money),
+    ("z_report.payments.change_given", FieldKind::Money),
+    ("z_report.payments.credit_sales", FieldKind::Money),
+    ("z_report.payments.credit_receipts", FieldKind::Money),
+    ("z_report.payments.refunds", FieldKind::Money),
+    ("z_report.payments.total_collected", FieldKind::Money),
+    ("z_report.opening_float", FieldKind::Money),
+    ("z_report.expected_cash", FieldKind::Money),
+    ("z_report.counted_cash", FieldKind::Money),
+    ("z_report.variance", FieldKind::Money),
+];
+
+const DISCOUNT_RULE_FIELDS: &[(&str, FieldKind)] = &[
+    ("min_bill_value", FieldKind::Money),
+    ("date_created", FieldKind::Timestamp),
+];
+const FOLLOW_UP_FIELDS: &[(&str, FieldKind)] = &[("follow_up_date", FieldKind::CalendarDate)];
+const SCHEDULED_APPOINTMENT_FIELDS: &[(&str, FieldKind)] = &[("date_created", FieldKind::Timestamp)];
+const QUEUE_TOKEN_FIELDS: &[(&str, FieldKind)] = &[
+    ("queue_date", FieldKind::CalendarDate),
+    ("issued_at", FieldKind::Timestamp),
+    ("called_at", FieldKind::Timestamp),
+];
+
+/// A value left as it was because it could not be read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationFailure {
+    pub collection: String,
+    pub id: String,
+    pub field: String,
+    pub value: String,
+}
+
+/// A document that still does not read as its type, e.g. a medicine with
+/// no `batch_number`; `fetch_medicine` fails until it is fixed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MalformedDocument {
+    pub collection: String,
+    pub id: String,
+    pub error: String,
+}
+
+/// What a migration did, or would do on a dry run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepOutcome {
+    pub changes: Vec<String>,
+    pub failures: Vec<MigrationFailure>,
+}
+
+/// A row of `_migrations`.
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedMigration {
+    #[serde(rename = "_id")]
+    id: String,
+    description: String,
+    applied_at: bson::DateTime,
+    #[serde(flatten)]
+    outcome: StepOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub id: String,
+    pub description: String,
+    pub applied_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStepReport {
+    pub id: String,
+    pub description: String,
+    #[serde(flatten)]
+    pub outcome: StepOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationRun {
+    pub dry_run: bool,
+    pub steps: Vec<MigrationStepReport>, // the pending migrations, in the order run
+    pub malformed: Vec<MalformedDocument>,
+}
+
+fn document_id(document: &Document) -> String {
+    match document.get("_id") {
+        Some(Bson::ObjectId(id)) => id.to_hex(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+/// The typed form of a legacy value; `Ok(None)` when it is already typed
+/// (or an absent optional field).
+fn convert(kind: FieldKind, value: &Bson) -> Result<Option<Bson>, ()> {
+    match (kind, value) {
+        (FieldKind::Money, Bson::Int64(_) | Bson::Int32(_)) => Ok(None),
+        (FieldKind::Money, Bson::Double(rupees)) if rupees.is_finite() => Ok(Some(Bson::Int64(to_paise(*rupees)))),
+        (FieldKind::Money, Bson::String(text)) => match text.trim().parse::<f64>() {
+            Ok(rupees) if rupees.is_finite() => Ok(Some(Bson::Int64(to_paise(rupees)))),
+            _ => Err(()),
+        },
+        (FieldKind::CalendarDate | FieldKind::Timestamp, Bson::DateTime(_)) => Ok(None),
+        (FieldKind::CalendarDate, Bson::String(text)) => {
+            parse_legacy_date(text).map(|date| Some(Bson::DateTime(midnight(date)))).ok_or(())
+        }
+        (FieldKind::Timestamp, Bson::String(text)) => {
+            parse_legacy_timestamp(text).map(|date| Some(Bson::DateTime(date))).ok_or(())
+        }
+        (_, Bson::Null) => Ok(None),
+        _ => Err(()),
+    }
+}
+
+/// Types the values at `path` below `value` in place, going through
+/// arrays along the way. Returns whether anything changed; values that
+/// cannot be read are added to `unreadable`.
+fn convert_at(value: &mut Bson, path: &[&str], kind: FieldKind, unreadable: &mut Vec<String>) -> bool {
+    match (path.split_first(), value) {
+        (None, value) => match convert(kind, value) {
+            Ok(Some(typed)) => {
+                *value = typed;
+                true
+            }
+            Ok(None) => false,
+            Err(()) => {
+                unreadable.push(match value {
+                    Bson::String(text) => text.clone(),
+                    other => other.to_string(),
+                });
+                false
+            }
+        },
+        (Some(_), Bson::Array(items)) => {
+            let mut changed = false;
+            for item in items {
+                changed |= convert_at(item, path, kind, unreadable);
+            }
+            changed
+        }
+        (Some((field, rest)), Bson::Document(document)) => match document.get_mut(*field) {
+            Some(value) => convert_at(value, rest, kind, unreadable),
+            None => false,
+        },
+        _ => false, // e.g. no Z-report yet on an open shift
+    }
+}
+
+/// The `$set` that types a document's fields, and the fields it could not.
+/// A field inside an embedded document or array sets the whole top-level
+/// field it belongs to.
+fn convert_document(
+    collection: &str,
+    document: &Document,
+    fields: &[(&str, FieldKind)],
+) -> (Document, Vec<MigrationFailure>) {
+    let mut typed = document.clone();
+    let mut set = Document::new();
+    let mut failures = Vec::new();
+    for (path, kind) in fields {
+        let mut segments = path.split('.');
+        let top = segments.next().unwrap_or_default();
+        let rest: Vec<&str> = segments.collect();
+        let Some(value) = typed.get_mut(top) else { continue };
+        let mut unreadable = Vec::new();
+        if convert_at(value, &rest, *kind, &mut unreadable) {
+            set.insert(top, value.clone());
+        }
+        failures.extend(unreadable.into_iter().map(|value| MigrationFailure {
+            collection: collection.to_string(),
+            id: document_id(document),
+            field: path.to_string(),
+            value,
+        }));
+    }
+    (set, failures)
+}
+
+/// Converts one collection; returns how many documents were (or would be)
+/// updated.
+async fn convert_collection(
+    db: &Database,
+    collection: &str,
+    fields: &[(&str, FieldKind)],
+    dry_run: bool,
+    failures: &mut Vec<MigrationFailure>,
+) -> Result<u64, AppError> {
+    let documents = db.collection::<Document>(collection);
+    let mut cursor = documents.find(None, None).await.map_err(AppError::database)?;
+    let mut updated = 0;
+    while let Some(document) = cursor.try_next().await.map_err(AppError::database)? {
+        let (set, mut failed) = convert_document(collection, &document, fields);
+        failures.append(&mut failed);
+        let Some(id) = document.get("_id").filter(|_| !set.is_empty()) else { continue };
+        if !dry_run {
+            documents
+                .update_one(doc! { "_id": id }, doc! { "$set": set }, None)
+                .await
+                .map_err(AppError::database)?;
+        }
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+async fn convert_typed_fields(
+    db: &Database,
+    collections: &[(&str, &[(&str, FieldKind)])],
+    dry_run: bool,
+) -> Result<StepOutcome, AppError> {
+    let mut outcome = StepOutcome::default();
+    for &(collection, fields) in collections {
+        let updated = convert_collection(db, collection, fields, dry_run, &mut outcome.failures).await?;
+        if updated > 0 {
+            outcome.changes.push(format!("Convert {} {} documents", updated, collection));
+        }
+    }
+    Ok(outcome)
+}
+
+/// The name MongoDB gives an index by default, e.g. `user_id_1_name_1`.
+fn index_name(keys: &[(&str, i32)]) -> String {
+    keys.iter().map(|(key, order)| format!("{}_{}", key, order)).collect::<Vec<_>>().join("_")
+}
+
+async fn index_names(db: &Database, collection: &str) -> Result<Vec<String>, AppError> {
+    match db.collection::<Document>(collection).list_index_names().await {
+        Ok(names) => Ok(names),
+        // NamespaceNotFound: the collection does not exist yet
+        Err(e) if matches!(&*e.kind, ErrorKind::Command(c) if c.code == 26) => Ok(Vec::new()),
+        Err(e) => Err(AppError::database(e)),
+    }
+}
+
+async fn create_indexes(db: &Database, specs: &[IndexSpec], dry_run: bool) -> Result<StepOutcome, AppError> {
+    let mut outcome = StepOutcome::default();
+    for spec in specs {
+        let name = index_name(spec.keys);
+        if index_names(db, spec.collection).await?.contains(&name) {
+            continue;
+        }
+        let unique = if spec.unique { "unique " } else { "" };
+        outcome.changes.push(format!("Create {}index {} on {}", unique, name, spec.collection));
+        if dry_run {
+            continue;
+        }
+        let keys: Document = spec.keys.iter().map(|(key, order)| (key.to_string(), Bson::Int32(*order))).collect();
+        let index = IndexModel::builder()
+            .keys(keys)
+            .options(IndexOptions::builder().unique(spec.unique).build())
+            .build();
+        // A unique index fails on existing duplicates, e.g. two accounts
+        // with one email; those need merging by hand first
+        db.collection::<Document>(spec.collection).create_index(index, None).await.map_err(|e| {
+            AppError::conflict(&format!("Could not create index {} on {}: {}", name, spec.collection, e))
+        })?;
+    }
+    Ok(outcome)
+}
+
+async fn applied_migrations(db: &Database) -> Result<Vec<AppliedMigration>, AppError> {
+    db.collection::<AppliedMigration>(MIGRATIONS_COLLECTION)
+        .find(None, None)
+        .await
+        .map_err(AppError::database)?
+        .try_collect()
+        .await
+        .map_err(AppError::database)
+}
+
+/// The migrations not yet recorded in `applied`, in order.
+fn pending(applied: &[AppliedMigration]) -> Vec<&'static Migration> {
+    MIGRATIONS.iter().filter(|m| !applied.iter().any(|a| a.id == m.id)).collect()
+}
+
+/// Runs the pending migrations in order, recording each as it completes.
+/// Stops at the first one that fails, so later ones never run on a
+/// half-migrated database.
+pub async fn run_pending(db: &Database, dry_run: bool) -> Result<Vec<MigrationStepReport>, AppError> {
+    let applied = applied_migrations(db).await?;
+    let mut steps = Vec::new();
+    for migration in pending(&applied) {
+        let outcome = match &migration.step {
+            Step::Indexes(specs) => create_indexes(db, specs, dry_run).await?,
+            Step::TypedFields(collections) => convert_typed_fields(db, collections, dry_run).await?,
+        };
+        if !dry_run {
+            let record = AppliedMigration {
+                id: migration.id.to_string(),
+                description: migration.description.to_string(),
+                applied_at: bson::DateTime::now(),
+                outcome: outcome.clone(),
+            };
+            db.collection::<AppliedMigration>(MIGRATIONS_COLLECTION)
+                .insert_one(record, None)
+                .await
+                .map_err(AppError::database)?;
+        }
+        steps.push(MigrationStepReport {
+            id: migration.id.to_string(),
+            description: migration.description.to_string(),
+            outcome,
+        });
+    }
+    Ok(steps)
+}
+
+async fn malformed_in<T: DeserializeOwned>(db: &Database, collection: &str) -> Result<Vec<MalformedDocument>, AppError> {
+    let mut cursor = db.collection::<Document>(collection).find(None, None).await.map_err(AppError::database)?;
+    let mut malformed = Vec::new();
+    while let Some(document) = cursor.try_next().await.map_err(AppError::database)? {
+        if let Err(error) = bson::from_document::<T>(document.clone()) {
+            malformed.push(MalformedDocument {
+                collection: collection.to_string(),
+                id: document_id(&document),
+                error: error.to_string(),
+            });
+        }
+    }
+    Ok(malformed)
+}
+
+/// Applied and pending migrations, in order.
+#[command]
+pub async fn get_migrations(db_state: State<'_, DbState>) -> Result<Vec<MigrationStatus>, AppError> {
+    let db = db_state.mongo("migrations").map_err(|e| AppError::validation(&e))?.db.clone();
+    let applied = applied_migrations(&db).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            id: migration.id.to_string(),
+            description: migration.description.to_string(),
+            applied_at: applied.iter().find(|a| a.id == migration.id).map(|a| dates::to_utc(a.applied_at)),
+        })
+        .collect())
+}
+
+/// Runs the pending migrations, or with `dry_run` reports what they would
+/// change without writing anything. Either way it lists the documents that
+/// do not read as medicines, visits, accounts, billing records, discount
+/// rules, bookings or queue tokens.
+#[command]
+pub async fn run_migrations(dry_run: Option<bool>, db_state: State<'_, DbState>) -> Result<MigrationRun, AppError> {
+    let db = db_state.mongo("migrations").map_err(|e| AppError::validation(&e))?.db.clone();
+    let dry_run = dry_run.unwrap_or(false);
+    let steps = run_pending(&db, dry_run).await?;
+
+    let mut malformed = malformed_in::<Medicine>(&db, "medicines").await?;
+    malformed.extend(malformed_in::<Appointment>(&db, "appointments").await?);
+    malformed.extend(malformed_in::<User>(&db, "users").await?);
+    malformed.extend(malformed_in::<Invoice>(&db, "invoices").await?);
+    malformed.extend(malformed_in::<CreditCustomer>(&db, "credit_customers").await?);
+    malformed.extend(malformed_in::<LedgerEntry>(&db, "credit_ledger").await?);
+    malformed.extend(malformed_in::<CreditNote>(&db, "credit_notes").await?);
+    malformed.extend(malformed_in::<Shift>(&db, "shifts").await?);
+    malformed.extend(malformed_in::<DiscountRule>(&db, "discount_rules").await?);
+    malformed.extend(malformed_in::<ScheduledAppointment>(&db, "scheduled_appointments").await?);
+    malformed.extend(malformed_in::<QueueToken>(&db, "queue_tokens").await?);
+    Ok(MigrationRun { dry_run, steps, malformed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use mongodb::bson::oid::ObjectId;
+
+    #[test]
+    fn migrations_run_in_order_once_each() {
+        let applied = |id: &str| AppliedMigration {
+            id: id.to_string(),
+            description: String::new(),
+            applied_at: bson::DateTime::now(),
+            outcome: StepOutcome::default(),
+        };
+        let ids = |migrations: Vec<&Migration>| migrations.iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(pending(&[])),
+            [
+                "0001_indexes",
+                "0002_typed_fields",
+                "0003_search_indexes",
+                "0004_typed_billing",
+                "0005_typed_discount_rules",
+                "0006_typed_visit_dates"
+            ]
+        );
+        assert_eq!(
+            ids(pending(&[
+                applied("0001_indexes"),
+                applied("0003_search_indexes"),
+                applied("0005_typed_discount_rules")
+            ])),
+            ["0002_typed_fields", "0004_typed_billing", "0006_typed_visit_dates"]
+        );
+        assert_eq!(index_name(&[("hospital_id", 1), ("date_created", -1)]), "hospital_id_1_date_created_-1");
+
+        let stored = bson::to_document(&applied("0001_indexes")).unwrap();
+        assert_eq!(stored.get_str("_id").unwrap(), "0001_indexes");
+        assert!(stored.get_array("changes").unwrap().is_empty());
+    }
+
+    #[test]
+    fn legacy_medicines_are_typed_and_unreadable_values_reported() {
+        let id = ObjectId::new();
+        let mut legacy = doc! {
+            "_id": id, "user_id": "h1", "name": "Paracetamol", "batch_number": "B1", "expiry_date": "31/03/2027",
+            "quantity": 5, "purchase_price": 8.125, "selling_price": "10", "wholesaler_name": "Apex Pharma",
+            "purchase_date": "next week",
+        };
+
+        let (set, failures) = convert_document("medicines", &legacy, MEDICINE_FIELDS);
+        assert_eq!(
+            failures,
+            [MigrationFailure {
+                collection: "medicines".to_string(),
+                id: id.to_hex(),
+                field: "purchase_date".to_string(),
+                value: "next week".to_string(),
+            }]
+        );
+        assert_eq!(set.get_i64("purchase_price").unwrap(), 813);
+        assert_eq!(set.get_i64("selling_price").unwrap(), 1000);
+
+        legacy.extend(set);
+        legacy.insert("purchase_date", "2026-10-01");
+        let (set, failures) = convert_document("medicines", &legacy, MEDICINE_FIELDS);
+        assert!(failures.is_empty());
+        legacy.extend(set);
+        let medicine: Medicine = bson::from_document(legacy.clone()).unwrap();
+        assert_eq!(medicine.expiry_date.to_string(), "2027-03-31");
+        assert_eq!(convert_document("medicines", &legacy, MEDICINE_FIELDS), (Document::new(), Vec::new()));
+    }
+
+    #[test]
+    fn timestamps_are_converted_and_missing_ones_left_alone() {
+        let user = doc! { "_id": ObjectId::new(), "otp_expiry": Bson::Null };
+        assert_eq!(convert_document("users", &user, USER_FIELDS).0, Document::new());
+
+        let visit = doc! { "_id": ObjectId::new(), "date_created": "2026-10-19T05:00:00+00:00" };
+        let (set, _) = convert_document("appointments", &visit, APPOINTMENT_FIELDS);
+        let created = dates::to_utc(*set.get_datetime("date_created").unwrap());
+        assert_eq!(created.to_rfc3339(), "2026-10-19T05:00:00+00:00");
+    }
+
+    #[test]
+    fn legacy_invoices_are_typed_through_lines_and_payments() {
+        let id = ObjectId::new();
+        let line = |total: f64| doc! { "name": "Paracetamol", "unit_price": total, "taxable_value": total, "total": total };
+        let legacy = doc! {
+            "_id": id, "lines": [line(30.0), line(18.755)], "grand_total": 49.0, "change_due": 1.0,
+            "payments": [{ "mode": "cash", "amount": 50.0 }, { "mode": "upi", "amount": "lots" }],
+            "date_created": "2026-10-19T05:00:00+00:00",
+        };
+
+        let (set, failures) = convert_document("invoices", &legacy, INVOICE_FIELDS);
+        let lines = set.get_array("lines").unwrap();
+        let totals: Vec<i64> = lines.iter().map(|l| l.as_document().unwrap().get_i64("total").unwrap()).collect();
+        assert_eq!(totals, [3000, 1876]);
+        assert_eq!(lines[0].as_document().unwrap().get_str("name").unwrap(), "Paracetamol");
+        assert_eq!(set.get_i64("grand_total").unwrap(), 4900);
+        assert!(set.get_datetime("date_created").is_ok());
+        let payments = set.get_array("payments").unwrap();
+        assert_eq!(payments[0].as_document().unwrap().get_i64("amount").unwrap(), 5000);
+        assert_eq!(
+            failures,
+            [MigrationFailure {
+                collection: "invoices".to_string(),
+                id: id.to_hex(),
+                field: "payments.amount".to_string(),
+                value: "lots".to_string(),
+            }]
+        );
+
+        let shift = doc! { "_id": ObjectId::new(), "opening_float": 500.0, "opened_at": "2026-10-19T03:00:00Z", "z_report": Bson::Null };
+        let (set, failures) = convert_document("shifts", &shift, SHIFT_FIELDS);
+        assert!(failures.is_empty());
+        assert_eq!(set.keys().collect::<Vec<_>>(), ["opening_float", "opened_at"]);
+    }
+
+    #[test]
+    fn legacy_discount_rules_are_typed_and_read_back() {
+        let mut legacy = doc! {
+            "_id": ObjectId::new(), "hospital_id": "h1", "name": "Big bills", "customer_category": Bson::Null,
+            "min_bill_value": 999.5, "medicine_name": Bson::Null, "discount": { "kind": "percent", "value": 5.0 },
+            "active": true, "date_created": "2026-10-01T04:00:00+00:00",
+        };
+        let (set, failures) = convert_document("discount_rules", &legacy, DISCOUNT_RULE_FIELDS);
+        assert!(failures.is_empty());
+        assert_eq!(set.get_i64("min_bill_value").unwrap(), 99950);
+
+        legacy.extend(set);
+        let rule: DiscountRule = bson::from_document(legacy.clone()).unwrap();
+        assert_eq!(rule.min_bill_value, Some(Money::from_paise(99950)));
+        assert_eq!(dates::to_utc(rule.date_created).to_rfc3339(), "2026-10-01T04:00:00+00:00");
+
+        let no_minimum = doc! { "_id": ObjectId::new(), "min_bill_value": Bson::Null, "date_created": Bson::Null };
+        let (set, failures) = convert_document("discount_rules", &no_minimum, DISCOUNT_RULE_FIELDS);
+        assert!(set.is_empty() && failures.is_empty());
+    }
+
+    #[test]
+    fn legacy_follow_ups_and_queue_tokens_get_bson_dates() {
+        let visit = doc! { "_id": ObjectId::new(), "follow_up_date": "2026-10-26" };
+        let (set, _) = convert_document("appointments", &visit, FOLLOW_UP_FIELDS);
+        let review = dates::to_utc(*set.get_datetime("follow_up_date").unwrap());
+        assert_eq!(review.to_rfc3339(), "2026-10-26T00:00:00+00:00");
+        let no_review = doc! { "_id": ObjectId::new(), "follow_up_date": Bson::Null };
+        assert_eq!(convert_document("appointments", &no_review, FOLLOW_UP_FIELDS), (Document::new(), Vec::new()));
+
+        let mut token = doc! {
+            "_id": ObjectId::new(), "hospital_id": "h1", "doctor_id": "d1", "queue_date": "2026-10-19",
+            "token_number": 4, "patient_name": "Meera Iyer", "mobile": "9123456780", "status": "waiting",
+            "issued_at": "2026-10-19T04:00:00+00:00", "called_at": Bson::Null,
+        };
+        let (set, failures) = convert_document("queue_tokens", &token, QUEUE_TOKEN_FIELDS);
+        assert!(failures.is_empty());
+        assert_eq!(set.keys().collect::<Vec<_>>(), ["queue_date", "issued_at"]);
+        token.extend(set);
+        let token: QueueToken = bson::from_document(token).unwrap();
+        assert_eq!(token.queue_date.to_string(), "2026-10-19");
+        assert_eq!(dates::to_utc(token.issued_at).to_rfc3339(), "2026-10-19T04:00:00+00:00");
+    }
+}
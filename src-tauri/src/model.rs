@@ -1,9 +1,11 @@
 // src-tauri/src/model.rs
-use mongodb::bson::oid::ObjectId;
+use crate::settings::HospitalSettings;
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub username: String,
     pub name: String,
@@ -14,5 +16,22 @@ pub struct User {
     pub password_hash_pharma: String,
     pub email: String,
     pub otp: Option<String>,
-    pub otp_expiry: Option<String>,
+    #[serde(default, deserialize_with = "crate::dates::optional_timestamp")]
+    pub otp_expiry: Option<DateTime>,
+    #[serde(default)]
+    pub registration_number: Option<String>, // Medical council registration, printed on prescriptions
+    #[serde(default)]
+    pub gstin: Option<String>,
+    #[serde(default)]
+    pub state_code: Option<String>, // GST state code, used when there is no GSTIN
+    #[serde(default)]
+    pub invoice_prefix: Option<String>, // e.g. "HOSP" in HOSP/2026-27/000123
+    #[serde(default)]
+    pub upi_id: Option<String>, // VPA printed as a payment QR on receipts
+    #[serde(default)]
+    pub logo_path: Option<String>, // JPEG printed on the invoice and prescription letterheads
+    #[serde(default)]
+    pub letterhead: Option<String>, // extra letterhead lines, e.g. drug licence numbers
+    #[serde(default)]
+    pub settings: HospitalSettings,
 }
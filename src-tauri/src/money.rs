@@ -0,0 +1,132 @@
+// src-tauri/src/money.rs
+//! Amounts in whole paise, so stock values and bill totals add up exactly.
+//! Stored as a BSON Int64. Documents written before this type held rupees
+//! as a double; those are read as rupees and rounded to the paisa until
+//! the `0002_typed_fields` migration rewrites them.
+use crate::error::AppError;
+use crate::tax::{to_paise, to_rupees};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub fn from_paise(paise: i64) -> Self {
+        Money(paise)
+    }
+
+    pub fn from_rupees(rupees: f64) -> Self {
+        Money(to_paise(rupees))
+    }
+
+    pub fn paise(self) -> i64 {
+        self.0
+    }
+
+    pub fn rupees(self) -> f64 {
+        to_rupees(self.0)
+    }
+
+    /// Checks an amount entered in rupees at the command boundary.
+    pub fn parse_rupees(field: &str, rupees: f64) -> Result<Self, AppError> {
+        if !rupees.is_finite() || rupees < 0.0 {
+            return Err(AppError::invalid(field, &format!("{} must be zero or more.", field.replace('_', " "))));
+        }
+        Ok(Money::from_rupees(rupees))
+    }
+}
+
+impl std::ops::Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl std::ops::Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl std::ops::Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(amounts: I) -> Money {
+        Money(amounts.map(|m| m.0).sum())
+    }
+}
+
+struct MoneyVisitor;
+
+impl Visitor<'_> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an amount in paise, or a legacy amount in rupees")
+    }
+
+    fn visit_i64<E: de::Error>(self, paise: i64) -> Result<Money, E> {
+        Ok(Money(paise))
+    }
+
+    fn visit_u64<E: de::Error>(self, paise: u64) -> Result<Money, E> {
+        i64::try_from(paise).map(Money).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, rupees: f64) -> Result<Money, E> {
+        Ok(Money::from_rupees(rupees))
+    }
+
+    fn visit_str<E: de::Error>(self, rupees: &str) -> Result<Money, E> {
+        rupees
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|r| r.is_finite())
+            .map(Money::from_rupees)
+            .ok_or_else(|| E::custom(format!("invalid amount: {}", rupees)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc};
+
+    #[test]
+    fn paise_are_stored_and_legacy_rupees_are_read() {
+        #[derive(Serialize, Deserialize)]
+        struct Priced {
+            price: Money,
+        }
+
+        let stored = bson::to_document(&Priced { price: Money::from_rupees(12.35) }).unwrap();
+        assert_eq!(stored, doc! { "price": 1235_i64 });
+        let legacy: Priced = bson::from_document(doc! { "price": 12.345 }).unwrap();
+        assert_eq!(legacy.price.paise(), 1235);
+        let typed: Priced = serde_json::from_str(r#"{"price": 1235}"#).unwrap();
+        assert_eq!(typed.price.rupees(), 12.35);
+
+        assert!(Money::parse_rupees("selling_price", -1.0).is_err());
+        assert!(Money::parse_rupees("selling_price", f64::NAN).is_err());
+    }
+}
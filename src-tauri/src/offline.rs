@@ -0,0 +1,487 @@
+// src-tauri/src/offline.rs
+//! Keeps the counter working while MongoDB is out of reach. The store holds
+//! a replica of each tracked hospital's stock, account (password hashes
+//! included, so staff can still log in) and discount rules and a journal of the sales, stock
+//! changes and appointments made offline. Both are written to one JSON file
+//! before a command returns; `sync.rs` pushes the journal to MongoDB once
+//! the link is back and refreshes the replica.
+use crate::billing::{financial_year, format_document_number, Invoice};
+use crate::clinical::{ClinicalNotes, Vitals};
+use crate::commands::{Appointment, Medicine};
+use crate::config::OfflineSettings;
+use crate::discount::DiscountRule;
+use crate::model::User;
+use crate::repository::memory::MemoryStore;
+use crate::repository::{
+    AppointmentQuery, AppointmentRepository, InvoiceInsert, InvoiceRepository, MedicineChanges, MedicineQuery,
+    MedicineRepository, PasswordKind, UpdateOutcome, UserRepository,
+};
+use crate::sync::{ChangeKind, PendingChange, StockDelta};
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const NEEDS_CONNECTION: &str = "This change needs the central database; it is not available offline.";
+
+/// What was done offline and not yet pushed, in the order it happened.
+#[derive(Default, Serialize, Deserialize)]
+struct Journal {
+    pending: Vec<PendingChange>,
+    hospitals: Vec<String>,          // whose stock and account the replica keeps
+    counters: BTreeMap<String, i64>, // offline invoice series, per hospital and year
+    last_sync: Option<String>,
+    #[serde(default)]
+    series: Option<String>, // made up on first use when `offline.series` is unset
+}
+
+#[derive(Serialize)]
+struct SavedRef<'a> {
+    replica: &'a MemoryStore,
+    journal: &'a Journal,
+}
+
+#[derive(Default, Deserialize)]
+struct Saved {
+    replica: MemoryStore,
+    journal: Journal,
+}
+
+struct Inner {
+    path: Option<PathBuf>,
+    series: String,
+    online: AtomicBool,
+    replica: MemoryStore,
+    // Held across every write, so a change and its journal entry land together
+    journal: Mutex<Journal>,
+}
+
+#[derive(Clone)]
+pub struct OfflineStore {
+    inner: Arc<Inner>,
+}
+
+fn parse_id(id: &str) -> Result<ObjectId, String> {
+    ObjectId::parse_str(id).map_err(|_| "Invalid medicine ID".to_string())
+}
+
+/// A series for a counter with none set: `T` and five random hex digits.
+fn counter_series() -> String {
+    format!("T{:05X}", rand::random::<u32>() & 0xF_FFFF)
+}
+
+fn is_quantity_only(changes: &MedicineChanges) -> bool {
+    changes.quantity.is_some() && MedicineChanges { quantity: None, ..changes.clone() }.is_empty()
+}
+
+impl OfflineStore {
+    /// Opens the store file (`offline.store_path`). Invoices made offline
+    /// are numbered in their own series, `<prefix>-<offline.series>`, so they
+    /// never take a number from the central one. Without `offline.series`
+    /// the counter makes up a random one and keeps it in the store file, so
+    /// two tills left on the defaults do not hand out the same numbers.
+    pub fn from_settings(settings: &OfflineSettings) -> Result<Self, String> {
+        let series = settings.series.as_ref().map(|series| series.to_uppercase());
+        Self::open(PathBuf::from(&settings.store_path), series.as_deref())
+    }
+
+    pub fn open(path: PathBuf, series: Option<&str>) -> Result<Self, String> {
+        let saved = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Offline store {} is unreadable: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Saved::default(),
+            Err(e) => return Err(format!("Failed to read offline store {}: {}", path.display(), e)),
+        };
+        Ok(Self::new(Some(path), series, saved))
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::new(None, None, Saved::default())
+    }
+
+    fn new(path: Option<PathBuf>, series: Option<&str>, mut saved: Saved) -> Self {
+        let series = match series {
+            Some(series) => series.to_string(),
+            None => saved.journal.series.get_or_insert_with(counter_series).clone(),
+        };
+        OfflineStore {
+            inner: Arc::new(Inner {
+                path,
+                series,
+                online: AtomicBool::new(true),
+                replica: saved.replica,
+                journal: Mutex::new(saved.journal),
+            }),
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.inner.online.load(Ordering::SeqCst)
+    }
+
+    pub fn set_online(&self, online: bool) {
+        self.inner.online.store(online, Ordering::SeqCst);
+    }
+
+    pub fn series(&self) -> &str {
+        &self.inner.series
+    }
+
+    /// Whether there is anything to work from without MongoDB.
+    pub fn has_replica(&self) -> bool {
+        !self.inner.replica.users.lock().unwrap().is_empty()
+    }
+
+    pub async fn pending(&self) -> Vec<PendingChange> {
+        self.inner.journal.lock().await.pending.clone()
+    }
+
+    pub async fn last_sync(&self) -> Option<String> {
+        self.inner.journal.lock().await.last_sync.clone()
+    }
+
+    pub async fn tracked_hospitals(&self) -> Vec<String> {
+        self.inner.journal.lock().await.hospitals.clone()
+    }
+
+    /// Keeps a replica of this hospital from the next sync on.
+    pub async fn track_hospital(&self, hospital_id: &str) -> Result<(), String> {
+        let mut journal = self.inner.journal.lock().await;
+        if journal.hospitals.iter().any(|h| h == hospital_id) {
+            return Ok(());
+        }
+        journal.hospitals.push(hospital_id.to_string());
+        self.persist(&journal)
+    }
+
+    /// Swaps a pushed change for what is left of it (nothing, or the stock
+    /// half of a sale whose invoice is in).
+    pub(crate) async fn replace_pending(&self, change_id: &str, rest: Vec<PendingChange>) -> Result<(), String> {
+        let mut journal = self.inner.journal.lock().await;
+        if let Some(position) = journal.pending.iter().position(|c| c.id == change_id) {
+            journal.pending.splice(position..=position, rest);
+        }
+        self.persist(&journal)
+    }
+
+    /// The discount rules a hospital had active at the last sync.
+    pub fn discount_rules(&self, hospital_id: &str) -> Vec<DiscountRule> {
+        let rules = self.inner.replica.discount_rules.lock().unwrap();
+        rules.iter().filter(|r| r.hospital_id == hospital_id && r.active).cloned().collect()
+    }
+
+    /// Replaces a hospital's replica with what MongoDB holds. Skipped while
+    /// the hospital still has changes to push, as they would vanish from
+    /// the local view. Returns whether the replica was replaced.
+    pub(crate) async fn refresh(
+        &self,
+        hospital: User,
+        medicines: Vec<Medicine>,
+        rules: Vec<DiscountRule>,
+    ) -> Result<bool, String> {
+        let hospital_id = hospital.id.map(|id| id.to_hex()).unwrap_or_default();
+        let mut journal = self.inner.journal.lock().await;
+        if journal.pending.iter().any(|c| c.hospital_id == hospital_id) {
+            return Ok(false);
+        }
+
+        let replica = &self.inner.replica;
+        {
+            let mut stored = replica.discount_rules.lock().unwrap();
+            stored.retain(|r| r.hospital_id != hospital_id);
+            stored.extend(rules);
+        }
+        {
+            let mut stored = replica.medicines.lock().unwrap();
+            stored.retain(|m| m.user_id != hospital_id);
+            stored.extend(medicines);
+        }
+        {
+            let mut users = replica.users.lock().unwrap();
+            users.retain(|u| u.id != hospital.id);
+            users.push(hospital);
+        }
+        // Offline sales and visits are in MongoDB now
+        replica.invoices.lock().unwrap().retain(|i| i.hospital_id != hospital_id);
+        replica.appointments.lock().unwrap().retain(|a| a.hospital_id != hospital_id);
+        journal.last_sync = Some(Utc::now().to_rfc3339());
+        self.persist(&journal)?;
+        Ok(true)
+    }
+
+    /// Bills from the replica: takes the stock locally and numbers the
+    /// invoice in this counter's offline series.
+    pub async fn save_invoice(&self, mut invoice: Invoice, prefix: &str, today: NaiveDate) -> Result<Invoice, String> {
+        let mut journal = self.inner.journal.lock().await;
+        let replica = &self.inner.replica;
+
+        let mut stock = Vec::with_capacity(invoice.lines.len());
+        {
+            let mut medicines = replica.medicines.lock().unwrap();
+            for line in &invoice.lines {
+                let id = parse_id(&line.medicine_id)?;
+                match medicines.iter().find(|m| m.id == Some(id) && m.user_id == invoice.hospital_id) {
+                    Some(medicine) if medicine.quantity >= line.quantity => stock.push(StockDelta {
+                        medicine_id: line.medicine_id.clone(),
+                        name: medicine.name.clone(),
+                        batch_number: medicine.batch_number.clone(),
+                        delta: -(line.quantity as i64),
+                        expected_quantity: medicine.quantity,
+                    }),
+                    _ => return Err(format!("Insufficient stock for {} (batch {}).", line.name, line.batch_number)),
+                }
+            }
+            for delta in &stock {
+                if let Some(medicine) = medicines.iter_mut().find(|m| m.id.is_some_and(|id| id.to_hex() == delta.medicine_id)) {
+                    medicine.quantity = (medicine.quantity as i64 + delta.delta) as u32;
+                }
+            }
+        }
+
+        let year = financial_year(today);
+        let counter = format!("{}:invoice:{}", invoice.hospital_id, year);
+        let sequence = *journal.counters.entry(counter.clone()).and_modify(|n| *n += 1).or_insert(1);
+        invoice.invoice_number = format_document_number(&format!("{}-{}", prefix, self.inner.series), &year, sequence);
+        invoice.id = Some(ObjectId::new());
+        replica.invoices.lock().unwrap().push(invoice.clone());
+
+        let change = PendingChange::new(&invoice.hospital_id, ChangeKind::Sale { invoice: Box::new(invoice.clone()), stock: stock.clone() });
+        if let Err(error) = self.append(&mut journal, change) {
+            *journal.counters.entry(counter).or_default() -= 1;
+            replica.invoices.lock().unwrap().retain(|i| i.id != invoice.id);
+            for delta in &stock {
+                self.set_quantity(&delta.medicine_id, delta.expected_quantity);
+            }
+            return Err(error);
+        }
+        Ok(invoice)
+    }
+
+    /// Changes one batch's quantity in the replica and journals it. Runs
+    /// with the journal locked.
+    fn change_quantity(
+        &self,
+        journal: &mut Journal,
+        found: Option<Medicine>,
+        quantity: impl FnOnce(u32) -> u32,
+    ) -> Result<Option<u32>, String> {
+        let Some(medicine) = found else { return Ok(None) };
+        let new_quantity = quantity(medicine.quantity);
+        if new_quantity == medicine.quantity {
+            return Ok(Some(new_quantity));
+        }
+        let medicine_id = medicine.id.map(|id| id.to_hex()).unwrap_or_default();
+        self.set_quantity(&medicine_id, new_quantity);
+
+        let delta = StockDelta {
+            medicine_id: medicine_id.clone(),
+            name: medicine.name,
+            batch_number: medicine.batch_number,
+            delta: new_quantity as i64 - medicine.quantity as i64,
+            expected_quantity: medicine.quantity,
+        };
+        let change = PendingChange::new(&medicine.user_id, ChangeKind::Stock { delta, invoice_number: None });
+        if let Err(error) = self.append(journal, change) {
+            self.set_quantity(&medicine_id, medicine.quantity);
+            return Err(error);
+        }
+        Ok(Some(new_quantity))
+    }
+
+    fn set_quantity(&self, medicine_id: &str, quantity: u32) {
+        let mut medicines = self.inner.replica.medicines.lock().unwrap();
+        if let Some(medicine) = medicines.iter_mut().find(|m| m.id.is_some_and(|id| id.to_hex() == medicine_id)) {
+            medicine.quantity = quantity;
+        }
+    }
+
+    /// Adds a change to the journal and writes the store out; on failure
+    /// the change is dropped again and the caller undoes its effect.
+    fn append(&self, journal: &mut Journal, change: PendingChange) -> Result<(), String> {
+        journal.pending.push(change);
+        let result = self.persist(journal);
+        if result.is_err() {
+            journal.pending.pop();
+        }
+        result
+    }
+
+    /// Writes to a temporary file and renames it over the store, so a crash
+    /// leaves either the old or the new state.
+    fn persist(&self, journal: &Journal) -> Result<(), String> {
+        let Some(path) = &self.inner.path else { return Ok(()) };
+        let bytes = serde_json::to_vec(&SavedRef { replica: &self.inner.replica, journal }).map_err(|e| e.to_string())?;
+        let temp = path.with_extension("tmp");
+        let write = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&temp)?;
+            std::io::Write::write_all(&mut file, &bytes)?;
+            file.sync_all()?;
+            std::fs::rename(&temp, path)
+        };
+        write().map_err(|e| format!("Failed to save offline store {}: {}", path.display(), e))
+    }
+}
+
+#[async_trait]
+impl MedicineRepository for OfflineStore {
+    async fn insert_medicine(&self, _medicine: Medicine) -> Result<ObjectId, String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+
+    async fn find_medicine(&self, id: ObjectId, hospital_id: Option<&str>) -> Result<Option<Medicine>, String> {
+        self.inner.replica.find_medicine(id, hospital_id).await
+    }
+
+    async fn find_medicines(&self, ids: &[ObjectId], hospital_id: &str) -> Result<Vec<Medicine>, String> {
+        self.inner.replica.find_medicines(ids, hospital_id).await
+    }
+
+    async fn list_medicines(&self, hospital_id: &str, query: &MedicineQuery) -> Result<Vec<Medicine>, String> {
+        self.inner.replica.list_medicines(hospital_id, query).await
+    }
+
+    /// Only quantity changes are taken offline; prices and details wait for
+    /// the connection.
+    async fn update_medicine(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        batch_number: Option<&str>,
+        changes: &MedicineChanges,
+    ) -> Result<UpdateOutcome, String> {
+        let (true, Some(quantity)) = (is_quantity_only(changes), changes.quantity) else {
+            return Err(NEEDS_CONNECTION.to_string());
+        };
+        let mut journal = self.inner.journal.lock().await;
+        let found = self
+            .inner
+            .replica
+            .find_medicine(id, Some(hospital_id))
+            .await?
+            .filter(|m| batch_number.is_none_or(|b| m.batch_number == b));
+        let before = found.as_ref().map(|m| m.quantity);
+        let after = self.change_quantity(&mut journal, found, |_| quantity)?;
+        Ok(UpdateOutcome { matched: after.is_some(), modified: after != before })
+    }
+
+    async fn take_stock(&self, id: ObjectId, batch_number: &str, quantity: u32) -> Result<bool, String> {
+        let mut journal = self.inner.journal.lock().await;
+        let found = self
+            .inner
+            .replica
+            .find_medicine(id, None)
+            .await?
+            .filter(|m| m.batch_number == batch_number && m.quantity >= quantity);
+        Ok(self.change_quantity(&mut journal, found, |q| q - quantity)?.is_some())
+    }
+
+    async fn adjust_quantity(&self, id: ObjectId, hospital_id: &str, delta: i64) -> Result<Option<u32>, String> {
+        let mut journal = self.inner.journal.lock().await;
+        let found = self.inner.replica.find_medicine(id, Some(hospital_id)).await?;
+        self.change_quantity(&mut journal, found, |q| (q as i64 + delta).clamp(0, u32::MAX as i64) as u32)
+    }
+
+    async fn delete_medicine(&self, _id: ObjectId, _hospital_id: &str, _batch_number: Option<&str>) -> Result<bool, String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+}
+
+/// Lists only the visits recorded since the last sync.
+#[async_trait]
+impl AppointmentRepository for OfflineStore {
+    async fn insert_appointment(&self, appointment: Appointment) -> Result<(), String> {
+        let mut journal = self.inner.journal.lock().await;
+        let id = appointment.id;
+        let change = PendingChange::new(&appointment.hospital_id, ChangeKind::Appointment { appointment: Box::new(appointment.clone()) });
+        self.inner.replica.insert_appointment(appointment).await?;
+        if let Err(error) = self.append(&mut journal, change) {
+            self.inner.replica.appointments.lock().unwrap().retain(|a| a.id != id);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    async fn list_appointments(&self, hospital_id: &str, query: &AppointmentQuery) -> Result<Vec<Appointment>, String> {
+        self.inner.replica.list_appointments(hospital_id, query).await
+    }
+
+    async fn delete_stale_appointments(
+        &self,
+        _hospital_id: &str,
+        _cutoff: DateTime,
+        _today: NaiveDate,
+    ) -> Result<u64, String> {
+        Ok(0) // the central database is cleaned once they are pushed
+    }
+
+    async fn update_clinical(
+        &self,
+        _id: ObjectId,
+        _hospital_id: &str,
+        _vitals: Option<&Vitals>,
+        _notes: Option<&ClinicalNotes>,
+    ) -> Result<bool, String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+
+    async fn vitals_history(&self, hospital_id: &str, mobile: &str, limit: usize) -> Result<Vec<Appointment>, String> {
+        self.inner.replica.vitals_history(hospital_id, mobile, limit).await
+    }
+}
+
+#[async_trait]
+impl UserRepository for OfflineStore {
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, String> {
+        self.inner.replica.find_user_by_id(id).await
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        self.inner.replica.find_user_by_username(username).await
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        self.inner.replica.find_user_by_email(email).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, String> {
+        self.inner.replica.list_users().await
+    }
+
+    async fn insert_user(&self, _user: User) -> Result<(), String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+
+    async fn replace_user(&self, _user: &User) -> Result<(), String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+
+    async fn set_otp(&self, _email: &str, _otp: Option<(&str, DateTime)>) -> Result<(), String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+
+    async fn set_password_hash(&self, _email: &str, _kind: PasswordKind, _hash: &str) -> Result<(), String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+}
+
+/// Lists only the invoices issued since the last sync.
+#[async_trait]
+impl InvoiceRepository for OfflineStore {
+    async fn find_invoice(&self, hospital_id: &str, invoice_number: &str) -> Result<Option<Invoice>, String> {
+        self.inner.replica.find_invoice(hospital_id, invoice_number).await
+    }
+
+    async fn list_invoices(&self, hospital_id: &str, from: DateTime, to: DateTime) -> Result<Vec<Invoice>, String> {
+        self.inner.replica.list_invoices(hospital_id, from, to).await
+    }
+
+    async fn insert_invoice(&self, _invoice: &Invoice) -> Result<InvoiceInsert, String> {
+        Err(NEEDS_CONNECTION.to_string())
+    }
+}
@@ -0,0 +1,352 @@
+// src-tauri/src/payment.rs
+use crate::billing::{invoices_collection, utc_day_range, Invoice};
+use crate::credit::{ledger_collection, LedgerEntry};
+use crate::db::DbState;
+use crate::money::Money;
+use crate::repository::InvoiceRepository;
+use crate::returns::{credit_notes_collection, CreditNote};
+use crate::tax::{to_paise, to_rupees};
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMode {
+    Cash,
+    Upi,
+    Card,
+    Credit, // on the customer's account
+}
+
+impl PaymentMode {
+    pub const ALL: [PaymentMode; 4] = [PaymentMode::Cash, PaymentMode::Upi, PaymentMode::Card, PaymentMode::Credit];
+
+    /// How the mode is printed on bills and reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            PaymentMode::Cash => "Cash",
+            PaymentMode::Upi => "UPI",
+            PaymentMode::Card => "Card",
+            PaymentMode::Credit => "On account",
+        }
+    }
+}
+
+/// One tender on a bill, as stored. For cash this is what the customer
+/// handed over; any excess goes back as change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payment {
+    pub mode: PaymentMode,
+    pub amount: Money,
+    #[serde(default)]
+    pub reference: Option<String>, // UPI transaction ID or card approval code
+}
+
+/// A payment as the counter enters and sees it, in rupees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tender {
+    pub mode: PaymentMode,
+    pub amount: f64,
+    #[serde(default)]
+    pub reference: Option<String>,
+}
+
+impl From<&Payment> for Tender {
+    fn from(payment: &Payment) -> Self {
+        Tender { mode: payment.mode, amount: payment.amount.rupees(), reference: payment.reference.clone() }
+    }
+}
+
+/// Checks the tenders against the bill total and works out the change.
+/// Only cash can be overpaid; UPI, card and credit amounts are charged
+/// exactly, so together they may not exceed the total.
+pub fn settle_payments(tenders: Vec<Tender>, total: Money) -> Result<(Vec<Payment>, Money), String> {
+    if tenders.is_empty() {
+        return Err("Add at least one payment.".to_string());
+    }
+
+    let total = total.paise();
+    let mut settled = Vec::with_capacity(tenders.len());
+    let (mut cash, mut other) = (0, 0);
+    for tender in tenders {
+        let amount = to_paise(tender.amount);
+        if !tender.amount.is_finite() || amount <= 0 {
+            return Err("Payment amounts must be more than zero.".to_string());
+        }
+        match tender.mode {
+            PaymentMode::Cash => cash += amount,
+            _ => other += amount,
+        }
+        settled.push(Payment {
+            mode: tender.mode,
+            amount: Money::from_paise(amount),
+            reference: tender.reference.map(|r| r.trim().to_string()).filter(|r| !r.is_empty()),
+        });
+    }
+
+    if other > total {
+        return Err(format!(
+            "UPI, card and credit payments ({:.2}) are more than the bill total ({:.2}).",
+            to_rupees(other),
+            to_rupees(total)
+        ));
+    }
+    let short = total - cash - other;
+    if short > 0 {
+        return Err(format!("Payments are short by {:.2}.", to_rupees(short)));
+    }
+
+    Ok((settled, Money::from_paise(-short)))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentModeTotal {
+    pub mode: PaymentMode,
+    pub count: u32, // payments in this mode
+    pub amount: Money, // cash is net of change given
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentTotals {
+    pub invoice_count: u32,
+    pub by_mode: Vec<PaymentModeTotal>, // includes receipts against credit accounts
+    pub change_given: Money,
+    pub credit_sales: Money,
+    pub credit_receipts: Money,
+    pub refunds: Money, // cash paid back on voids and returns
+    pub total_collected: Money, // money kept, so credit sales and refunds are left out
+}
+
+impl PaymentTotals {
+    pub fn mode_total(&self, mode: PaymentMode) -> Money {
+        self.by_mode.iter().find(|m| m.mode == mode).map(|m| m.amount).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PaymentModeTotalResponse {
+    pub mode: PaymentMode,
+    pub count: u32,
+    pub amount: f64,
+}
+
+/// Payment totals in rupees.
+#[derive(Serialize, Debug, Clone)]
+pub struct PaymentTotalsResponse {
+    pub invoice_count: u32,
+    pub by_mode: Vec<PaymentModeTotalResponse>,
+    pub change_given: f64,
+    pub credit_sales: f64,
+    pub credit_receipts: f64,
+    pub refunds: f64,
+    pub total_collected: f64,
+}
+
+impl From<&PaymentTotals> for PaymentTotalsResponse {
+    fn from(totals: &PaymentTotals) -> Self {
+        PaymentTotalsResponse {
+            invoice_count: totals.invoice_count,
+            by_mode: totals
+                .by_mode
+                .iter()
+                .map(|m| PaymentModeTotalResponse { mode: m.mode, count: m.count, amount: m.amount.rupees() })
+                .collect(),
+            change_given: totals.change_given.rupees(),
+            credit_sales: totals.credit_sales.rupees(),
+            credit_receipts: totals.credit_receipts.rupees(),
+            refunds: totals.refunds.rupees(),
+            total_collected: totals.total_collected.rupees(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PaymentSummary {
+    pub from_date: String,
+    pub to_date: String,
+    #[serde(flatten)]
+    pub totals: PaymentTotalsResponse,
+}
+
+/// Adds up what was taken in each mode on `invoices` and on `receipts`
+/// against credit accounts. Voided and returned sales still count where
+/// they were paid; their refunds come off through `credit_notes`, which may
+/// fall in a different period or shift.
+pub fn summarize_payments(invoices: &[Invoice], receipts: &[LedgerEntry], credit_notes: &[CreditNote]) -> PaymentTotals {
+    let mut totals: Vec<(PaymentMode, u32, i64)> = PaymentMode::ALL.iter().map(|m| (*m, 0, 0)).collect();
+    let (mut invoice_count, mut change) = (0, 0);
+
+    for invoice in invoices {
+        invoice_count += 1;
+        change += invoice.change_due.paise();
+        for payment in &invoice.payments {
+            if let Some(entry) = totals.iter_mut().find(|(mode, _, _)| *mode == payment.mode) {
+                entry.1 += 1;
+                entry.2 += payment.amount.paise();
+            }
+        }
+    }
+    if let Some(cash) = totals.iter_mut().find(|(mode, _, _)| *mode == PaymentMode::Cash) {
+        cash.2 -= change;
+    }
+    let credit_sales = totals
+        .iter()
+        .find(|(mode, _, _)| *mode == PaymentMode::Credit)
+        .map_or(0, |(_, _, amount)| *amount);
+
+    let mut credit_receipts = 0;
+    for receipt in receipts {
+        let Some(mode) = receipt.mode else { continue };
+        if let Some(entry) = totals.iter_mut().find(|(m, _, _)| *m == mode) {
+            entry.1 += 1;
+            entry.2 -= receipt.amount.paise(); // receipts are stored as negative amounts
+            credit_receipts -= receipt.amount.paise();
+        }
+    }
+
+    let refunds: i64 = credit_notes
+        .iter()
+        .map(|note| (note.total - note.account_credit).paise())
+        .sum();
+
+    let collected: i64 = totals.iter().map(|(_, _, amount)| amount).sum::<i64>() - credit_sales - refunds;
+    PaymentTotals {
+        invoice_count,
+        by_mode: totals
+            .into_iter()
+            .map(|(mode, count, amount)| PaymentModeTotal { mode, count, amount: Money::from_paise(amount) })
+            .collect(),
+        change_given: Money::from_paise(change),
+        credit_sales: Money::from_paise(credit_sales),
+        credit_receipts: Money::from_paise(credit_receipts),
+        refunds: Money::from_paise(refunds),
+        total_collected: Money::from_paise(collected),
+    }
+}
+
+/// Collections by payment mode between two dates (inclusive, `YYYY-MM-DD`),
+/// for matching against the bank and UPI settlement statements.
+#[command]
+pub async fn get_payment_summary(
+    from_date: String,
+    to_date: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<PaymentSummary, String> {
+    let (from, to) = utc_day_range(&from_date, &to_date)?;
+
+    if let DbState::Sqlite(store) = db_state.inner() {
+        // Receipts and credit notes come from credit accounts and returns,
+        // which SQLite does not keep yet
+        let invoices = store.list_invoices(&hospital_id, from, to).await?;
+        let totals = PaymentTotalsResponse::from(&summarize_payments(&invoices, &[], &[]));
+        return Ok(PaymentSummary { from_date, to_date, totals });
+    }
+    let db = db_state.mongo("the payment summary")?.db.clone();
+    let range = doc! { "$gte": from, "$lt": to };
+    let invoices: Vec<Invoice> = invoices_collection(&db)
+        .find(doc! { "hospital_id": &hospital_id, "date_created": range.clone() }, None)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing invoices: {}", e))?;
+    let receipts: Vec<LedgerEntry> = ledger_collection(&db)
+        .find(doc! { "hospital_id": &hospital_id, "kind": "receipt", "date_created": range.clone() }, None)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing receipts: {}", e))?;
+    let credit_notes: Vec<CreditNote> = credit_notes_collection(&db)
+        .find(doc! { "hospital_id": &hospital_id, "date_created": range }, None)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing credit notes: {}", e))?;
+
+    Ok(PaymentSummary {
+        from_date,
+        to_date,
+        totals: PaymentTotalsResponse::from(&summarize_payments(&invoices, &receipts, &credit_notes)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+
+    fn rupees(amount: f64) -> Money {
+        Money::from_rupees(amount)
+    }
+
+    fn tender(mode: PaymentMode, amount: f64, reference: Option<&str>) -> Tender {
+        Tender { mode, amount, reference: reference.map(str::to_string) }
+    }
+
+    fn invoice(number: &str, grand_total: f64, payments: &[Payment]) -> Invoice {
+        TestInvoice::new(number).line("m1", "Paracetamol", "3004", 12.0, 1, grand_total).payments(payments).build()
+    }
+
+    #[test]
+    fn short_tenders_are_refused() {
+        let error = settle_payments(vec![tender(PaymentMode::Cash, 200.0, None)], rupees(249.5)).unwrap_err();
+        assert_eq!(error, "Payments are short by 49.50.");
+        let split = vec![tender(PaymentMode::Upi, 100.0, Some("4122")), tender(PaymentMode::Cash, 149.49, None)];
+        assert_eq!(settle_payments(split, rupees(249.5)).unwrap_err(), "Payments are short by 0.01.");
+
+        assert_eq!(settle_payments(Vec::new(), rupees(10.0)).unwrap_err(), "Add at least one payment.");
+        let error = settle_payments(vec![tender(PaymentMode::Cash, 0.0, None)], rupees(10.0)).unwrap_err();
+        assert_eq!(error, "Payment amounts must be more than zero.");
+    }
+
+    #[test]
+    fn overpaid_cash_gives_change_but_other_modes_cannot_overpay() {
+        let (payments, change) = settle_payments(vec![tender(PaymentMode::Cash, 500.0, None)], rupees(480.25)).unwrap();
+        assert_eq!(change, rupees(19.75));
+        assert_eq!(payments[0].amount, rupees(500.0));
+
+        let card_over = vec![tender(PaymentMode::Card, 300.0, Some("A1")), tender(PaymentMode::Cash, 20.0, None)];
+        let error = settle_payments(card_over, rupees(250.0)).unwrap_err();
+        assert_eq!(error, "UPI, card and credit payments (300.00) are more than the bill total (250.00).");
+
+        let invoices = [invoice("A/1", 480.25, &payments)];
+        assert_eq!(invoices[0].change_due, change);
+        let totals = summarize_payments(&invoices, &[], &[]);
+        assert_eq!(totals.mode_total(PaymentMode::Cash), rupees(480.25));
+        assert_eq!((totals.change_given, totals.total_collected), (rupees(19.75), rupees(480.25)));
+    }
+
+    #[test]
+    fn upi_and_card_split_is_counted_under_each_mode() {
+        let split = vec![
+            tender(PaymentMode::Upi, 600.0, Some(" 4122 ")),
+            tender(PaymentMode::Card, 399.0, Some("")),
+        ];
+        let (payments, change) = settle_payments(split, rupees(999.0)).unwrap();
+        assert_eq!(change, Money::default());
+        let references: Vec<Option<&str>> = payments.iter().map(|p| p.reference.as_deref()).collect();
+        assert_eq!(references, [Some("4122"), None]);
+
+        let cash = [Payment { mode: PaymentMode::Cash, amount: rupees(100.0), reference: None }];
+        let invoices = [invoice("A/1", 999.0, &payments), invoice("A/2", 100.0, &cash)];
+        let totals = summarize_payments(&invoices, &[], &[]);
+        let by_mode: Vec<(PaymentMode, u32, Money)> =
+            totals.by_mode.iter().map(|m| (m.mode, m.count, m.amount)).collect();
+        assert_eq!(
+            by_mode,
+            [
+                (PaymentMode::Cash, 1, rupees(100.0)),
+                (PaymentMode::Upi, 1, rupees(600.0)),
+                (PaymentMode::Card, 1, rupees(399.0)),
+                (PaymentMode::Credit, 0, Money::default()),
+            ]
+        );
+        assert_eq!((totals.invoice_count, totals.total_collected), (2, rupees(1099.0)));
+        assert_eq!(PaymentTotalsResponse::from(&totals).total_collected, 1099.0);
+    }
+}
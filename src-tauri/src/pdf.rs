@@ -0,0 +1,314 @@
+// src-tauri/src/pdf.rs
+use printpdf::{
+    BuiltinFont, ColorBits, ColorSpace, Image, ImageFilter, ImageTransform, ImageXObject, IndirectFontRef, Line, Mm,
+    PdfDocument, PdfDocumentReference, PdfLayerReference, Point, Px,
+};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const PT_TO_MM: f32 = 0.3528;
+
+#[derive(Debug, Clone, Copy)]
+pub enum PageSize {
+    A4,
+    A5,
+}
+
+impl PageSize {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "A4" => Ok(PageSize::A4),
+            "A5" => Ok(PageSize::A5),
+            _ => Err(format!("Unsupported page size: {}. Use A4 or A5.", value)),
+        }
+    }
+
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::A5 => (148.0, 210.0),
+        }
+    }
+}
+
+/// A baseline JPEG, embedded as-is with the DCT filter so no image decoder
+/// has to be bundled.
+#[derive(Debug, Clone)]
+pub struct JpegImage {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+    color_space: ColorSpace,
+}
+
+impl JpegImage {
+    /// Reads the frame header for the size and colour components.
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+            return Err("Logo must be a JPEG image.".to_string());
+        }
+        let mut at = 2;
+        while at + 4 <= data.len() {
+            if data[at] != 0xFF {
+                return Err("Logo is not a valid JPEG image.".to_string());
+            }
+            let marker = data[at + 1];
+            if marker == 0xFF {
+                at += 1;
+                continue;
+            }
+            let length = usize::from(data[at + 2]) << 8 | usize::from(data[at + 3]);
+            // SOF0-SOF15 carry the frame size; C4, C8 and CC share the range but are not frames
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                let frame = data.get(at + 4..at + 10).ok_or("Logo is not a valid JPEG image.")?;
+                let height = usize::from(frame[1]) << 8 | usize::from(frame[2]);
+                let width = usize::from(frame[3]) << 8 | usize::from(frame[4]);
+                let color_space = match frame[5] {
+                    1 => ColorSpace::Greyscale,
+                    3 => ColorSpace::Rgb,
+                    _ => return Err("Logo must be a greyscale or RGB JPEG.".to_string()),
+                };
+                if width == 0 || height == 0 {
+                    return Err("Logo is not a valid JPEG image.".to_string());
+                }
+                return Ok(JpegImage { data, width, height, color_space });
+            }
+            at += 2 + length;
+        }
+        Err("Logo is not a valid JPEG image.".to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(data)
+    }
+}
+
+/// Top-to-bottom text layout over printpdf's absolute coordinates. Keeps a
+/// cursor, starts a new page when the current one fills up, and only uses
+/// the built-in Helvetica fonts so nothing has to be bundled.
+pub struct PdfCanvas {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    width: f32,
+    height: f32,
+    margin: f32,
+    y: f32,
+}
+
+impl PdfCanvas {
+    pub fn new(title: &str, size: PageSize) -> Result<Self, String> {
+        let (width, height) = size.dimensions();
+        let (doc, page, layer) = PdfDocument::new(title, Mm(width), Mm(height), "Layer 1");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+        let layer = doc.get_page(page).get_layer(layer);
+        let margin = if width < 200.0 { 12.0 } else { 18.0 };
+
+        Ok(PdfCanvas {
+            doc,
+            layer,
+            font,
+            bold,
+            width,
+            height,
+            margin,
+            y: height - margin,
+        })
+    }
+
+    pub fn left(&self) -> f32 {
+        self.margin
+    }
+
+    pub fn right(&self) -> f32 {
+        self.width - self.margin
+    }
+
+    fn line_height(size: f32) -> f32 {
+        size * PT_TO_MM * 1.4
+    }
+
+    /// Rough Helvetica width; good enough for right-aligning short values.
+    pub fn text_width(text: &str, size: f32) -> f32 {
+        text.chars().count() as f32 * size * PT_TO_MM * 0.5
+    }
+
+    /// Height of the cursor from the bottom of the page, in mm.
+    pub fn y(&self) -> f32 {
+        self.y
+    }
+
+    /// Starts a new page if fewer than `needed` millimetres are left, and
+    /// says whether it did.
+    pub fn ensure_space(&mut self, needed: f32) -> bool {
+        if self.y - needed < self.margin {
+            let (page, layer) = self.doc.add_page(Mm(self.width), Mm(self.height), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = self.height - self.margin;
+            return true;
+        }
+        false
+    }
+
+    /// Writes at `x` on the current line without moving the cursor.
+    pub fn text_at(&self, text: &str, size: f32, x: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer.use_text(text, size, Mm(x), Mm(self.y - size * PT_TO_MM), font);
+    }
+
+    /// Writes so the text ends at `x` on the current line.
+    pub fn text_right(&self, text: &str, size: f32, x: f32, bold: bool) {
+        self.text_at(text, size, x - Self::text_width(text, size), bold);
+    }
+
+    pub fn text_centered(&mut self, text: &str, size: f32, bold: bool) {
+        self.ensure_space(Self::line_height(size));
+        let x = (self.width - Self::text_width(text, size)) / 2.0;
+        self.text_at(text, size, x.max(self.margin), bold);
+        self.newline(size);
+    }
+
+    pub fn text(&mut self, text: &str, size: f32, bold: bool) {
+        self.ensure_space(Self::line_height(size));
+        self.text_at(text, size, self.margin, bold);
+        self.newline(size);
+    }
+
+    /// Word-wraps `text` to the printable width, indented by `indent` mm.
+    pub fn paragraph(&mut self, text: &str, size: f32, indent: f32) {
+        let max_chars = ((self.right() - self.margin - indent) / (size * PT_TO_MM * 0.5)) as usize;
+        for source_line in text.lines() {
+            let mut line = String::new();
+            for word in source_line.split_whitespace() {
+                if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                    self.ensure_space(Self::line_height(size));
+                    self.text_at(&line, size, self.margin + indent, false);
+                    self.newline(size);
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+            }
+            self.ensure_space(Self::line_height(size));
+            self.text_at(&line, size, self.margin + indent, false);
+            self.newline(size);
+        }
+    }
+
+    pub fn newline(&mut self, size: f32) {
+        self.y -= Self::line_height(size);
+    }
+
+    pub fn gap(&mut self, mm: f32) {
+        self.y -= mm;
+    }
+
+    /// Horizontal rule between `from` and `to`.
+    pub fn rule_between(&mut self, from: f32, to: f32) {
+        self.ensure_space(3.0);
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(from), Mm(self.y)), false),
+                (Point::new(Mm(to), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        });
+        self.y -= 3.0;
+    }
+
+    /// Draws `image` with its top-left corner at `x` on the current line,
+    /// scaled to fit `max_width` by `max_height` mm. Returns the drawn size
+    /// and leaves the cursor where it was.
+    pub fn image_at(&self, image: &JpegImage, x: f32, max_width: f32, max_height: f32) -> (f32, f32) {
+        let aspect = image.width as f32 / image.height as f32;
+        let (width, height) = if max_width / max_height < aspect {
+            (max_width, max_width / aspect)
+        } else {
+            (max_height * aspect, max_height)
+        };
+
+        // printpdf places one pixel per dot at the given dpi; pick the dpi that gives `width`
+        let dpi = image.width as f32 * 25.4 / width;
+        Image::from(ImageXObject {
+            width: Px(image.width),
+            height: Px(image.height),
+            color_space: image.color_space,
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data: image.data.clone(),
+            image_filter: Some(ImageFilter::DCT),
+            smask: None,
+            clipping_bbox: None,
+        })
+        .add_to_layer(
+            self.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(x)),
+                translate_y: Some(Mm(self.y - height)),
+                dpi: Some(dpi),
+                ..Default::default()
+            },
+        );
+        (width, height)
+    }
+
+    pub fn rule(&mut self) {
+        self.rule_between(self.margin, self.right());
+    }
+
+    pub fn save(self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        self.doc
+            .save(&mut BufWriter::new(file))
+            .map_err(|e| format!("Failed to write PDF: {}", e))
+    }
+}
+
+/// Keeps file names portable across Windows and Linux.
+pub fn safe_file_name(value: &str) -> String {
+    let name: String = value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    name.trim_matches('_').to_string()
+}
+
+/// Where generated documents of one kind go: the `[output]` directory if
+/// set, otherwise `folder` inside the app data directory.
+pub fn output_dir(app: &AppHandle, configured: &Option<String>, folder: &str) -> Result<PathBuf, String> {
+    match configured {
+        Some(dir) => Ok(PathBuf::from(dir.trim())),
+        None => app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join(folder))
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e)),
+    }
+}
+
+/// Every piece of text drawn on the pages of a saved PDF, for tests.
+#[cfg(test)]
+pub(crate) fn shown_text(path: &Path) -> Vec<String> {
+    let pdf = std::fs::read(path).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    // Page streams are left uncompressed; builtin fonts write WinAnsi hex strings
+    let shown = regex::Regex::new(r"<([0-9A-Fa-f]*)>\s*Tj").unwrap();
+    shown
+        .captures_iter(&String::from_utf8_lossy(&pdf))
+        .map(|c| {
+            let hex = &c[1];
+            (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as char).collect()
+        })
+        .collect()
+}
@@ -0,0 +1,295 @@
+// src-tauri/src/prescription.rs
+use crate::billing::SellerDetails;
+use crate::commands::{Appointment, Medicine};
+use crate::config;
+use crate::dates;
+use crate::db::DbState;
+use crate::invoice_pdf::draw_letterhead;
+use crate::model::User;
+use crate::pdf::{output_dir, safe_file_name, PageSize, PdfCanvas};
+use crate::user::{find_hospital, users_collection};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use tauri::{command, AppHandle, State};
+
+fn vitals_summary(appointment: &Appointment) -> Option<String> {
+    let vitals = appointment.vitals.as_ref()?;
+    let mut parts = Vec::new();
+    if let (Some(systolic), Some(diastolic)) = (vitals.bp_systolic, vitals.bp_diastolic) {
+        parts.push(format!("BP {}/{} mmHg", systolic, diastolic));
+    }
+    if let Some(pulse) = vitals.pulse {
+        parts.push(format!("Pulse {}/min", pulse));
+    }
+    if let Some(temperature) = vitals.temperature_c {
+        parts.push(format!("Temp {:.1} C", temperature));
+    }
+    if let Some(spo2) = vitals.spo2 {
+        parts.push(format!("SpO2 {}%", spo2));
+    }
+    if let Some(weight) = vitals.weight_kg {
+        parts.push(format!("Wt {:.1} kg", weight));
+    }
+    if let Some(bmi) = vitals.bmi {
+        parts.push(format!("BMI {:.1}", bmi));
+    }
+    if let Some(sugar) = vitals.blood_sugar_mg_dl {
+        parts.push(format!("Sugar {:.0} mg/dL", sugar));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// "Dr. " before a name that does not already carry it.
+fn doctor_title(name: &str) -> String {
+    let lower = name.to_lowercase();
+    if lower.starts_with("dr.") || lower.starts_with("dr ") {
+        name.to_string()
+    } else {
+        format!("Dr. {}", name)
+    }
+}
+
+/// Lays out a single prescription on the hospital's letterhead, signed by
+/// the doctor recorded on the visit.
+fn render_prescription(
+    appointment: &Appointment,
+    hospital: &User,
+    medicines: &[Medicine],
+) -> Result<PdfCanvas, String> {
+    let mut pdf = PdfCanvas::new("Prescription", PageSize::A4)?;
+
+    draw_letterhead(&mut pdf, &SellerDetails::from(hospital), false);
+    pdf.gap(1.0);
+
+    // Doctor and patient. The account's registration number is its
+    // holder's, so it is only shown when they saw the patient.
+    let doctor = appointment.doctor_name.as_deref();
+    if let Some(doctor) = doctor {
+        pdf.text(&doctor_title(doctor), 13.0, true);
+        if doctor.eq_ignore_ascii_case(hospital.name.trim()) {
+            if let Some(registration) = &hospital.registration_number {
+                pdf.text(&format!("Reg. No: {}", registration), 10.0, false);
+            }
+        }
+        pdf.gap(3.0);
+    }
+
+    let visit_date = dates::to_utc(appointment.date_created).format("%d %b %Y").to_string();
+    pdf.text_at(&format!("Patient: {}", appointment.patient_name), 11.0, pdf.left(), true);
+    pdf.text_right(&format!("Date: {}", visit_date), 11.0, pdf.right(), false);
+    pdf.newline(11.0);
+    pdf.text(&format!("Mobile: {}", appointment.mobile), 10.0, false);
+    if let Some(vitals) = vitals_summary(appointment) {
+        pdf.text(&format!("Vitals: {}", vitals), 10.0, false);
+    }
+    if !appointment.disease.trim().is_empty() {
+        pdf.text(&format!("Diagnosis: {}", appointment.disease), 10.0, false);
+    }
+    pdf.gap(2.0);
+    pdf.rule();
+
+    // Rx lines
+    pdf.text("Rx", 16.0, true);
+    for (index, detail) in appointment.medicines.iter().enumerate() {
+        let name = medicines
+            .iter()
+            .find(|m| m.id.map(|id| id.to_hex()).as_deref() == Some(detail.id.as_str()))
+            .map(|m| m.name.as_str())
+            .unwrap_or("Unknown medicine");
+        pdf.text_at(&format!("{}. {}", index + 1, name), 11.0, pdf.left() + 4.0, true);
+        pdf.text_right(&format!("Qty: {}", detail.quantity), 11.0, pdf.right(), false);
+        pdf.newline(11.0);
+        if let Some(dosage) = detail.dosage.as_deref().filter(|d| !d.trim().is_empty()) {
+            pdf.paragraph(dosage, 10.0, 10.0);
+        }
+        pdf.gap(1.5);
+    }
+    pdf.gap(2.0);
+
+    // Advice
+    let plan = appointment.notes.as_ref().and_then(|n| n.plan.clone());
+    let advice: Vec<&str> = [Some(appointment.precautions.as_str()), plan.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.trim().is_empty())
+        .collect();
+    if !advice.is_empty() {
+        pdf.text("Advice", 12.0, true);
+        for line in advice {
+            pdf.paragraph(line, 10.0, 4.0);
+        }
+    }
+    if let Some(follow_up) = appointment.follow_up_date {
+        pdf.gap(2.0);
+        pdf.text(&format!("Review on: {}", follow_up.format("%d %b %Y")), 11.0, true);
+    }
+
+    // Signature block
+    pdf.ensure_space(30.0);
+    pdf.gap(18.0);
+    let sign_from = pdf.right() - 60.0;
+    pdf.rule_between(sign_from, pdf.right());
+    if let Some(doctor) = doctor {
+        pdf.text_at(&doctor_title(doctor), 10.0, sign_from, true);
+        pdf.newline(10.0);
+    }
+    pdf.text_at("Signature", 9.0, sign_from, false);
+    pdf.newline(9.0);
+
+    Ok(pdf)
+}
+
+/// Renders the prescription for a saved appointment and returns the PDF path.
+#[command]
+pub async fn generate_prescription(
+    appointment_id: String,
+    hospital_id: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let db = db_state.mongo("prescriptions")?.db.clone();
+    let object_id = ObjectId::parse_str(&appointment_id).map_err(|_| "Invalid appointment ID".to_string())?;
+
+    let appointment = db
+        .collection::<Appointment>("appointments")
+        .find_one(doc! { "_id": object_id, "hospital_id": &hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Appointment not found".to_string())?;
+    let hospital = find_hospital(&users_collection(&db), &hospital_id).await?;
+
+    let medicine_ids: Vec<ObjectId> = appointment
+        .medicines
+        .iter()
+        .filter_map(|m| ObjectId::parse_str(&m.id).ok())
+        .collect();
+    let medicines: Vec<Medicine> = db
+        .collection::<Medicine>("medicines")
+        .find(doc! { "_id": { "$in": medicine_ids } }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let pdf = render_prescription(&appointment, &hospital, &medicines)?;
+    let path = output_dir(&app, &config::get().output.prescriptions, "prescriptions")?.join(format!(
+        "rx_{}_{}.pdf",
+        safe_file_name(&appointment.patient_name),
+        appointment.id.to_hex()
+    ));
+    pdf.save(&path)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clinical::ClinicalNotes;
+    use crate::commands::MedicineDetail;
+    use crate::money::Money;
+    use crate::pdf::shown_text;
+    use chrono::NaiveDate;
+    use std::path::Path;
+
+    fn hospital() -> User {
+        let mut hospital: User = serde_json::from_value(serde_json::json!({
+            "username": "city", "name": "Asha Rao", "mobile": "9800000000", "hospital": "City Clinic",
+            "address": "MG Road, Bengaluru", "password_hash_doc": "", "password_hash_pharma": "",
+            "email": "desk@cityclinic.in", "otp": null,
+        }))
+        .unwrap();
+        hospital.registration_number = Some("KMC 12345".to_string());
+        hospital.letterhead = Some("Mon-Sat 9am-1pm".to_string());
+        hospital
+    }
+
+    fn medicine(name: &str) -> Medicine {
+        Medicine {
+            id: Some(ObjectId::new()),
+            user_id: "h1".to_string(),
+            name: name.to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity: 50,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        }
+    }
+
+    fn visit(doctor_name: Option<&str>, medicines: &[Medicine]) -> Appointment {
+        let detail = |medicine: &Medicine, dosage: &str| MedicineDetail {
+            id: medicine.id.unwrap().to_hex(),
+            quantity: 10,
+            dosage: Some(dosage.to_string()),
+            discount: None,
+        };
+        Appointment {
+            id: ObjectId::new(),
+            patient_name: "Meera Iyer".to_string(),
+            mobile: "9123456780".to_string(),
+            disease: "Viral fever".to_string(),
+            precautions: "Plenty of fluids".to_string(),
+            medicines: vec![
+                detail(&medicines[0], "1-0-1 after food x 5 days"),
+                detail(&medicines[1], "0-0-1 x 3 days"),
+            ],
+            hospital_id: "h1".to_string(),
+            date_created: dates::midnight(NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()),
+            email: None,
+            follow_up_date: NaiveDate::from_ymd_opt(2026, 10, 26),
+            vitals: None,
+            notes: Some(ClinicalNotes {
+                plan: Some("Paracetamol only if above 100 F".to_string()),
+                ..Default::default()
+            }),
+            doctor_name: doctor_name.map(str::to_string),
+        }
+    }
+
+    fn render(appointment: &Appointment, medicines: &[Medicine], dir: &Path) -> Vec<String> {
+        let path = dir.join(format!("rx_{}.pdf", appointment.id.to_hex()));
+        render_prescription(appointment, &hospital(), medicines).unwrap().save(&path).unwrap();
+        shown_text(&path)
+    }
+
+    #[test]
+    fn prescription_lists_the_medicines_and_follow_up_under_the_visit_doctor() {
+        let dir = tempfile::tempdir().unwrap();
+        let medicines = [medicine("Dolo 650"), medicine("Cetirizine 10mg")];
+        let text = render(&visit(Some("Dr Kiran Shetty"), &medicines), &medicines, dir.path());
+
+        for shown in ["City Clinic", "MG Road, Bengaluru", "Mon-Sat 9am-1pm", "Patient: Meera Iyer"] {
+            assert!(text.iter().any(|t| t == shown), "{} missing", shown);
+        }
+        assert_eq!(text.iter().filter(|t| *t == "Dr Kiran Shetty").count(), 2); // heading and signature
+        assert!(!text.iter().any(|t| t.contains("Asha Rao") || t.contains("KMC 12345")));
+        assert!(text.iter().any(|t| t == "1. Dolo 650") && text.iter().any(|t| t == "2. Cetirizine 10mg"));
+        assert!(text.iter().any(|t| t.contains("1-0-1 after food x 5 days")));
+        assert!(text.iter().any(|t| t.contains("Paracetamol only if above 100 F")));
+        assert!(text.iter().any(|t| t == "Review on: 26 Oct 2026"));
+    }
+
+    #[test]
+    fn account_holder_is_shown_with_their_registration_and_unknown_doctors_are_left_blank() {
+        let dir = tempfile::tempdir().unwrap();
+        let medicines = [medicine("Dolo 650"), medicine("Cetirizine 10mg")];
+
+        let text = render(&visit(Some("asha rao"), &medicines), &medicines, dir.path());
+        assert!(text.iter().any(|t| t == "Dr. asha rao") && text.iter().any(|t| t == "Reg. No: KMC 12345"));
+
+        let text = render(&visit(None, &medicines), &medicines, dir.path());
+        assert!(!text.iter().any(|t| t.starts_with("Dr") || t.contains("KMC 12345")));
+        assert!(text.iter().any(|t| t == "Signature"));
+    }
+}
@@ -0,0 +1,552 @@
+// src-tauri/src/queue.rs
+use crate::billing::{run_transaction, TxnError};
+use crate::dates;
+use crate::db::{DbState, MongoDb};
+use crate::repository::memory::MemoryStore;
+use crate::schedule::find_doctor;
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter, State};
+
+/// Event every window listens on; the waiting-room display only needs this.
+pub const QUEUE_UPDATED_EVENT: &str = "queue-updated";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStatus {
+    Waiting,
+    Called,
+    Completed,
+    Skipped,
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub doctor_id: String,
+    #[serde(with = "crate::dates::calendar")]
+    pub queue_date: NaiveDate,
+    pub token_number: u32,
+    pub patient_name: String,
+    pub mobile: String,
+    pub status: TokenStatus,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub issued_at: bson::DateTime,
+    #[serde(default, deserialize_with = "crate::dates::optional_timestamp")]
+    pub called_at: Option<bson::DateTime>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct QueueTokenResponse {
+    pub id: String,
+    pub token_number: u32,
+    pub patient_name: String,
+    pub status: TokenStatus,
+}
+
+impl From<QueueToken> for QueueTokenResponse {
+    fn from(token: QueueToken) -> Self {
+        QueueTokenResponse {
+            id: token.id.map(|id| id.to_hex()).unwrap_or_default(),
+            token_number: token.token_number,
+            patient_name: token.patient_name,
+            status: token.status,
+        }
+    }
+}
+
+/// Snapshot of one doctor's queue, sent with every `queue-updated` event.
+#[derive(Serialize, Debug, Clone)]
+pub struct QueueState {
+    pub hospital_id: String,
+    pub doctor_id: String,
+    pub doctor_name: String,
+    pub queue_date: NaiveDate,
+    pub current: Option<QueueTokenResponse>,
+    pub waiting: Vec<QueueTokenResponse>,
+    pub last_issued: u32,
+}
+
+fn tokens_collection(db: &Database) -> Collection<QueueToken> {
+    db.collection("queue_tokens")
+}
+
+/// Queues run on the clinic's local calendar day.
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn date_key(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Where the patient queue is kept. Each call is a single step, so screens
+/// pressing buttons at the same moment never hand out one number twice or
+/// call two patients at once.
+#[async_trait]
+pub trait QueueRepository: Send + Sync {
+    /// Stores `token` under the next number of its doctor's queue for its
+    /// `queue_date`, starting again from 1 every day, and returns the number.
+    async fn issue_token(&self, token: QueueToken) -> Result<u32, String>;
+
+    /// One doctor's tokens for a day, by number.
+    async fn day_tokens(&self, hospital_id: &str, doctor_id: &str, date: NaiveDate) -> Result<Vec<QueueToken>, String>;
+
+    /// Closes out the token with the doctor as `finished` and, with
+    /// `call_next`, calls the lowest waiting token. `current` is the token
+    /// the screen shows with the doctor; if another screen has moved the
+    /// queue on since, nothing changes and `false` comes back.
+    async fn advance(
+        &self,
+        hospital_id: &str,
+        doctor_id: &str,
+        date: NaiveDate,
+        current: Option<ObjectId>,
+        finished: TokenStatus,
+        call_next: bool,
+    ) -> Result<bool, String>;
+
+    /// Marks tokens still waiting or called before `date` as expired.
+    async fn expire_before(&self, date: NaiveDate) -> Result<u64, String>;
+}
+
+fn day_filter(hospital_id: &str, doctor_id: &str, date: NaiveDate) -> mongodb::bson::Document {
+    doc! { "hospital_id": hospital_id, "doctor_id": doctor_id, "queue_date": dates::midnight(date) }
+}
+
+/// Hands out the next token number for a doctor's queue. The counter `_id`
+/// is derived from the queue itself, so concurrent upserts can never create
+/// two counters and each call gets a distinct number.
+async fn next_token_number(
+    db: &Database,
+    hospital_id: &str,
+    doctor_id: &str,
+    date: NaiveDate,
+) -> Result<u32, String> {
+    let counter_id = format!("{}:{}:{}", hospital_id, doctor_id, date_key(date));
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+
+    let counter = db
+        .collection::<mongodb::bson::Document>("queue_counters")
+        .find_one_and_update(doc! { "_id": counter_id }, doc! { "$inc": { "last_token": 1 } }, options)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Failed to issue token".to_string())?;
+
+    counter
+        .get_i32("last_token")
+        .map(|n| n as u32)
+        .map_err(|e| e.to_string())
+}
+
+#[async_trait]
+impl QueueRepository for MongoDb {
+    async fn issue_token(&self, mut token: QueueToken) -> Result<u32, String> {
+        token.token_number = next_token_number(&self.db, &token.hospital_id, &token.doctor_id, token.queue_date).await?;
+        let number = token.token_number;
+        tokens_collection(&self.db)
+            .insert_one(token, None)
+            .await
+            .map_err(|e| format!("Database insert error: {}", e))?;
+        Ok(number)
+    }
+
+    async fn day_tokens(&self, hospital_id: &str, doctor_id: &str, date: NaiveDate) -> Result<Vec<QueueToken>, String> {
+        let find_options = FindOptions::builder().sort(doc! { "token_number": 1 }).build();
+        tokens_collection(&self.db)
+            .find(day_filter(hospital_id, doctor_id, date), find_options)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn advance(
+        &self,
+        hospital_id: &str,
+        doctor_id: &str,
+        date: NaiveDate,
+        current: Option<ObjectId>,
+        finished: TokenStatus,
+        call_next: bool,
+    ) -> Result<bool, String> {
+        let finished = mongodb::bson::to_bson(&finished).map_err(|e| e.to_string())?;
+        // Two screens racing write the same token, so one of them retries and
+        // then finds the queue has moved on
+        run_transaction(&self.client, |session| {
+            let (tokens, day, finished) =
+                (tokens_collection(&self.db), day_filter(hospital_id, doctor_id, date), finished.clone());
+            Box::pin(async move {
+                let mut called = day.clone();
+                called.insert("status", "called");
+                let with_doctor = tokens.find_one_with_session(called, None, session).await?;
+                if with_doctor.as_ref().and_then(|token| token.id) != current {
+                    return Ok(false);
+                }
+                if let Some(id) = current {
+                    tokens
+                        .update_one_with_session(
+                            doc! { "_id": id, "status": "called" },
+                            doc! { "$set": { "status": finished } },
+                            None,
+                            session,
+                        )
+                        .await?;
+                }
+                if call_next {
+                    let mut waiting = day;
+                    waiting.insert("status", "waiting");
+                    let options = FindOneAndUpdateOptions::builder().sort(doc! { "token_number": 1 }).build();
+                    tokens
+                        .find_one_and_update_with_session(
+                            waiting,
+                            doc! { "$set": { "status": "called", "called_at": bson::DateTime::now() } },
+                            options,
+                            session,
+                        )
+                        .await?;
+                }
+                Ok::<_, TxnError>(true)
+            })
+        })
+        .await
+    }
+
+    async fn expire_before(&self, date: NaiveDate) -> Result<u64, String> {
+        let result = tokens_collection(&self.db)
+            .update_many(
+                doc! {
+                    "queue_date": { "$lt": dates::midnight(date) },
+                    "status": { "$in": ["waiting", "called"] },
+                },
+                doc! { "$set": { "status": "expired" } },
+                None,
+            )
+            .await
+            .map_err(|e| format!("Error expiring old tokens: {}", e))?;
+        Ok(result.modified_count)
+    }
+}
+
+#[async_trait]
+impl QueueRepository for MemoryStore {
+    async fn issue_token(&self, mut token: QueueToken) -> Result<u32, String> {
+        let mut tokens = self.queue_tokens.lock().unwrap();
+        let counter_id = format!("{}:{}:{}", token.hospital_id, token.doctor_id, date_key(token.queue_date));
+        let mut counters = self.queue_counters.lock().unwrap();
+        let last_token = counters.entry(counter_id).or_default();
+        *last_token += 1;
+        token.id = Some(ObjectId::new());
+        token.token_number = *last_token;
+        tokens.push(token);
+        Ok(*last_token)
+    }
+
+    async fn day_tokens(&self, hospital_id: &str, doctor_id: &str, date: NaiveDate) -> Result<Vec<QueueToken>, String> {
+        let mut found: Vec<QueueToken> = self
+            .queue_tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.hospital_id == hospital_id && t.doctor_id == doctor_id && t.queue_date == date)
+            .cloned()
+            .collect();
+        found.sort_by_key(|t| t.token_number);
+        Ok(found)
+    }
+
+    async fn advance(
+        &self,
+        hospital_id: &str,
+        doctor_id: &str,
+        date: NaiveDate,
+        current: Option<ObjectId>,
+        finished: TokenStatus,
+        call_next: bool,
+    ) -> Result<bool, String> {
+        let mut tokens = self.queue_tokens.lock().unwrap();
+        let mut day: Vec<&mut QueueToken> = tokens
+            .iter_mut()
+            .filter(|t| t.hospital_id == hospital_id && t.doctor_id == doctor_id && t.queue_date == date)
+            .collect();
+        let with_doctor = day.iter_mut().find(|t| t.status == TokenStatus::Called);
+        if with_doctor.as_ref().and_then(|token| token.id) != current {
+            return Ok(false);
+        }
+        if let Some(token) = with_doctor {
+            token.status = finished;
+        }
+        if call_next {
+            let next = day.into_iter().filter(|t| t.status == TokenStatus::Waiting).min_by_key(|t| t.token_number);
+            if let Some(token) = next {
+                token.status = TokenStatus::Called;
+                token.called_at = Some(bson::DateTime::now());
+            }
+        }
+        Ok(true)
+    }
+
+    async fn expire_before(&self, date: NaiveDate) -> Result<u64, String> {
+        let mut expired = 0;
+        for token in self.queue_tokens.lock().unwrap().iter_mut() {
+            if token.queue_date < date && matches!(token.status, TokenStatus::Waiting | TokenStatus::Called) {
+                token.status = TokenStatus::Expired;
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+}
+
+async fn load_queue_state(
+    queue: &dyn QueueRepository,
+    hospital_id: &str,
+    doctor_id: &str,
+    doctor_name: String,
+    date: NaiveDate,
+) -> Result<QueueState, String> {
+    let tokens = queue.day_tokens(hospital_id, doctor_id, date).await?;
+
+    let last_issued = tokens.last().map(|t| t.token_number).unwrap_or(0);
+    let mut current = None;
+    let mut waiting = Vec::new();
+    for token in tokens {
+        match token.status {
+            TokenStatus::Called => current = Some(QueueTokenResponse::from(token)),
+            TokenStatus::Waiting => waiting.push(QueueTokenResponse::from(token)),
+            _ => {}
+        }
+    }
+
+    Ok(QueueState {
+        hospital_id: hospital_id.to_string(),
+        doctor_id: doctor_id.to_string(),
+        doctor_name,
+        queue_date: date,
+        current,
+        waiting,
+        last_issued,
+    })
+}
+
+/// Reloads the queue and pushes it to every open window.
+async fn broadcast_queue(
+    app: &AppHandle,
+    queue: &dyn QueueRepository,
+    hospital_id: &str,
+    doctor_id: &str,
+    doctor_name: String,
+) -> Result<QueueState, String> {
+    let state = load_queue_state(queue, hospital_id, doctor_id, doctor_name, today()).await?;
+    app.emit(QUEUE_UPDATED_EVENT, state.clone())
+        .map_err(|e| format!("Failed to notify windows: {}", e))?;
+    Ok(state)
+}
+
+fn parse_token_id(id: Option<String>) -> Result<Option<ObjectId>, String> {
+    id.map(|id| ObjectId::parse_str(&id).map_err(|_| "Invalid token ID".to_string())).transpose()
+}
+
+#[command]
+pub async fn issue_token(
+    doctor_id: String,
+    patient_name: String,
+    mobile: String,
+    hospital_id: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<QueueState, String> {
+    if patient_name.trim().is_empty() {
+        return Err("Patient name is required.".to_string());
+    }
+
+    let mongo = db_state.mongo("the patient queue")?;
+    let doctor = find_doctor(&mongo.db.collection("doctor_schedules"), &doctor_id, &hospital_id).await?;
+
+    let token = QueueToken {
+        id: None,
+        hospital_id: hospital_id.clone(),
+        doctor_id: doctor_id.clone(),
+        queue_date: today(),
+        token_number: 0,
+        patient_name: patient_name.trim().to_string(),
+        mobile: mobile.trim().to_string(),
+        status: TokenStatus::Waiting,
+        issued_at: bson::DateTime::now(),
+        called_at: None,
+    };
+    mongo.issue_token(token).await?;
+
+    broadcast_queue(&app, mongo, &hospital_id, &doctor_id, doctor.doctor_name).await
+}
+
+/// Completes the token with the doctor and calls the next one.
+/// `current_token_id` is the token this screen shows with the doctor; if
+/// another screen got there first, the queue is left as it is and the
+/// fresh state comes back.
+#[command]
+pub async fn call_next_token(
+    doctor_id: String,
+    hospital_id: String,
+    current_token_id: Option<String>,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<QueueState, String> {
+    let current = parse_token_id(current_token_id)?;
+    let mongo = db_state.mongo("the patient queue")?;
+    let doctor = find_doctor(&mongo.db.collection("doctor_schedules"), &doctor_id, &hospital_id).await?;
+
+    mongo.advance(&hospital_id, &doctor_id, today(), current, TokenStatus::Completed, true).await?;
+
+    broadcast_queue(&app, mongo, &hospital_id, &doctor_id, doctor.doctor_name).await
+}
+
+#[command]
+pub async fn skip_current_token(
+    doctor_id: String,
+    hospital_id: String,
+    current_token_id: Option<String>,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<QueueState, String> {
+    let current = parse_token_id(current_token_id)?;
+    let mongo = db_state.mongo("the patient queue")?;
+    let doctor = find_doctor(&mongo.db.collection("doctor_schedules"), &doctor_id, &hospital_id).await?;
+
+    mongo.advance(&hospital_id, &doctor_id, today(), current, TokenStatus::Skipped, false).await?;
+
+    broadcast_queue(&app, mongo, &hospital_id, &doctor_id, doctor.doctor_name).await
+}
+
+#[command]
+pub async fn get_queue_state(
+    doctor_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<QueueState, String> {
+    let mongo = db_state.mongo("the patient queue")?;
+    let doctor = find_doctor(&mongo.db.collection("doctor_schedules"), &doctor_id, &hospital_id).await?;
+
+    load_queue_state(mongo, &hospital_id, &doctor_id, doctor.doctor_name, today()).await
+}
+
+/// Marks tokens left over from earlier days as expired. Numbering needs no
+/// reset since counters are keyed by date; this just keeps old patients off
+/// today's screens.
+#[command]
+pub async fn expire_stale_tokens(db_state: State<'_, DbState>) -> Result<String, String> {
+    let expired = db_state.mongo("the patient queue")?.expire_before(today()).await?;
+    Ok(format!("Expired {} tokens from previous days.", expired))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    fn token(doctor_id: &str, queue_date: NaiveDate, patient_name: &str) -> QueueToken {
+        QueueToken {
+            id: None,
+            hospital_id: "h1".to_string(),
+            doctor_id: doctor_id.to_string(),
+            queue_date,
+            token_number: 0,
+            patient_name: patient_name.to_string(),
+            mobile: "9800000000".to_string(),
+            status: TokenStatus::Waiting,
+            issued_at: bson::DateTime::parse_rfc3339_str("2026-10-19T04:00:00Z").unwrap(),
+            called_at: None,
+        }
+    }
+
+    async fn state(queue: &MemoryStore, day: NaiveDate) -> QueueState {
+        load_queue_state(queue, "h1", "d1", "Dr Rao".to_string(), day).await.unwrap()
+    }
+
+    async fn statuses(queue: &MemoryStore, day: NaiveDate) -> Vec<TokenStatus> {
+        queue.day_tokens("h1", "d1", day).await.unwrap().iter().map(|t| t.status).collect()
+    }
+
+    fn current_id(state: &QueueState) -> Option<ObjectId> {
+        state.current.as_ref().map(|token| ObjectId::parse_str(&token.id).unwrap())
+    }
+
+    #[tokio::test]
+    async fn token_numbers_count_up_per_doctor_and_start_again_each_day() {
+        let queue = MemoryStore::default();
+        assert_eq!(queue.issue_token(token("d1", date(19), "Asha")).await.unwrap(), 1);
+        assert_eq!(queue.issue_token(token("d1", date(19), "Ravi")).await.unwrap(), 2);
+        assert_eq!(queue.issue_token(token("d2", date(19), "Meena")).await.unwrap(), 1);
+        assert_eq!(queue.issue_token(token("d1", date(20), "Kiran")).await.unwrap(), 1);
+        assert_eq!(queue.issue_token(token("d1", date(19), "Sunil")).await.unwrap(), 3);
+
+        let today = state(&queue, date(19)).await;
+        assert_eq!(today.last_issued, 3);
+        let names: Vec<&str> = today.waiting.iter().map(|t| t.patient_name.as_str()).collect();
+        assert_eq!(names, ["Asha", "Ravi", "Sunil"]);
+        assert_eq!(state(&queue, date(20)).await.last_issued, 1);
+    }
+
+    #[tokio::test]
+    async fn call_next_takes_the_lowest_waiting_token_once() {
+        let queue = MemoryStore::default();
+        for name in ["Asha", "Ravi", "Sunil"] {
+            queue.issue_token(token("d1", date(19), name)).await.unwrap();
+        }
+
+        assert!(queue.advance("h1", "d1", date(19), None, TokenStatus::Completed, true).await.unwrap());
+        let first = state(&queue, date(19)).await;
+        assert_eq!(first.current.as_ref().unwrap().token_number, 1);
+        assert_eq!(first.waiting.len(), 2);
+
+        // A second screen pressing "next" with the old view changes nothing
+        assert!(!queue.advance("h1", "d1", date(19), None, TokenStatus::Completed, true).await.unwrap());
+        assert_eq!(current_id(&state(&queue, date(19)).await), current_id(&first));
+
+        assert!(queue.advance("h1", "d1", date(19), current_id(&first), TokenStatus::Skipped, false).await.unwrap());
+        let skipped = state(&queue, date(19)).await;
+        assert!(skipped.current.is_none());
+        assert_eq!(skipped.waiting[0].token_number, 2);
+
+        assert!(queue.advance("h1", "d1", date(19), None, TokenStatus::Completed, true).await.unwrap());
+        let second = state(&queue, date(19)).await;
+        assert!(queue.advance("h1", "d1", date(19), current_id(&second), TokenStatus::Completed, true).await.unwrap());
+        let third = state(&queue, date(19)).await;
+        assert_eq!(third.current.as_ref().unwrap().patient_name, "Sunil");
+        assert!(third.waiting.is_empty());
+
+        assert_eq!(statuses(&queue, date(19)).await, [TokenStatus::Skipped, TokenStatus::Completed, TokenStatus::Called]);
+    }
+
+    #[tokio::test]
+    async fn tokens_left_from_earlier_days_expire() {
+        let queue = MemoryStore::default();
+        queue.issue_token(token("d1", date(18), "Asha")).await.unwrap();
+        queue.issue_token(token("d1", date(18), "Ravi")).await.unwrap();
+        queue.issue_token(token("d1", date(18), "Sunil")).await.unwrap();
+        queue.issue_token(token("d1", date(19), "Meena")).await.unwrap();
+        queue.advance("h1", "d1", date(18), None, TokenStatus::Completed, true).await.unwrap();
+        let called = current_id(&state(&queue, date(18)).await);
+        queue.advance("h1", "d1", date(18), called, TokenStatus::Completed, true).await.unwrap();
+
+        assert_eq!(queue.expire_before(date(19)).await.unwrap(), 2);
+        assert_eq!(statuses(&queue, date(18)).await, [TokenStatus::Completed, TokenStatus::Expired, TokenStatus::Expired]);
+        assert_eq!(state(&queue, date(19)).await.waiting.len(), 1);
+        assert_eq!(queue.expire_before(date(19)).await.unwrap(), 0);
+    }
+}
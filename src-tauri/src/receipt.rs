@@ -0,0 +1,608 @@
+// src-tauri/src/receipt.rs
+use crate::billing::{find_invoice, Invoice};
+use crate::dates;
+use crate::db::DbState;
+use crate::model::User;
+use crate::money::Money;
+use crate::user::find_hospital;
+use chrono::{Local, NaiveDateTime};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use tauri::{command, State};
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// Thermal roll width. Columns are for the printer's default font A.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaperWidth {
+    Mm58,
+    Mm80,
+}
+
+impl PaperWidth {
+    pub fn from_mm(mm: u32) -> Result<Self, String> {
+        match mm {
+            58 => Ok(PaperWidth::Mm58),
+            80 => Ok(PaperWidth::Mm80),
+            _ => Err(format!("Unsupported paper width {}mm; use 58 or 80.", mm)),
+        }
+    }
+
+    pub fn columns(self) -> usize {
+        match self {
+            PaperWidth::Mm58 => 32,
+            PaperWidth::Mm80 => 48,
+        }
+    }
+
+    fn qr_module_size(self) -> u8 {
+        match self {
+            PaperWidth::Mm58 => 4,
+            PaperWidth::Mm80 => 6,
+        }
+    }
+
+    /// Column widths for the HSN / rate / taxable / tax table.
+    fn tax_columns(self) -> [usize; 4] {
+        match self {
+            PaperWidth::Mm58 => [8, 6, 9, 9],
+            PaperWidth::Mm80 => [12, 8, 14, 14],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left = 0,
+    Center = 1,
+}
+
+/// ESC/POS byte stream builder covering the commands common to Epson
+/// compatible receipt printers.
+pub struct EscPos {
+    bytes: Vec<u8>,
+}
+
+impl EscPos {
+    pub fn new() -> Self {
+        EscPos { bytes: vec![ESC, b'@'] }
+    }
+
+    pub fn align(&mut self, align: Align) -> &mut Self {
+        self.bytes.extend([ESC, b'a', align as u8]);
+        self
+    }
+
+    pub fn bold(&mut self, on: bool) -> &mut Self {
+        self.bytes.extend([ESC, b'E', on as u8]);
+        self
+    }
+
+    /// Double width and height, for the shop name and the total.
+    pub fn large(&mut self, on: bool) -> &mut Self {
+        self.bytes.extend([GS, b'!', if on { 0x11 } else { 0x00 }]);
+        self
+    }
+
+    /// Printers default to code page 437, so anything outside ASCII is
+    /// replaced; the rupee sign is spelt out.
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        for c in text.chars() {
+            match c {
+                '₹' => self.bytes.extend(b"Rs."),
+                c if c.is_ascii() && !c.is_ascii_control() => self.bytes.push(c as u8),
+                _ => self.bytes.push(b'?'),
+            }
+        }
+        self
+    }
+
+    pub fn line(&mut self, text: &str) -> &mut Self {
+        self.text(text).feed(1)
+    }
+
+    pub fn feed(&mut self, lines: u8) -> &mut Self {
+        if lines == 1 {
+            self.bytes.push(b'\n');
+        } else {
+            self.bytes.extend([ESC, b'd', lines]);
+        }
+        self
+    }
+
+    /// QR code (model 2, error correction M) using the `GS ( k` functions.
+    pub fn qr(&mut self, data: &str, module_size: u8) -> &mut Self {
+        let store_len = data.len() + 3;
+        self.bytes.extend([GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+        self.bytes.extend([GS, b'(', b'k', 3, 0, 49, 67, module_size]);
+        self.bytes.extend([GS, b'(', b'k', 3, 0, 49, 69, 49]);
+        self.bytes.extend([GS, b'(', b'k', (store_len % 256) as u8, (store_len / 256) as u8, 49, 80, 48]);
+        self.bytes.extend(data.bytes());
+        self.bytes.extend([GS, b'(', b'k', 3, 0, 49, 81, 48]);
+        self
+    }
+
+    /// Feeds past the tear bar and makes a partial cut.
+    pub fn cut(&mut self) -> &mut Self {
+        self.bytes.extend([GS, b'V', 66, 3]);
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Default for EscPos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `left` and `right` on one line, padded apart to `width`.
+fn spread(left: &str, right: &str, width: usize) -> String {
+    let used = left.chars().count() + right.chars().count();
+    if used >= width {
+        let keep = width.saturating_sub(right.chars().count() + 1);
+        let left: String = left.chars().take(keep).collect();
+        return format!("{} {}", left, right);
+    }
+    format!("{}{}{}", left, " ".repeat(width - used), right)
+}
+
+/// A table row: first cell left-aligned, the rest right-aligned.
+fn row(cells: &[&str], widths: &[usize]) -> String {
+    cells
+        .iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(i, (cell, width))| {
+            let cell: String = cell.chars().take(*width).collect();
+            if i == 0 {
+                format!("{:<width$}", cell, width = width)
+            } else {
+                format!("{:>width$}", cell, width = width)
+            }
+        })
+        .collect()
+}
+
+/// Breaks `text` into lines of at most `width` characters on word
+/// boundaries, splitting words that are longer than a line.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: String = word.to_string();
+        while word.chars().count() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            let head: String = word.chars().take(width).collect();
+            word = word.chars().skip(width).collect();
+            lines.push(head);
+        }
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn money(amount: Money) -> String {
+    format!("{:.2}", amount.rupees())
+}
+
+/// `upi://pay` link for the bill amount, which any UPI app can scan.
+pub fn upi_payment_uri(vpa: &str, payee: &str, amount: f64, note: &str) -> String {
+    let encode = |value: &str| {
+        value
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'@' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect::<String>()
+    };
+    format!(
+        "upi://pay?pa={}&pn={}&am={:.2}&cu=INR&tn={}",
+        encode(vpa),
+        encode(payee),
+        amount,
+        encode(note)
+    )
+}
+
+/// Lays out an invoice as an ESC/POS receipt. `issued_at` is the invoice
+/// time in the shop's local time.
+pub fn format_receipt(invoice: &Invoice, hospital: &User, width: PaperWidth, issued_at: NaiveDateTime) -> Vec<u8> {
+    let cols = width.columns();
+    let rule = "-".repeat(cols);
+    let mut p = EscPos::new();
+
+    // Header
+    p.align(Align::Center).bold(true).large(true);
+    for line in wrap(&hospital.hospital, cols / 2) {
+        p.line(&line);
+    }
+    p.large(false).bold(false);
+    for line in wrap(&hospital.address, cols) {
+        p.line(&line);
+    }
+    if !hospital.mobile.trim().is_empty() {
+        p.line(&format!("Ph: {}", hospital.mobile));
+    }
+    if let Some(gstin) = &hospital.gstin {
+        p.line(&format!("GSTIN: {}", gstin));
+    }
+    p.bold(true).line("TAX INVOICE").bold(false);
+
+    p.align(Align::Left).line(&rule);
+    let number = format!("No: {}", invoice.invoice_number);
+    let date = issued_at.format("%d-%m-%Y %H:%M").to_string();
+    if number.len() + 1 + date.len() > cols {
+        p.line(&number).line(&format!("Date: {}", date));
+    } else {
+        p.line(&spread(&number, &date, cols));
+    }
+    p.line(&format!("Customer: {}", invoice.customer_name));
+    if let Some(mobile) = &invoice.customer_mobile {
+        p.line(&format!("Mobile: {}", mobile));
+    }
+    p.line(&rule);
+
+    // Items
+    p.bold(true).line(&spread("Item", "Amount", cols)).bold(false);
+    for line in &invoice.lines {
+        for name in wrap(&line.name, cols) {
+            p.line(&name);
+        }
+        let detail = format!("  {} x {}  B:{}", line.quantity, money(line.unit_price), line.batch_number);
+        let gross = Money::from_paise(line.unit_price.paise() * line.quantity as i64);
+        p.line(&spread(&detail, &money(gross), cols));
+        if line.discount > Money::default() {
+            p.line(&spread("  Discount", &format!("-{}", money(line.discount)), cols));
+        }
+    }
+    p.line(&rule);
+
+    // Totals
+    if invoice.discount_total > Money::default() {
+        p.line(&spread("Discount", &format!("-{}", money(invoice.discount_total)), cols));
+    }
+    p.line(&spread("Taxable value", &money(invoice.taxable_total), cols));
+    if invoice.igst_total > Money::default() {
+        p.line(&spread("IGST", &money(invoice.igst_total), cols));
+    } else {
+        p.line(&spread("CGST", &money(invoice.cgst_total), cols));
+        p.line(&spread("SGST", &money(invoice.sgst_total), cols));
+    }
+    if invoice.round_off != Money::default() {
+        p.line(&spread("Round off", &format!("{:+.2}", invoice.round_off.rupees()), cols));
+    }
+    p.bold(true)
+        .line(&spread("TOTAL", &format!("Rs.{}", money(invoice.grand_total)), cols))
+        .bold(false);
+    p.line(&rule);
+
+    // Tax summary
+    let widths = width.tax_columns();
+    p.line(&row(&["HSN", "Rate", "Taxable", "Tax"], &widths));
+    for summary in &invoice.tax_summary {
+        let tax = summary.cgst + summary.sgst + summary.igst;
+        p.line(&row(
+            &[
+                &summary.hsn_code,
+                &format!("{}%", summary.gst_rate),
+                &money(summary.taxable_value),
+                &money(tax),
+            ],
+            &widths,
+        ));
+    }
+    p.line(&rule);
+
+    // Payments
+    for payment in &invoice.payments {
+        let mode = payment.mode.label();
+        let label = match &payment.reference {
+            Some(reference) => format!("{} ({})", mode, reference),
+            None => mode.to_string(),
+        };
+        p.line(&spread(&label, &money(payment.amount), cols));
+    }
+    if invoice.change_due > Money::default() {
+        p.line(&spread("Change", &money(invoice.change_due), cols));
+    }
+
+    // Scan-to-pay with the amount filled in
+    p.align(Align::Center);
+    if let Some(vpa) = hospital.upi_id.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        let uri = upi_payment_uri(vpa, &hospital.hospital, invoice.grand_total.rupees(), &invoice.invoice_number);
+        p.feed(1).qr(&uri, width.qr_module_size()).feed(1);
+        p.line(&format!("UPI: {}", vpa));
+    }
+    p.feed(1).line("Thank you! Get well soon.");
+    p.feed(3).cut();
+
+    p.into_bytes()
+}
+
+/// Where receipt bytes go: `tcp://host:port` for a network printer,
+/// `file:<path>` to save them, anything else is a raw device path such as
+/// `/dev/usb/lp0` or `\\.\COM3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiptTarget {
+    Device(String),
+    Network(String),
+    File(String),
+}
+
+impl ReceiptTarget {
+    pub fn parse(target: &str) -> Result<Self, String> {
+        let target = target.trim();
+        if target.is_empty() {
+            return Err("No receipt printer configured. Choose one in the hospital settings or set printing.receipt_printer in caton.toml.".to_string());
+        }
+        if let Some(address) = target.strip_prefix("tcp://") {
+            let address = if address.contains(':') { address.to_string() } else { format!("{}:9100", address) };
+            return Ok(ReceiptTarget::Network(address));
+        }
+        if let Some(path) = target.strip_prefix("file:") {
+            return Ok(ReceiptTarget::File(path.to_string()));
+        }
+        Ok(ReceiptTarget::Device(target.to_string()))
+    }
+
+    pub fn send(&self, bytes: &[u8]) -> Result<(), String> {
+        match self {
+            ReceiptTarget::Device(path) => std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|mut device| device.write_all(bytes))
+                .map_err(|e| format!("Failed to write to printer {}: {}", path, e)),
+            ReceiptTarget::File(path) => {
+                std::fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))
+            }
+            ReceiptTarget::Network(address) => {
+                let socket = address
+                    .to_socket_addrs()
+                    .map_err(|e| format!("Invalid printer address {}: {}", address, e))?
+                    .next()
+                    .ok_or_else(|| format!("Invalid printer address {}", address))?;
+                let mut stream = TcpStream::connect_timeout(&socket, Duration::from_secs(5))
+                    .map_err(|e| format!("Failed to reach printer at {}: {}", address, e))?;
+                stream
+                    .write_all(bytes)
+                    .and_then(|_| stream.flush())
+                    .map_err(|e| format!("Failed to send to printer at {}: {}", address, e))
+            }
+        }
+    }
+}
+
+/// Prints an invoice on a thermal printer. `target` and `width_mm` fall
+/// back to the hospital's printer settings, then to `[printing]`.
+#[command]
+pub async fn print_receipt(
+    invoice_number: String,
+    target: Option<String>,
+    width_mm: Option<u32>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let invoice = find_invoice(&*db_state.invoices(), &hospital_id, &invoice_number).await?;
+    let hospital = find_hospital(&*db_state.users(), &hospital_id).await?;
+
+    // An explicit choice wins, then the hospital's printer, then the installation's
+    let printer = &hospital.settings.printer;
+    let target = target
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| printer.receipt_printer())
+        .unwrap_or_default();
+    let target = ReceiptTarget::parse(&target)?;
+    let width = PaperWidth::from_mm(width_mm.unwrap_or_else(|| printer.receipt_width_mm()))?;
+
+    let issued_at = dates::to_utc(invoice.date_created).with_timezone(&Local).naive_local();
+    let bytes = format_receipt(&invoice, &hospital, width, issued_at);
+    target.send(&bytes)?;
+
+    Ok(format!("Receipt for {} sent to printer.", invoice.invoice_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::{InvoiceLine, InvoiceStatus, InvoiceTaxRow};
+    use crate::payment::{Payment, PaymentMode};
+    use crate::tax::SupplyType;
+    use chrono::NaiveDate;
+    use mongodb::bson;
+
+    fn hospital() -> User {
+        User {
+            id: None,
+            username: "sunrise".to_string(),
+            name: "A Rao".to_string(),
+            mobile: "9876543210".to_string(),
+            hospital: "Sunrise Pharmacy".to_string(),
+            address: "12 MG Road, Pune".to_string(),
+            password_hash_doc: String::new(),
+            password_hash_pharma: String::new(),
+            email: "desk@sunrise.test".to_string(),
+            otp: None,
+            otp_expiry: None,
+            registration_number: None,
+            gstin: Some("27AAPFU0939F1ZV".to_string()),
+            state_code: None,
+            invoice_prefix: Some("SUN".to_string()),
+            upi_id: Some("sunrise@upi".to_string()),
+            logo_path: None,
+            letterhead: None,
+            settings: Default::default(),
+        }
+    }
+
+    fn invoice(payment: PaymentMode) -> Invoice {
+        let rupees = Money::from_rupees;
+        let line = |name: &str, batch: &str, quantity: u32, price: f64, taxable: f64, half_tax: f64| InvoiceLine {
+            medicine_id: String::new(),
+            name: name.to_string(),
+            batch_number: batch.to_string(),
+            hsn_code: "3004".to_string(),
+            gst_rate: 12.0,
+            quantity,
+            unit_price: rupees(price),
+            discount: Money::default(),
+            taxable_value: rupees(taxable),
+            cgst: rupees(half_tax),
+            sgst: rupees(half_tax),
+            igst: Money::default(),
+            total: rupees(price * quantity as f64),
+            returned_quantity: 0,
+        };
+        Invoice {
+            id: None,
+            hospital_id: "h1".to_string(),
+            invoice_number: "SUN/2026-27/000042".to_string(),
+            financial_year: "2026-27".to_string(),
+            customer_name: "Meera Iyer".to_string(),
+            customer_mobile: Some("9123456780".to_string()),
+            appointment_id: None,
+            place_of_supply: None,
+            supply_type: SupplyType::IntraState,
+            lines: vec![
+                line("Paracetamol 500mg Tablets (Strip of 15)", "PCM2291", 2, 30.0, 53.58, 3.21),
+                line("Cetirizine 10mg", "CTZ118", 1, 18.75, 16.74, 1.00),
+            ],
+            tax_summary: vec![InvoiceTaxRow {
+                hsn_code: "3004".to_string(),
+                gst_rate: 12.0,
+                taxable_value: rupees(70.32),
+                cgst: rupees(4.21),
+                sgst: rupees(4.21),
+                igst: Money::default(),
+            }],
+            customer_category: None,
+            discount_total: Money::default(),
+            applied_rules: Vec::new(),
+            price_override: false,
+            taxable_total: rupees(70.32),
+            cgst_total: rupees(4.21),
+            sgst_total: rupees(4.21),
+            igst_total: Money::default(),
+            round_off: rupees(0.25),
+            grand_total: rupees(79.0),
+            payments: vec![Payment {
+                mode: payment,
+                amount: rupees(if payment == PaymentMode::Cash { 100.0 } else { 79.0 }),
+                reference: (payment == PaymentMode::Upi).then(|| "412233445566".to_string()),
+            }],
+            change_due: rupees(if payment == PaymentMode::Cash { 21.0 } else { 0.0 }),
+            credit_customer_id: None,
+            over_credit_limit: false,
+            shift_id: None,
+            seller: None,
+            date_created: bson::DateTime::parse_rfc3339_str("2026-10-19T05:00:00+00:00").unwrap(),
+            status: InvoiceStatus::Issued,
+        }
+    }
+
+    fn issued_at() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(10, 30, 0).unwrap()
+    }
+
+    /// Compares against a stored fixture. Run with `UPDATE_FIXTURES=1` to
+    /// rewrite the fixture after an intended layout change.
+    fn assert_fixture(name: &str, bytes: &[u8]) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        if std::env::var("UPDATE_FIXTURES").is_ok() {
+            std::fs::write(&path, bytes).unwrap();
+        }
+        let expected = std::fs::read(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path.display(), e));
+        assert!(
+            expected == bytes,
+            "receipt differs from {}:\n{}",
+            name,
+            String::from_utf8_lossy(bytes)
+        );
+    }
+
+    #[test]
+    fn cash_receipt_80mm_matches_fixture() {
+        let bytes = format_receipt(&invoice(PaymentMode::Cash), &hospital(), PaperWidth::Mm80, issued_at());
+        assert_fixture("receipt_cash_80mm.bin", &bytes);
+    }
+
+    #[test]
+    fn upi_receipt_58mm_matches_fixture() {
+        let bytes = format_receipt(&invoice(PaymentMode::Upi), &hospital(), PaperWidth::Mm58, issued_at());
+        assert_fixture("receipt_upi_58mm.bin", &bytes);
+    }
+
+    #[test]
+    fn lines_fit_the_paper_width() {
+        for width in [PaperWidth::Mm58, PaperWidth::Mm80] {
+            let bytes = format_receipt(&invoice(PaymentMode::Cash), &hospital(), width, issued_at());
+            // Printable runs after the last control byte never exceed the
+            // width; the QR payload is data, not a printed line
+            let text = String::from_utf8_lossy(&bytes);
+            for line in text.split('\n').filter(|l| !l.contains("upi://")) {
+                let printable = line.rsplit(|c: char| c.is_ascii_control()).next().unwrap_or("");
+                assert!(printable.chars().count() <= width.columns(), "too wide: {:?}", printable);
+            }
+        }
+    }
+
+    #[test]
+    fn qr_store_command_carries_data_length() {
+        let mut p = EscPos::new();
+        p.qr("upi://pay?pa=a@b", 6);
+        let bytes = p.into_bytes();
+        let store = [GS, b'(', b'k', 19, 0, 49, 80, 48];
+        assert!(bytes.windows(store.len()).any(|w| w == store));
+    }
+
+    #[test]
+    fn non_ascii_text_is_replaced() {
+        let mut p = EscPos::new();
+        p.text("₹5 é");
+        assert_eq!(&p.into_bytes()[2..], b"Rs.5 ?");
+    }
+
+    #[test]
+    fn parses_printer_targets() {
+        assert_eq!(
+            ReceiptTarget::parse("tcp://192.168.1.50").unwrap(),
+            ReceiptTarget::Network("192.168.1.50:9100".to_string())
+        );
+        assert_eq!(
+            ReceiptTarget::parse("file:/tmp/r.bin").unwrap(),
+            ReceiptTarget::File("/tmp/r.bin".to_string())
+        );
+        assert_eq!(
+            ReceiptTarget::parse("/dev/usb/lp0").unwrap(),
+            ReceiptTarget::Device("/dev/usb/lp0".to_string())
+        );
+        assert!(ReceiptTarget::parse("  ").is_err());
+    }
+
+    #[tokio::test]
+    async fn invoices_are_looked_up_within_the_hospital() {
+        let store = crate::repository::memory::MemoryStore::default();
+        store.invoices.lock().unwrap().push(invoice(PaymentMode::Cash));
+
+        let found = find_invoice(&store, "h1", " SUN/2026-27/000042 ").await.unwrap();
+        assert_eq!(found.customer_name, "Meera Iyer");
+        assert_eq!(find_invoice(&store, "h2", "SUN/2026-27/000042").await.unwrap_err(), "Invoice not found");
+    }
+}
@@ -0,0 +1,406 @@
+// src-tauri/src/reminder.rs
+use crate::commands::Appointment;
+use crate::config::{self, SmsSettings};
+use crate::dates;
+use crate::db::DbState;
+use crate::utils::send_email;
+use chrono::{Duration, Local, NaiveDate, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{FindOptions, ReplaceOptions};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use tauri::{command, State};
+
+/// How many days ahead of the visit a reminder goes out.
+const REMINDER_LEAD_DAYS: i64 = 1;
+/// Failed reminders are retried on later runs until this many attempts.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Anything that can deliver a text message to a mobile number.
+pub trait SmsProvider: Send + Sync {
+    fn send_sms(&self, mobile: &str, message: &str) -> Result<(), String>;
+}
+
+/// Stand-in provider that appends each message to a local file instead of
+/// calling an SMS gateway.
+pub struct FileSmsProvider {
+    path: PathBuf,
+}
+
+impl FileSmsProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSmsProvider { path: path.into() }
+    }
+}
+
+impl SmsProvider for FileSmsProvider {
+    fn send_sms(&self, mobile: &str, message: &str) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open SMS outbox: {}", e))?;
+        writeln!(file, "{}\t{}\t{}", Utc::now().to_rfc3339(), mobile, message)
+            .map_err(|e| format!("Failed to write SMS outbox: {}", e))
+    }
+}
+
+/// Picks the SMS provider from the `[sms]` settings; the file outbox is
+/// the default and, for now, the only one.
+pub fn sms_provider_from_settings(settings: &SmsSettings) -> Result<Box<dyn SmsProvider>, String> {
+    match settings.provider.as_str() {
+        "file" => Ok(Box::new(FileSmsProvider::new(settings.outbox_path.clone()))),
+        other => Err(format!("Unknown SMS provider: {}", other)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderChannel {
+    Email,
+    Sms,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderStatus {
+    Sent,
+    Failed,
+}
+
+/// One row per appointment; retries update the same row.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reminder {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub appointment_id: String,
+    pub hospital_id: String,
+    pub patient_name: String,
+    pub follow_up_date: NaiveDate,
+    pub channel: ReminderChannel,
+    pub recipient: String,
+    pub status: ReminderStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FollowUp {
+    pub appointment_id: String,
+    pub patient_name: String,
+    pub mobile: String,
+    pub email: Option<String>,
+    pub follow_up_date: NaiveDate,
+    pub reminder_status: Option<ReminderStatus>,
+}
+
+fn reminders_collection(db: &Database) -> Collection<Reminder> {
+    db.collection("reminders")
+}
+
+/// Appointments whose follow-up falls between today and `until`, inclusive.
+async fn find_due_appointments(
+    db: &Database,
+    hospital_id: Option<&str>,
+    until: NaiveDate,
+) -> Result<Vec<Appointment>, String> {
+    let today = Local::now().date_naive();
+    let mut filter = doc! {
+        "follow_up_date": { "$gte": dates::midnight(today), "$lte": dates::midnight(until) },
+    };
+    if let Some(hospital_id) = hospital_id {
+        filter.insert("hospital_id", hospital_id);
+    }
+
+    let find_options = FindOptions::builder().sort(doc! { "follow_up_date": 1 }).build();
+    db.collection::<Appointment>("appointments")
+        .find(filter, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing appointments: {}", e))
+}
+
+async fn find_reminders(db: &Database, appointments: &[Appointment]) -> Result<Vec<Reminder>, String> {
+    let ids: Vec<String> = appointments.iter().map(|a| a.id.to_hex()).collect();
+    reminders_collection(db)
+        .find(doc! { "appointment_id": { "$in": ids } }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn reminder_message(appointment: &Appointment, follow_up_date: NaiveDate) -> String {
+    format!(
+        "Dear {}, this is a reminder of your follow-up visit on {}. Please visit the clinic or call to reschedule.",
+        appointment.patient_name,
+        follow_up_date.format("%d %b %Y")
+    )
+}
+
+#[command]
+pub async fn get_due_followups(
+    hospital_id: String,
+    until: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<FollowUp>, String> {
+    let until = match until {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))?,
+        None => Local::now().date_naive() + Duration::days(REMINDER_LEAD_DAYS),
+    };
+
+    let db = db_state.mongo("reminders")?.db.clone();
+    let appointments = find_due_appointments(&db, Some(&hospital_id), until).await?;
+    let reminders = find_reminders(&db, &appointments).await?;
+
+    Ok(appointments
+        .into_iter()
+        .filter_map(|appointment| {
+            let appointment_id = appointment.id.to_hex();
+            let reminder_status = reminders
+                .iter()
+                .find(|r| r.appointment_id == appointment_id)
+                .map(|r| r.status);
+            Some(FollowUp {
+                appointment_id,
+                follow_up_date: appointment.follow_up_date?,
+                patient_name: appointment.patient_name,
+                mobile: appointment.mobile,
+                email: appointment.email,
+                reminder_status,
+            })
+        })
+        .collect())
+}
+
+/// The appointments a dispatch run should remind: follow-up between
+/// `today` and `until`, and no reminder sent or given up on yet.
+fn due_reminders<'a>(
+    appointments: &'a [Appointment],
+    reminders: &[Reminder],
+    today: NaiveDate,
+    until: NaiveDate,
+) -> Vec<&'a Appointment> {
+    appointments
+        .iter()
+        .filter(|a| a.follow_up_date.is_some_and(|date| date >= today && date <= until))
+        .filter(|a| {
+            let id = a.id.to_hex();
+            let previous = reminders.iter().find(|r| r.appointment_id == id);
+            !previous.is_some_and(|r| r.status == ReminderStatus::Sent || r.attempts >= MAX_ATTEMPTS)
+        })
+        .collect()
+}
+
+/// The reminder row after one more attempt, keeping the count of earlier ones.
+fn record_attempt(
+    previous: Option<&Reminder>,
+    appointment: &Appointment,
+    follow_up_date: NaiveDate,
+    channel: ReminderChannel,
+    recipient: String,
+    result: Result<(), String>,
+) -> Reminder {
+    let (status, last_error) = match result {
+        Ok(()) => (ReminderStatus::Sent, None),
+        Err(error) => (ReminderStatus::Failed, Some(error)),
+    };
+    Reminder {
+        id: previous.and_then(|r| r.id),
+        appointment_id: appointment.id.to_hex(),
+        hospital_id: appointment.hospital_id.clone(),
+        patient_name: appointment.patient_name.clone(),
+        follow_up_date,
+        channel,
+        recipient,
+        status,
+        attempts: previous.map_or(0, |r| r.attempts) + 1,
+        last_error,
+        updated_at: Utc::now().to_rfc3339(),
+    }
+}
+
+/// Sends reminders for follow-ups due within the lead time. Patients with an
+/// email get one by mail, everyone else by SMS. Already-sent reminders are
+/// skipped and failed ones are retried up to `MAX_ATTEMPTS`.
+#[command]
+pub async fn dispatch_followup_reminders(
+    hospital_id: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let sms = sms_provider_from_settings(&config::get().sms)?;
+    let today = Local::now().date_naive();
+    let until = today + Duration::days(REMINDER_LEAD_DAYS);
+
+    let db = db_state.mongo("reminders")?.db.clone();
+    let appointments = find_due_appointments(&db, hospital_id.as_deref(), until).await?;
+    let reminders = find_reminders(&db, &appointments).await?;
+    let collection = reminders_collection(&db);
+
+    let (mut sent, mut failed) = (0, 0);
+    for appointment in due_reminders(&appointments, &reminders, today, until) {
+        let Some(follow_up_date) = appointment.follow_up_date else { continue };
+        let appointment_id = appointment.id.to_hex();
+        let previous = reminders.iter().find(|r| r.appointment_id == appointment_id);
+
+        let message = reminder_message(appointment, follow_up_date);
+        let (channel, recipient, result) = match &appointment.email {
+            Some(email) => (
+                ReminderChannel::Email,
+                email.clone(),
+                send_email(email, "Follow-up visit reminder", &message).await,
+            ),
+            None => (ReminderChannel::Sms, appointment.mobile.clone(), sms.send_sms(&appointment.mobile, &message)),
+        };
+        if result.is_ok() {
+            sent += 1;
+        } else {
+            failed += 1;
+        }
+
+        let reminder = record_attempt(previous, appointment, follow_up_date, channel, recipient, result);
+        collection
+            .replace_one(
+                doc! { "appointment_id": &appointment_id },
+                reminder,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|e| format!("Failed to record reminder: {}", e))?;
+    }
+
+    Ok(format!("Sent {} follow-up reminders, {} failed.", sent, failed))
+}
+
+#[command]
+pub async fn get_reminder_log(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<Reminder>, String> {
+    let db = db_state.mongo("reminders")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "updated_at": -1 }).build();
+
+    reminders_collection(&db)
+        .find(doc! { "hospital_id": hospital_id }, find_options)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(follow_up_date: NaiveDate) -> Appointment {
+        Appointment {
+            id: ObjectId::new(),
+            patient_name: "Meera Iyer".to_string(),
+            mobile: "9123456780".to_string(),
+            disease: "Fever".to_string(),
+            precautions: "Review after 7 days".to_string(),
+            medicines: Vec::new(),
+            hospital_id: "h1".to_string(),
+            date_created: dates::midnight(follow_up_date - Duration::days(7)),
+            email: None,
+            follow_up_date: Some(follow_up_date),
+            vitals: None,
+            notes: None,
+            doctor_name: None,
+        }
+    }
+
+    /// One dispatch run for a single SMS reminder, as the command does it.
+    fn dispatch(sms: &dyn SmsProvider, appointment: &Appointment, previous: Option<Reminder>) -> Reminder {
+        let follow_up_date = appointment.follow_up_date.unwrap();
+        let result = sms.send_sms(&appointment.mobile, &reminder_message(appointment, follow_up_date));
+        let recipient = appointment.mobile.clone();
+        record_attempt(previous.as_ref(), appointment, follow_up_date, ReminderChannel::Sms, recipient, result)
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+    }
+
+    #[test]
+    fn sms_outbox_records_each_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = dir.path().join("sms_outbox.log");
+        let sms = FileSmsProvider::new(&outbox);
+        let appointment = visit(today());
+
+        let reminder = dispatch(&sms, &appointment, None);
+        assert_eq!((reminder.status, reminder.attempts, reminder.last_error), (ReminderStatus::Sent, 1, None));
+        sms.send_sms("9000000000", "Second message").unwrap();
+
+        let lines: Vec<Vec<String>> = std::fs::read_to_string(&outbox)
+            .unwrap()
+            .lines()
+            .map(|line| line.split('\t').map(str::to_string).collect())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][1], "9123456780");
+        assert!(lines[0][2].starts_with("Dear Meera Iyer, this is a reminder of your follow-up visit on 19 Oct 2026."));
+        assert_eq!(lines[1][1..], ["9000000000", "Second message"]);
+    }
+
+    #[test]
+    fn failed_reminders_are_retried_then_given_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let appointment = visit(today());
+        let appointments = [appointment.clone()];
+        let due = |reminders: &[Reminder]| due_reminders(&appointments, reminders, today(), today()).len();
+
+        // The outbox's folder is missing, so every send fails
+        let broken = FileSmsProvider::new(dir.path().join("missing").join("sms_outbox.log"));
+        let first = dispatch(&broken, &appointment, None);
+        assert_eq!((first.status, first.attempts), (ReminderStatus::Failed, 1));
+        assert!(first.last_error.as_deref().unwrap().starts_with("Failed to open SMS outbox"));
+        assert_eq!(due(std::slice::from_ref(&first)), 1);
+
+        // Retried on the next run, and it gets through
+        let sms = FileSmsProvider::new(dir.path().join("sms_outbox.log"));
+        let retried = dispatch(&sms, &appointment, Some(first));
+        assert_eq!((retried.status, retried.attempts, retried.last_error.clone()), (ReminderStatus::Sent, 2, None));
+        assert_eq!(due(&[retried]), 0);
+
+        // Three failures in a row and it is not tried again
+        let mut reminder = None;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let failed = dispatch(&broken, &appointment, reminder.take());
+            assert_eq!(failed.attempts, attempt);
+            reminder = Some(failed);
+        }
+        let gave_up = reminder.unwrap();
+        assert_eq!(gave_up.status, ReminderStatus::Failed);
+        assert_eq!(due(&[gave_up]), 0);
+    }
+
+    #[test]
+    fn only_follow_ups_in_the_lead_window_are_due() {
+        let until = today() + Duration::days(REMINDER_LEAD_DAYS);
+        let appointments = [
+            visit(today() - Duration::days(1)), // already passed
+            visit(today()),
+            visit(until),
+            visit(until + Duration::days(1)), // too early to remind
+            Appointment { follow_up_date: None, ..visit(today()) },
+        ];
+        let due: Vec<_> = due_reminders(&appointments, &[], today(), until).iter().map(|a| a.id).collect();
+        assert_eq!(due, [appointments[1].id, appointments[2].id]);
+
+        let sms = FileSmsProvider::new(tempfile::tempdir().unwrap().path().join("missing/outbox.log"));
+        let sent = Reminder { status: ReminderStatus::Sent, ..dispatch(&sms, &appointments[1], None) };
+        let due = due_reminders(&appointments, &[sent], today(), until);
+        assert_eq!(due.iter().map(|a| a.id).collect::<Vec<_>>(), [appointments[2].id]);
+    }
+}
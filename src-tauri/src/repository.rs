@@ -0,0 +1,1029 @@
+// src-tauri/src/repository.rs
+//! Storage behind the stock, appointment, account, invoice and discount rules. The
+//! MongoDB implementation is the typed collection itself and the SQLite one
+//! lives in `sqlite.rs`; commands get the configured one from `DbState`,
+//! tests pass a `MemoryStore`. Multi-document transactions (billing,
+//! returns, credit) still work on collections directly, since they need a
+//! session.
+use crate::billing::Invoice;
+use crate::clinical::{ClinicalNotes, Vitals};
+use crate::commands::{Appointment, Medicine};
+use crate::discount::DiscountRule;
+use crate::model::User;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use crate::dates;
+use crate::money::Money;
+use chrono::NaiveDate;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::Collection;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// Fields to change on a medicine batch; `None` leaves a field as it is.
+#[derive(Debug, Clone, Default)]
+pub struct MedicineChanges {
+    pub quantity: Option<u32>,
+    pub purchase_price: Option<Money>,
+    pub selling_price: Option<Money>,
+    pub batch_number: Option<String>,
+    pub expiry_date: Option<NaiveDate>,
+    pub wholesaler_name: Option<String>,
+    pub purchase_date: Option<NaiveDate>,
+    pub hsn_code: Option<String>,
+    pub gst_rate: Option<f64>,
+    pub tax_inclusive: Option<bool>,
+    pub generic_name: Option<String>,
+    pub brand_names: Option<Vec<String>>,
+    pub composition: Option<String>,
+    pub barcode: Option<String>,
+}
+
+impl MedicineChanges {
+    pub fn is_empty(&self) -> bool {
+        self.to_document().is_empty()
+    }
+
+    fn to_document(&self) -> Document {
+        let mut update = doc! {};
+        if let Some(quantity) = self.quantity {
+            update.insert("quantity", quantity);
+        }
+        if let Some(price) = self.purchase_price {
+            update.insert("purchase_price", price.paise());
+        }
+        if let Some(price) = self.selling_price {
+            update.insert("selling_price", price.paise());
+        }
+        if let Some(batch) = &self.batch_number {
+            update.insert("batch_number", batch);
+        }
+        if let Some(expiry) = self.expiry_date {
+            update.insert("expiry_date", dates::midnight(expiry));
+        }
+        if let Some(wholesaler) = &self.wholesaler_name {
+            update.insert("wholesaler_name", wholesaler);
+        }
+        if let Some(date) = self.purchase_date {
+            update.insert("purchase_date", dates::midnight(date));
+        }
+        if let Some(hsn) = &self.hsn_code {
+            update.insert("hsn_code", hsn);
+        }
+        if let Some(rate) = self.gst_rate {
+            update.insert("gst_rate", rate);
+        }
+        if let Some(inclusive) = self.tax_inclusive {
+            update.insert("tax_inclusive", inclusive);
+        }
+        if let Some(generic) = &self.generic_name {
+            update.insert("generic_name", generic);
+        }
+        if let Some(brands) = &self.brand_names {
+            update.insert("brand_names", brands);
+        }
+        if let Some(composition) = &self.composition {
+            update.insert("composition", composition);
+        }
+        if let Some(barcode) = &self.barcode {
+            update.insert("barcode", barcode);
+        }
+        update
+    }
+
+    /// Applies the changes to a loaded batch, for stores that update whole rows.
+    pub(crate) fn apply(&self, medicine: &mut Medicine) {
+        if let Some(quantity) = self.quantity {
+            medicine.quantity = quantity;
+        }
+        if let Some(price) = self.purchase_price {
+            medicine.purchase_price = price;
+        }
+        if let Some(price) = self.selling_price {
+            medicine.selling_price = price;
+        }
+        if let Some(batch) = &self.batch_number {
+            medicine.batch_number = batch.clone();
+        }
+        if let Some(expiry) = self.expiry_date {
+            medicine.expiry_date = expiry;
+        }
+        if let Some(wholesaler) = &self.wholesaler_name {
+            medicine.wholesaler_name = wholesaler.clone();
+        }
+        if let Some(date) = self.purchase_date {
+            medicine.purchase_date = date;
+        }
+        if let Some(hsn) = &self.hsn_code {
+            medicine.hsn_code = Some(hsn.clone());
+        }
+        if let Some(rate) = self.gst_rate {
+            medicine.gst_rate = Some(rate);
+        }
+        if let Some(inclusive) = self.tax_inclusive {
+            medicine.tax_inclusive = inclusive;
+        }
+        if let Some(generic) = &self.generic_name {
+            medicine.generic_name = Some(generic.clone());
+        }
+        if let Some(brands) = &self.brand_names {
+            medicine.brand_names = brands.clone();
+        }
+        if let Some(composition) = &self.composition {
+            medicine.composition = Some(composition.clone());
+        }
+        if let Some(barcode) = &self.barcode {
+            medicine.barcode = Some(barcode.clone());
+        }
+    }
+}
+
+/// Which rows of a listing to return; a `limit` of `None` returns the rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Page {
+    pub skip: u64,
+    pub limit: Option<u64>,
+}
+
+impl Page {
+    pub(crate) fn apply<T>(self, rows: impl Iterator<Item = T>) -> Vec<T> {
+        let rows = rows.skip(self.skip as usize);
+        match self.limit {
+            Some(limit) => rows.take(limit as usize).collect(),
+            None => rows.collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MedicineSort {
+    #[default]
+    Relevance, // best name match first; by name when there is no search text
+    Name,
+    Expiry,
+    Quantity,
+    Price,
+}
+
+impl MedicineSort {
+    pub(crate) fn field(self) -> &'static str {
+        match self {
+            MedicineSort::Relevance | MedicineSort::Name => "name",
+            MedicineSort::Expiry => "expiry_date",
+            MedicineSort::Quantity => "quantity",
+            MedicineSort::Price => "selling_price",
+        }
+    }
+}
+
+/// A page of a hospital's batches, optionally only those whose name
+/// contains `text` (case-insensitive).
+#[derive(Debug, Clone, Default)]
+pub struct MedicineQuery {
+    pub text: Option<String>,
+    pub sort: MedicineSort,
+    pub descending: bool,
+    pub page: Page,
+}
+
+impl MedicineQuery {
+    pub(crate) fn ranked(&self) -> bool {
+        self.sort == MedicineSort::Relevance && self.text.is_some()
+    }
+
+    /// The order within a rank: the sort field, then name and ID so pages
+    /// do not overlap.
+    pub(crate) fn compare(&self, a: &Medicine, b: &Medicine) -> Ordering {
+        let by_field = match self.sort {
+            MedicineSort::Relevance | MedicineSort::Name => a.name.cmp(&b.name),
+            MedicineSort::Expiry => a.expiry_date.cmp(&b.expiry_date),
+            MedicineSort::Quantity => a.quantity.cmp(&b.quantity),
+            MedicineSort::Price => a.selling_price.cmp(&b.selling_price),
+        };
+        let by_field = if self.descending { by_field.reverse() } else { by_field };
+        by_field.then_with(|| a.name.cmp(&b.name)).then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+/// How well a name matches lowercase search text: 0 for the whole name,
+/// 1 for its start, 2 anywhere else; `None` if it does not contain it.
+pub(crate) fn match_rank(name: &str, text: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    match name.find(text)? {
+        0 if name.len() == text.len() => Some(0),
+        0 => Some(1),
+        _ => Some(2),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppointmentSort {
+    #[default]
+    Newest,
+    Oldest,
+    Patient,
+}
+
+/// A page of a hospital's visits, optionally only those whose patient name
+/// contains `text` or whose mobile number starts with it.
+#[derive(Debug, Clone, Default)]
+pub struct AppointmentQuery {
+    pub text: Option<String>,
+    pub sort: AppointmentSort,
+    pub page: Page,
+}
+
+impl AppointmentQuery {
+    fn matches(&self, appointment: &Appointment) -> bool {
+        self.text.as_deref().is_none_or(|text| {
+            appointment.patient_name.to_lowercase().contains(&text.to_lowercase()) || appointment.mobile.starts_with(text)
+        })
+    }
+
+    fn compare(&self, a: &Appointment, b: &Appointment) -> Ordering {
+        match self.sort {
+            AppointmentSort::Newest => b.date_created.cmp(&a.date_created).then_with(|| b.id.cmp(&a.id)),
+            AppointmentSort::Oldest => a.date_created.cmp(&b.date_created).then_with(|| a.id.cmp(&b.id)),
+            AppointmentSort::Patient => a
+                .patient_name
+                .cmp(&b.patient_name)
+                .then_with(|| b.date_created.cmp(&a.date_created))
+                .then_with(|| b.id.cmp(&a.id)),
+        }
+    }
+}
+
+/// Whether an update found its document and whether anything changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateOutcome {
+    pub matched: bool,
+    pub modified: bool,
+}
+
+/// The two logins on a hospital account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordKind {
+    Doctor,
+    Pharmacy,
+}
+
+impl PasswordKind {
+    /// "Doctor" is the doctor (admin) login; anything else is the pharmacy.
+    pub fn from_role(role: &str) -> Self {
+        if role == "Doctor" {
+            PasswordKind::Doctor
+        } else {
+            PasswordKind::Pharmacy
+        }
+    }
+
+    pub(crate) fn field(self) -> &'static str {
+        match self {
+            PasswordKind::Doctor => "password_hash_doc",
+            PasswordKind::Pharmacy => "password_hash_pharma",
+        }
+    }
+}
+
+#[async_trait]
+pub trait MedicineRepository: Send + Sync {
+    async fn insert_medicine(&self, medicine: Medicine) -> Result<ObjectId, String>;
+    /// `hospital_id` of `None` looks the batch up by ID alone.
+    async fn find_medicine(&self, id: ObjectId, hospital_id: Option<&str>) -> Result<Option<Medicine>, String>;
+    async fn find_medicines(&self, ids: &[ObjectId], hospital_id: &str) -> Result<Vec<Medicine>, String>;
+    /// A hospital's batches matching `query`, in its order.
+    async fn list_medicines(&self, hospital_id: &str, query: &MedicineQuery) -> Result<Vec<Medicine>, String>;
+    async fn update_medicine(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        batch_number: Option<&str>,
+        changes: &MedicineChanges,
+    ) -> Result<UpdateOutcome, String>;
+    /// Takes `quantity` off a batch if it has that many; false otherwise.
+    async fn take_stock(&self, id: ObjectId, batch_number: &str, quantity: u32) -> Result<bool, String>;
+    /// Adds `delta` (which may be negative) to a hospital's batch, stopping
+    /// at zero; returns the new quantity, or `None` if there is no batch.
+    async fn adjust_quantity(&self, id: ObjectId, hospital_id: &str, delta: i64) -> Result<Option<u32>, String>;
+    async fn delete_medicine(&self, id: ObjectId, hospital_id: &str, batch_number: Option<&str>) -> Result<bool, String>;
+}
+
+#[async_trait]
+pub trait AppointmentRepository: Send + Sync {
+    async fn insert_appointment(&self, appointment: Appointment) -> Result<(), String>;
+    /// A hospital's visits matching `query`; newest first by default.
+    async fn list_appointments(&self, hospital_id: &str, query: &AppointmentQuery) -> Result<Vec<Appointment>, String>;
+    /// Removes a hospital's visits created before `cutoff` that have no
+    /// clinical record and no follow-up on or after `today`.
+    async fn delete_stale_appointments(&self, hospital_id: &str, cutoff: DateTime, today: NaiveDate)
+        -> Result<u64, String>;
+    /// Records a visit's vitals and notes; `None` leaves them as they are.
+    /// Returns whether the visit was found.
+    async fn update_clinical(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        vitals: Option<&Vitals>,
+        notes: Option<&ClinicalNotes>,
+    ) -> Result<bool, String>;
+    /// A patient's latest `limit` visits with vitals, newest first. Patients
+    /// are matched on mobile number.
+    async fn vitals_history(&self, hospital_id: &str, mobile: &str, limit: usize) -> Result<Vec<Appointment>, String>;
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, String>;
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, String>;
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, String>;
+    /// Every account, for jobs that run per hospital.
+    async fn list_users(&self) -> Result<Vec<User>, String>;
+    async fn insert_user(&self, user: User) -> Result<(), String>;
+    /// Replaces a stored account, matched on its `id`.
+    async fn replace_user(&self, user: &User) -> Result<(), String>;
+    /// Stores an OTP with its expiry, or clears it with `None`.
+    async fn set_otp(&self, email: &str, otp: Option<(&str, DateTime)>) -> Result<(), String>;
+    async fn set_password_hash(&self, email: &str, kind: PasswordKind, hash: &str) -> Result<(), String>;
+}
+
+#[async_trait]
+pub trait InvoiceRepository: Send + Sync {
+    async fn find_invoice(&self, hospital_id: &str, invoice_number: &str) -> Result<Option<Invoice>, String>;
+    /// Invoices created in `[from, to)`, newest first.
+    async fn list_invoices(&self, hospital_id: &str, from: DateTime, to: DateTime) -> Result<Vec<Invoice>, String>;
+    /// Stores an invoice numbered elsewhere, matching it on its `_id`.
+    async fn insert_invoice(&self, invoice: &Invoice) -> Result<InvoiceInsert, String>;
+}
+
+#[async_trait]
+pub trait DiscountRuleRepository: Send + Sync {
+    /// A hospital's rules by name, or only its active ones.
+    async fn list_rules(&self, hospital_id: &str, active_only: bool) -> Result<Vec<DiscountRule>, String>;
+    async fn insert_rule(&self, rule: DiscountRule) -> Result<ObjectId, String>;
+    /// Replaces a stored rule, matched on its `id` and hospital, keeping its
+    /// `date_created`. `false` when there is no such rule.
+    async fn replace_rule(&self, rule: &DiscountRule) -> Result<bool, String>;
+    async fn delete_rule(&self, id: ObjectId, hospital_id: &str) -> Result<bool, String>;
+}
+
+/// What became of an invoice numbered elsewhere (see `sync.rs`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceInsert {
+    Stored,
+    AlreadyStored, // the same `_id`, from an earlier push
+    NumberTaken,   // another invoice of the hospital has its number
+}
+
+fn medicine_filter(id: ObjectId, hospital_id: Option<&str>, batch_number: Option<&str>) -> Document {
+    let mut filter = doc! { "_id": id };
+    if let Some(hospital_id) = hospital_id {
+        filter.insert("user_id", hospital_id);
+    }
+    if let Some(batch_number) = batch_number {
+        filter.insert("batch_number", batch_number);
+    }
+    filter
+}
+
+#[async_trait]
+impl MedicineRepository for Collection<Medicine> {
+    async fn insert_medicine(&self, medicine: Medicine) -> Result<ObjectId, String> {
+        let result = self.insert_one(medicine, None).await.map_err(|e| e.to_string())?;
+        result.inserted_id.as_object_id().ok_or_else(|| "Database did not return an ID".to_string())
+    }
+
+    async fn find_medicine(&self, id: ObjectId, hospital_id: Option<&str>) -> Result<Option<Medicine>, String> {
+        self.find_one(medicine_filter(id, hospital_id, None), None)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn find_medicines(&self, ids: &[ObjectId], hospital_id: &str) -> Result<Vec<Medicine>, String> {
+        self.find(doc! { "_id": { "$in": ids }, "user_id": hospital_id }, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Runs on the `user_id` + `name` index: the regex is checked against
+    /// the hospital's index keys rather than every document.
+    async fn list_medicines(&self, hospital_id: &str, query: &MedicineQuery) -> Result<Vec<Medicine>, String> {
+        let text = query.text.as_deref().map(str::to_lowercase);
+        let mut filter = doc! { "user_id": hospital_id };
+        if let Some(text) = &text {
+            filter.insert("name", doc! { "$regex": regex::escape(text), "$options": "i" });
+        }
+        let mut pipeline = vec![doc! { "$match": filter }];
+
+        let mut sort = Document::new();
+        let ranked = query.ranked();
+        if let (true, Some(text)) = (ranked, &text) {
+            let name = doc! { "$toLower": "$name" };
+            pipeline.push(doc! { "$addFields": { "_rank": { "$switch": {
+                "branches": [
+                    { "case": { "$eq": [&name, text] }, "then": 0 },
+                    { "case": { "$eq": [{ "$indexOfCP": [&name, text] }, 0] }, "then": 1 },
+                ],
+                "default": 2,
+            } } } });
+            sort.insert("_rank", 1);
+        }
+        sort.insert(query.sort.field(), if query.descending { -1 } else { 1 });
+        if !sort.contains_key("name") {
+            sort.insert("name", 1);
+        }
+        sort.insert("_id", 1);
+        pipeline.push(doc! { "$sort": sort });
+        if query.page.skip > 0 {
+            pipeline.push(doc! { "$skip": query.page.skip as i64 });
+        }
+        if let Some(limit) = query.page.limit {
+            pipeline.push(doc! { "$limit": limit as i64 });
+        }
+        if ranked {
+            pipeline.push(doc! { "$project": { "_rank": 0 } });
+        }
+
+        let documents: Vec<Document> = self
+            .aggregate(pipeline, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?;
+        documents
+            .into_iter()
+            .map(|document| bson::from_document(document).map_err(|e| format!("Error parsing medicine: {}", e)))
+            .collect()
+    }
+
+    async fn update_medicine(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        batch_number: Option<&str>,
+        changes: &MedicineChanges,
+    ) -> Result<UpdateOutcome, String> {
+        let result = self
+            .update_one(
+                medicine_filter(id, Some(hospital_id), batch_number),
+                doc! { "$set": changes.to_document() },
+                None,
+            )
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        Ok(UpdateOutcome { matched: result.matched_count > 0, modified: result.modified_count > 0 })
+    }
+
+    async fn take_stock(&self, id: ObjectId, batch_number: &str, quantity: u32) -> Result<bool, String> {
+        let mut filter = medicine_filter(id, None, Some(batch_number));
+        filter.insert("quantity", doc! { "$gte": quantity });
+        let result = self
+            .update_one(filter, doc! { "$inc": { "quantity": -(quantity as i64) } }, None)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn adjust_quantity(&self, id: ObjectId, hospital_id: &str, delta: i64) -> Result<Option<u32>, String> {
+        let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        let pipeline = vec![doc! { "$set": { "quantity": { "$max": [0, { "$add": ["$quantity", delta] }] } } }];
+        let medicine = self
+            .find_one_and_update(medicine_filter(id, Some(hospital_id), None), pipeline, options)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        Ok(medicine.map(|m| m.quantity))
+    }
+
+    async fn delete_medicine(&self, id: ObjectId, hospital_id: &str, batch_number: Option<&str>) -> Result<bool, String> {
+        let result = self
+            .delete_one(medicine_filter(id, Some(hospital_id), batch_number), None)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(result.deleted_count > 0)
+    }
+}
+
+#[async_trait]
+impl AppointmentRepository for Collection<Appointment> {
+    async fn insert_appointment(&self, appointment: Appointment) -> Result<(), String> {
+        self.insert_one(appointment, None)
+            .await
+            .map_err(|e| format!("Database insert error: {}", e))?;
+        Ok(())
+    }
+
+    async fn list_appointments(&self, hospital_id: &str, query: &AppointmentQuery) -> Result<Vec<Appointment>, String> {
+        let mut filter = doc! { "hospital_id": hospital_id };
+        if let Some(text) = &query.text {
+            let text = regex::escape(text);
+            filter.insert(
+                "$or",
+                vec![
+                    doc! { "patient_name": { "$regex": &text, "$options": "i" } },
+                    doc! { "mobile": { "$regex": format!("^{}", text) } },
+                ],
+            );
+        }
+        let mut find_options = FindOptions::default();
+        find_options.sort = Some(match query.sort {
+            AppointmentSort::Newest => doc! { "date_created": -1, "_id": -1 },
+            AppointmentSort::Oldest => doc! { "date_created": 1, "_id": 1 },
+            AppointmentSort::Patient => doc! { "patient_name": 1, "date_created": -1, "_id": -1 },
+        });
+        find_options.skip = Some(query.page.skip);
+        find_options.limit = query.page.limit.map(|limit| limit as i64);
+        self.find(filter, find_options)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?
+            .try_collect()
+            .await
+            .map_err(|e| format!("Error parsing appointments: {}", e))
+    }
+
+    async fn delete_stale_appointments(
+        &self,
+        hospital_id: &str,
+        cutoff: DateTime,
+        today: NaiveDate,
+    ) -> Result<u64, String> {
+        let filter = doc! {
+            "hospital_id": hospital_id,
+            "date_created": { "$lt": cutoff },
+            "vitals": null,
+            "notes": null,
+            "$or": [
+                { "follow_up_date": null },
+                { "follow_up_date": { "$lt": dates::midnight(today) } },
+            ],
+        };
+        let result = self
+            .delete_many(filter, None)
+            .await
+            .map_err(|e| format!("Error deleting old appointments: {}", e))?;
+        Ok(result.deleted_count)
+    }
+
+    async fn update_clinical(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        vitals: Option<&Vitals>,
+        notes: Option<&ClinicalNotes>,
+    ) -> Result<bool, String> {
+        let mut changes = Document::new();
+        if let Some(vitals) = vitals {
+            changes.insert("vitals", bson::to_bson(vitals).map_err(|e| e.to_string())?);
+        }
+        if let Some(notes) = notes {
+            changes.insert("notes", bson::to_bson(notes).map_err(|e| e.to_string())?);
+        }
+        let result = self
+            .update_one(doc! { "_id": id, "hospital_id": hospital_id }, doc! { "$set": changes }, None)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn vitals_history(&self, hospital_id: &str, mobile: &str, limit: usize) -> Result<Vec<Appointment>, String> {
+        let filter = doc! { "hospital_id": hospital_id, "mobile": mobile, "vitals": { "$ne": null } };
+        let find_options =
+            FindOptions::builder().sort(doc! { "date_created": -1, "_id": -1 }).limit(limit as i64).build();
+        self.find(filter, find_options)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?
+            .try_collect()
+            .await
+            .map_err(|e| format!("Error parsing appointments: {}", e))
+    }
+}
+
+#[async_trait]
+impl UserRepository for Collection<User> {
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, String> {
+        self.find_one(doc! { "_id": id }, None)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        self.find_one(doc! { "username": username }, None)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        self.find_one(doc! { "email": email }, None)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, String> {
+        self.find(None, None)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?
+            .try_collect()
+            .await
+            .map_err(|e| format!("Error parsing users: {}", e))
+    }
+
+    async fn insert_user(&self, user: User) -> Result<(), String> {
+        self.insert_one(user, None)
+            .await
+            .map_err(|e| format!("Failed to create user: {}", e))?;
+        Ok(())
+    }
+
+    async fn replace_user(&self, user: &User) -> Result<(), String> {
+        self.replace_one(doc! { "_id": user.id }, user, None)
+            .await
+            .map_err(|e| format!("Failed to update profile: {}", e))?;
+        Ok(())
+    }
+
+    async fn set_otp(&self, email: &str, otp: Option<(&str, DateTime)>) -> Result<(), String> {
+        let update = match otp {
+            Some((otp, expiry)) => doc! { "$set": { "otp": otp, "otp_expiry": expiry } },
+            None => doc! { "$unset": { "otp": "", "otp_expiry": "" } },
+        };
+        self.update_one(doc! { "email": email }, update, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn set_password_hash(&self, email: &str, kind: PasswordKind, hash: &str) -> Result<(), String> {
+        self.update_one(doc! { "email": email }, doc! { "$set": { kind.field(): hash } }, None)
+            .await
+            .map_err(|e| format!("Failed to update password: {}", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InvoiceRepository for Collection<Invoice> {
+    async fn find_invoice(&self, hospital_id: &str, invoice_number: &str) -> Result<Option<Invoice>, String> {
+        self.find_one(doc! { "hospital_id": hospital_id, "invoice_number": invoice_number }, None)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn list_invoices(&self, hospital_id: &str, from: DateTime, to: DateTime) -> Result<Vec<Invoice>, String> {
+        let find_options = FindOptions::builder().sort(doc! { "date_created": -1 }).build();
+        self.find(
+            doc! { "hospital_id": hospital_id, "date_created": { "$gte": from, "$lt": to } },
+            find_options,
+        )
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing invoices: {}", e))
+    }
+
+    async fn insert_invoice(&self, invoice: &Invoice) -> Result<InvoiceInsert, String> {
+        let mut invoice = invoice.clone();
+        let id = *invoice.id.get_or_insert_with(ObjectId::new);
+        // A repeated `_id` or number (unique per hospital) fails with E11000
+        match self.insert_one(&invoice, None).await {
+            Ok(_) => Ok(InvoiceInsert::Stored),
+            Err(e) if matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == 11000) => {
+                let stored = self.find_one(doc! { "_id": id }, None).await.map_err(|e| e.to_string())?;
+                Ok(if stored.is_some() { InvoiceInsert::AlreadyStored } else { InvoiceInsert::NumberTaken })
+            }
+            Err(e) => Err(format!("Database insert error: {}", e)),
+        }
+    }
+}
+
+#[async_trait]
+impl DiscountRuleRepository for Collection<DiscountRule> {
+    async fn list_rules(&self, hospital_id: &str, active_only: bool) -> Result<Vec<DiscountRule>, String> {
+        let mut filter = doc! { "hospital_id": hospital_id };
+        if active_only {
+            filter.insert("active", true);
+        }
+        let find_options = FindOptions::builder().sort(doc! { "name": 1 }).build();
+        self.find(filter, find_options)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?
+            .try_collect()
+            .await
+            .map_err(|e| format!("Error parsing discount rules: {}", e))
+    }
+
+    async fn insert_rule(&self, rule: DiscountRule) -> Result<ObjectId, String> {
+        let result = self.insert_one(rule, None).await.map_err(|e| format!("Database insert error: {}", e))?;
+        result.inserted_id.as_object_id().ok_or_else(|| "Failed to read the new rule ID".to_string())
+    }
+
+    async fn replace_rule(&self, rule: &DiscountRule) -> Result<bool, String> {
+        let id = rule.id.ok_or_else(|| "Discount rule not found".to_string())?;
+        let update = doc! {
+            "$set": {
+                "name": &rule.name,
+                "customer_category": &rule.customer_category,
+                "min_bill_value": rule.min_bill_value.map(Money::paise),
+                "medicine_name": &rule.medicine_name,
+                "discount": bson::to_bson(&rule.discount).map_err(|e| e.to_string())?,
+                "active": rule.active,
+            }
+        };
+        let result = self
+            .update_one(doc! { "_id": id, "hospital_id": &rule.hospital_id }, update, None)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete_rule(&self, id: ObjectId, hospital_id: &str) -> Result<bool, String> {
+        let result = self
+            .delete_one(doc! { "_id": id, "hospital_id": hospital_id }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(result.deleted_count > 0)
+    }
+}
+
+/// In-memory stand-in for the database, for unit tests and as the local
+/// replica behind the offline store. It follows the same matching rules as
+/// the MongoDB queries above.
+pub mod memory {
+    use super::*;
+    use crate::queue::QueueToken;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    #[derive(Default, Serialize, Deserialize)]
+    pub struct MemoryStore {
+        pub medicines: Mutex<Vec<Medicine>>,
+        pub appointments: Mutex<Vec<Appointment>>,
+        pub users: Mutex<Vec<User>>,
+        pub invoices: Mutex<Vec<Invoice>>,
+        #[serde(default)]
+        pub discount_rules: Mutex<Vec<DiscountRule>>, // active rules, kept for offline billing
+        #[serde(skip)]
+        pub queue_tokens: Mutex<Vec<QueueToken>>,
+        #[serde(skip)]
+        pub queue_counters: Mutex<BTreeMap<String, u32>>, // last token, per doctor and day
+    }
+
+    fn matches(medicine: &Medicine, id: ObjectId, hospital_id: Option<&str>, batch_number: Option<&str>) -> bool {
+        medicine.id == Some(id)
+            && hospital_id.is_none_or(|h| medicine.user_id == h)
+            && batch_number.is_none_or(|b| medicine.batch_number == b)
+    }
+
+    #[async_trait]
+    impl MedicineRepository for MemoryStore {
+        async fn insert_medicine(&self, mut medicine: Medicine) -> Result<ObjectId, String> {
+            let id = ObjectId::new();
+            medicine.id = Some(id);
+            self.medicines.lock().unwrap().push(medicine);
+            Ok(id)
+        }
+
+        async fn find_medicine(&self, id: ObjectId, hospital_id: Option<&str>) -> Result<Option<Medicine>, String> {
+            let medicines = self.medicines.lock().unwrap();
+            Ok(medicines.iter().find(|m| matches(m, id, hospital_id, None)).cloned())
+        }
+
+        async fn find_medicines(&self, ids: &[ObjectId], hospital_id: &str) -> Result<Vec<Medicine>, String> {
+            let medicines = self.medicines.lock().unwrap();
+            Ok(medicines
+                .iter()
+                .filter(|m| m.user_id == hospital_id && m.id.is_some_and(|id| ids.contains(&id)))
+                .cloned()
+                .collect())
+        }
+
+        async fn list_medicines(&self, hospital_id: &str, query: &MedicineQuery) -> Result<Vec<Medicine>, String> {
+            let text = query.text.as_deref().map(str::to_lowercase);
+            let medicines = self.medicines.lock().unwrap();
+            let mut found: Vec<(u8, &Medicine)> = medicines
+                .iter()
+                .filter(|m| m.user_id == hospital_id)
+                .filter_map(|m| match &text {
+                    Some(text) => match_rank(&m.name, text).map(|rank| (rank, m)),
+                    None => Some((0, m)),
+                })
+                .collect();
+            let ranked = query.ranked();
+            found.sort_by(|(rank_a, a), (rank_b, b)| {
+                let by_rank = if ranked { rank_a.cmp(rank_b) } else { Ordering::Equal };
+                by_rank.then_with(|| query.compare(a, b))
+            });
+            Ok(query.page.apply(found.into_iter().map(|(_, m)| m.clone())))
+        }
+
+        async fn update_medicine(
+            &self,
+            id: ObjectId,
+            hospital_id: &str,
+            batch_number: Option<&str>,
+            changes: &MedicineChanges,
+        ) -> Result<UpdateOutcome, String> {
+            let mut medicines = self.medicines.lock().unwrap();
+            let Some(medicine) = medicines.iter_mut().find(|m| matches(m, id, Some(hospital_id), batch_number)) else {
+                return Ok(UpdateOutcome { matched: false, modified: false });
+            };
+            let before = medicine.clone();
+            changes.apply(medicine);
+            Ok(UpdateOutcome { matched: true, modified: *medicine != before })
+        }
+
+        async fn take_stock(&self, id: ObjectId, batch_number: &str, quantity: u32) -> Result<bool, String> {
+            let mut medicines = self.medicines.lock().unwrap();
+            match medicines
+                .iter_mut()
+                .find(|m| matches(m, id, None, Some(batch_number)) && m.quantity >= quantity)
+            {
+                Some(medicine) => {
+                    medicine.quantity -= quantity;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        async fn adjust_quantity(&self, id: ObjectId, hospital_id: &str, delta: i64) -> Result<Option<u32>, String> {
+            let mut medicines = self.medicines.lock().unwrap();
+            Ok(medicines.iter_mut().find(|m| matches(m, id, Some(hospital_id), None)).map(|medicine| {
+                medicine.quantity = (medicine.quantity as i64 + delta).clamp(0, u32::MAX as i64) as u32;
+                medicine.quantity
+            }))
+        }
+
+        async fn delete_medicine(&self, id: ObjectId, hospital_id: &str, batch_number: Option<&str>) -> Result<bool, String> {
+            let mut medicines = self.medicines.lock().unwrap();
+            let before = medicines.len();
+            medicines.retain(|m| !matches(m, id, Some(hospital_id), batch_number));
+            Ok(medicines.len() < before)
+        }
+    }
+
+    #[async_trait]
+    impl AppointmentRepository for MemoryStore {
+        async fn insert_appointment(&self, appointment: Appointment) -> Result<(), String> {
+            self.appointments.lock().unwrap().push(appointment);
+            Ok(())
+        }
+
+        async fn list_appointments(&self, hospital_id: &str, query: &AppointmentQuery) -> Result<Vec<Appointment>, String> {
+            let appointments = self.appointments.lock().unwrap();
+            let mut found: Vec<&Appointment> =
+                appointments.iter().filter(|a| a.hospital_id == hospital_id && query.matches(a)).collect();
+            found.sort_by(|a, b| query.compare(a, b));
+            Ok(query.page.apply(found.into_iter().cloned()))
+        }
+
+        async fn delete_stale_appointments(
+            &self,
+            hospital_id: &str,
+            cutoff: DateTime,
+            today: NaiveDate,
+        ) -> Result<u64, String> {
+            let mut appointments = self.appointments.lock().unwrap();
+            let before = appointments.len();
+            appointments.retain(|a| {
+                let follow_up_ahead = a.follow_up_date.is_some_and(|d| d >= today);
+                a.hospital_id != hospital_id || a.date_created >= cutoff || a.vitals.is_some() || a.notes.is_some() || follow_up_ahead
+            });
+            Ok((before - appointments.len()) as u64)
+        }
+
+        async fn update_clinical(
+            &self,
+            id: ObjectId,
+            hospital_id: &str,
+            vitals: Option<&Vitals>,
+            notes: Option<&ClinicalNotes>,
+        ) -> Result<bool, String> {
+            let mut appointments = self.appointments.lock().unwrap();
+            let Some(appointment) = appointments.iter_mut().find(|a| a.id == id && a.hospital_id == hospital_id) else {
+                return Ok(false);
+            };
+            if let Some(vitals) = vitals {
+                appointment.vitals = Some(vitals.clone());
+            }
+            if let Some(notes) = notes {
+                appointment.notes = Some(notes.clone());
+            }
+            Ok(true)
+        }
+
+        async fn vitals_history(&self, hospital_id: &str, mobile: &str, limit: usize) -> Result<Vec<Appointment>, String> {
+            let query = AppointmentQuery::default();
+            let appointments = self.appointments.lock().unwrap();
+            let mut found: Vec<&Appointment> = appointments
+                .iter()
+                .filter(|a| a.hospital_id == hospital_id && a.mobile == mobile && a.vitals.is_some())
+                .collect();
+            found.sort_by(|a, b| query.compare(a, b));
+            Ok(found.into_iter().take(limit).cloned().collect())
+        }
+    }
+
+    #[async_trait]
+    impl UserRepository for MemoryStore {
+        async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, String> {
+            Ok(self.users.lock().unwrap().iter().find(|u| u.id == Some(id)).cloned())
+        }
+
+        async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+            Ok(self.users.lock().unwrap().iter().find(|u| u.username == username).cloned())
+        }
+
+        async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, String> {
+            Ok(self.users.lock().unwrap().iter().find(|u| u.email == email).cloned())
+        }
+
+        async fn list_users(&self) -> Result<Vec<User>, String> {
+            Ok(self.users.lock().unwrap().clone())
+        }
+
+        async fn insert_user(&self, mut user: User) -> Result<(), String> {
+            user.id = Some(ObjectId::new());
+            self.users.lock().unwrap().push(user);
+            Ok(())
+        }
+
+        async fn replace_user(&self, user: &User) -> Result<(), String> {
+            let mut users = self.users.lock().unwrap();
+            if let Some(stored) = users.iter_mut().find(|u| u.id == user.id) {
+                *stored = user.clone();
+            }
+            Ok(())
+        }
+
+        async fn set_otp(&self, email: &str, otp: Option<(&str, DateTime)>) -> Result<(), String> {
+            let mut users = self.users.lock().unwrap();
+            if let Some(user) = users.iter_mut().find(|u| u.email == email) {
+                user.otp = otp.map(|(otp, _)| otp.to_string());
+                user.otp_expiry = otp.map(|(_, expiry)| expiry);
+            }
+            Ok(())
+        }
+
+        async fn set_password_hash(&self, email: &str, kind: PasswordKind, hash: &str) -> Result<(), String> {
+            let mut users = self.users.lock().unwrap();
+            if let Some(user) = users.iter_mut().find(|u| u.email == email) {
+                match kind {
+                    PasswordKind::Doctor => user.password_hash_doc = hash.to_string(),
+                    PasswordKind::Pharmacy => user.password_hash_pharma = hash.to_string(),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl InvoiceRepository for MemoryStore {
+        async fn find_invoice(&self, hospital_id: &str, invoice_number: &str) -> Result<Option<Invoice>, String> {
+            let invoices = self.invoices.lock().unwrap();
+            Ok(invoices
+                .iter()
+                .find(|i| i.hospital_id == hospital_id && i.invoice_number == invoice_number)
+                .cloned())
+        }
+
+        async fn list_invoices(&self, hospital_id: &str, from: DateTime, to: DateTime) -> Result<Vec<Invoice>, String> {
+            let mut found: Vec<Invoice> = self
+                .invoices
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|i| i.hospital_id == hospital_id)
+                .filter(|i| i.date_created >= from && i.date_created < to)
+                .cloned()
+                .collect();
+            found.sort_by_key(|i| std::cmp::Reverse(i.date_created));
+            Ok(found)
+        }
+
+        async fn insert_invoice(&self, invoice: &Invoice) -> Result<InvoiceInsert, String> {
+            let mut invoices = self.invoices.lock().unwrap();
+            if invoice.id.is_some() && invoices.iter().any(|i| i.id == invoice.id) {
+                return Ok(InvoiceInsert::AlreadyStored);
+            }
+            if invoices
+                .iter()
+                .any(|i| i.hospital_id == invoice.hospital_id && i.invoice_number == invoice.invoice_number)
+            {
+                return Ok(InvoiceInsert::NumberTaken);
+            }
+            let mut invoice = invoice.clone();
+            invoice.id.get_or_insert_with(ObjectId::new);
+            invoices.push(invoice);
+            Ok(InvoiceInsert::Stored)
+        }
+    }
+}
+
@@ -0,0 +1,591 @@
+// src-tauri/src/returns.rs
+use crate::billing::{
+    invoices_collection, next_document_number, run_transaction, Invoice, InvoiceLine, InvoiceStatus, TxnError,
+    DEFAULT_INVOICE_PREFIX,
+};
+use crate::commands::MedicineDetail;
+use crate::credit::{post_to_account, LedgerKind, Posting};
+use crate::dates;
+use crate::db::DbState;
+use crate::money::Money;
+use crate::payment::PaymentMode;
+use crate::tax::div_round;
+use crate::shift::{authorize_closed_shift, shift_is_closed, touch_open_shift};
+use crate::user::{find_hospital, users_collection};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, to_bson};
+use mongodb::options::FindOptions;
+use mongodb::{ClientSession, Collection, Database};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditNoteKind {
+    Void,
+    Return,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditNoteLine {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub quantity: u32,
+    pub unit_price: Money,
+    pub taxable_value: Money,
+    pub cgst: Money,
+    pub sgst: Money,
+    pub igst: Money,
+    pub total: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditNote {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub credit_note_number: String,
+    pub invoice_number: String,
+    pub kind: CreditNoteKind,
+    pub reason: String,
+    pub lines: Vec<CreditNoteLine>,
+    pub taxable_total: Money,
+    pub cgst_total: Money,
+    pub sgst_total: Money,
+    pub igst_total: Money,
+    pub round_off: Money,
+    pub total: Money,
+    #[serde(default)]
+    pub account_credit: Money, // part of `total` put back on a credit account; the rest is refunded in cash
+    #[serde(default)]
+    pub shift_id: Option<String>, // shift that paid out the refund
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditNoteLineResponse {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub quantity: u32,
+    pub unit_price: f64,
+    pub taxable_value: f64,
+    pub cgst: f64,
+    pub sgst: f64,
+    pub igst: f64,
+    pub total: f64,
+}
+
+/// A credit note in rupees, as the returns screens see it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditNoteResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub credit_note_number: String,
+    pub invoice_number: String,
+    pub kind: CreditNoteKind,
+    pub reason: String,
+    pub lines: Vec<CreditNoteLineResponse>,
+    pub taxable_total: f64,
+    pub cgst_total: f64,
+    pub sgst_total: f64,
+    pub igst_total: f64,
+    pub round_off: f64,
+    pub total: f64,
+    pub account_credit: f64,
+    pub shift_id: Option<String>,
+    pub date_created: DateTime<Utc>,
+}
+
+impl From<CreditNote> for CreditNoteResponse {
+    fn from(note: CreditNote) -> Self {
+        CreditNoteResponse {
+            id: note.id,
+            credit_note_number: note.credit_note_number,
+            invoice_number: note.invoice_number,
+            kind: note.kind,
+            reason: note.reason,
+            lines: note
+                .lines
+                .into_iter()
+                .map(|line| CreditNoteLineResponse {
+                    medicine_id: line.medicine_id,
+                    name: line.name,
+                    batch_number: line.batch_number,
+                    quantity: line.quantity,
+                    unit_price: line.unit_price.rupees(),
+                    taxable_value: line.taxable_value.rupees(),
+                    cgst: line.cgst.rupees(),
+                    sgst: line.sgst.rupees(),
+                    igst: line.igst.rupees(),
+                    total: line.total.rupees(),
+                })
+                .collect(),
+            taxable_total: note.taxable_total.rupees(),
+            cgst_total: note.cgst_total.rupees(),
+            sgst_total: note.sgst_total.rupees(),
+            igst_total: note.igst_total.rupees(),
+            round_off: note.round_off.rupees(),
+            total: note.total.rupees(),
+            account_credit: note.account_credit.rupees(),
+            shift_id: note.shift_id,
+            date_created: dates::to_utc(note.date_created),
+        }
+    }
+}
+
+pub(crate) fn credit_notes_collection(db: &Database) -> Collection<CreditNote> {
+    db.collection("credit_notes")
+}
+
+/// Share of a line amount for units `from..to` out of the line's quantity.
+/// Taken as the difference of two cumulative roundings, so a line returned
+/// in several goes adds back up to exactly what was billed.
+fn portion(amount: Money, from: u32, to: u32, quantity: u32) -> Money {
+    let (paise, quantity) = (amount.paise(), quantity as i64);
+    Money::from_paise(div_round(paise * to as i64, quantity) - div_round(paise * from as i64, quantity))
+}
+
+fn credit_line(line: &InvoiceLine, quantity: u32) -> CreditNoteLine {
+    let (from, to) = (line.returned_quantity, line.returned_quantity + quantity);
+    let taxable = portion(line.taxable_value, from, to, line.quantity);
+    let cgst = portion(line.cgst, from, to, line.quantity);
+    let sgst = portion(line.sgst, from, to, line.quantity);
+    let igst = portion(line.igst, from, to, line.quantity);
+
+    CreditNoteLine {
+        medicine_id: line.medicine_id.clone(),
+        name: line.name.clone(),
+        batch_number: line.batch_number.clone(),
+        quantity,
+        unit_price: line.unit_price,
+        taxable_value: taxable,
+        cgst,
+        sgst,
+        igst,
+        total: taxable + cgst + sgst + igst,
+    }
+}
+
+/// Marks every unit on the invoice as returned for a void on `today`,
+/// giving the lines of the credit note.
+fn take_back_all(invoice: &mut Invoice, today: NaiveDate) -> Result<Vec<CreditNoteLine>, String> {
+    if invoice.status != InvoiceStatus::Issued {
+        return Err("Only invoices without voids or returns can be voided.".to_string());
+    }
+    let issued_on = dates::to_utc(invoice.date_created).with_timezone(&Local).date_naive();
+    if issued_on != today {
+        return Err(
+            "Invoices can only be voided on the day they are issued; create a sales return instead.".to_string(),
+        );
+    }
+
+    let lines = invoice.lines.iter().map(|l| credit_line(l, l.quantity)).collect();
+    for line in &mut invoice.lines {
+        line.returned_quantity = line.quantity;
+    }
+    invoice.status = InvoiceStatus::Voided;
+    Ok(lines)
+}
+
+/// Marks the returned units of each (medicine ID, quantity) item on the
+/// invoice, giving the lines of the credit note. Nothing is marked if any
+/// item cannot be returned.
+fn take_back(invoice: &mut Invoice, items: &[(String, u32)]) -> Result<Vec<CreditNoteLine>, String> {
+    if invoice.status == InvoiceStatus::Voided {
+        return Err("This invoice has been voided.".to_string());
+    }
+
+    let mut returned = invoice.lines.clone();
+    let mut lines = Vec::with_capacity(items.len());
+    for (medicine_id, quantity) in items {
+        let line = returned
+            .iter_mut()
+            .find(|l| &l.medicine_id == medicine_id)
+            .ok_or_else(|| "Item is not on this invoice.".to_string())?;
+        let returnable = line.quantity - line.returned_quantity;
+        if returnable == 0 {
+            return Err(format!("{} has already been fully returned.", line.name));
+        }
+        if *quantity > returnable {
+            return Err(format!("Cannot return {} of {}; only {} left to return.", quantity, line.name, returnable));
+        }
+        lines.push(credit_line(line, *quantity));
+        line.returned_quantity += quantity;
+    }
+
+    invoice.status = if returned.iter().all(|l| l.returned_quantity == l.quantity) {
+        InvoiceStatus::Returned
+    } else {
+        InvoiceStatus::PartiallyReturned
+    };
+    invoice.lines = returned;
+    Ok(lines)
+}
+
+/// Returns the credit note prefix, first checking that the invoice may
+/// still be changed: once the shift it was billed in is closed, that takes
+/// an admin override.
+async fn prepare_credit_note(
+    db: &Database,
+    invoice_number: &str,
+    admin_password: Option<&str>,
+    hospital_id: &str,
+) -> Result<String, String> {
+    let hospital = find_hospital(&users_collection(db), hospital_id).await?;
+    let invoice = invoices_collection(db)
+        .find_one(doc! { "hospital_id": hospital_id, "invoice_number": invoice_number }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Invoice not found".to_string())?;
+
+    if let Some(shift_id) = &invoice.shift_id {
+        authorize_closed_shift(shift_is_closed(db, shift_id, hospital_id).await?, &hospital, admin_password)?;
+    }
+
+    Ok(format!(
+        "{}-CN",
+        hospital.invoice_prefix.unwrap_or_else(|| DEFAULT_INVOICE_PREFIX.to_string())
+    ))
+}
+
+async fn load_invoice(
+    db: &Database,
+    session: &mut ClientSession,
+    invoice_number: &str,
+    hospital_id: &str,
+) -> Result<Invoice, TxnError> {
+    invoices_collection(db)
+        .find_one_with_session(
+            doc! { "hospital_id": hospital_id, "invoice_number": invoice_number },
+            None,
+            session,
+        )
+        .await?
+        .ok_or_else(|| TxnError::Rejected("Invoice not found".to_string()))
+}
+
+/// Puts returned units back on the batch they were sold from.
+async fn restore_stock(
+    db: &Database,
+    session: &mut ClientSession,
+    hospital_id: &str,
+    lines: &[CreditNoteLine],
+) -> Result<(), TxnError> {
+    let medicines = db.collection::<mongodb::bson::Document>("medicines");
+    for line in lines {
+        let object_id = ObjectId::parse_str(&line.medicine_id)
+            .map_err(|_| TxnError::Rejected("Invalid medicine ID".to_string()))?;
+        let result = medicines
+            .update_one_with_session(
+                doc! { "_id": object_id, "user_id": hospital_id },
+                doc! { "$inc": { "quantity": line.quantity as i64 } },
+                None,
+                session,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(TxnError::Rejected(format!(
+                "Batch {} of {} no longer exists; add it back before returning.",
+                line.batch_number, line.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Saves the credit note and applies its stock and invoice changes.
+async fn issue_credit_note(
+    db: &Database,
+    session: &mut ClientSession,
+    invoice: &Invoice,
+    mut note: CreditNote,
+    prefix: &str,
+) -> Result<CreditNote, TxnError> {
+    if let Some(shift_id) = &note.shift_id {
+        touch_open_shift(db, session, shift_id, &invoice.hospital_id).await?;
+    }
+    restore_stock(db, session, &invoice.hospital_id, &note.lines).await?;
+
+    let lines = to_bson(&invoice.lines).map_err(|e| TxnError::Rejected(e.to_string()))?;
+    let status = to_bson(&invoice.status).map_err(|e| TxnError::Rejected(e.to_string()))?;
+    invoices_collection(db)
+        .update_one_with_session(
+            doc! { "_id": invoice.id, "hospital_id": &invoice.hospital_id },
+            doc! { "$set": { "lines": lines, "status": status } },
+            None,
+            session,
+        )
+        .await?;
+
+    note.credit_note_number = next_document_number(
+        db,
+        session,
+        &invoice.hospital_id,
+        "credit_note",
+        prefix,
+        Local::now().date_naive(),
+    )
+    .await?;
+
+    // What was sold on credit comes off the customer's account rather than
+    // being refunded in cash
+    if let Some(customer_id) = &invoice.credit_customer_id {
+        let on_credit: Money = invoice
+            .payments
+            .iter()
+            .filter(|p| p.mode == PaymentMode::Credit)
+            .map(|p| p.amount)
+            .sum();
+        let amount = on_credit.min(note.total).paise();
+        if amount > 0 {
+            let posting = Posting {
+                customer_id,
+                kind: LedgerKind::CreditNote,
+                reference: &note.credit_note_number,
+                amount: -amount,
+                mode: None,
+                payment_reference: None,
+                shift_id: note.shift_id.clone(),
+            };
+            post_to_account(db, session, &invoice.hospital_id, posting).await?;
+            note.account_credit = Money::from_paise(amount);
+        }
+    }
+
+    let result = credit_notes_collection(db)
+        .insert_one_with_session(&note, None, session)
+        .await?;
+    note.id = result.inserted_id.as_object_id();
+    Ok(note)
+}
+
+fn new_credit_note(invoice: &Invoice, kind: CreditNoteKind, reason: &str, lines: Vec<CreditNoteLine>) -> CreditNote {
+    let sum = |f: fn(&CreditNoteLine) -> Money| lines.iter().map(f).sum::<Money>();
+    let (taxable, cgst, sgst, igst) = (sum(|l| l.taxable_value), sum(|l| l.cgst), sum(|l| l.sgst), sum(|l| l.igst));
+    let gross = taxable + cgst + sgst + igst;
+
+    // A void hands back exactly what was charged, round-off included
+    let total = match kind {
+        CreditNoteKind::Void => invoice.grand_total,
+        CreditNoteKind::Return => Money::from_paise(div_round(gross.paise(), 100) * 100),
+    };
+
+    CreditNote {
+        id: None,
+        hospital_id: invoice.hospital_id.clone(),
+        credit_note_number: String::new(),
+        invoice_number: invoice.invoice_number.clone(),
+        kind,
+        reason: reason.to_string(),
+        lines,
+        taxable_total: taxable,
+        cgst_total: cgst,
+        sgst_total: sgst,
+        igst_total: igst,
+        round_off: total - gross,
+        total,
+        account_credit: Money::default(),
+        shift_id: None,
+        date_created: bson::DateTime::now(),
+    }
+}
+
+/// Cancels an invoice on the day it was issued, putting every item back in
+/// stock. Invoices from earlier days, or with returns against them, have to
+/// go through `create_sales_return` instead.
+#[command]
+pub async fn void_invoice(
+    invoice_number: String,
+    reason: String,
+    shift_id: Option<String>,
+    admin_password: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<CreditNoteResponse, String> {
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err("A reason is required to void an invoice.".to_string());
+    }
+
+    let db = db_state.mongo("returns")?.db.clone();
+    let prefix = prepare_credit_note(&db, &invoice_number, admin_password.as_deref(), &hospital_id).await?;
+
+    run_transaction(&db_state.mongo("returns")?.client, |session| {
+        let db = db.clone();
+        let (invoice_number, reason, hospital_id, prefix, shift_id) =
+            (invoice_number.clone(), reason.clone(), hospital_id.clone(), prefix.clone(), shift_id.clone());
+        Box::pin(async move {
+            let mut invoice = load_invoice(&db, session, &invoice_number, &hospital_id).await?;
+            let lines = take_back_all(&mut invoice, Local::now().date_naive()).map_err(TxnError::Rejected)?;
+            let mut note = new_credit_note(&invoice, CreditNoteKind::Void, &reason, lines);
+            note.shift_id = shift_id;
+            issue_credit_note(&db, session, &invoice, note, &prefix).await
+        })
+    })
+    .await
+    .map(CreditNoteResponse::from)
+}
+
+/// Takes back some or all of the items on an invoice. Each item names the
+/// billed batch (medicine ID) and how many units come back.
+#[command]
+pub async fn create_sales_return(
+    invoice_number: String,
+    items: Vec<MedicineDetail>,
+    reason: String,
+    shift_id: Option<String>,
+    admin_password: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<CreditNoteResponse, String> {
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err("A reason is required for a sales return.".to_string());
+    }
+    if items.is_empty() || items.iter().any(|i| i.quantity == 0) {
+        return Err("Select at least one item and quantity to return.".to_string());
+    }
+
+    let db = db_state.mongo("returns")?.db.clone();
+    let prefix = prepare_credit_note(&db, &invoice_number, admin_password.as_deref(), &hospital_id).await?;
+
+    run_transaction(&db_state.mongo("returns")?.client, |session| {
+        let db = db.clone();
+        let (invoice_number, reason, hospital_id, prefix, shift_id) =
+            (invoice_number.clone(), reason.clone(), hospital_id.clone(), prefix.clone(), shift_id.clone());
+        let items: Vec<(String, u32)> = items.iter().map(|i| (i.id.clone(), i.quantity)).collect();
+        Box::pin(async move {
+            let mut invoice = load_invoice(&db, session, &invoice_number, &hospital_id).await?;
+            let lines = take_back(&mut invoice, &items).map_err(TxnError::Rejected)?;
+            let mut note = new_credit_note(&invoice, CreditNoteKind::Return, &reason, lines);
+            note.shift_id = shift_id;
+            issue_credit_note(&db, session, &invoice, note, &prefix).await
+        })
+    })
+    .await
+    .map(CreditNoteResponse::from)
+}
+
+#[command]
+pub async fn get_credit_notes(
+    invoice_number: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<CreditNoteResponse>, String> {
+    let mut filter = doc! { "hospital_id": hospital_id };
+    if let Some(invoice_number) = invoice_number {
+        filter.insert("invoice_number", invoice_number);
+    }
+
+    let db = db_state.mongo("returns")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "date_created": -1 }).build();
+    let notes: Vec<CreditNote> = credit_notes_collection(&db)
+        .find(filter, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing credit notes: {}", e))?;
+
+    Ok(notes.into_iter().map(CreditNoteResponse::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+    use chrono::{Duration, TimeZone};
+
+    fn rupees(amount: f64) -> Money {
+        Money::from_rupees(amount)
+    }
+
+    // 3 strips of each at 35.00 with 12% GST included: 93.75 taxable, 5.63 + 5.62 tax
+    fn invoice(issued_at: DateTime<Local>) -> Invoice {
+        TestInvoice::new("INV/1")
+            .line("m1", "Azithromycin", "3004", 12.0, 3, 35.0)
+            .line("m2", "Cetirizine", "3004", 12.0, 3, 35.0)
+            .issued_at(issued_at.with_timezone(&Utc))
+            .build()
+    }
+
+    fn noon(date: NaiveDate) -> DateTime<Local> {
+        Local.from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap()).unwrap()
+    }
+
+    fn item(medicine_id: &str, quantity: u32) -> (String, u32) {
+        (medicine_id.to_string(), quantity)
+    }
+
+    #[test]
+    fn returning_more_than_is_left_is_refused_and_changes_nothing() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let mut invoice = invoice(noon(today));
+
+        let error = take_back(&mut invoice, &[item("m1", 4)]).unwrap_err();
+        assert_eq!(error, "Cannot return 4 of Azithromycin; only 3 left to return.");
+
+        take_back(&mut invoice, &[item("m1", 2)]).unwrap();
+        // The first item is fine, the second is one too many
+        let error = take_back(&mut invoice, &[item("m2", 1), item("m1", 2)]).unwrap_err();
+        assert_eq!(error, "Cannot return 2 of Azithromycin; only 1 left to return.");
+        let returned: Vec<u32> = invoice.lines.iter().map(|l| l.returned_quantity).collect();
+        assert_eq!(returned, [2, 0]);
+        assert_eq!(invoice.status, InvoiceStatus::PartiallyReturned);
+
+        take_back(&mut invoice, &[item("m1", 1)]).unwrap();
+        let error = take_back(&mut invoice, &[item("m1", 1)]).unwrap_err();
+        assert_eq!(error, "Azithromycin has already been fully returned.");
+        assert_eq!(take_back(&mut invoice, &[item("m9", 1)]).unwrap_err(), "Item is not on this invoice.");
+    }
+
+    #[test]
+    fn partial_returns_add_up_to_exactly_what_was_billed() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let mut invoice = invoice(noon(today));
+        let billed = invoice.lines[0].clone();
+
+        let lines: Vec<CreditNoteLine> = (0..3)
+            .flat_map(|_| take_back(&mut invoice, &[item("m1", 1)]).unwrap())
+            .collect();
+        // 93.75 does not split evenly in three; the paise land on different returns
+        let taxable: Vec<Money> = lines.iter().map(|l| l.taxable_value).collect();
+        assert_eq!(taxable, [rupees(31.25), rupees(31.25), rupees(31.25)]);
+        let cgst: Vec<Money> = lines.iter().map(|l| l.cgst).collect();
+        assert_eq!(cgst, [rupees(1.88), rupees(1.87), rupees(1.88)]);
+
+        let sum = |f: fn(&CreditNoteLine) -> Money| lines.iter().map(f).sum::<Money>();
+        assert_eq!(sum(|l| l.taxable_value), billed.taxable_value);
+        assert_eq!(sum(|l| l.cgst), billed.cgst);
+        assert_eq!(sum(|l| l.sgst), billed.sgst);
+        assert_eq!(sum(|l| l.total), billed.total);
+        assert_eq!(invoice.status, InvoiceStatus::PartiallyReturned);
+
+        take_back(&mut invoice, &[item("m2", 3)]).unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Returned);
+    }
+
+    #[test]
+    fn voids_are_only_allowed_on_the_day_of_issue() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let mut yesterdays = invoice(noon(today) - Duration::days(1));
+        let error = take_back_all(&mut yesterdays, today).unwrap_err();
+        assert_eq!(error, "Invoices can only be voided on the day they are issued; create a sales return instead.");
+        assert_eq!(yesterdays.status, InvoiceStatus::Issued);
+        // A return still goes through
+        take_back(&mut yesterdays, &[item("m1", 1)]).unwrap();
+
+        let mut todays = invoice(noon(today));
+        let lines = take_back_all(&mut todays, today).unwrap();
+        assert_eq!(todays.status, InvoiceStatus::Voided);
+        let note = new_credit_note(&todays, CreditNoteKind::Void, "Billed twice", lines);
+        assert_eq!((note.total, note.round_off), (rupees(210.0), Money::default()));
+        let error = take_back_all(&mut todays, today).unwrap_err();
+        assert_eq!(error, "Only invoices without voids or returns can be voided.");
+        assert_eq!(take_back(&mut todays, &[item("m1", 1)]).unwrap_err(), "This invoice has been voided.");
+    }
+}
@@ -0,0 +1,531 @@
+// src-tauri/src/schedule.rs
+use crate::billing::{run_transaction, TxnError};
+use crate::db::DbState;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
+use mongodb::options::FindOptions;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppointmentStatus {
+    Booked,
+    CheckedIn,
+    InConsultation,
+    Completed,
+    Cancelled,
+    NoShow,
+}
+
+impl AppointmentStatus {
+    /// Cancelled and no-show appointments free their slot for someone else.
+    pub fn blocks_slot(self) -> bool {
+        !matches!(self, AppointmentStatus::Cancelled | AppointmentStatus::NoShow)
+    }
+
+    pub fn can_transition_to(self, next: AppointmentStatus) -> bool {
+        use AppointmentStatus::*;
+        matches!(
+            (self, next),
+            (Booked, CheckedIn)
+                | (Booked, Cancelled)
+                | (Booked, NoShow)
+                | (CheckedIn, InConsultation)
+                | (CheckedIn, Cancelled)
+                | (InConsultation, Completed)
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AppointmentStatus::Booked => "booked",
+            AppointmentStatus::CheckedIn => "checked_in",
+            AppointmentStatus::InConsultation => "in_consultation",
+            AppointmentStatus::Completed => "completed",
+            AppointmentStatus::Cancelled => "cancelled",
+            AppointmentStatus::NoShow => "no_show",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DoctorSchedule {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub doctor_name: String,
+    pub working_days: Vec<u32>, // 1 = Monday ... 7 = Sunday
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub slot_minutes: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledAppointment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub doctor_id: String,
+    pub doctor_name: String,
+    pub patient_name: String,
+    pub mobile: String,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub status: AppointmentStatus,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub date_created: bson::DateTime,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduledAppointmentResponse {
+    pub id: String,
+    pub doctor_id: String,
+    pub doctor_name: String,
+    pub patient_name: String,
+    pub mobile: String,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub status: AppointmentStatus,
+}
+
+impl From<ScheduledAppointment> for ScheduledAppointmentResponse {
+    fn from(appointment: ScheduledAppointment) -> Self {
+        ScheduledAppointmentResponse {
+            id: appointment.id.map(|id| id.to_hex()).unwrap_or_default(),
+            doctor_id: appointment.doctor_id,
+            doctor_name: appointment.doctor_name,
+            patient_name: appointment.patient_name,
+            mobile: appointment.mobile,
+            start_time: appointment.start_time,
+            end_time: appointment.end_time,
+            status: appointment.status,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Slot {
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub available: bool,
+}
+
+/// Accepts the `datetime-local` input format with or without seconds and
+/// drops anything below a minute so stored times compare cleanly.
+fn parse_slot_time(value: &str) -> Result<NaiveDateTime, String> {
+    let parsed = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| format!("Invalid date/time: {}", value))?;
+    Ok(parsed.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(parsed))
+}
+
+fn parse_time_of_day(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| format!("Invalid time: {}", value))
+}
+
+/// Same layout serde uses for `NaiveDateTime`, so range filters line up
+/// with the stored strings.
+fn time_key(time: &NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+fn schedules_collection(db: &mongodb::Database) -> Collection<DoctorSchedule> {
+    db.collection("doctor_schedules")
+}
+
+fn appointments_collection(db: &mongodb::Database) -> Collection<ScheduledAppointment> {
+    db.collection("scheduled_appointments")
+}
+
+pub(crate) async fn find_doctor(
+    collection: &Collection<DoctorSchedule>,
+    doctor_id: &str,
+    hospital_id: &str,
+) -> Result<DoctorSchedule, String> {
+    let object_id = ObjectId::parse_str(doctor_id).map_err(|_| "Invalid doctor ID".to_string())?;
+    collection
+        .find_one(doc! { "_id": object_id, "hospital_id": hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Doctor not found".to_string())
+}
+
+/// Slots a doctor works on a given day, in order. Empty on days off.
+pub fn slots_for_day(schedule: &DoctorSchedule, date: NaiveDate) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut slots = Vec::new();
+    if schedule.slot_minutes == 0 || !schedule.working_days.contains(&date.weekday().number_from_monday()) {
+        return slots;
+    }
+
+    let step = Duration::minutes(schedule.slot_minutes as i64);
+    let day_end = date.and_time(schedule.end_time);
+    let mut start = date.and_time(schedule.start_time);
+    while start + step <= day_end {
+        slots.push((start, start + step));
+        start += step;
+    }
+    slots
+}
+
+/// A doctor's appointments that overlap `[start, end)`, whatever their status.
+fn overlap_filter(doctor_id: &str, start: &NaiveDateTime, end: &NaiveDateTime) -> Document {
+    doc! {
+        "doctor_id": doctor_id,
+        "start_time": { "$lt": time_key(end) },
+        "end_time": { "$gt": time_key(start) },
+    }
+}
+
+/// Whether an appointment still holds any of `[start, end)`.
+fn holds(appointment: &ScheduledAppointment, start: &NaiveDateTime, end: &NaiveDateTime) -> bool {
+    appointment.status.blocks_slot() && appointment.start_time < *end && appointment.end_time > *start
+}
+
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_doctor_schedule(
+    doctor_id: Option<String>,
+    doctor_name: String,
+    working_days: Vec<u32>,
+    start_time: String,
+    end_time: String,
+    slot_minutes: u32,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    if doctor_name.trim().is_empty() {
+        return Err("Doctor name is required.".to_string());
+    }
+    if working_days.is_empty() || working_days.iter().any(|d| !(1..=7).contains(d)) {
+        return Err("Working days must be between 1 (Monday) and 7 (Sunday).".to_string());
+    }
+    if slot_minutes == 0 {
+        return Err("Slot length must be at least one minute.".to_string());
+    }
+    let start_time = parse_time_of_day(&start_time)?;
+    let end_time = parse_time_of_day(&end_time)?;
+    if start_time >= end_time {
+        return Err("Working hours must end after they start.".to_string());
+    }
+
+    let db = db_state.mongo("appointment scheduling")?.db.clone();
+    let collection = schedules_collection(&db);
+
+    let schedule = DoctorSchedule {
+        id: None,
+        hospital_id: hospital_id.clone(),
+        doctor_name: doctor_name.trim().to_string(),
+        working_days,
+        start_time,
+        end_time,
+        slot_minutes,
+    };
+
+    match doctor_id {
+        Some(doctor_id) => {
+            let existing = find_doctor(&collection, &doctor_id, &hospital_id).await?;
+            let schedule = DoctorSchedule { id: existing.id, ..schedule };
+            collection
+                .replace_one(doc! { "_id": existing.id, "hospital_id": &hospital_id }, schedule, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(doctor_id)
+        }
+        None => {
+            let result = collection.insert_one(schedule, None).await.map_err(|e| e.to_string())?;
+            Ok(result.inserted_id.as_object_id().map(|id| id.to_hex()).unwrap_or_default())
+        }
+    }
+}
+
+#[command]
+pub async fn get_doctor_schedules(
+    hospital_id: &str,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<DoctorSchedule>, String> {
+    let db = db_state.mongo("appointment scheduling")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "doctor_name": 1 }).build();
+
+    schedules_collection(&db)
+        .find(doc! { "hospital_id": hospital_id }, find_options)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn book_appointment(
+    doctor_id: String,
+    patient_name: String,
+    mobile: String,
+    start_time: String,
+    end_time: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    if patient_name.trim().is_empty() || mobile.trim().is_empty() {
+        return Err("Patient name and mobile number are required.".to_string());
+    }
+
+    let mongo = db_state.mongo("appointment scheduling")?;
+    let db = &mongo.db;
+    let doctor = find_doctor(&schedules_collection(db), &doctor_id, &hospital_id).await?;
+
+    // Default to a single slot when the desk doesn't pick an end time
+    let start = parse_slot_time(&start_time)?;
+    let end = match end_time {
+        Some(end_time) => parse_slot_time(&end_time)?,
+        None => start + Duration::minutes(doctor.slot_minutes as i64),
+    };
+    if end <= start {
+        return Err("Appointment must end after it starts.".to_string());
+    }
+    if start.date() != end.date() {
+        return Err("Appointment must start and end on the same day.".to_string());
+    }
+
+    // Must fall inside the doctor's working hours on a working day
+    let day = start.date();
+    if !doctor.working_days.contains(&day.weekday().number_from_monday())
+        || start.time() < doctor.start_time
+        || end.time() > doctor.end_time
+    {
+        return Err(format!("{} is not available at that time.", doctor.doctor_name));
+    }
+
+    let appointment = ScheduledAppointment {
+        id: None,
+        hospital_id,
+        doctor_id,
+        doctor_name: doctor.doctor_name,
+        patient_name: patient_name.trim().to_string(),
+        mobile: mobile.trim().to_string(),
+        start_time: start,
+        end_time: end,
+        status: AppointmentStatus::Booked,
+        date_created: bson::DateTime::now(),
+    };
+
+    // Every booking writes to the doctor's schedule, so two desks booking the
+    // same doctor at once conflict and the retry sees the other appointment
+    run_transaction(&mongo.client, |session| {
+        let (db, appointment) = (db.clone(), appointment.clone());
+        Box::pin(async move {
+            schedules_collection(&db)
+                .update_one_with_session(
+                    doc! { "_id": doctor.id, "hospital_id": &appointment.hospital_id },
+                    doc! { "$set": { "last_booked_at": &appointment.date_created } },
+                    None,
+                    session,
+                )
+                .await?;
+
+            let collection = appointments_collection(&db);
+            let filter = overlap_filter(&appointment.doctor_id, &start, &end);
+            let mut cursor = collection.find_with_session(filter, None, session).await?;
+            let overlapping: Vec<ScheduledAppointment> = cursor.stream(session).try_collect().await?;
+            if let Some(conflict) = overlapping.iter().find(|a| holds(a, &start, &end)) {
+                return Err(TxnError::Rejected(format!(
+                    "{} is already booked from {} to {} for {}.",
+                    appointment.doctor_name,
+                    conflict.start_time.format("%H:%M"),
+                    conflict.end_time.format("%H:%M"),
+                    conflict.patient_name
+                )));
+            }
+
+            let result = collection.insert_one_with_session(appointment, None, session).await?;
+            Ok(result.inserted_id.as_object_id().map(|id| id.to_hex()).unwrap_or_default())
+        })
+    })
+    .await
+}
+
+#[command]
+pub async fn update_appointment_status(
+    appointment_id: String,
+    status: AppointmentStatus,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let db = db_state.mongo("appointment scheduling")?.db.clone();
+    let collection = appointments_collection(&db);
+
+    let object_id = ObjectId::parse_str(&appointment_id).map_err(|_| "Invalid appointment ID".to_string())?;
+    let filter = doc! { "_id": object_id, "hospital_id": &hospital_id };
+    let appointment = collection
+        .find_one(filter.clone(), None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Appointment not found".to_string())?;
+
+    if !appointment.status.can_transition_to(status) {
+        return Err(format!(
+            "Cannot change an appointment from {} to {}.",
+            appointment.status.as_str(),
+            status.as_str()
+        ));
+    }
+
+    // Only update if nobody else moved it in the meantime
+    let mut filter = filter;
+    filter.insert("status", appointment.status.as_str());
+    let result = collection
+        .update_one(filter, doc! { "$set": { "status": status.as_str() } }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.modified_count == 0 {
+        return Err("Appointment was updated elsewhere, please refresh.".to_string());
+    }
+
+    Ok("Appointment status updated successfully.".to_string())
+}
+
+#[command]
+pub async fn get_day_schedule(
+    date: String,
+    doctor_id: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<ScheduledAppointmentResponse>, String> {
+    let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))?;
+    let day_start = day.and_time(NaiveTime::MIN);
+    let day_end = day_start + Duration::days(1);
+
+    let mut filter = doc! {
+        "hospital_id": hospital_id,
+        "start_time": { "$gte": time_key(&day_start), "$lt": time_key(&day_end) },
+    };
+    if let Some(doctor_id) = doctor_id {
+        filter.insert("doctor_id", doctor_id);
+    }
+
+    let db = db_state.mongo("appointment scheduling")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "start_time": 1, "doctor_name": 1 }).build();
+    let appointments: Vec<ScheduledAppointment> = appointments_collection(&db)
+        .find(filter, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing appointments: {}", e))?;
+
+    Ok(appointments.into_iter().map(ScheduledAppointmentResponse::from).collect())
+}
+
+#[command]
+pub async fn get_available_slots(
+    doctor_id: String,
+    date: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<Slot>, String> {
+    let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))?;
+
+    let db = db_state.mongo("appointment scheduling")?.db.clone();
+    let doctor = find_doctor(&schedules_collection(&db), &doctor_id, &hospital_id).await?;
+    let slots = slots_for_day(&doctor, day);
+    let (Some(first), Some(last)) = (slots.first(), slots.last()) else {
+        return Ok(Vec::new());
+    };
+
+    let booked: Vec<ScheduledAppointment> = appointments_collection(&db)
+        .find(overlap_filter(&doctor_id, &first.0, &last.1), None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(slots
+        .into_iter()
+        .map(|(start, end)| Slot {
+            start_time: start,
+            end_time: end,
+            available: !booked.iter().any(|a| holds(a, &start, &end)),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveDateTime {
+        parse_slot_time(&format!("2026-10-19T{}", time)).unwrap() // a Monday
+    }
+
+    fn schedule(working_days: Vec<u32>, start: &str, end: &str, slot_minutes: u32) -> DoctorSchedule {
+        DoctorSchedule {
+            id: None,
+            hospital_id: "h1".to_string(),
+            doctor_name: "Dr Rao".to_string(),
+            working_days,
+            start_time: parse_time_of_day(start).unwrap(),
+            end_time: parse_time_of_day(end).unwrap(),
+            slot_minutes,
+        }
+    }
+
+    fn booked(start: &str, end: &str, status: AppointmentStatus) -> ScheduledAppointment {
+        ScheduledAppointment {
+            id: None,
+            hospital_id: "h1".to_string(),
+            doctor_id: "d1".to_string(),
+            doctor_name: "Dr Rao".to_string(),
+            patient_name: "Meera Iyer".to_string(),
+            mobile: "9123456780".to_string(),
+            start_time: at(start),
+            end_time: at(end),
+            status,
+            date_created: bson::DateTime::parse_rfc3339_str("2026-10-18T10:00:00Z").unwrap(),
+        }
+    }
+
+    #[test]
+    fn slots_fill_working_hours_on_working_days_only() {
+        let monday = at("00:00").date();
+        let slots = slots_for_day(&schedule(vec![1, 3], "09:00", "10:40", 15), monday);
+        assert_eq!(slots.len(), 6); // the last 10 minutes are too short for a slot
+        assert_eq!(slots[0], (at("09:00"), at("09:15")));
+        assert_eq!(slots[5], (at("10:15"), at("10:30")));
+
+        assert!(slots_for_day(&schedule(vec![2], "09:00", "17:00", 15), monday).is_empty());
+        assert!(slots_for_day(&schedule(vec![1], "09:00", "17:00", 0), monday).is_empty());
+    }
+
+    #[test]
+    fn status_moves_forward_only() {
+        use AppointmentStatus::*;
+        assert!(Booked.can_transition_to(CheckedIn));
+        assert!(CheckedIn.can_transition_to(InConsultation));
+        assert!(InConsultation.can_transition_to(Completed));
+        assert!(Booked.can_transition_to(NoShow));
+        assert!(!Booked.can_transition_to(Completed));
+        assert!(!InConsultation.can_transition_to(Cancelled));
+        assert!(!Completed.can_transition_to(Booked));
+        assert!(!Cancelled.can_transition_to(Booked));
+        assert!(!NoShow.can_transition_to(CheckedIn));
+    }
+
+    #[test]
+    fn overlapping_appointments_hold_the_slot_until_cancelled() {
+        let visit = booked("10:00", "10:30", AppointmentStatus::Booked);
+        assert!(holds(&visit, &at("10:15"), &at("10:45")));
+        assert!(holds(&visit, &at("09:45"), &at("10:15")));
+        assert!(holds(&visit, &at("09:00"), &at("11:00")));
+        assert!(!holds(&visit, &at("10:30"), &at("11:00"))); // back to back is fine
+        assert!(!holds(&visit, &at("09:30"), &at("10:00")));
+
+        assert!(holds(&booked("10:00", "10:30", AppointmentStatus::CheckedIn), &at("10:00"), &at("10:15")));
+        assert!(!holds(&booked("10:00", "10:30", AppointmentStatus::Cancelled), &at("10:00"), &at("10:30")));
+        assert!(!holds(&booked("10:00", "10:30", AppointmentStatus::NoShow), &at("10:00"), &at("10:30")));
+    }
+}
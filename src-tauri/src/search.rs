@@ -0,0 +1,555 @@
+// src-tauri/src/search.rs
+//! Typo-tolerant medicine search. Each hospital's catalogue (names, brand
+//! names, generic names, compositions and barcodes) is kept in memory, one
+//! entry per product, and the search text is matched word by word: exactly,
+//! as a prefix or inside a word, then within a couple of typing mistakes,
+//! then by sound. The batches of the matching products are read back from
+//! the store, so quantities and expiries are always current; only the
+//! catalogue itself can be up to a minute old when stock is added on
+//! another counter.
+use crate::commands::{Medicine, MedicineResponse};
+use crate::db::DbState;
+use crate::error::AppError;
+use crate::repository::{MedicineQuery, MedicineRepository};
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{command, State};
+
+const INDEX_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_PRODUCT_LIMIT: u32 = 20;
+const MAX_PRODUCT_LIMIT: u32 = 100;
+
+/// Which of a product's names the search text matched best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+    Barcode,
+    Name,
+    Brand,
+    Generic,
+    Composition,
+}
+
+impl MatchField {
+    /// A match on the name the stock is filed under counts for most.
+    fn weight(self) -> f64 {
+        match self {
+            MatchField::Barcode | MatchField::Name | MatchField::Brand => 1.0,
+            MatchField::Generic => 0.9,
+            MatchField::Composition => 0.75,
+        }
+    }
+}
+
+/// The lowercase words of `text`, split on anything but letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn trigrams(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// A rough sound-alike key: vowels after the first letter are dropped,
+/// letters that sound the same are merged and repeats collapsed, so
+/// "amoxcilin" and "amoxicillin" both give "amkskln".
+fn sound(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let mut key = String::new();
+    let mut push = |c: char| {
+        if !key.ends_with(c) {
+            key.push(c);
+        }
+    };
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            'a' | 'e' | 'i' | 'o' | 'u' | 'y' if i == 0 => push('a'),
+            'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'h' | 'w' => {}
+            'p' if chars.get(i + 1) == Some(&'h') => push('f'),
+            'c' | 'k' | 'q' => push('k'),
+            'x' => {
+                push('k');
+                push('s');
+            }
+            'z' => push('s'),
+            c => push(c),
+        }
+    }
+    key
+}
+
+/// Edits (insert, delete, substitute or swap neighbours) between two words.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+fn allowed_typos(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// How well one typed word matches one word of a product, from 1.0 for the
+/// same word down to a sound-alike; `None` if it does not match.
+fn word_score(typed: &str, word: &str) -> Option<f64> {
+    if word == typed {
+        return Some(1.0);
+    }
+    if word.starts_with(typed) {
+        return Some(0.9);
+    }
+    let typed_chars: Vec<char> = typed.chars().collect();
+    if typed_chars.len() >= 3 && word.contains(typed) {
+        return Some(0.75);
+    }
+    let allowed = allowed_typos(typed_chars.len());
+    if allowed > 0 {
+        let word_chars: Vec<char> = word.chars().collect();
+        // A word still being typed is compared with the start of the product's word
+        let start = &word_chars[..typed_chars.len().min(word_chars.len())];
+        let typos = edit_distance(&typed_chars, &word_chars).min(edit_distance(&typed_chars, start));
+        if typos <= allowed {
+            return Some(if typos == 1 { 0.55 } else { 0.4 });
+        }
+    }
+    if typed_chars.len() >= 4 && typed.chars().all(char::is_alphabetic) && sound(typed) == sound(word) {
+        return Some(0.35);
+    }
+    None
+}
+
+/// Every batch filed under one name, with the other names it is known by.
+struct Product {
+    name: String,
+    generic_name: Option<String>,
+    brand_names: Vec<String>,
+    composition: Option<String>,
+    words: Vec<(MatchField, String)>,
+    batches: Vec<ObjectId>,
+}
+
+impl Product {
+    fn new(medicine: &Medicine) -> Self {
+        Product {
+            name: medicine.name.trim().to_string(),
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            words: Vec::new(),
+            batches: Vec::new(),
+        }
+    }
+
+    fn add_batch(&mut self, medicine: &Medicine) {
+        if let Some(id) = medicine.id {
+            self.batches.push(id);
+        }
+        let filled = |text: &Option<String>| text.as_deref().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string);
+        if self.generic_name.is_none() {
+            self.generic_name = filled(&medicine.generic_name);
+        }
+        if self.composition.is_none() {
+            self.composition = filled(&medicine.composition);
+        }
+        for brand in &medicine.brand_names {
+            if !brand.trim().is_empty() && !self.brand_names.iter().any(|b| b.eq_ignore_ascii_case(brand.trim())) {
+                self.brand_names.push(brand.trim().to_string());
+            }
+        }
+    }
+
+    fn index_words(&mut self) {
+        let mut fields = vec![(MatchField::Name, self.name.clone())];
+        fields.extend(self.brand_names.iter().map(|b| (MatchField::Brand, b.clone())));
+        fields.extend(self.generic_name.iter().map(|g| (MatchField::Generic, g.clone())));
+        fields.extend(self.composition.iter().map(|c| (MatchField::Composition, c.clone())));
+        for (field, text) in fields {
+            for word in words(&text) {
+                if !self.words.contains(&(field, word.clone())) {
+                    self.words.push((field, word));
+                }
+            }
+        }
+    }
+
+    /// The mean of each typed word's best match, and the field matched best.
+    fn score(&self, typed: &[String]) -> Option<(f64, MatchField)> {
+        let mut total = 0.0;
+        let mut best = (0.0, MatchField::Name);
+        for typed in typed {
+            let (score, field) = self
+                .words
+                .iter()
+                .filter_map(|(field, word)| word_score(typed, word).map(|s| (s * field.weight(), *field)))
+                .max_by(|a, b| a.0.total_cmp(&b.0))?;
+            total += score;
+            if score > best.0 {
+                best = (score, field);
+            }
+        }
+        Some((total / typed.len() as f64, best.1))
+    }
+}
+
+/// A product found by a search, best match first.
+pub struct Hit {
+    pub score: f64,
+    pub matched_on: MatchField,
+    product: usize,
+}
+
+/// One hospital's catalogue, looked up by word fragments, sound and barcode.
+pub struct MedicineIndex {
+    built: Instant,
+    products: Vec<Product>,
+    trigrams: HashMap<String, Vec<usize>>,
+    sounds: HashMap<String, Vec<usize>>,
+    barcodes: HashMap<String, usize>,
+}
+
+impl MedicineIndex {
+    pub fn build(medicines: &[Medicine]) -> Self {
+        let mut products: Vec<Product> = Vec::new();
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        let mut barcodes = HashMap::new();
+        for medicine in medicines {
+            let key = words(&medicine.name).join(" ");
+            let product = *by_name.entry(key).or_insert_with(|| {
+                products.push(Product::new(medicine));
+                products.len() - 1
+            });
+            products[product].add_batch(medicine);
+            if let Some(barcode) = medicine.barcode.as_deref().map(str::trim).filter(|b| !b.is_empty()) {
+                barcodes.entry(barcode.to_string()).or_insert(product);
+            }
+        }
+
+        let mut trigram_index: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut sounds: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, product) in products.iter_mut().enumerate() {
+            product.index_words();
+            for (_, word) in &product.words {
+                for trigram in trigrams(&format!(" {} ", word)) {
+                    let postings = trigram_index.entry(trigram).or_default();
+                    if postings.last() != Some(&i) {
+                        postings.push(i);
+                    }
+                }
+                let postings = sounds.entry(sound(word)).or_default();
+                if postings.last() != Some(&i) {
+                    postings.push(i);
+                }
+            }
+        }
+        MedicineIndex { built: Instant::now(), products, trigrams: trigram_index, sounds, barcodes }
+    }
+
+    /// Products matching `text`, best first. A scanned barcode matches its
+    /// product alone; otherwise every typed word has to match some word of
+    /// the product's names.
+    pub fn search(&self, text: &str) -> Vec<Hit> {
+        if let Some(&product) = self.barcodes.get(text.trim()) {
+            return vec![Hit { score: 1.0, matched_on: MatchField::Barcode, product }];
+        }
+        let typed = words(text);
+        if typed.is_empty() {
+            return Vec::new();
+        }
+
+        // Short words can only be matched by scanning the whole catalogue
+        let candidates: Vec<usize> = if typed.iter().any(|w| w.chars().count() < 3) {
+            (0..self.products.len()).collect()
+        } else {
+            let mut candidates = HashSet::new();
+            for word in &typed {
+                for trigram in trigrams(word) {
+                    candidates.extend(self.trigrams.get(&trigram).into_iter().flatten());
+                }
+                candidates.extend(self.sounds.get(&sound(word)).into_iter().flatten());
+            }
+            candidates.into_iter().collect()
+        };
+
+        let mut hits: Vec<Hit> = candidates
+            .into_iter()
+            .filter_map(|product| {
+                let (score, matched_on) = self.products[product].score(&typed)?;
+                Some(Hit { score, matched_on, product })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| self.products[a.product].name.cmp(&self.products[b.product].name))
+        });
+        hits
+    }
+
+    fn batches(&self, hits: &[Hit]) -> Vec<ObjectId> {
+        hits.iter().flat_map(|hit| self.products[hit.product].batches.iter().copied()).collect()
+    }
+}
+
+/// Search indexes by hospital, held in Tauri managed state.
+#[derive(Default)]
+pub struct SearchIndexes {
+    hospitals: Mutex<HashMap<String, Arc<MedicineIndex>>>,
+}
+
+impl SearchIndexes {
+    /// The hospital's index, rebuilt from its stock when missing or stale.
+    pub async fn get(&self, repo: &dyn MedicineRepository, hospital_id: &str) -> Result<Arc<MedicineIndex>, String> {
+        if let Some(index) = self.hospitals.lock().unwrap().get(hospital_id) {
+            if index.built.elapsed() < INDEX_TTL {
+                return Ok(index.clone());
+            }
+        }
+        let medicines = repo.list_medicines(hospital_id, &MedicineQuery::default()).await?;
+        let index = Arc::new(MedicineIndex::build(&medicines));
+        self.hospitals.lock().unwrap().insert(hospital_id.to_string(), index.clone());
+        Ok(index)
+    }
+
+    /// Drops the hospital's index after its catalogue changed on this counter.
+    pub fn invalidate(&self, hospital_id: &str) {
+        self.hospitals.lock().unwrap().remove(hospital_id);
+    }
+}
+
+/// Loads the current batches of the matching products, each tagged with
+/// its product's place in `hits`.
+async fn load_batches(
+    repo: &dyn MedicineRepository,
+    index: &MedicineIndex,
+    hospital_id: &str,
+    hits: &[Hit],
+) -> Result<Vec<(usize, Medicine)>, AppError> {
+    let rank: HashMap<ObjectId, usize> = hits
+        .iter()
+        .enumerate()
+        .flat_map(|(rank, hit)| index.products[hit.product].batches.iter().map(move |id| (*id, rank)))
+        .collect();
+    let medicines = repo.find_medicines(&index.batches(hits), hospital_id).await?;
+    Ok(medicines
+        .into_iter()
+        .filter_map(|m| Some((*rank.get(&m.id?)?, m)))
+        .collect())
+}
+
+/// Batches of the products matching `query.text`: best match first, and
+/// within a product the soonest expiry first, unless the query asks for
+/// another order.
+pub async fn search_batches(
+    repo: &dyn MedicineRepository,
+    index: &MedicineIndex,
+    hospital_id: &str,
+    query: &MedicineQuery,
+) -> Result<Vec<Medicine>, AppError> {
+    let hits = index.search(query.text.as_deref().unwrap_or_default());
+    let mut found = load_batches(repo, index, hospital_id, &hits).await?;
+    if query.ranked() {
+        found.sort_by(|(rank_a, a), (rank_b, b)| {
+            rank_a.cmp(rank_b).then_with(|| a.expiry_date.cmp(&b.expiry_date)).then_with(|| a.id.cmp(&b.id))
+        });
+    } else {
+        found.sort_by(|(_, a), (_, b)| query.compare(a, b));
+    }
+    Ok(query.page.apply(found.into_iter().map(|(_, m)| m)))
+}
+
+/// A matching product with all its batches.
+#[derive(Debug, Serialize)]
+pub struct ProductMatch {
+    pub name: String,
+    pub generic_name: Option<String>,
+    pub brand_names: Vec<String>,
+    pub composition: Option<String>,
+    pub matched_on: MatchField,
+    pub score: f64,
+    pub total_quantity: u32,
+    pub nearest_expiry: Option<NaiveDate>, // of the batches still in stock
+    pub batches: Vec<MedicineResponse>,    // soonest expiry first
+}
+
+/// Products matching `text`, best first, with their stock totals.
+pub async fn find_products(
+    repo: &dyn MedicineRepository,
+    index: &MedicineIndex,
+    hospital_id: &str,
+    text: &str,
+    limit: usize,
+) -> Result<Vec<ProductMatch>, AppError> {
+    let mut hits = index.search(text);
+    hits.truncate(limit);
+    let mut batches: Vec<Vec<Medicine>> = hits.iter().map(|_| Vec::new()).collect();
+    for (rank, medicine) in load_batches(repo, index, hospital_id, &hits).await? {
+        batches[rank].push(medicine);
+    }
+
+    Ok(hits
+        .iter()
+        .zip(batches)
+        .filter(|(_, batches)| !batches.is_empty())
+        .map(|(hit, mut batches)| {
+            let product = &index.products[hit.product];
+            batches.sort_by(|a, b| a.expiry_date.cmp(&b.expiry_date).then_with(|| a.batch_number.cmp(&b.batch_number)));
+            ProductMatch {
+                name: product.name.clone(),
+                generic_name: product.generic_name.clone(),
+                brand_names: product.brand_names.clone(),
+                composition: product.composition.clone(),
+                matched_on: hit.matched_on,
+                score: hit.score,
+                total_quantity: batches.iter().map(|b| b.quantity).sum(),
+                nearest_expiry: batches.iter().filter(|b| b.quantity > 0).map(|b| b.expiry_date).min(),
+                batches: batches.into_iter().map(MedicineResponse::from).collect(),
+            }
+        })
+        .collect())
+}
+
+/// Medicines grouped by product for the search box: typo-tolerant, across
+/// brand, generic and composition names, or by a scanned barcode.
+#[command]
+pub async fn search_products(
+    query: String,
+    hospital_id: String,
+    limit: Option<u32>,
+    db_state: State<'_, DbState>,
+    indexes: State<'_, SearchIndexes>,
+) -> Result<Vec<ProductMatch>, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PRODUCT_LIMIT);
+    if limit == 0 || limit > MAX_PRODUCT_LIMIT {
+        return Err(AppError::invalid("limit", &format!("Limit must be between 1 and {}.", MAX_PRODUCT_LIMIT)));
+    }
+    let repo = db_state.medicines();
+    let index = indexes.get(&*repo, &hospital_id).await?;
+    find_products(&*repo, &index, &hospital_id, &query, limit as usize).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::repository::memory::MemoryStore;
+    use crate::repository::Page;
+
+    fn batch(name: &str, batch_number: &str, expiry: (i32, u32), quantity: u32) -> Medicine {
+        Medicine {
+            id: None,
+            user_id: "h1".to_string(),
+            name: name.to_string(),
+            batch_number: batch_number.to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(expiry.0, expiry.1, 1).unwrap(),
+            quantity,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        }
+    }
+
+    async fn stocked() -> (MemoryStore, MedicineIndex) {
+        let store = MemoryStore::default();
+        let paracetamol = Medicine {
+            brand_names: vec!["Crocin".to_string(), "Calpol".to_string()],
+            barcode: Some("8901234567890".to_string()),
+            ..batch("Paracetamol 500", "P1", (2027, 6), 20)
+        };
+        let batches = [
+            paracetamol.clone(),
+            Medicine { batch_number: "P2".to_string(), ..batch("paracetamol  500", "P2", (2027, 1), 5) },
+            Medicine { batch_number: "P0".to_string(), quantity: 0, ..paracetamol.clone() },
+            Medicine { generic_name: Some("Paracetamol".to_string()), ..batch("Dolo 650", "D1", (2027, 3), 8) },
+            Medicine {
+                composition: Some("Amoxicillin 500 mg + Clavulanic acid 125 mg".to_string()),
+                ..batch("Augmentin 625", "A1", (2027, 2), 4)
+            },
+        ];
+        for medicine in batches {
+            store.insert_medicine(medicine).await.unwrap();
+        }
+        let index = MedicineIndex::build(&store.list_medicines("h1", &MedicineQuery::default()).await.unwrap());
+        (store, index)
+    }
+
+    fn names(index: &MedicineIndex, text: &str) -> Vec<(String, MatchField)> {
+        index.search(text).iter().map(|hit| (index.products[hit.product].name.clone(), hit.matched_on)).collect()
+    }
+
+    #[test]
+    fn typo_tolerant_words_and_sounds() {
+        assert_eq!(edit_distance(&['p', 'a', 'r', 'a'], &['p', 'r', 'a', 'a']), 1);
+        assert_eq!(sound("amoxcilin"), sound("amoxicillin"));
+        assert_eq!(word_score("paracetmol", "paracetamol"), Some(0.55));
+        assert_eq!(word_score("paracetm", "paracetamol"), Some(0.55));
+        assert_eq!(word_score("amx", "amoxicillin"), None);
+    }
+
+    #[tokio::test]
+    async fn misspellings_brands_and_barcodes_find_the_product() {
+        let (_, index) = stocked().await;
+        let paracetamol = ("Paracetamol 500".to_string(), MatchField::Name);
+
+        assert_eq!(
+            names(&index, "paracetmol"),
+            [paracetamol.clone(), ("Dolo 650".to_string(), MatchField::Generic)]
+        );
+        assert_eq!(names(&index, "CROCIN"), [("Paracetamol 500".to_string(), MatchField::Brand)]);
+        assert_eq!(names(&index, "amoxcilin 500"), [("Augmentin 625".to_string(), MatchField::Composition)]);
+        assert_eq!(names(&index, " 8901234567890 "), [("Paracetamol 500".to_string(), MatchField::Barcode)]);
+        assert_eq!(names(&index, "par 500"), [paracetamol]);
+        assert!(names(&index, "ibuprofen").is_empty());
+    }
+
+    #[tokio::test]
+    async fn products_total_their_batches_and_batches_page() {
+        let (store, index) = stocked().await;
+
+        let products = find_products(&store, &index, "h1", "paracetamol", 1).await.unwrap();
+        assert_eq!(products.len(), 1);
+        let product = &products[0];
+        assert_eq!((product.total_quantity, product.brand_names.len()), (25, 2));
+        assert_eq!(product.nearest_expiry, NaiveDate::from_ymd_opt(2027, 1, 1));
+        let batches: Vec<&str> = product.batches.iter().map(|b| b.batch_number.as_str()).collect();
+        assert_eq!(batches, ["P2", "P0", "P1"]);
+
+        let query = MedicineQuery {
+            text: Some("paracetamol".to_string()),
+            page: Page { skip: 2, limit: Some(2) },
+            ..Default::default()
+        };
+        let found = search_batches(&store, &index, "h1", &query).await.unwrap();
+        let found: Vec<&str> = found.iter().map(|m| m.batch_number.as_str()).collect();
+        assert_eq!(found, ["P0", "D1"]);
+    }
+}
@@ -0,0 +1,258 @@
+// src-tauri/src/settings.rs
+//! Settings each hospital chooses for itself, stored on its account so they
+//! follow it to every counter. Installation-wide settings are in
+//! `config.rs`; the printer choices here fall back to its `[printing]`.
+use crate::config;
+use crate::db::DbState;
+use crate::error::AppError;
+use crate::pdf::PageSize;
+use crate::receipt::{PaperWidth, ReceiptTarget};
+use crate::repository::UserRepository;
+use crate::user::find_hospital;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+/// Whether prices entered for new stock already include GST.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxMode {
+    #[default]
+    Inclusive, // MRP in India already includes GST
+    Exclusive,
+}
+
+impl TaxMode {
+    pub fn is_inclusive(self) -> bool {
+        self == TaxMode::Inclusive
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Visits without clinical notes or a follow-up are removed this many
+    /// hours after they were recorded.
+    pub visit_hours: i64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy { visit_hours: 1 }
+    }
+}
+
+/// `None` uses the installation's `[printing]` setting.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrinterSettings {
+    pub receipt_printer: Option<String>,
+    pub receipt_width_mm: Option<u32>,
+    pub invoice_page_size: Option<String>,
+}
+
+impl PrinterSettings {
+    pub fn receipt_printer(&self) -> Option<String> {
+        self.receipt_printer.clone().or_else(|| config::get().printing.receipt_printer.clone())
+    }
+
+    pub fn receipt_width_mm(&self) -> u32 {
+        self.receipt_width_mm.unwrap_or(config::get().printing.receipt_width_mm)
+    }
+
+    pub fn invoice_page_size(&self) -> Result<PageSize, String> {
+        PageSize::parse(self.invoice_page_size.as_deref().unwrap_or(&config::get().printing.invoice_page_size))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HospitalSettings {
+    pub tax_mode: TaxMode,
+    pub retention: RetentionPolicy,
+    pub printer: PrinterSettings,
+}
+
+/// A hospital's settings as the settings screen shows them. The invoice
+/// prefix is kept on the profile, where invoices have always read it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingsResponse {
+    pub invoice_prefix: Option<String>,
+    pub tax_mode: TaxMode,
+    pub retention: RetentionPolicy,
+    pub printer: PrinterSettings,
+    pub printing_defaults: config::PrintingSettings,
+}
+
+/// Settings to change; `None` leaves a setting as it is and an empty
+/// printer or page size goes back to the installation default.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsChanges {
+    pub invoice_prefix: Option<String>,
+    pub tax_mode: Option<TaxMode>,
+    pub visit_retention_hours: Option<i64>,
+    pub receipt_printer: Option<String>,
+    pub receipt_width_mm: Option<u32>,
+    pub invoice_page_size: Option<String>,
+}
+
+impl SettingsChanges {
+    fn is_empty(&self) -> bool {
+        self.invoice_prefix.is_none()
+            && self.tax_mode.is_none()
+            && self.visit_retention_hours.is_none()
+            && self.receipt_printer.is_none()
+            && self.receipt_width_mm.is_none()
+            && self.invoice_page_size.is_none()
+    }
+}
+
+/// Uppercases an invoice prefix and checks it is 1-12 letters, digits or
+/// dashes, e.g. "HOSP" in HOSP/2026-27/000123.
+pub fn clean_invoice_prefix(prefix: &str) -> Result<String, AppError> {
+    let prefix = prefix.trim().to_uppercase();
+    if prefix.is_empty() || prefix.len() > 12 || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(AppError::invalid("invoice_prefix", "Invoice prefix must be 1-12 letters, digits or dashes"));
+    }
+    Ok(prefix)
+}
+
+fn response(invoice_prefix: Option<String>, settings: HospitalSettings) -> SettingsResponse {
+    SettingsResponse {
+        invoice_prefix,
+        tax_mode: settings.tax_mode,
+        retention: settings.retention,
+        printer: settings.printer,
+        printing_defaults: config::get().printing.clone(),
+    }
+}
+
+/// Empty text means "use the default".
+fn cleared(value: String) -> Option<String> {
+    Some(value.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+pub async fn settings_for(users: &dyn UserRepository, hospital_id: &str) -> Result<SettingsResponse, AppError> {
+    let hospital = find_hospital(users, hospital_id).await?;
+    Ok(response(hospital.invoice_prefix, hospital.settings))
+}
+
+pub async fn update_settings(
+    users: &dyn UserRepository,
+    hospital_id: &str,
+    changes: SettingsChanges,
+) -> Result<SettingsResponse, AppError> {
+    if changes.is_empty() {
+        return Err(AppError::validation("No settings to update."));
+    }
+    let mut hospital = find_hospital(users, hospital_id).await?;
+
+    if let Some(prefix) = &changes.invoice_prefix {
+        hospital.invoice_prefix = Some(clean_invoice_prefix(prefix)?);
+    }
+    let settings = &mut hospital.settings;
+    if let Some(mode) = changes.tax_mode {
+        settings.tax_mode = mode;
+    }
+    if let Some(hours) = changes.visit_retention_hours {
+        if !(1..=24 * 365).contains(&hours) {
+            return Err(AppError::invalid("visit_retention_hours", "Visits must be kept between 1 hour and 1 year"));
+        }
+        settings.retention.visit_hours = hours;
+    }
+    if let Some(printer) = changes.receipt_printer {
+        let printer = cleared(printer);
+        if let Some(target) = &printer {
+            ReceiptTarget::parse(target).map_err(|e| AppError::invalid("receipt_printer", &e))?;
+        }
+        settings.printer.receipt_printer = printer;
+    }
+    if let Some(width) = changes.receipt_width_mm {
+        PaperWidth::from_mm(width).map_err(|e| AppError::invalid("receipt_width_mm", &e))?;
+        settings.printer.receipt_width_mm = Some(width);
+    }
+    if let Some(size) = changes.invoice_page_size {
+        let size = cleared(size).map(|s| s.to_uppercase());
+        if let Some(size) = &size {
+            PageSize::parse(size).map_err(|e| AppError::invalid("invoice_page_size", &e))?;
+        }
+        settings.printer.invoice_page_size = size;
+    }
+
+    users.replace_user(&hospital).await?;
+    Ok(response(hospital.invoice_prefix, hospital.settings))
+}
+
+#[command]
+pub async fn get_hospital_settings(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<SettingsResponse, AppError> {
+    settings_for(&*db_state.users(), &hospital_id).await
+}
+
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_hospital_settings(
+    hospital_id: String,
+    invoice_prefix: Option<String>,
+    tax_mode: Option<TaxMode>,
+    visit_retention_hours: Option<i64>,
+    receipt_printer: Option<String>,
+    receipt_width_mm: Option<u32>,
+    invoice_page_size: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<SettingsResponse, AppError> {
+    let changes = SettingsChanges {
+        invoice_prefix,
+        tax_mode,
+        visit_retention_hours,
+        receipt_printer,
+        receipt_width_mm,
+        invoice_page_size,
+    };
+    update_settings(&*db_state.users(), &hospital_id, changes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryStore;
+    use crate::user::signup_user;
+
+    #[tokio::test]
+    async fn settings_are_validated_and_stored_on_the_account() {
+        let store = MemoryStore::default();
+        signup_user(&store, "city", "Dr Rao", "9800000000", "City Clinic", "MG Road", "doc", "pharma", "rao@example.com")
+            .await
+            .unwrap();
+        let id = store.find_user_by_username("city").await.unwrap().unwrap().id.unwrap().to_hex();
+
+        let fresh = settings_for(&store, &id).await.unwrap();
+        assert_eq!((fresh.tax_mode, fresh.retention.visit_hours), (TaxMode::Inclusive, 1));
+        assert!(update_settings(&store, &id, SettingsChanges::default()).await.is_err());
+        let narrow = SettingsChanges { receipt_width_mm: Some(72), ..Default::default() };
+        match update_settings(&store, &id, narrow).await.unwrap_err() {
+            AppError::Validation { fields, .. } => assert_eq!(fields[0].field, "receipt_width_mm"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let changes = SettingsChanges {
+            invoice_prefix: Some("city".to_string()),
+            tax_mode: Some(TaxMode::Exclusive),
+            visit_retention_hours: Some(48),
+            receipt_printer: Some("tcp://192.168.1.50".to_string()),
+            invoice_page_size: Some("a5".to_string()),
+            ..Default::default()
+        };
+        update_settings(&store, &id, changes).await.unwrap();
+        let hospital = find_hospital(&store, &id).await.unwrap();
+        assert_eq!(hospital.invoice_prefix.as_deref(), Some("CITY"));
+        assert_eq!(hospital.settings.retention.visit_hours, 48);
+        assert!(matches!(hospital.settings.printer.invoice_page_size(), Ok(PageSize::A5)));
+        assert_eq!(hospital.settings.printer.receipt_width_mm(), 80);
+
+        let cleared = SettingsChanges { receipt_printer: Some(" ".to_string()), ..Default::default() };
+        let settings = update_settings(&store, &id, cleared).await.unwrap();
+        assert_eq!((settings.printer.receipt_printer, settings.tax_mode), (None, TaxMode::Exclusive));
+    }
+}
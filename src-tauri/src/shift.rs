@@ -0,0 +1,647 @@
+// src-tauri/src/shift.rs
+use crate::billing::{invoices_collection, run_transaction, Invoice, TxnError};
+use crate::config;
+use crate::credit::{ledger_collection, LedgerEntry};
+use crate::dates;
+use crate::db::DbState;
+use crate::model::User;
+use crate::money::Money;
+use crate::payment::{summarize_payments, PaymentMode, PaymentTotals, PaymentTotalsResponse};
+use crate::pdf::{output_dir, safe_file_name, PageSize, PdfCanvas};
+use crate::returns::{credit_notes_collection, CreditNote};
+use crate::user::{find_hospital, users_collection, verify_admin_password};
+use chrono::{DateTime, Local, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, to_bson};
+use mongodb::options::FindOptions;
+use mongodb::{Client, ClientSession, Collection, Database};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShiftStatus {
+    Open,
+    Closed,
+}
+
+/// End-of-shift totals, worked out once at closing and kept with the shift
+/// so reprints always show the same figures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZReport {
+    pub invoice_count: u32,
+    pub first_invoice: Option<String>,
+    pub last_invoice: Option<String>,
+    pub gross_sales: Money,
+    pub discount_total: Money,
+    pub taxable_total: Money,
+    pub tax_total: Money,
+    pub credit_note_count: u32,
+    pub returns_total: Money,
+    pub payments: PaymentTotals,
+    pub opening_float: Money,
+    pub expected_cash: Money,
+    pub counted_cash: Money,
+    pub variance: Money, // counted less expected; negative means cash is short
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shift {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub cashier: String,
+    pub opening_float: Money,
+    pub status: ShiftStatus,
+    #[serde(deserialize_with = "crate::dates::timestamp")]
+    pub opened_at: bson::DateTime,
+    #[serde(default, deserialize_with = "crate::dates::optional_timestamp")]
+    pub closed_at: Option<bson::DateTime>,
+    #[serde(default, deserialize_with = "crate::dates::optional_timestamp")]
+    pub last_activity_at: Option<bson::DateTime>,
+    pub z_report: Option<ZReport>,
+    #[serde(default, deserialize_with = "crate::dates::optional_timestamp")]
+    pub amended_at: Option<bson::DateTime>, // when sync added offline sales after the close
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ZReportResponse {
+    pub invoice_count: u32,
+    pub first_invoice: Option<String>,
+    pub last_invoice: Option<String>,
+    pub gross_sales: f64,
+    pub discount_total: f64,
+    pub taxable_total: f64,
+    pub tax_total: f64,
+    pub credit_note_count: u32,
+    pub returns_total: f64,
+    pub payments: PaymentTotalsResponse,
+    pub opening_float: f64,
+    pub expected_cash: f64,
+    pub counted_cash: f64,
+    pub variance: f64,
+}
+
+impl From<ZReport> for ZReportResponse {
+    fn from(report: ZReport) -> Self {
+        ZReportResponse {
+            invoice_count: report.invoice_count,
+            first_invoice: report.first_invoice,
+            last_invoice: report.last_invoice,
+            gross_sales: report.gross_sales.rupees(),
+            discount_total: report.discount_total.rupees(),
+            taxable_total: report.taxable_total.rupees(),
+            tax_total: report.tax_total.rupees(),
+            credit_note_count: report.credit_note_count,
+            returns_total: report.returns_total.rupees(),
+            payments: PaymentTotalsResponse::from(&report.payments),
+            opening_float: report.opening_float.rupees(),
+            expected_cash: report.expected_cash.rupees(),
+            counted_cash: report.counted_cash.rupees(),
+            variance: report.variance.rupees(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ShiftResponse {
+    pub id: String,
+    pub cashier: String,
+    pub opening_float: f64,
+    pub status: ShiftStatus,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub z_report: Option<ZReportResponse>,
+    pub amended_at: Option<DateTime<Utc>>,
+}
+
+impl From<Shift> for ShiftResponse {
+    fn from(shift: Shift) -> Self {
+        ShiftResponse {
+            id: shift.id.map(|id| id.to_hex()).unwrap_or_default(),
+            cashier: shift.cashier,
+            opening_float: shift.opening_float.rupees(),
+            status: shift.status,
+            opened_at: dates::to_utc(shift.opened_at),
+            closed_at: shift.closed_at.map(dates::to_utc),
+            z_report: shift.z_report.map(ZReportResponse::from),
+            amended_at: shift.amended_at.map(dates::to_utc),
+        }
+    }
+}
+
+fn shifts_collection(db: &Database) -> Collection<Shift> {
+    db.collection("shifts")
+}
+
+async fn find_shift(db: &Database, shift_id: &str, hospital_id: &str) -> Result<Shift, String> {
+    let object_id = ObjectId::parse_str(shift_id).map_err(|_| "Invalid shift ID".to_string())?;
+    shifts_collection(db)
+        .find_one(doc! { "_id": object_id, "hospital_id": hospital_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Shift not found".to_string())
+}
+
+/// Ties a sale, refund or receipt to an open shift inside the caller's
+/// transaction. Writing to the shift makes the transaction conflict with a
+/// concurrent close, so nothing can slip in after the Z-report is drawn up.
+pub(crate) async fn touch_open_shift(
+    db: &Database,
+    session: &mut ClientSession,
+    shift_id: &str,
+    hospital_id: &str,
+) -> Result<(), TxnError> {
+    let object_id =
+        ObjectId::parse_str(shift_id).map_err(|_| TxnError::Rejected("Invalid shift ID".to_string()))?;
+    let result = shifts_collection(db)
+        .update_one_with_session(
+            doc! { "_id": object_id, "hospital_id": hospital_id, "status": "open" },
+            doc! { "$set": { "last_activity_at": bson::DateTime::now() } },
+            None,
+            session,
+        )
+        .await?;
+    if result.matched_count == 0 {
+        return Err(TxnError::Rejected(
+            "This shift is closed; open a new shift to continue.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) async fn shift_is_closed(db: &Database, shift_id: &str, hospital_id: &str) -> Result<bool, String> {
+    Ok(find_shift(db, shift_id, hospital_id).await?.status == ShiftStatus::Closed)
+}
+
+/// An invoice billed in a closed shift is already in its Z-report, so
+/// returning or voiding it needs the admin password.
+pub(crate) fn authorize_closed_shift(
+    closed: bool,
+    hospital: &User,
+    admin_password: Option<&str>,
+) -> Result<(), String> {
+    if !closed {
+        return Ok(());
+    }
+    verify_admin_password(hospital, admin_password)
+        .map_err(|e| format!("The shift this invoice was billed in is closed. {}", e))
+}
+
+/// Totals a shift's sales, refunds and collections. Expected cash is the
+/// opening float plus cash taken (net of change), less cash refunded.
+pub fn build_z_report(
+    opening_float: Money,
+    invoices: &[Invoice],
+    receipts: &[LedgerEntry],
+    credit_notes: &[CreditNote],
+    counted_cash: Money,
+) -> ZReport {
+    let sum = |f: fn(&Invoice) -> Money| invoices.iter().map(f).sum::<Money>();
+    let tax_total = sum(|i| i.cgst_total) + sum(|i| i.sgst_total) + sum(|i| i.igst_total);
+    let payments = summarize_payments(invoices, receipts, credit_notes);
+    let expected = opening_float + payments.mode_total(PaymentMode::Cash) - payments.refunds;
+
+    ZReport {
+        invoice_count: invoices.len() as u32,
+        first_invoice: invoices.first().map(|i| i.invoice_number.clone()),
+        last_invoice: invoices.last().map(|i| i.invoice_number.clone()),
+        gross_sales: sum(|i| i.grand_total),
+        discount_total: sum(|i| i.discount_total),
+        taxable_total: sum(|i| i.taxable_total),
+        tax_total,
+        credit_note_count: credit_notes.len() as u32,
+        returns_total: credit_notes.iter().map(|n| n.total).sum(),
+        payments,
+        opening_float,
+        expected_cash: expected,
+        counted_cash,
+        variance: counted_cash - expected,
+    }
+}
+
+/// Builds the Z-report from everything recorded against the shift so far.
+async fn draw_up_report(
+    db: &Database,
+    session: &mut ClientSession,
+    shift: &Shift,
+    counted_cash: Money,
+) -> Result<ZReport, TxnError> {
+    let shift_id = shift.id.map(|id| id.to_hex()).unwrap_or_default();
+    let filter = doc! { "hospital_id": &shift.hospital_id, "shift_id": &shift_id };
+    let find_options = FindOptions::builder().sort(doc! { "date_created": 1 }).build();
+    let mut cursor = invoices_collection(db).find_with_session(filter.clone(), find_options, session).await?;
+    let invoices: Vec<Invoice> = cursor.stream(session).try_collect().await?;
+    let mut receipt_filter = filter.clone();
+    receipt_filter.insert("kind", "receipt");
+    let mut cursor = ledger_collection(db).find_with_session(receipt_filter, None, session).await?;
+    let receipts: Vec<LedgerEntry> = cursor.stream(session).try_collect().await?;
+    let mut cursor = credit_notes_collection(db).find_with_session(filter, None, session).await?;
+    let credit_notes: Vec<CreditNote> = cursor.stream(session).try_collect().await?;
+
+    Ok(build_z_report(shift.opening_float, &invoices, &receipts, &credit_notes, counted_cash))
+}
+
+/// Called by sync for each shift that offline sales were billed against.
+/// An open shift picks them up when it closes; a closed one has its
+/// Z-report drawn up again, keeping the counted cash, and is marked
+/// amended so the variance is looked at again.
+pub(crate) async fn amend_closed_shift(
+    client: &Client,
+    db: &Database,
+    shift_id: &str,
+    hospital_id: &str,
+) -> Result<bool, String> {
+    let object_id = ObjectId::parse_str(shift_id).map_err(|_| "Invalid shift ID".to_string())?;
+    run_transaction(client, |session| {
+        let (db, hospital_id) = (db.clone(), hospital_id.to_string());
+        Box::pin(async move {
+            let filter = doc! { "_id": object_id, "hospital_id": &hospital_id, "status": "closed" };
+            let Some(shift) = shifts_collection(&db).find_one_with_session(filter.clone(), None, session).await? else {
+                return Ok(false);
+            };
+            let Some(closed) = &shift.z_report else { return Ok(false) };
+            let report = draw_up_report(&db, session, &shift, closed.counted_cash).await?;
+            if report.invoice_count == closed.invoice_count {
+                return Ok(false);
+            }
+
+            let report_bson = to_bson(&report).map_err(|e| TxnError::Rejected(e.to_string()))?;
+            shifts_collection(&db)
+                .update_one_with_session(
+                    filter,
+                    doc! { "$set": { "z_report": report_bson, "amended_at": bson::DateTime::now() } },
+                    None,
+                    session,
+                )
+                .await?;
+            Ok(true)
+        })
+    })
+    .await
+}
+
+#[command]
+pub async fn open_shift(
+    cashier: String,
+    opening_float: f64,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<ShiftResponse, String> {
+    let cashier = cashier.trim().to_string();
+    if cashier.is_empty() {
+        return Err("Cashier name is required.".to_string());
+    }
+    if !opening_float.is_finite() || opening_float < 0.0 {
+        return Err("Opening float cannot be negative.".to_string());
+    }
+
+    let db = db_state.mongo("shifts")?.db.clone();
+    let collection = shifts_collection(&db);
+    let already_open = collection
+        .find_one(doc! { "hospital_id": &hospital_id, "cashier": &cashier, "status": "open" }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if already_open.is_some() {
+        return Err(format!("{} already has an open shift; close it first.", cashier));
+    }
+
+    let mut shift = Shift {
+        id: None,
+        hospital_id,
+        cashier,
+        opening_float: Money::from_rupees(opening_float),
+        status: ShiftStatus::Open,
+        opened_at: bson::DateTime::now(),
+        closed_at: None,
+        last_activity_at: None,
+        z_report: None,
+        amended_at: None,
+    };
+    let result = collection
+        .insert_one(&shift, None)
+        .await
+        .map_err(|e| format!("Database insert error: {}", e))?;
+    shift.id = result.inserted_id.as_object_id();
+    Ok(ShiftResponse::from(shift))
+}
+
+#[command]
+pub async fn get_open_shifts(
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<ShiftResponse>, String> {
+    let db = db_state.mongo("shifts")?.db.clone();
+    let find_options = FindOptions::builder().sort(doc! { "opened_at": 1 }).build();
+    let shifts: Vec<Shift> = shifts_collection(&db)
+        .find(doc! { "hospital_id": hospital_id, "status": "open" }, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing shifts: {}", e))?;
+
+    Ok(shifts.into_iter().map(ShiftResponse::from).collect())
+}
+
+/// Closes a shift with the cash counted in the drawer and stores its
+/// Z-report. From then on its invoices can only be voided or returned with
+/// an admin override.
+#[command]
+pub async fn close_shift(
+    shift_id: String,
+    counted_cash: f64,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<ShiftResponse, String> {
+    if !counted_cash.is_finite() || counted_cash < 0.0 {
+        return Err("Counted cash cannot be negative.".to_string());
+    }
+    let object_id = ObjectId::parse_str(&shift_id).map_err(|_| "Invalid shift ID".to_string())?;
+    let counted_cash = Money::from_rupees(counted_cash);
+
+    // The report and the status change land together; a sale still in flight
+    // writes to the shift too, so one of the two is retried or refused
+    let mongo = db_state.mongo("shifts")?;
+    let shift = run_transaction(&mongo.client, |session| {
+        let (db, hospital_id) = (mongo.db.clone(), hospital_id.clone());
+        Box::pin(async move {
+            let mut shift = shifts_collection(&db)
+                .find_one_with_session(
+                    doc! { "_id": object_id, "hospital_id": &hospital_id, "status": "open" },
+                    None,
+                    session,
+                )
+                .await?
+                .ok_or_else(|| TxnError::Rejected("Shift not found or already closed".to_string()))?;
+
+            let report = draw_up_report(&db, session, &shift, counted_cash).await?;
+            let closed_at = bson::DateTime::now();
+            let report_bson = to_bson(&report).map_err(|e| TxnError::Rejected(e.to_string()))?;
+            shifts_collection(&db)
+                .update_one_with_session(
+                    doc! { "_id": object_id, "status": "open" },
+                    doc! { "$set": { "status": "closed", "closed_at": closed_at, "z_report": report_bson } },
+                    None,
+                    session,
+                )
+                .await?;
+
+            shift.status = ShiftStatus::Closed;
+            shift.closed_at = Some(closed_at);
+            shift.z_report = Some(report);
+            Ok(shift)
+        })
+    })
+    .await?;
+
+    Ok(ShiftResponse::from(shift))
+}
+
+#[command]
+pub async fn get_shift_report(
+    shift_id: String,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<ShiftResponse, String> {
+    let db = db_state.mongo("shifts")?.db.clone();
+    find_shift(&db, &shift_id, &hospital_id).await.map(ShiftResponse::from)
+}
+
+fn render_z_report(shift: &Shift, report: &ZReport, hospital: &User) -> Result<PdfCanvas, String> {
+    let mut pdf = PdfCanvas::new("Z-Report", PageSize::A4)?;
+    let time = |value: bson::DateTime| {
+        dates::to_utc(value).with_timezone(&Local).format("%d %b %Y %H:%M").to_string()
+    };
+
+    pdf.text_centered(&hospital.hospital, 16.0, true);
+    pdf.text_centered(&hospital.address, 10.0, false);
+    pdf.gap(2.0);
+    pdf.text_centered("Z-Report (Shift Close)", 13.0, true);
+    pdf.rule();
+
+    let right = pdf.right();
+    let row = |pdf: &mut PdfCanvas, label: &str, value: String, bold: bool| {
+        pdf.ensure_space(6.0);
+        pdf.text_at(label, 10.0, pdf.left(), bold);
+        pdf.text_right(&value, 10.0, right, bold);
+        pdf.newline(10.0);
+    };
+
+    row(&mut pdf, "Cashier", shift.cashier.clone(), false);
+    row(&mut pdf, "Opened", time(shift.opened_at), false);
+    row(&mut pdf, "Closed", shift.closed_at.map(time).unwrap_or_default(), false);
+    if let Some(amended_at) = shift.amended_at {
+        row(&mut pdf, "Amended after sync", time(amended_at), true);
+    }
+    pdf.rule();
+
+    row(&mut pdf, "Invoices", report.invoice_count.to_string(), false);
+    if let (Some(first), Some(last)) = (&report.first_invoice, &report.last_invoice) {
+        row(&mut pdf, "Range", format!("{} - {}", first, last), false);
+    }
+    row(&mut pdf, "Gross sales", format!("{:.2}", report.gross_sales.rupees()), true);
+    row(&mut pdf, "Discounts", format!("{:.2}", report.discount_total.rupees()), false);
+    row(&mut pdf, "Taxable value", format!("{:.2}", report.taxable_total.rupees()), false);
+    row(&mut pdf, "GST", format!("{:.2}", report.tax_total.rupees()), false);
+    row(&mut pdf, "Credit notes", report.credit_note_count.to_string(), false);
+    row(&mut pdf, "Voids and returns", format!("{:.2}", report.returns_total.rupees()), false);
+    pdf.rule();
+
+    pdf.text("Payments", 11.0, true);
+    for mode in &report.payments.by_mode {
+        let label = match mode.mode {
+            PaymentMode::Cash => "Cash (net of change)",
+            other => other.label(),
+        };
+        row(&mut pdf, &format!("{} x{}", label, mode.count), format!("{:.2}", mode.amount.rupees()), false);
+    }
+    row(&mut pdf, "Credit receipts", format!("{:.2}", report.payments.credit_receipts.rupees()), false);
+    row(&mut pdf, "Refunds paid", format!("{:.2}", report.payments.refunds.rupees()), false);
+    row(&mut pdf, "Net collected", format!("{:.2}", report.payments.total_collected.rupees()), true);
+    pdf.rule();
+
+    pdf.text("Cash drawer", 11.0, true);
+    row(&mut pdf, "Opening float", format!("{:.2}", report.opening_float.rupees()), false);
+    row(&mut pdf, "Expected cash", format!("{:.2}", report.expected_cash.rupees()), false);
+    row(&mut pdf, "Counted cash", format!("{:.2}", report.counted_cash.rupees()), false);
+    row(&mut pdf, "Variance", format!("{:+.2}", report.variance.rupees()), true);
+
+    Ok(pdf)
+}
+
+/// Renders a closed shift's stored Z-report as a PDF and returns its path.
+#[command]
+pub async fn print_z_report(
+    shift_id: String,
+    hospital_id: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<String, String> {
+    let db = db_state.mongo("shifts")?.db.clone();
+    let shift = find_shift(&db, &shift_id, &hospital_id).await?;
+    let report = shift
+        .z_report
+        .as_ref()
+        .ok_or_else(|| "This shift has not been closed yet.".to_string())?;
+    let hospital = find_hospital(&users_collection(&db), &hospital_id).await?;
+
+    let pdf = render_z_report(&shift, report, &hospital)?;
+    let path = output_dir(&app, &config::get().output.z_reports, "z-reports")?.join(format!(
+        "z_{}_{}.pdf",
+        safe_file_name(&shift.cashier),
+        shift_id
+    ));
+    pdf.save(&path)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+    use crate::payment::Payment;
+    use serde_json::json;
+
+    fn rupees(amount: f64) -> Money {
+        Money::from_rupees(amount)
+    }
+
+    fn invoice(number: &str, grand_total: f64, payments: &[Payment]) -> Invoice {
+        let sold = TestInvoice::new(number).line("m1", "Paracetamol", "3004", 12.0, 1, grand_total);
+        sold.payments(payments).shift("s1").build()
+    }
+
+    fn payment(mode: PaymentMode, amount: f64) -> Payment {
+        Payment { mode, amount: rupees(amount), reference: None }
+    }
+
+    fn credit_note(kind: &str, invoice_number: &str, total: f64) -> CreditNote {
+        serde_json::from_value(json!({
+            "hospital_id": "h1", "credit_note_number": format!("CN-{}", invoice_number),
+            "invoice_number": invoice_number, "kind": kind, "reason": "Customer request", "lines": [],
+            "taxable_total": 0, "cgst_total": 0, "sgst_total": 0, "igst_total": 0, "round_off": 0,
+            "total": rupees(total), "shift_id": "s1", "date_created": "2026-10-19T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn z_report_totals_each_mode_and_takes_refunds_out_of_cash() {
+        let upi = Payment { reference: Some("4122".to_string()), ..payment(PaymentMode::Upi, 300.0) };
+        let invoices = vec![
+            invoice("A/1", 480.0, &[payment(PaymentMode::Cash, 500.0)]),
+            invoice("A/2", 300.0, &[upi]),
+            invoice("A/3", 300.0, &[payment(PaymentMode::Card, 200.0), payment(PaymentMode::Cash, 100.0)]),
+            invoice("A/4", 150.0, &[payment(PaymentMode::Cash, 150.0)]), // voided below
+        ];
+        let receipt: LedgerEntry = serde_json::from_value(json!({
+            "hospital_id": "h1", "customer_id": "c1", "kind": "receipt", "reference": "RCPT-1",
+            "amount": rupees(-250.0), "balance_after": 0, "mode": "cash", "payment_reference": null,
+            "shift_id": "s1", "date_created": "2026-10-19T06:00:00Z",
+        }))
+        .unwrap();
+        let notes = vec![credit_note("void", "A/4", 150.0), credit_note("return", "A/1", 80.0)];
+
+        let report = build_z_report(rupees(1000.0), &invoices, &[receipt], &notes, rupees(1740.0));
+        let by_mode = |mode| report.payments.by_mode.iter().find(|m| m.mode == mode).map(|m| (m.count, m.amount));
+        // 500 + 100 + 150 + 250 received, less 20 change
+        assert_eq!(by_mode(PaymentMode::Cash), Some((4, rupees(980.0))));
+        assert_eq!(by_mode(PaymentMode::Upi), Some((1, rupees(300.0))));
+        assert_eq!(by_mode(PaymentMode::Card), Some((1, rupees(200.0))));
+        assert_eq!(by_mode(PaymentMode::Credit), Some((0, Money::default())));
+
+        assert_eq!(report.invoice_count, 4);
+        assert_eq!((report.first_invoice.as_deref(), report.last_invoice.as_deref()), (Some("A/1"), Some("A/4")));
+        assert_eq!((report.gross_sales, report.returns_total), (rupees(1230.0), rupees(230.0)));
+        assert_eq!(report.credit_note_count, 2);
+        assert_eq!(report.payments.refunds, rupees(230.0));
+        assert_eq!(report.expected_cash, rupees(1750.0)); // 1000 float + 980 taken - 230 refunded
+        assert_eq!(report.variance, rupees(-10.0));
+        assert_eq!(invoices[0].change_due, rupees(20.0));
+        assert_eq!((report.taxable_total, report.tax_total), (rupees(1098.22), rupees(131.78)));
+    }
+
+    #[test]
+    fn empty_shift_expects_only_the_float() {
+        let report = build_z_report(rupees(500.0), &[], &[], &[], rupees(500.0));
+        assert_eq!((report.invoice_count, report.first_invoice), (0, None));
+        assert_eq!(report.expected_cash, rupees(500.0));
+        assert_eq!((report.variance, report.payments.total_collected), (Money::default(), Money::default()));
+    }
+
+    #[test]
+    fn stored_report_reads_back_in_paise_and_shows_in_rupees() {
+        let report = build_z_report(rupees(500.0), &[], &[], &[], rupees(499.5));
+        let stored = bson::to_document(&report).unwrap();
+        assert_eq!(stored.get_i64("variance").unwrap(), -50);
+        let shown = ZReportResponse::from(bson::from_document::<ZReport>(stored).unwrap());
+        assert_eq!((shown.expected_cash, shown.variance), (500.0, -0.5));
+    }
+
+    fn hospital() -> User {
+        serde_json::from_value(json!({
+            "username": "city", "name": "Dr Rao", "mobile": "9800000000", "hospital": "City Clinic",
+            "address": "MG Road", "password_hash_doc": bcrypt::hash("doc-pass", 4).unwrap(),
+            "password_hash_pharma": bcrypt::hash("pharma-pass", 4).unwrap(), "email": "rao@example.com",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn invoices_of_a_closed_shift_change_only_with_the_admin_password() {
+        let hospital = hospital();
+        assert!(authorize_closed_shift(false, &hospital, None).is_ok());
+
+        let refused = authorize_closed_shift(true, &hospital, None).unwrap_err();
+        assert_eq!(refused, "The shift this invoice was billed in is closed. Admin password is required.");
+        // The counter login is not enough
+        assert!(authorize_closed_shift(true, &hospital, Some("pharma-pass")).is_err());
+        assert!(authorize_closed_shift(true, &hospital, Some("doc-pass")).is_ok());
+    }
+
+    /// Needs a replica set in `TEST_MONGODB_URI`:
+    /// `cargo test closed_shift -- --ignored`. Uses a scratch `caton_test`
+    /// database that is dropped afterwards.
+    #[tokio::test]
+    #[ignore]
+    async fn a_closed_shift_takes_no_new_sales() {
+        let uri = std::env::var("TEST_MONGODB_URI").expect("TEST_MONGODB_URI");
+        let client = Client::with_uri_str(&uri).await.unwrap();
+        let db = client.database("caton_test");
+        db.drop(None).await.unwrap();
+
+        let shift = Shift {
+            id: None,
+            hospital_id: "h1".to_string(),
+            cashier: "Asha".to_string(),
+            opening_float: rupees(500.0),
+            status: ShiftStatus::Open,
+            opened_at: bson::DateTime::now(),
+            closed_at: None,
+            last_activity_at: None,
+            z_report: None,
+            amended_at: None,
+        };
+        let id = shifts_collection(&db).insert_one(&shift, None).await.unwrap().inserted_id;
+        let shift_id = id.as_object_id().unwrap().to_hex();
+        let sell = || {
+            run_transaction(&client, |session| {
+                let (db, shift_id) = (db.clone(), shift_id.clone());
+                Box::pin(async move { touch_open_shift(&db, session, &shift_id, "h1").await })
+            })
+        };
+
+        sell().await.unwrap();
+        assert!(!shift_is_closed(&db, &shift_id, "h1").await.unwrap());
+        let close = doc! { "$set": { "status": "closed" } };
+        shifts_collection(&db).update_one(doc! { "_id": &id }, close, None).await.unwrap();
+
+        let refused = sell().await.unwrap_err();
+        let closed = shift_is_closed(&db, &shift_id, "h1").await.unwrap();
+        db.drop(None).await.unwrap();
+        assert_eq!(refused, "This shift is closed; open a new shift to continue.");
+        assert!(closed);
+    }
+}
@@ -0,0 +1,869 @@
+// src-tauri/src/sqlite.rs
+//! Local SQLite storage for pharmacies that run without MongoDB. Medicines
+//! are kept in columns so stock can be changed in place; appointments,
+//! accounts, invoices and discount rules are stored as JSON next to the
+//! columns they are looked up by. IDs are ObjectId hex strings, so the UI sees the same IDs
+//! on either backend. The schema lives in `migrations/sqlite`.
+use crate::billing::{financial_year, format_document_number, Invoice};
+use crate::clinical::{ClinicalNotes, Vitals};
+use crate::commands::{Appointment, Medicine};
+use crate::dates;
+use crate::discount::DiscountRule;
+use crate::model::User;
+use crate::money::Money;
+use crate::repository::{
+    AppointmentQuery, AppointmentRepository, AppointmentSort, DiscountRuleRepository, InvoiceInsert, InvoiceRepository,
+    MedicineChanges, MedicineQuery, MedicineRepository, Page, PasswordKind, UpdateOutcome, UserRepository,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, SecondsFormat};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::de::DeserializeOwned;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{FromRow, QueryBuilder, Sqlite};
+use std::time::{Duration, Instant};
+
+const MAX_CONNECTIONS: u32 = 4;
+
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+    path: String,
+}
+
+fn db_error(error: sqlx::Error) -> String {
+    format!("Database error: {}", error)
+}
+
+fn from_json<T: DeserializeOwned>(data: &str) -> Result<T, String> {
+    serde_json::from_str(data).map_err(|e| format!("Unreadable record: {}", e))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| e.to_string())
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// `text` as a LIKE pattern matching it anywhere, with wildcards escaped.
+fn contains_pattern(text: &str) -> String {
+    format!("%{}%", escape_like(text))
+}
+
+fn prefix_pattern(text: &str) -> String {
+    format!("{}%", escape_like(text))
+}
+
+fn push_page(sql: &mut QueryBuilder<'_, Sqlite>, page: Page) {
+    // A LIMIT of -1 means no limit
+    let limit = page.limit.map_or(-1, |limit| limit as i64);
+    sql.push(" LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(page.skip as i64);
+}
+
+impl SqliteStore {
+    /// Opens the database file, creating it if needed, and applies any
+    /// pending migrations.
+    pub async fn open(path: &str) -> Result<Self, String> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_with(options)
+            .await
+            .map_err(|e| format!("Could not open SQLite database {}: {}", path, e))?;
+        Self::migrate(pool, path).await
+    }
+
+    /// A private in-memory database, for tests.
+    #[cfg(test)]
+    pub async fn open_in_memory() -> Result<Self, String> {
+        // Each connection to :memory: is its own database, so keep one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .map_err(db_error)?;
+        Self::migrate(pool, ":memory:").await
+    }
+
+    async fn migrate(pool: SqlitePool, path: &str) -> Result<Self, String> {
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| format!("Failed to migrate SQLite database {}: {}", path, e))?;
+        Ok(SqliteStore { pool, path: path.to_string() })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub async fn ping(&self) -> Result<Duration, String> {
+        let started = Instant::now();
+        sqlx::query("SELECT 1").execute(&self.pool).await.map_err(db_error)?;
+        Ok(started.elapsed())
+    }
+
+    /// Saves a bill the way the MongoDB transaction does: takes the stock
+    /// off each batch, numbers the invoice from the hospital's series and
+    /// stores it, all or nothing.
+    pub async fn save_invoice(&self, mut invoice: Invoice, prefix: &str, today: NaiveDate) -> Result<Invoice, String> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        for line in &invoice.lines {
+            let result = sqlx::query(
+                "UPDATE medicines SET quantity = quantity - ? WHERE id = ? AND user_id = ? AND quantity >= ?",
+            )
+            .bind(line.quantity as i64)
+            .bind(&line.medicine_id)
+            .bind(&invoice.hospital_id)
+            .bind(line.quantity as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            if result.rows_affected() == 0 {
+                // Dropping the transaction rolls back what was taken so far
+                return Err(format!("Insufficient stock for {} (batch {}).", line.name, line.batch_number));
+            }
+        }
+
+        let year = financial_year(today);
+        let sequence: i64 = sqlx::query_scalar(
+            "INSERT INTO counters (id, seq) VALUES (?, 1) \
+             ON CONFLICT (id) DO UPDATE SET seq = seq + 1 RETURNING seq",
+        )
+        .bind(format!("{}:invoice:{}", invoice.hospital_id, year))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        invoice.invoice_number = format_document_number(prefix, &year, sequence);
+
+        let id = ObjectId::new();
+        invoice.id = Some(id);
+        sqlx::query("INSERT INTO invoices (id, hospital_id, invoice_number, date_created, data) VALUES (?, ?, ?, ?, ?)")
+            .bind(id.to_hex())
+            .bind(&invoice.hospital_id)
+            .bind(&invoice.invoice_number)
+            .bind(timestamp(invoice.date_created))
+            .bind(to_json(&invoice)?)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(invoice)
+    }
+}
+
+fn day(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn stored_date(text: &str) -> Result<NaiveDate, String> {
+    dates::parse_legacy_date(text).ok_or_else(|| format!("Invalid stored date: {}", text))
+}
+
+/// Fixed-width UTC, so `date_created` columns compare correctly as text.
+fn timestamp(date: DateTime) -> String {
+    dates::to_utc(date).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[derive(FromRow)]
+struct MedicineRow {
+    id: String,
+    user_id: String,
+    name: String,
+    batch_number: String,
+    expiry_date: String,
+    quantity: i64,
+    purchase_price: i64, // paise
+    selling_price: i64,
+    wholesaler_name: String,
+    purchase_date: String,
+    hsn_code: Option<String>,
+    gst_rate: Option<f64>,
+    tax_inclusive: bool,
+    generic_name: Option<String>,
+    brand_names: String, // JSON array
+    composition: Option<String>,
+    barcode: Option<String>,
+}
+
+impl MedicineRow {
+    fn into_medicine(self) -> Result<Medicine, String> {
+        Ok(Medicine {
+            id: Some(ObjectId::parse_str(&self.id).map_err(|_| format!("Invalid medicine ID {}", self.id))?),
+            user_id: self.user_id,
+            name: self.name,
+            batch_number: self.batch_number,
+            expiry_date: stored_date(&self.expiry_date)?,
+            quantity: u32::try_from(self.quantity).unwrap_or(0),
+            purchase_price: Money::from_paise(self.purchase_price),
+            selling_price: Money::from_paise(self.selling_price),
+            wholesaler_name: self.wholesaler_name,
+            purchase_date: stored_date(&self.purchase_date)?,
+            hsn_code: self.hsn_code,
+            gst_rate: self.gst_rate,
+            tax_inclusive: self.tax_inclusive,
+            generic_name: self.generic_name,
+            brand_names: from_json(&self.brand_names)?,
+            composition: self.composition,
+            barcode: self.barcode,
+        })
+    }
+}
+
+fn into_medicines(rows: Vec<MedicineRow>) -> Result<Vec<Medicine>, String> {
+    rows.into_iter().map(MedicineRow::into_medicine).collect()
+}
+
+#[async_trait]
+impl MedicineRepository for SqliteStore {
+    async fn insert_medicine(&self, medicine: Medicine) -> Result<ObjectId, String> {
+        let id = ObjectId::new();
+        sqlx::query(
+            "INSERT INTO medicines (id, user_id, name, batch_number, expiry_date, quantity, purchase_price, \
+             selling_price, wholesaler_name, purchase_date, hsn_code, gst_rate, tax_inclusive, generic_name, \
+             brand_names, composition, barcode) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id.to_hex())
+        .bind(&medicine.user_id)
+        .bind(&medicine.name)
+        .bind(&medicine.batch_number)
+        .bind(day(medicine.expiry_date))
+        .bind(medicine.quantity as i64)
+        .bind(medicine.purchase_price.paise())
+        .bind(medicine.selling_price.paise())
+        .bind(&medicine.wholesaler_name)
+        .bind(day(medicine.purchase_date))
+        .bind(&medicine.hsn_code)
+        .bind(medicine.gst_rate)
+        .bind(medicine.tax_inclusive)
+        .bind(&medicine.generic_name)
+        .bind(to_json(&medicine.brand_names)?)
+        .bind(&medicine.composition)
+        .bind(&medicine.barcode)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(id)
+    }
+
+    async fn find_medicine(&self, id: ObjectId, hospital_id: Option<&str>) -> Result<Option<Medicine>, String> {
+        sqlx::query_as::<_, MedicineRow>("SELECT * FROM medicines WHERE id = ? AND (? IS NULL OR user_id = ?)")
+            .bind(id.to_hex())
+            .bind(hospital_id)
+            .bind(hospital_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?
+            .map(MedicineRow::into_medicine)
+            .transpose()
+    }
+
+    async fn find_medicines(&self, ids: &[ObjectId], hospital_id: &str) -> Result<Vec<Medicine>, String> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM medicines WHERE user_id = ");
+        query.push_bind(hospital_id).push(" AND id IN (");
+        let mut list = query.separated(", ");
+        for id in ids {
+            list.push_bind(id.to_hex());
+        }
+        list.push_unseparated(")");
+
+        let rows = query.build_query_as::<MedicineRow>().fetch_all(&self.pool).await.map_err(db_error)?;
+        into_medicines(rows)
+    }
+
+    async fn list_medicines(&self, hospital_id: &str, query: &MedicineQuery) -> Result<Vec<Medicine>, String> {
+        let text = query.text.as_deref().map(str::to_lowercase);
+        let mut sql = QueryBuilder::<Sqlite>::new("SELECT * FROM medicines WHERE user_id = ");
+        sql.push_bind(hospital_id);
+        if let Some(text) = &text {
+            // LIKE ignores ASCII case, as the MongoDB regex search does
+            sql.push(" AND name LIKE ").push_bind(contains_pattern(text)).push(" ESCAPE '\\'");
+        }
+        sql.push(" ORDER BY ");
+        if let (true, Some(text)) = (query.ranked(), &text) {
+            sql.push("CASE WHEN lower(name) = ")
+                .push_bind(text.clone())
+                .push(" THEN 0 WHEN instr(lower(name), ")
+                .push_bind(text.clone())
+                .push(") = 1 THEN 1 ELSE 2 END, ");
+        }
+        sql.push(query.sort.field()).push(if query.descending { " DESC" } else { " ASC" }).push(", name, id");
+        push_page(&mut sql, query.page);
+
+        let rows = sql.build_query_as::<MedicineRow>().fetch_all(&self.pool).await.map_err(db_error)?;
+        into_medicines(rows)
+    }
+
+    async fn update_medicine(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        batch_number: Option<&str>,
+        changes: &MedicineChanges,
+    ) -> Result<UpdateOutcome, String> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let row = sqlx::query_as::<_, MedicineRow>(
+            "SELECT * FROM medicines WHERE id = ? AND user_id = ? AND (? IS NULL OR batch_number = ?)",
+        )
+        .bind(id.to_hex())
+        .bind(hospital_id)
+        .bind(batch_number)
+        .bind(batch_number)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        let Some(row) = row else {
+            return Ok(UpdateOutcome { matched: false, modified: false });
+        };
+
+        let before = row.into_medicine()?;
+        let mut medicine = before.clone();
+        changes.apply(&mut medicine);
+        if medicine == before {
+            return Ok(UpdateOutcome { matched: true, modified: false });
+        }
+
+        sqlx::query(
+            "UPDATE medicines SET name = ?, batch_number = ?, expiry_date = ?, quantity = ?, purchase_price = ?, \
+             selling_price = ?, wholesaler_name = ?, purchase_date = ?, hsn_code = ?, gst_rate = ?, \
+             tax_inclusive = ?, generic_name = ?, brand_names = ?, composition = ?, barcode = ? WHERE id = ?",
+        )
+        .bind(&medicine.name)
+        .bind(&medicine.batch_number)
+        .bind(day(medicine.expiry_date))
+        .bind(medicine.quantity as i64)
+        .bind(medicine.purchase_price.paise())
+        .bind(medicine.selling_price.paise())
+        .bind(&medicine.wholesaler_name)
+        .bind(day(medicine.purchase_date))
+        .bind(&medicine.hsn_code)
+        .bind(medicine.gst_rate)
+        .bind(medicine.tax_inclusive)
+        .bind(&medicine.generic_name)
+        .bind(to_json(&medicine.brand_names)?)
+        .bind(&medicine.composition)
+        .bind(&medicine.barcode)
+        .bind(id.to_hex())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(UpdateOutcome { matched: true, modified: true })
+    }
+
+    async fn take_stock(&self, id: ObjectId, batch_number: &str, quantity: u32) -> Result<bool, String> {
+        let result = sqlx::query(
+            "UPDATE medicines SET quantity = quantity - ? WHERE id = ? AND batch_number = ? AND quantity >= ?",
+        )
+        .bind(quantity as i64)
+        .bind(id.to_hex())
+        .bind(batch_number)
+        .bind(quantity as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn adjust_quantity(&self, id: ObjectId, hospital_id: &str, delta: i64) -> Result<Option<u32>, String> {
+        let quantity: Option<i64> = sqlx::query_scalar(
+            "UPDATE medicines SET quantity = MAX(0, quantity + ?) WHERE id = ? AND user_id = ? RETURNING quantity",
+        )
+        .bind(delta)
+        .bind(id.to_hex())
+        .bind(hospital_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(quantity.map(|q| u32::try_from(q).unwrap_or(u32::MAX)))
+    }
+
+    async fn delete_medicine(&self, id: ObjectId, hospital_id: &str, batch_number: Option<&str>) -> Result<bool, String> {
+        let result = sqlx::query(
+            "DELETE FROM medicines WHERE id = ? AND user_id = ? AND (? IS NULL OR batch_number = ?)",
+        )
+        .bind(id.to_hex())
+        .bind(hospital_id)
+        .bind(batch_number)
+        .bind(batch_number)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl AppointmentRepository for SqliteStore {
+    async fn insert_appointment(&self, appointment: Appointment) -> Result<(), String> {
+        sqlx::query(
+            "INSERT INTO appointments (id, hospital_id, date_created, follow_up_date, has_clinical, data) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(appointment.id.to_hex())
+        .bind(&appointment.hospital_id)
+        .bind(timestamp(appointment.date_created))
+        .bind(appointment.follow_up_date.map(day))
+        .bind(appointment.vitals.is_some() || appointment.notes.is_some())
+        .bind(to_json(&appointment)?)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Database insert error: {}", e))?;
+        Ok(())
+    }
+
+    async fn list_appointments(&self, hospital_id: &str, query: &AppointmentQuery) -> Result<Vec<Appointment>, String> {
+        let mut sql = QueryBuilder::<Sqlite>::new("SELECT data FROM appointments WHERE hospital_id = ");
+        sql.push_bind(hospital_id);
+        if let Some(text) = &query.text {
+            sql.push(" AND (json_extract(data, '$.patient_name') LIKE ")
+                .push_bind(contains_pattern(text))
+                .push(" ESCAPE '\\' OR json_extract(data, '$.mobile') LIKE ")
+                .push_bind(prefix_pattern(text))
+                .push(" ESCAPE '\\')");
+        }
+        sql.push(match query.sort {
+            AppointmentSort::Newest => " ORDER BY date_created DESC, id DESC",
+            AppointmentSort::Oldest => " ORDER BY date_created, id",
+            AppointmentSort::Patient => " ORDER BY json_extract(data, '$.patient_name'), date_created DESC, id DESC",
+        });
+        push_page(&mut sql, query.page);
+
+        let rows: Vec<String> = sql
+            .build_query_scalar()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+
+    async fn delete_stale_appointments(
+        &self,
+        hospital_id: &str,
+        cutoff: DateTime,
+        today: NaiveDate,
+    ) -> Result<u64, String> {
+        let result = sqlx::query(
+            "DELETE FROM appointments WHERE hospital_id = ? AND date_created < ? AND has_clinical = 0 \
+             AND (follow_up_date IS NULL OR follow_up_date < ?)",
+        )
+        .bind(hospital_id)
+        .bind(timestamp(cutoff))
+        .bind(day(today))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Error deleting old appointments: {}", e))?;
+        Ok(result.rows_affected())
+    }
+
+    async fn update_clinical(
+        &self,
+        id: ObjectId,
+        hospital_id: &str,
+        vitals: Option<&Vitals>,
+        notes: Option<&ClinicalNotes>,
+    ) -> Result<bool, String> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let data: Option<String> = sqlx::query_scalar("SELECT data FROM appointments WHERE id = ? AND hospital_id = ?")
+            .bind(id.to_hex())
+            .bind(hospital_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        let Some(data) = data else { return Ok(false) };
+
+        let mut appointment: Appointment = from_json(&data)?;
+        if let Some(vitals) = vitals {
+            appointment.vitals = Some(vitals.clone());
+        }
+        if let Some(notes) = notes {
+            appointment.notes = Some(notes.clone());
+        }
+        sqlx::query("UPDATE appointments SET has_clinical = ?, data = ? WHERE id = ?")
+            .bind(appointment.vitals.is_some() || appointment.notes.is_some())
+            .bind(to_json(&appointment)?)
+            .bind(id.to_hex())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    async fn vitals_history(&self, hospital_id: &str, mobile: &str, limit: usize) -> Result<Vec<Appointment>, String> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT data FROM appointments WHERE hospital_id = ? AND json_extract(data, '$.mobile') = ? \
+             AND json_extract(data, '$.vitals') IS NOT NULL ORDER BY date_created DESC, id DESC LIMIT ?",
+        )
+        .bind(hospital_id)
+        .bind(mobile)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+}
+
+impl SqliteStore {
+    async fn find_user_where(&self, column: &str, value: &str) -> Result<Option<User>, String> {
+        // `column` is one of ours, never user input
+        let data: Option<String> = sqlx::query_scalar(&format!("SELECT data FROM users WHERE {} = ?", column))
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        data.map(|data| from_json(&data)).transpose()
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteStore {
+    async fn find_user_by_id(&self, id: ObjectId) -> Result<Option<User>, String> {
+        self.find_user_where("id", &id.to_hex()).await
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        self.find_user_where("username", username).await
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        self.find_user_where("email", email).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, String> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT data FROM users")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Database query error: {}", e))?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+
+    async fn insert_user(&self, mut user: User) -> Result<(), String> {
+        let id = ObjectId::new();
+        user.id = Some(id);
+        sqlx::query("INSERT INTO users (id, username, email, data) VALUES (?, ?, ?, ?)")
+            .bind(id.to_hex())
+            .bind(&user.username)
+            .bind(&user.email)
+            .bind(to_json(&user)?)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to create user: {}", e))?;
+        Ok(())
+    }
+
+    async fn replace_user(&self, user: &User) -> Result<(), String> {
+        let id = user.id.ok_or_else(|| "Hospital not found".to_string())?;
+        sqlx::query("UPDATE users SET username = ?, email = ?, data = ? WHERE id = ?")
+            .bind(&user.username)
+            .bind(&user.email)
+            .bind(to_json(user)?)
+            .bind(id.to_hex())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update profile: {}", e))?;
+        Ok(())
+    }
+
+    async fn set_otp(&self, email: &str, otp: Option<(&str, DateTime)>) -> Result<(), String> {
+        let expiry = otp.map(|(_, expiry)| to_json(&expiry)).transpose()?;
+        sqlx::query("UPDATE users SET data = json_set(data, '$.otp', ?, '$.otp_expiry', json(?)) WHERE email = ?")
+            .bind(otp.map(|(otp, _)| otp))
+            .bind(expiry)
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    async fn set_password_hash(&self, email: &str, kind: PasswordKind, hash: &str) -> Result<(), String> {
+        sqlx::query("UPDATE users SET data = json_set(data, ?, ?) WHERE email = ?")
+            .bind(format!("$.{}", kind.field()))
+            .bind(hash)
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update password: {}", e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InvoiceRepository for SqliteStore {
+    async fn find_invoice(&self, hospital_id: &str, invoice_number: &str) -> Result<Option<Invoice>, String> {
+        let data: Option<String> =
+            sqlx::query_scalar("SELECT data FROM invoices WHERE hospital_id = ? AND invoice_number = ?")
+                .bind(hospital_id)
+                .bind(invoice_number)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(db_error)?;
+        data.map(|data| from_json(&data)).transpose()
+    }
+
+    async fn list_invoices(&self, hospital_id: &str, from: DateTime, to: DateTime) -> Result<Vec<Invoice>, String> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT data FROM invoices WHERE hospital_id = ? AND date_created >= ? AND date_created < ? \
+             ORDER BY date_created DESC",
+        )
+        .bind(hospital_id)
+        .bind(timestamp(from))
+        .bind(timestamp(to))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+
+    async fn insert_invoice(&self, invoice: &Invoice) -> Result<InvoiceInsert, String> {
+        let mut invoice = invoice.clone();
+        let id = *invoice.id.get_or_insert_with(ObjectId::new);
+        let result = sqlx::query(
+            "INSERT INTO invoices (id, hospital_id, invoice_number, date_created, data) VALUES (?, ?, ?, ?, ?) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id.to_hex())
+        .bind(&invoice.hospital_id)
+        .bind(&invoice.invoice_number)
+        .bind(timestamp(invoice.date_created))
+        .bind(to_json(&invoice)?)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        if result.rows_affected() > 0 {
+            return Ok(InvoiceInsert::Stored);
+        }
+        let stored: Option<i64> = sqlx::query_scalar("SELECT 1 FROM invoices WHERE id = ?")
+            .bind(id.to_hex())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(if stored.is_some() { InvoiceInsert::AlreadyStored } else { InvoiceInsert::NumberTaken })
+    }
+}
+
+#[async_trait]
+impl DiscountRuleRepository for SqliteStore {
+    async fn list_rules(&self, hospital_id: &str, active_only: bool) -> Result<Vec<DiscountRule>, String> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT data FROM discount_rules WHERE hospital_id = ? AND (active = 1 OR NOT ?) ORDER BY name",
+        )
+        .bind(hospital_id)
+        .bind(active_only)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+
+    async fn insert_rule(&self, mut rule: DiscountRule) -> Result<ObjectId, String> {
+        let id = ObjectId::new();
+        rule.id = Some(id);
+        sqlx::query("INSERT INTO discount_rules (id, hospital_id, name, active, data) VALUES (?, ?, ?, ?, ?)")
+            .bind(id.to_hex())
+            .bind(&rule.hospital_id)
+            .bind(&rule.name)
+            .bind(rule.active)
+            .bind(to_json(&rule)?)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Database insert error: {}", e))?;
+        Ok(id)
+    }
+
+    async fn replace_rule(&self, rule: &DiscountRule) -> Result<bool, String> {
+        let Some(id) = rule.id else { return Ok(false) };
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let data: Option<String> =
+            sqlx::query_scalar("SELECT data FROM discount_rules WHERE id = ? AND hospital_id = ?")
+                .bind(id.to_hex())
+                .bind(&rule.hospital_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        let Some(data) = data else { return Ok(false) };
+        let stored: DiscountRule = from_json(&data)?;
+        let rule = DiscountRule { date_created: stored.date_created, ..rule.clone() };
+        sqlx::query("UPDATE discount_rules SET name = ?, active = ?, data = ? WHERE id = ?")
+            .bind(&rule.name)
+            .bind(rule.active)
+            .bind(to_json(&rule)?)
+            .bind(id.to_hex())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Database update error: {}", e))?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    async fn delete_rule(&self, id: ObjectId, hospital_id: &str) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM discount_rules WHERE id = ? AND hospital_id = ?")
+            .bind(id.to_hex())
+            .bind(hospital_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+    use crate::discount::{Discount, DiscountKind};
+    use crate::repository::MedicineSort;
+    use crate::stock;
+    use crate::user::{issue_otp, signup_user, validate_otp};
+
+    #[tokio::test]
+    async fn stock_rules_hold_on_sqlite() {
+        let store = SqliteStore::open_in_memory().await.unwrap();
+        let medicine = Medicine {
+            id: None,
+            user_id: "h1".to_string(),
+            name: "Paracetamol 100%".to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity: 5,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        };
+        let id = stock::add_medicine(&store, medicine.clone()).await.unwrap().to_hex();
+
+        stock::reduce_batch(&store, &id, "B1", 3).await.unwrap();
+        assert!(stock::reduce_batch(&store, &id, "B1", 3).await.unwrap_err().message().starts_with("Insufficient stock"));
+        let text = |text: &str| MedicineQuery { text: Some(text.to_string()), ..Default::default() };
+        assert_eq!(stock::search(&store, text("PARACETAMOL 100%"), "h1").await.unwrap().len(), 1);
+        assert!(stock::search(&store, text("_"), "h1").await.unwrap().is_empty());
+        let syrup = Medicine { name: "Paracetamol".to_string(), quantity: 9, ..medicine };
+        stock::add_medicine(&store, syrup).await.unwrap();
+        let names = |medicines: Vec<Medicine>| medicines.into_iter().map(|m| m.name).collect::<Vec<_>>();
+        let ranked = stock::search(&store, text("paracetamol"), "h1").await.unwrap();
+        assert_eq!(names(ranked), ["Paracetamol", "Paracetamol 100%"]);
+        let page = Page { skip: 1, limit: Some(1) };
+        let page = MedicineQuery { sort: MedicineSort::Quantity, page, ..Default::default() };
+        assert_eq!(names(stock::search(&store, page, "h1").await.unwrap()), ["Paracetamol"]);
+
+        let price = MedicineChanges { selling_price: Some(Money::from_rupees(10.0)), ..Default::default() };
+        assert!(stock::update_stock(&store, &id, "h1", price).await.unwrap_err().message().starts_with("No changes"));
+        let price = MedicineChanges { selling_price: Some(Money::from_rupees(11.5)), ..Default::default() };
+        stock::update_stock(&store, &id, "h1", price).await.unwrap();
+        let stored = stock::get_medicine(&store, &id).await.unwrap();
+        assert_eq!((stored.quantity, stored.selling_price.paise()), (2, 1150));
+    }
+
+    #[tokio::test]
+    async fn discount_rules_are_kept_with_their_creation_date() {
+        let store = SqliteStore::open_in_memory().await.unwrap();
+        let rule = |name: &str, active: bool| DiscountRule {
+            id: None,
+            hospital_id: "h1".to_string(),
+            name: name.to_string(),
+            customer_category: Some("senior_citizen".to_string()),
+            min_bill_value: None,
+            medicine_name: None,
+            discount: Discount { kind: DiscountKind::Percent, value: 10.0 },
+            active,
+            date_created: DateTime::parse_rfc3339_str("2026-10-01T04:00:00Z").unwrap(),
+        };
+        let senior = store.insert_rule(rule("Senior", true)).await.unwrap();
+        store.insert_rule(rule("Diwali", false)).await.unwrap();
+
+        let names = |rules: Vec<DiscountRule>| rules.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        assert_eq!(names(store.list_rules("h1", false).await.unwrap()), ["Diwali", "Senior"]);
+        assert_eq!(names(store.list_rules("h1", true).await.unwrap()), ["Senior"]);
+        assert!(store.list_rules("h2", false).await.unwrap().is_empty());
+
+        let edited = DiscountRule { id: Some(senior), date_created: DateTime::now(), ..rule("Senior 15", true) };
+        assert!(store.replace_rule(&edited).await.unwrap());
+        let stored = store.list_rules("h1", true).await.unwrap().remove(0);
+        assert_eq!(stored.name, "Senior 15");
+        assert_eq!(stored.date_created.try_to_rfc3339_string().unwrap(), "2026-10-01T04:00:00Z");
+        assert!(!store.replace_rule(&DiscountRule { hospital_id: "h2".to_string(), ..edited }).await.unwrap());
+
+        assert!(store.delete_rule(senior, "h1").await.unwrap());
+        assert!(!store.delete_rule(senior, "h1").await.unwrap());
+        assert_eq!(names(store.list_rules("h1", false).await.unwrap()), ["Diwali"]);
+    }
+
+    #[tokio::test]
+    async fn invoice_numbers_run_per_hospital_and_financial_year_without_gaps() {
+        let store = SqliteStore::open_in_memory().await.unwrap();
+        let medicine = Medicine {
+            id: None,
+            user_id: "h1".to_string(),
+            name: "Paracetamol".to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity: 12,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        };
+        let id = stock::add_medicine(&store, medicine).await.unwrap().to_hex();
+        let sale = |quantity| TestInvoice::new("").line(&id, "Paracetamol", "3004", 12.0, quantity, 10.0).build();
+        let save = |invoice: Invoice, today: NaiveDate| store.save_invoice(invoice, "INV", today);
+        let march_31 = NaiveDate::from_ymd_opt(2027, 3, 31).unwrap();
+        let april_1 = NaiveDate::from_ymd_opt(2027, 4, 1).unwrap();
+
+        // Counters billing at once still get one number each
+        let saved = futures::future::join_all((0..8).map(|_| save(sale(1), march_31))).await;
+        let mut numbers: Vec<String> = saved.into_iter().map(|i| i.unwrap().invoice_number).collect();
+        numbers.sort();
+        let expected: Vec<String> = (1..=8).map(|n| format_document_number("INV", "2026-27", n)).collect();
+        assert_eq!(numbers, expected);
+
+        // A sale that fails hands its number back
+        assert!(save(sale(5), march_31).await.unwrap_err().starts_with("Insufficient stock"));
+        assert_eq!(save(sale(1), march_31).await.unwrap().invoice_number, "INV/2026-27/000009");
+        assert_eq!(save(sale(1), april_1).await.unwrap().invoice_number, "INV/2027-28/000001");
+        let elsewhere = TestInvoice::new("").hospital("h2").build();
+        assert_eq!(save(elsewhere, march_31).await.unwrap().invoice_number, "INV/2026-27/000001");
+    }
+
+    #[tokio::test]
+    async fn accounts_keep_otps_in_the_stored_json() {
+        let store = SqliteStore::open_in_memory().await.unwrap();
+        signup_user(&store, "city", "Dr Rao", "9800000000", "City Clinic", "MG Road", "doc", "pharma", "rao@example.com")
+            .await
+            .unwrap();
+
+        let otp = issue_otp(&store, "rao@example.com").await.unwrap();
+        validate_otp(&store, "rao@example.com", &otp).await.unwrap();
+        let user = store.find_user_by_email("rao@example.com").await.unwrap().unwrap();
+        assert_eq!((user.otp, user.otp_expiry), (None, None));
+        assert_eq!(store.find_user_by_id(user.id.unwrap()).await.unwrap().unwrap().username, "city");
+    }
+}
@@ -0,0 +1,279 @@
+// src-tauri/src/stock.rs
+//! Stock rules behind the medicine commands, written against
+//! `MedicineRepository` so they run the same on MongoDB and in tests.
+use crate::commands::{Medicine, MedicineResponse, Wholesaler};
+use crate::error::AppError;
+use crate::repository::{MedicineChanges, MedicineQuery, MedicineRepository, MedicineSort};
+use crate::tax::validate_gst_rate;
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+use std::collections::BTreeMap;
+
+fn parse_medicine_id(id: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(id).map_err(|_| AppError::invalid("medicine_id", "Invalid medicine ID"))
+}
+
+fn clean_text(text: Option<String>) -> Option<String> {
+    text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+fn clean_names(names: Vec<String>) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if !cleaned.iter().any(|c| c.eq_ignore_ascii_case(name)) {
+            cleaned.push(name.to_string());
+        }
+    }
+    cleaned
+}
+
+/// Validates and stores a new batch; returns its ID.
+pub async fn add_medicine(repo: &dyn MedicineRepository, mut medicine: Medicine) -> Result<ObjectId, AppError> {
+    if medicine.name.trim().is_empty() {
+        return Err(AppError::invalid("name", "Medicine name and batch number are required."));
+    }
+    if medicine.batch_number.trim().is_empty() {
+        return Err(AppError::invalid("batch_number", "Medicine name and batch number are required."));
+    }
+    if let Some(rate) = medicine.gst_rate {
+        validate_gst_rate(rate).map_err(|e| AppError::invalid("gst_rate", &e))?;
+    }
+    medicine.id = None;
+    medicine.hsn_code = clean_text(medicine.hsn_code);
+    medicine.generic_name = clean_text(medicine.generic_name);
+    medicine.brand_names = clean_names(medicine.brand_names);
+    medicine.composition = clean_text(medicine.composition);
+    medicine.barcode = clean_text(medicine.barcode);
+    Ok(repo.insert_medicine(medicine).await?)
+}
+
+/// A hospital's stock grouped by purchase (wholesaler and date), oldest
+/// purchase first, optionally only the medicines whose name contains `name`.
+pub async fn stock_by_wholesaler(
+    repo: &dyn MedicineRepository,
+    hospital_id: &str,
+    name: Option<&str>,
+) -> Result<Vec<Wholesaler>, AppError> {
+    let query = MedicineQuery {
+        text: name.map(str::trim).filter(|name| !name.is_empty()).map(str::to_string),
+        sort: MedicineSort::Name,
+        ..Default::default()
+    };
+    let mut groups: BTreeMap<(NaiveDate, String), Vec<MedicineResponse>> = BTreeMap::new();
+    for medicine in repo.list_medicines(hospital_id, &query).await? {
+        let key = (medicine.purchase_date, medicine.wholesaler_name.clone());
+        groups.entry(key).or_default().push(medicine.into());
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|((purchase_date, wholesaler_name), medicines)| Wholesaler {
+            wholesaler_id: hospital_id.to_string(),
+            wholesaler_name,
+            purchase_date,
+            medicines,
+        })
+        .collect())
+}
+
+/// Takes `quantity` off a batch, refusing to go below zero.
+pub async fn reduce_batch(
+    repo: &dyn MedicineRepository,
+    id: &str,
+    batch_number: &str,
+    quantity: u32,
+) -> Result<(), AppError> {
+    let object_id = parse_medicine_id(id)?;
+    if quantity == 0 {
+        return Err(AppError::invalid("quantity", "Quantity must be at least 1."));
+    }
+    if repo.take_stock(object_id, batch_number, quantity).await? {
+        return Ok(());
+    }
+    match repo.find_medicine(object_id, None).await? {
+        Some(medicine) if medicine.batch_number == batch_number => Err(AppError::insufficient_stock(
+            &medicine.name,
+            &medicine.batch_number,
+            medicine.quantity,
+        )),
+        _ => Err(AppError::not_found("No matching medicine found.")),
+    }
+}
+
+/// Edits a hospital's medicine; at least one field must change.
+pub async fn update_stock(
+    repo: &dyn MedicineRepository,
+    medicine_id: &str,
+    hospital_id: &str,
+    mut changes: MedicineChanges,
+) -> Result<(), AppError> {
+    if let Some(rate) = changes.gst_rate {
+        validate_gst_rate(rate).map_err(|e| AppError::invalid("gst_rate", &e))?;
+    }
+    let object_id = parse_medicine_id(medicine_id)?;
+    if repo.find_medicine(object_id, Some(hospital_id)).await?.is_none() {
+        return Err(AppError::not_found("No matching document found."));
+    }
+
+    let trim = |text: Option<String>| text.map(|t| t.trim().to_string());
+    changes.hsn_code = trim(changes.hsn_code);
+    changes.generic_name = trim(changes.generic_name);
+    changes.brand_names = changes.brand_names.map(clean_names);
+    changes.composition = trim(changes.composition);
+    changes.barcode = trim(changes.barcode);
+    if changes.is_empty() {
+        return Err(AppError::validation("No fields to update."));
+    }
+    let outcome = repo.update_medicine(object_id, hospital_id, None, &changes).await?;
+    if !outcome.modified {
+        return Err(AppError::conflict("No changes were made (value may be the same)."));
+    }
+    Ok(())
+}
+
+/// Edits one batch of a hospital's medicine.
+pub async fn update_batch(
+    repo: &dyn MedicineRepository,
+    medicine_id: &str,
+    batch_number: &str,
+    hospital_id: &str,
+    changes: MedicineChanges,
+) -> Result<(), AppError> {
+    let object_id = parse_medicine_id(medicine_id)?;
+    if changes.is_empty() {
+        return Err(AppError::validation("No fields to update."));
+    }
+    let outcome = repo.update_medicine(object_id, hospital_id, Some(batch_number), &changes).await?;
+    if !outcome.matched {
+        return Err(AppError::not_found("No matching batch found."));
+    }
+    Ok(())
+}
+
+/// Removes a hospital's medicine, or only the given batch of it.
+pub async fn delete_medicine(
+    repo: &dyn MedicineRepository,
+    medicine_id: &str,
+    batch_number: Option<&str>,
+    hospital_id: &str,
+) -> Result<(), AppError> {
+    let object_id = parse_medicine_id(medicine_id)?;
+    if repo.delete_medicine(object_id, hospital_id, batch_number).await? {
+        Ok(())
+    } else {
+        Err(AppError::not_found("No matching medicine found."))
+    }
+}
+
+/// A hospital's medicines whose name contains the query text, ignoring
+/// case. Blank text lists every medicine.
+pub async fn search(
+    repo: &dyn MedicineRepository,
+    mut query: MedicineQuery,
+    hospital_id: &str,
+) -> Result<Vec<Medicine>, AppError> {
+    query.text = query.text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+    Ok(repo.list_medicines(hospital_id, &query).await?)
+}
+
+pub async fn get_medicine(repo: &dyn MedicineRepository, medicine_id: &str) -> Result<Medicine, AppError> {
+    repo.find_medicine(parse_medicine_id(medicine_id)?, None)
+        .await?
+        .ok_or_else(|| AppError::not_found("Medicine not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::repository::memory::MemoryStore;
+    use crate::repository::Page;
+
+    fn medicine(hospital_id: &str, name: &str, quantity: u32) -> Medicine {
+        Medicine {
+            id: None,
+            user_id: hospital_id.to_string(),
+            name: name.to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: Some(" 3004 ".to_string()),
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn reducing_a_batch_never_goes_below_zero() {
+        let store = MemoryStore::default();
+        let id = add_medicine(&store, medicine("h1", "Paracetamol", 5)).await.unwrap().to_hex();
+
+        reduce_batch(&store, &id, "B1", 3).await.unwrap();
+        let error = reduce_batch(&store, &id, "B1", 3).await.unwrap_err();
+        assert_eq!(error, AppError::insufficient_stock("Paracetamol", "B1", 2));
+        assert_eq!(error.message(), "Insufficient stock for Paracetamol (batch B1): 2 left.");
+        assert_eq!(get_medicine(&store, &id).await.unwrap().quantity, 2);
+        assert_eq!(
+            reduce_batch(&store, &id, "B2", 1).await.unwrap_err(),
+            AppError::not_found("No matching medicine found.")
+        );
+    }
+
+    #[tokio::test]
+    async fn stock_edits_are_validated_and_scoped_to_the_hospital() {
+        let store = MemoryStore::default();
+        assert!(add_medicine(&store, Medicine { gst_rate: Some(7.0), ..medicine("h1", "Cetirizine", 1) })
+            .await
+            .is_err());
+        let id = add_medicine(&store, medicine("h1", "Cetirizine", 10)).await.unwrap();
+        assert_eq!(get_medicine(&store, &id.to_hex()).await.unwrap().hsn_code.as_deref(), Some("3004"));
+        let id = id.to_hex();
+
+        let quantity = |q| MedicineChanges { quantity: Some(q), ..Default::default() };
+        let error = |result: Result<(), AppError>| result.unwrap_err().to_string();
+        assert_eq!(error(update_stock(&store, &id, "h1", MedicineChanges::default()).await), "No fields to update.");
+        assert_eq!(error(update_stock(&store, &id, "h2", quantity(4)).await), "No matching document found.");
+        assert!(error(update_stock(&store, &id, "h1", quantity(10)).await).starts_with("No changes"));
+        update_stock(&store, &id, "h1", quantity(4)).await.unwrap();
+        assert_eq!(error(update_batch(&store, &id, "B9", "h1", quantity(1)).await), "No matching batch found.");
+
+        assert!(delete_medicine(&store, &id, None, "h2").await.is_err());
+        assert_eq!(search(&store, text("cetiri"), "h1").await.unwrap().len(), 1);
+        assert!(search(&store, text("cetiri"), "h2").await.unwrap().is_empty());
+        delete_medicine(&store, &id, Some("B1"), "h1").await.unwrap();
+        assert!(search(&store, text(" "), "h1").await.unwrap().is_empty());
+    }
+
+    fn text(text: &str) -> MedicineQuery {
+        MedicineQuery { text: Some(text.to_string()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn search_ranks_exact_then_prefix_then_contains_and_pages() {
+        let store = MemoryStore::default();
+        let stock = [("Dolo 650", 3), ("Paracetamol Syrup", 9), ("Dolopar", 1), ("Dolo", 5), ("Crocin", 2)];
+        for (name, quantity) in stock {
+            add_medicine(&store, medicine("h1", name, quantity)).await.unwrap();
+        }
+        let names = |medicines: Vec<Medicine>| medicines.into_iter().map(|m| m.name).collect::<Vec<_>>();
+
+        assert_eq!(names(search(&store, text("DOLO"), "h1").await.unwrap()), ["Dolo", "Dolo 650", "Dolopar"]);
+        assert_eq!(names(search(&store, text("par"), "h1").await.unwrap()), ["Paracetamol Syrup", "Dolopar"]);
+
+        let by_quantity = MedicineQuery {
+            sort: MedicineSort::Quantity,
+            descending: true,
+            page: Page { skip: 1, limit: Some(2) },
+            ..Default::default()
+        };
+        assert_eq!(names(search(&store, by_quantity, "h1").await.unwrap()), ["Dolo", "Dolo 650"]);
+    }
+}
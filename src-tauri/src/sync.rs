@@ -0,0 +1,609 @@
+// src-tauri/src/sync.rs
+//! Pushes the offline journal (see `offline.rs`) to the central database.
+//! Every change carries the time it was made and, for stock, the quantity
+//! the counter saw before it. Stock moves are applied as deltas, so sales
+//! made centrally in the meantime are kept; when the central quantity no
+//! longer matches what the counter saw, or a sale would take it below
+//! zero, the change is still applied (the goods have left the shelf) and a
+//! conflict is recorded for the admin to reconcile. Sales are matched on
+//! the `_id` given offline; one whose number is already taken centrally is
+//! stored under a new number, and that is recorded as a conflict too.
+use crate::billing::{invoices_collection, Invoice};
+use crate::commands::{appointments_collection, medicines_collection, Appointment};
+use crate::database::ping;
+use crate::db::DbState;
+use crate::discount::rules_collection;
+use crate::offline::OfflineStore;
+use crate::shift::amend_closed_shift;
+use crate::repository::{
+    AppointmentRepository, DiscountRuleRepository, InvoiceInsert, InvoiceRepository, MedicineQuery, MedicineRepository,
+};
+use crate::user::{find_hospital, users_collection, verify_admin_password};
+use chrono::Utc;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+/// A change to one batch's quantity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockDelta {
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub delta: i64,
+    pub expected_quantity: u32, // what the counter saw before the change
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChangeKind {
+    Sale { invoice: Box<Invoice>, stock: Vec<StockDelta> },
+    Stock { delta: StockDelta, invoice_number: Option<String> },
+    Appointment { appointment: Box<Appointment> },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    pub id: String,
+    pub hospital_id: String,
+    pub recorded_at: String,
+    pub kind: ChangeKind,
+}
+
+impl PendingChange {
+    pub fn new(hospital_id: &str, kind: ChangeKind) -> Self {
+        PendingChange {
+            id: ObjectId::new().to_hex(),
+            hospital_id: hospital_id.to_string(),
+            recorded_at: Utc::now().to_rfc3339(),
+            kind,
+        }
+    }
+}
+
+/// What a conflict is about.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    #[default]
+    Stock,
+    InvoiceNumber, // another invoice had the number; see `renumbered_as`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub hospital_id: String,
+    pub change_id: String,
+    #[serde(default)]
+    pub kind: ConflictKind,
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub invoice_number: Option<String>,
+    pub delta: i64,
+    pub expected_quantity: u32,
+    pub central_quantity: Option<u32>, // None when the batch is gone centrally
+    pub shortfall: u32,                // units sold offline that the central stock did not have
+    pub recorded_at: String,
+    pub synced_at: String,
+    #[serde(default)]
+    pub resolved_at: Option<String>,
+    #[serde(default)]
+    pub resolution_note: Option<String>,
+    #[serde(default)]
+    pub renumbered_as: Option<String>, // the number the sale was stored under instead
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SyncConflictResponse {
+    pub id: String,
+    pub kind: ConflictKind,
+    pub medicine_id: String,
+    pub name: String,
+    pub batch_number: String,
+    pub invoice_number: Option<String>,
+    pub delta: i64,
+    pub expected_quantity: u32,
+    pub central_quantity: Option<u32>,
+    pub shortfall: u32,
+    pub recorded_at: String,
+    pub synced_at: String,
+    pub resolved_at: Option<String>,
+    pub resolution_note: Option<String>,
+    pub renumbered_as: Option<String>,
+}
+
+impl From<SyncConflict> for SyncConflictResponse {
+    fn from(conflict: SyncConflict) -> Self {
+        SyncConflictResponse {
+            id: conflict.id.map(|id| id.to_hex()).unwrap_or_default(),
+            kind: conflict.kind,
+            medicine_id: conflict.medicine_id,
+            name: conflict.name,
+            batch_number: conflict.batch_number,
+            invoice_number: conflict.invoice_number,
+            delta: conflict.delta,
+            expected_quantity: conflict.expected_quantity,
+            central_quantity: conflict.central_quantity,
+            shortfall: conflict.shortfall,
+            recorded_at: conflict.recorded_at,
+            synced_at: conflict.synced_at,
+            resolved_at: conflict.resolved_at,
+            resolution_note: conflict.resolution_note,
+            renumbered_as: conflict.renumbered_as,
+        }
+    }
+}
+
+fn conflicts_collection(db: &Database) -> Collection<SyncConflict> {
+    db.collection("sync_conflicts")
+}
+
+/// Where the journal is pushed to: MongoDB collections, or a `MemoryStore`
+/// in tests.
+pub struct Central<'a> {
+    pub medicines: &'a dyn MedicineRepository,
+    pub appointments: &'a dyn AppointmentRepository,
+    pub invoices: &'a dyn InvoiceRepository,
+}
+
+#[derive(Default)]
+pub struct PushOutcome {
+    pub applied: usize,
+    pub conflicts: Vec<SyncConflict>,
+    pub shifts: Vec<(String, String)>, // hospital and shift of each offline sale pushed
+    pub error: Option<String>,         // where the push stopped; the rest stays queued
+}
+
+/// Applies the journal in order, dropping each change once it is in. A
+/// sale is split on the way: once its invoice is stored, its stock moves
+/// are queued on their own, so a retry never stores the invoice twice and
+/// never skips the stock. Only a sale whose `_id` is already stored counts
+/// as pushed before.
+pub async fn push_pending(central: &Central<'_>, store: &OfflineStore) -> PushOutcome {
+    let mut outcome = PushOutcome::default();
+    for change in store.pending().await {
+        if let Err(error) = push_change(central, store, change, &mut outcome).await {
+            outcome.error = Some(error);
+            break;
+        }
+    }
+    outcome
+}
+
+async fn push_change(
+    central: &Central<'_>,
+    store: &OfflineStore,
+    change: PendingChange,
+    outcome: &mut PushOutcome,
+) -> Result<(), String> {
+    let changes = match change.kind.clone() {
+        ChangeKind::Sale { mut invoice, stock } => {
+            let mut stored = central.invoices.insert_invoice(&invoice).await?;
+            if stored == InvoiceInsert::NumberTaken {
+                // Another till or a reset store file gave out the same number
+                let original = invoice.invoice_number.clone();
+                let id = invoice.id.map(|id| id.to_hex()).unwrap_or_default();
+                invoice.invoice_number = format!("{}-{}", original, &id[id.len().saturating_sub(6)..]);
+                stored = central.invoices.insert_invoice(&invoice).await?;
+                if stored == InvoiceInsert::NumberTaken {
+                    return Err(format!("Invoice numbers {} and {} are both taken.", original, invoice.invoice_number));
+                }
+                let conflict = renumbered(&change, original, &invoice.invoice_number);
+                outcome.conflicts.push(conflict);
+            }
+            // Stored by an earlier push, stock included
+            let rest = if stored == InvoiceInsert::Stored {
+                if let Some(shift_id) = &invoice.shift_id {
+                    outcome.shifts.push((change.hospital_id.clone(), shift_id.clone()));
+                }
+                stock
+                    .into_iter()
+                    .map(|delta| PendingChange {
+                        id: ObjectId::new().to_hex(),
+                        hospital_id: change.hospital_id.clone(),
+                        recorded_at: change.recorded_at.clone(),
+                        kind: ChangeKind::Stock { delta, invoice_number: Some(invoice.invoice_number.clone()) },
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            store.replace_pending(&change.id, rest.clone()).await?;
+            outcome.applied += 1;
+            rest
+        }
+        kind => vec![PendingChange { kind, ..change }],
+    };
+
+    for change in changes {
+        match &change.kind {
+            ChangeKind::Stock { delta, invoice_number } => {
+                if let Some(conflict) = push_stock(central.medicines, &change, delta, invoice_number.as_deref()).await? {
+                    outcome.conflicts.push(conflict);
+                }
+            }
+            ChangeKind::Appointment { appointment } => central.appointments.insert_appointment((**appointment).clone()).await?,
+            ChangeKind::Sale { .. } => unreachable!("sales are split above"),
+        }
+        store.replace_pending(&change.id, Vec::new()).await?;
+        outcome.applied += 1;
+    }
+    Ok(())
+}
+
+/// Files a sale stored under another number than the one it was given.
+fn renumbered(change: &PendingChange, original: String, renumbered_as: &str) -> SyncConflict {
+    SyncConflict {
+        id: None,
+        hospital_id: change.hospital_id.clone(),
+        change_id: change.id.clone(),
+        kind: ConflictKind::InvoiceNumber,
+        medicine_id: String::new(),
+        name: String::new(),
+        batch_number: String::new(),
+        invoice_number: Some(original),
+        delta: 0,
+        expected_quantity: 0,
+        central_quantity: None,
+        shortfall: 0,
+        recorded_at: change.recorded_at.clone(),
+        synced_at: Utc::now().to_rfc3339(),
+        resolved_at: None,
+        resolution_note: None,
+        renumbered_as: Some(renumbered_as.to_string()),
+    }
+}
+
+async fn push_stock(
+    medicines: &dyn MedicineRepository,
+    change: &PendingChange,
+    delta: &StockDelta,
+    invoice_number: Option<&str>,
+) -> Result<Option<SyncConflict>, String> {
+    let id = ObjectId::parse_str(&delta.medicine_id).map_err(|_| "Invalid medicine ID".to_string())?;
+    let central_quantity = medicines.find_medicine(id, Some(&change.hospital_id)).await?.map(|m| m.quantity);
+    let wanted = match central_quantity {
+        Some(quantity) => {
+            medicines.adjust_quantity(id, &change.hospital_id, delta.delta).await?;
+            quantity as i64 + delta.delta
+        }
+        None => delta.delta,
+    };
+    if central_quantity == Some(delta.expected_quantity) {
+        return Ok(None);
+    }
+
+    Ok(Some(SyncConflict {
+        id: None,
+        hospital_id: change.hospital_id.clone(),
+        change_id: change.id.clone(),
+        kind: ConflictKind::Stock,
+        medicine_id: delta.medicine_id.clone(),
+        name: delta.name.clone(),
+        batch_number: delta.batch_number.clone(),
+        invoice_number: invoice_number.map(str::to_string),
+        delta: delta.delta,
+        expected_quantity: delta.expected_quantity,
+        central_quantity,
+        shortfall: u32::try_from(-wanted.min(0)).unwrap_or(u32::MAX),
+        recorded_at: change.recorded_at.clone(),
+        synced_at: Utc::now().to_rfc3339(),
+        resolved_at: None,
+        resolution_note: None,
+        renumbered_as: None,
+    }))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SyncReport {
+    pub online: bool,
+    pub applied: usize,
+    pub conflicts: usize,
+    pub pending: usize,
+    pub last_sync: Option<String>,
+    pub series: String, // the offline invoice series of this counter
+    pub error: Option<String>,
+}
+
+/// Checks the link, then pushes the journal, files any conflicts, redraws
+/// the Z-report of shifts that closed before their offline sales came in
+/// and refreshes the replica of each tracked hospital.
+pub async fn sync_once(db_state: &DbState) -> Result<SyncReport, String> {
+    let DbState::Mongo(mongo) = db_state else {
+        return Err("Sync needs the MongoDB backend.".to_string());
+    };
+    let offline = &mongo.offline;
+    let online = ping(&mongo.db).await.is_ok();
+    offline.set_online(online);
+
+    let mut outcome = PushOutcome::default();
+    if online {
+        let (medicines, appointments, invoices) =
+            (medicines_collection(&mongo.db), appointments_collection(&mongo.db), invoices_collection(&mongo.db));
+        let central = Central { medicines: &medicines, appointments: &appointments, invoices: &invoices };
+        outcome = push_pending(&central, offline).await;
+        if !outcome.conflicts.is_empty() {
+            conflicts_collection(&mongo.db)
+                .insert_many(&outcome.conflicts, None)
+                .await
+                .map_err(|e| format!("Failed to save sync conflicts: {}", e))?;
+        }
+        outcome.shifts.sort();
+        outcome.shifts.dedup();
+        for (hospital_id, shift_id) in &outcome.shifts {
+            amend_closed_shift(&mongo.client, &mongo.db, shift_id, hospital_id).await?;
+        }
+        if outcome.error.is_none() {
+            for hospital_id in offline.tracked_hospitals().await {
+                refresh_replica(&mongo.db, offline, &hospital_id).await?;
+            }
+        }
+    }
+
+    Ok(SyncReport {
+        online,
+        applied: outcome.applied,
+        conflicts: outcome.conflicts.len(),
+        pending: offline.pending().await.len(),
+        last_sync: offline.last_sync().await,
+        series: offline.series().to_string(),
+        error: outcome.error,
+    })
+}
+
+async fn refresh_replica(db: &Database, offline: &OfflineStore, hospital_id: &str) -> Result<bool, String> {
+    let hospital = find_hospital(&users_collection(db), hospital_id).await?;
+    let medicines = medicines_collection(db).list_medicines(hospital_id, &MedicineQuery::default()).await?;
+    let rules = rules_collection(db).list_rules(hospital_id, true).await?;
+    offline.refresh(hospital, medicines, rules).await
+}
+
+/// Starts keeping a replica of a hospital that logged in on this counter.
+pub async fn track_hospital(db_state: &DbState, hospital_id: &str) -> Result<(), String> {
+    let DbState::Mongo(mongo) = db_state else { return Ok(()) };
+    mongo.offline.track_hospital(hospital_id).await?;
+    if mongo.offline.is_online() {
+        refresh_replica(&mongo.db, &mongo.offline, hospital_id).await?;
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn get_sync_status(db_state: State<'_, DbState>) -> Result<SyncReport, String> {
+    let offline = &db_state.mongo_state()?.offline;
+    Ok(SyncReport {
+        online: offline.is_online(),
+        applied: 0,
+        conflicts: 0,
+        pending: offline.pending().await.len(),
+        last_sync: offline.last_sync().await,
+        series: offline.series().to_string(),
+        error: None,
+    })
+}
+
+/// Syncs now instead of waiting for the next round.
+#[command]
+pub async fn sync_now(db_state: State<'_, DbState>) -> Result<SyncReport, String> {
+    sync_once(&db_state).await
+}
+
+/// Stock conflicts found while syncing, newest first; unresolved only
+/// unless `include_resolved`.
+#[command]
+pub async fn get_sync_conflicts(
+    include_resolved: Option<bool>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<SyncConflictResponse>, String> {
+    let db = db_state.mongo("sync conflicts")?.db.clone();
+    let mut filter = doc! { "hospital_id": &hospital_id };
+    if !include_resolved.unwrap_or(false) {
+        filter.insert("resolved_at", None::<String>);
+    }
+    let find_options = FindOptions::builder().sort(doc! { "synced_at": -1 }).build();
+    let conflicts: Vec<SyncConflict> = conflicts_collection(&db)
+        .find(filter, find_options)
+        .await
+        .map_err(|e| format!("Database query error: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Error parsing sync conflicts: {}", e))?;
+    Ok(conflicts.into_iter().map(SyncConflictResponse::from).collect())
+}
+
+/// Marks a conflict as reconciled, once the admin has counted the shelf
+/// and corrected the stock.
+#[command]
+pub async fn resolve_sync_conflict(
+    conflict_id: String,
+    note: Option<String>,
+    admin_password: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<(), String> {
+    let db = db_state.mongo("sync conflicts")?.db.clone();
+    let object_id = ObjectId::parse_str(&conflict_id).map_err(|_| "Invalid conflict ID".to_string())?;
+    let hospital = find_hospital(&users_collection(&db), &hospital_id).await?;
+    verify_admin_password(&hospital, admin_password.as_deref())?;
+
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let result = conflicts_collection(&db)
+        .update_one(
+            doc! { "_id": object_id, "hospital_id": &hospital_id, "resolved_at": None::<String> },
+            doc! { "$set": { "resolved_at": Utc::now().to_rfc3339(), "resolution_note": note } },
+            None,
+        )
+        .await
+        .map_err(|e| format!("Database update error: {}", e))?;
+    if result.matched_count == 0 {
+        return Err("No open conflict found.".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::TestInvoice;
+    use crate::commands::Medicine;
+    use crate::model::User;
+    use crate::money::Money;
+    use crate::repository::memory::MemoryStore;
+    use chrono::NaiveDate;
+
+    fn medicine(hospital_id: &str, quantity: u32) -> Medicine {
+        Medicine {
+            id: Some(ObjectId::new()),
+            user_id: hospital_id.to_string(),
+            name: "Paracetamol".to_string(),
+            batch_number: "B1".to_string(),
+            expiry_date: NaiveDate::from_ymd_opt(2027, 3, 31).unwrap(),
+            quantity,
+            purchase_price: Money::from_rupees(8.0),
+            selling_price: Money::from_rupees(10.0),
+            wholesaler_name: "Apex Pharma".to_string(),
+            purchase_date: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            hsn_code: None,
+            gst_rate: Some(12.0),
+            tax_inclusive: true,
+            generic_name: None,
+            brand_names: Vec::new(),
+            composition: None,
+            barcode: None,
+        }
+    }
+
+    fn hospital(id: ObjectId) -> User {
+        serde_json::from_value(serde_json::json!({
+            "_id": id, "username": "city", "name": "Dr Rao", "mobile": "9800000000", "hospital": "City Clinic",
+            "address": "MG Road", "password_hash_doc": "", "password_hash_pharma": "", "email": "rao@example.com",
+        }))
+        .unwrap()
+    }
+
+    fn invoice(hospital_id: &str, medicine: &Medicine, quantity: u32) -> Invoice {
+        let id = medicine.id.unwrap().to_hex();
+        let sold = TestInvoice::new("").hospital(hospital_id).line(&id, &medicine.name, "3004", 12.0, quantity, 10.0);
+        sold.issued_at(Utc::now()).build()
+    }
+
+    async fn offline_counter(hospital_id: ObjectId, stock: &Medicine) -> OfflineStore {
+        let store = OfflineStore::in_memory();
+        store.refresh(hospital(hospital_id), vec![stock.clone()], Vec::new()).await.unwrap();
+        store.set_online(false);
+        store
+    }
+
+    #[tokio::test]
+    async fn offline_sales_are_pushed_once_and_drift_is_reported() {
+        let hospital_id = ObjectId::new();
+        let h = hospital_id.to_hex();
+        let stock = medicine(&h, 10);
+        let id = stock.id.unwrap();
+        let counter = offline_counter(hospital_id, &stock).await;
+
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let on_shift = Invoice { shift_id: Some("s1".to_string()), ..invoice(&h, &stock, 4) };
+        let sold = counter.save_invoice(on_shift, "INV", today).await.unwrap();
+        assert!(counter.series().starts_with('T') && counter.series().len() == 6);
+        assert!(sold.invoice_number.starts_with(&format!("INV-{}/", counter.series())));
+        assert_eq!(counter.find_medicine(id, Some(&h)).await.unwrap().unwrap().quantity, 6);
+        assert!(counter.save_invoice(invoice(&h, &stock, 7), "INV", today).await.is_err());
+
+        // The central database sold 3 of the same batch meanwhile
+        let central = MemoryStore::default();
+        central.medicines.lock().unwrap().push(Medicine { quantity: 7, ..stock.clone() });
+        let targets = Central { medicines: &central, appointments: &central, invoices: &central };
+        let outcome = push_pending(&targets, &counter).await;
+
+        assert_eq!(outcome.error, None);
+        assert!(counter.pending().await.is_empty());
+        assert_eq!(central.find_medicine(id, None).await.unwrap().unwrap().quantity, 3);
+        assert_eq!(central.invoices.lock().unwrap().len(), 1);
+        let conflict = &outcome.conflicts[0];
+        assert_eq!((conflict.expected_quantity, conflict.central_quantity, conflict.shortfall), (10, Some(7), 0));
+        assert_eq!(conflict.invoice_number.as_deref(), Some(sold.invoice_number.as_str()));
+        assert_eq!(outcome.shifts, vec![(h.clone(), "s1".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn a_sale_already_stored_centrally_is_not_deducted_again() {
+        let hospital_id = ObjectId::new();
+        let h = hospital_id.to_hex();
+        let stock = medicine(&h, 10);
+        let id = stock.id.unwrap();
+        let counter = offline_counter(hospital_id, &stock).await;
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let sold = counter.save_invoice(invoice(&h, &stock, 4), "INV", today).await.unwrap();
+
+        // An earlier push stored the invoice and its stock, then stopped
+        let central = MemoryStore::default();
+        central.medicines.lock().unwrap().push(Medicine { quantity: 6, ..stock });
+        central.insert_invoice(&sold).await.unwrap();
+        let targets = Central { medicines: &central, appointments: &central, invoices: &central };
+        let outcome = push_pending(&targets, &counter).await;
+
+        assert!(outcome.conflicts.is_empty());
+        assert!(counter.pending().await.is_empty());
+        assert_eq!(central.find_medicine(id, None).await.unwrap().unwrap().quantity, 6);
+    }
+
+    #[tokio::test]
+    async fn a_number_taken_by_another_counter_is_renumbered_not_dropped() {
+        let hospital_id = ObjectId::new();
+        let h = hospital_id.to_hex();
+        let stock = medicine(&h, 10);
+        let id = stock.id.unwrap();
+        let counter = offline_counter(hospital_id, &stock).await;
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let sold = counter.save_invoice(invoice(&h, &stock, 4), "INV", today).await.unwrap();
+
+        // Another till on the same series pushed its own sale under that number
+        let central = MemoryStore::default();
+        central.medicines.lock().unwrap().push(Medicine { quantity: 8, ..stock });
+        let other = Invoice { id: Some(ObjectId::new()), ..sold.clone() };
+        assert_eq!(central.insert_invoice(&other).await.unwrap(), InvoiceInsert::Stored);
+        let targets = Central { medicines: &central, appointments: &central, invoices: &central };
+        let outcome = push_pending(&targets, &counter).await;
+
+        assert_eq!(outcome.error, None);
+        assert!(counter.pending().await.is_empty());
+        assert_eq!(central.find_medicine(id, None).await.unwrap().unwrap().quantity, 4);
+        let stored = central.invoices.lock().unwrap().clone();
+        assert_eq!(stored.len(), 2);
+        let conflict = outcome.conflicts.iter().find(|c| c.kind == ConflictKind::InvoiceNumber).unwrap();
+        assert_eq!(conflict.invoice_number.as_deref(), Some(sold.invoice_number.as_str()));
+        let renumbered = conflict.renumbered_as.clone().unwrap();
+        assert!(renumbered.starts_with(&format!("{}-", sold.invoice_number)));
+        assert!(stored.iter().any(|i| i.id == sold.id && i.invoice_number == renumbered));
+
+        // Pushing the same sale again is recognised by its `_id`
+        assert_eq!(central.insert_invoice(&sold).await.unwrap(), InvoiceInsert::AlreadyStored);
+    }
+
+    #[tokio::test]
+    async fn overselling_is_clamped_and_reported_as_a_shortfall() {
+        let hospital_id = ObjectId::new();
+        let h = hospital_id.to_hex();
+        let stock = medicine(&h, 5);
+        let id = stock.id.unwrap();
+        let counter = offline_counter(hospital_id, &stock).await;
+        assert!(counter.take_stock(id, "B1", 5).await.unwrap());
+
+        let central = MemoryStore::default();
+        central.medicines.lock().unwrap().push(Medicine { quantity: 2, ..stock });
+        let targets = Central { medicines: &central, appointments: &central, invoices: &central };
+        let outcome = push_pending(&targets, &counter).await;
+
+        assert_eq!(central.find_medicine(id, None).await.unwrap().unwrap().quantity, 0);
+        assert_eq!(outcome.conflicts[0].shortfall, 3);
+        assert!(counter.pending().await.is_empty());
+    }
+}
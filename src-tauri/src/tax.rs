@@ -0,0 +1,409 @@
+// src-tauri/src/tax.rs
+use crate::commands::{Medicine, MedicineDetail};
+use crate::db::DbState;
+use crate::discount::{price_bill, Discount};
+use crate::model::User;
+use crate::repository::MedicineRepository;
+use crate::user::find_hospital;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::{command, State};
+
+/// GST slabs we accept on products, in percent. Includes both the pre- and
+/// post-September 2025 slabs so older stock entries stay valid.
+pub const GST_RATES: [f64; 8] = [0.0, 0.25, 3.0, 5.0, 12.0, 18.0, 28.0, 40.0];
+
+pub fn validate_gst_rate(rate: f64) -> Result<(), String> {
+    if GST_RATES.iter().any(|r| (r - rate).abs() < f64::EPSILON) {
+        Ok(())
+    } else {
+        Err(format!("{}% is not a valid GST rate.", rate))
+    }
+}
+
+/// Amounts are worked out in whole paise so totals never drift.
+pub(crate) fn to_paise(rupees: f64) -> i64 {
+    (rupees * 100.0).round() as i64
+}
+
+pub(crate) fn to_rupees(paise: i64) -> f64 {
+    paise as f64 / 100.0
+}
+
+/// Rate in hundredths of a percent, e.g. 12% -> 1200.
+pub(crate) fn to_basis_points(rate: f64) -> i64 {
+    (rate * 100.0).round() as i64
+}
+
+/// Integer division rounding half away from zero.
+pub(crate) fn div_round(numerator: i64, denominator: i64) -> i64 {
+    let half = denominator / 2;
+    if numerator >= 0 {
+        (numerator + half) / denominator
+    } else {
+        (numerator - half) / denominator
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupplyType {
+    IntraState, // CGST + SGST
+    InterState, // IGST
+}
+
+/// Place-of-supply rule: IGST applies when the supply crosses state lines.
+/// A missing place of supply means an over-the-counter sale in the seller's
+/// own state.
+pub fn supply_type(seller_state: Option<&str>, place_of_supply: Option<&str>) -> SupplyType {
+    match (seller_state, place_of_supply) {
+        (Some(seller), Some(place)) if !seller.trim().eq_ignore_ascii_case(place.trim()) => SupplyType::InterState,
+        _ => SupplyType::IntraState,
+    }
+}
+
+/// GST state code: two digits, as at the start of a GSTIN.
+pub fn state_code_from_gstin(gstin: &str) -> Option<String> {
+    let code: String = gstin.trim().chars().take(2).collect();
+    (code.len() == 2 && code.chars().all(|c| c.is_ascii_digit())).then_some(code)
+}
+
+#[derive(Debug, Clone)]
+pub struct TaxLineInput {
+    pub description: String,
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub unit_price: f64,
+    pub quantity: u32,
+    pub discount: f64, // off the whole line, on the same basis as the price
+    pub tax_inclusive: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxedLine {
+    pub description: String,
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub quantity: u32,
+    pub unit_price: f64,
+    pub discount: f64,
+    pub taxable_value: f64,
+    pub cgst: f64,
+    pub sgst: f64,
+    pub igst: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxSummaryRow {
+    pub hsn_code: String,
+    pub gst_rate: f64,
+    pub taxable_value: f64,
+    pub cgst: f64,
+    pub sgst: f64,
+    pub igst: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceTax {
+    pub supply_type: SupplyType,
+    pub lines: Vec<TaxedLine>,
+    pub summary: Vec<TaxSummaryRow>,
+    pub discount_total: f64,
+    pub taxable_total: f64,
+    pub cgst_total: f64,
+    pub sgst_total: f64,
+    pub igst_total: f64,
+    pub gross_total: f64,
+    pub round_off: f64,
+    pub grand_total: f64,
+}
+
+struct LinePaise {
+    taxable: i64,
+    cgst: i64,
+    sgst: i64,
+    igst: i64,
+}
+
+/// Tax on one line. CGST and SGST are each half the rate on the taxable
+/// value, rounded to the paisa. For tax-inclusive prices the taxable value
+/// absorbs the rounding so the line still totals the printed MRP.
+fn tax_line(gross: i64, rate_bp: i64, tax_inclusive: bool, supply: SupplyType) -> LinePaise {
+    let taxable = if tax_inclusive {
+        div_round(gross * 10_000, 10_000 + rate_bp)
+    } else {
+        gross
+    };
+
+    let (cgst, sgst, igst) = match supply {
+        SupplyType::IntraState => {
+            let half = div_round(taxable * rate_bp, 20_000);
+            (half, half, 0)
+        }
+        SupplyType::InterState => (0, 0, div_round(taxable * rate_bp, 10_000)),
+    };
+
+    let taxable = if tax_inclusive { gross - cgst - sgst - igst } else { taxable };
+    LinePaise { taxable, cgst, sgst, igst }
+}
+
+/// Computes line taxes, the HSN/rate summary and the invoice total rounded
+/// to the nearest rupee. Discounts come off before tax, as GST is charged on
+/// the discounted value.
+pub fn compute_invoice_tax(lines: &[TaxLineInput], supply: SupplyType) -> InvoiceTax {
+    let mut taxed_lines = Vec::with_capacity(lines.len());
+    let mut summary: BTreeMap<(String, i64), LinePaise> = BTreeMap::new();
+    let (mut taxable_total, mut cgst_total, mut sgst_total, mut igst_total) = (0, 0, 0, 0);
+    let mut discount_total = 0;
+
+    for line in lines {
+        let rate_bp = to_basis_points(line.gst_rate);
+        let discount = to_paise(line.discount);
+        let gross = to_paise(line.unit_price) * line.quantity as i64 - discount;
+        let paise = tax_line(gross, rate_bp, line.tax_inclusive, supply);
+
+        discount_total += discount;
+        taxable_total += paise.taxable;
+        cgst_total += paise.cgst;
+        sgst_total += paise.sgst;
+        igst_total += paise.igst;
+
+        let row = summary
+            .entry((line.hsn_code.clone(), rate_bp))
+            .or_insert(LinePaise { taxable: 0, cgst: 0, sgst: 0, igst: 0 });
+        row.taxable += paise.taxable;
+        row.cgst += paise.cgst;
+        row.sgst += paise.sgst;
+        row.igst += paise.igst;
+
+        taxed_lines.push(TaxedLine {
+            description: line.description.clone(),
+            hsn_code: line.hsn_code.clone(),
+            gst_rate: line.gst_rate,
+            quantity: line.quantity,
+            unit_price: line.unit_price,
+            discount: to_rupees(discount),
+            taxable_value: to_rupees(paise.taxable),
+            cgst: to_rupees(paise.cgst),
+            sgst: to_rupees(paise.sgst),
+            igst: to_rupees(paise.igst),
+            total: to_rupees(paise.taxable + paise.cgst + paise.sgst + paise.igst),
+        });
+    }
+
+    let gross_total = taxable_total + cgst_total + sgst_total + igst_total;
+    let grand_total = div_round(gross_total, 100) * 100;
+
+    InvoiceTax {
+        supply_type: supply,
+        lines: taxed_lines,
+        summary: summary
+            .into_iter()
+            .map(|((hsn_code, rate_bp), row)| TaxSummaryRow {
+                hsn_code,
+                gst_rate: rate_bp as f64 / 100.0,
+                taxable_value: to_rupees(row.taxable),
+                cgst: to_rupees(row.cgst),
+                sgst: to_rupees(row.sgst),
+                igst: to_rupees(row.igst),
+            })
+            .collect(),
+        discount_total: to_rupees(discount_total),
+        taxable_total: to_rupees(taxable_total),
+        cgst_total: to_rupees(cgst_total),
+        sgst_total: to_rupees(sgst_total),
+        igst_total: to_rupees(igst_total),
+        gross_total: to_rupees(gross_total),
+        round_off: to_rupees(grand_total - gross_total),
+        grand_total: to_rupees(grand_total),
+    }
+}
+
+/// Seller's state from the hospital profile, preferring the GSTIN.
+pub fn seller_state(hospital: &User) -> Option<String> {
+    hospital
+        .gstin
+        .as_deref()
+        .and_then(state_code_from_gstin)
+        .or_else(|| hospital.state_code.clone())
+}
+
+pub fn tax_line_for(medicine: &Medicine, quantity: u32, discount: f64) -> TaxLineInput {
+    TaxLineInput {
+        description: medicine.name.clone(),
+        hsn_code: medicine.hsn_code.clone().unwrap_or_default(),
+        gst_rate: medicine.gst_rate.unwrap_or(0.0),
+        unit_price: medicine.selling_price.rupees(),
+        quantity,
+        discount,
+        tax_inclusive: medicine.tax_inclusive,
+    }
+}
+
+/// Loads the billed medicines in the order given, failing on any unknown ID.
+pub async fn load_bill_medicines(
+    repo: &dyn MedicineRepository,
+    items: &[MedicineDetail],
+    hospital_id: &str,
+) -> Result<Vec<Medicine>, String> {
+    let ids = items
+        .iter()
+        .map(|item| ObjectId::parse_str(&item.id).map_err(|_| "Invalid medicine ID".to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let medicines = repo.find_medicines(&ids, hospital_id).await?;
+
+    ids.iter()
+        .map(|id| {
+            medicines
+                .iter()
+                .find(|m| m.id.as_ref() == Some(id))
+                .cloned()
+                .ok_or_else(|| format!("Medicine {} not found", id.to_hex()))
+        })
+        .collect()
+}
+
+/// Tax breakdown for a prospective bill, with discounts applied the same
+/// way `create_invoice` will. `place_of_supply` is the buyer's state code or
+/// GSTIN; leave it empty for walk-in customers.
+#[command]
+pub async fn compute_bill_tax(
+    items: Vec<MedicineDetail>,
+    place_of_supply: Option<String>,
+    bill_discount: Option<Discount>,
+    customer_category: Option<String>,
+    hospital_id: String,
+    db_state: State<'_, DbState>,
+) -> Result<BillQuote, String> {
+    if items.is_empty() {
+        return Err("Bill has no items.".to_string());
+    }
+
+    let hospital = find_hospital(&*db_state.users(), &hospital_id).await?;
+
+    let place = place_of_supply
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| state_code_from_gstin(&p).unwrap_or(p));
+    let supply = supply_type(seller_state(&hospital).as_deref(), place.as_deref());
+
+    let priced = price_bill(&db_state, &items, bill_discount, customer_category.as_deref(), supply, &hospital_id).await?;
+    Ok(BillQuote {
+        tax: priced.tax,
+        applied_rules: priced.applied_rules,
+        below_cost: priced.below_cost,
+    })
+}
+
+/// Bill preview. `below_cost` lists items that will need an admin override.
+#[derive(Debug, Clone, Serialize)]
+pub struct BillQuote {
+    #[serde(flatten)]
+    pub tax: InvoiceTax,
+    pub applied_rules: Vec<String>,
+    pub below_cost: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(hsn: &str, rate: f64, price: f64, quantity: u32, inclusive: bool) -> TaxLineInput {
+        TaxLineInput {
+            description: format!("{} @ {}%", hsn, rate),
+            hsn_code: hsn.to_string(),
+            gst_rate: rate,
+            unit_price: price,
+            quantity,
+            discount: 0.0,
+            tax_inclusive: inclusive,
+        }
+    }
+
+    #[test]
+    fn inclusive_mrp_splits_back_to_taxable_value() {
+        // 2 strips at MRP 30.00 incl. 12%: 60.00 gross
+        let tax = compute_invoice_tax(&[line("3004", 12.0, 30.0, 2, true)], SupplyType::IntraState);
+        let l = &tax.lines[0];
+        assert_eq!(l.taxable_value, 53.58);
+        assert_eq!(l.cgst, 3.21);
+        assert_eq!(l.sgst, 3.21);
+        assert_eq!(l.igst, 0.0);
+        assert_eq!(l.total, 60.0);
+        assert_eq!(tax.grand_total, 60.0);
+        assert_eq!(tax.round_off, 0.0);
+    }
+
+    #[test]
+    fn exclusive_price_adds_tax_on_top() {
+        let tax = compute_invoice_tax(&[line("9018", 18.0, 100.0, 1, false)], SupplyType::IntraState);
+        assert_eq!(tax.taxable_total, 100.0);
+        assert_eq!(tax.cgst_total, 9.0);
+        assert_eq!(tax.sgst_total, 9.0);
+        assert_eq!(tax.grand_total, 118.0);
+    }
+
+    #[test]
+    fn inter_state_supply_charges_igst_only() {
+        let tax = compute_invoice_tax(&[line("9018", 18.0, 100.0, 1, false)], SupplyType::InterState);
+        assert_eq!(tax.cgst_total, 0.0);
+        assert_eq!(tax.sgst_total, 0.0);
+        assert_eq!(tax.igst_total, 18.0);
+        assert_eq!(tax.grand_total, 118.0);
+    }
+
+    #[test]
+    fn mixed_invoice_groups_summary_by_hsn_and_rate() {
+        // Known pharmacy bill: two 12% medicines under one HSN, a 5% item
+        // and a 0% item, all at inclusive MRP.
+        let lines = [
+            line("3004", 12.0, 42.50, 3, true),  // 127.50
+            line("3004", 12.0, 18.75, 2, true),  //  37.50
+            line("3006", 5.0, 99.0, 1, true),    //  99.00
+            line("3004", 0.0, 12.35, 1, true),   //  12.35
+        ];
+        let tax = compute_invoice_tax(&lines, SupplyType::IntraState);
+
+        assert_eq!(tax.summary.len(), 3);
+        let twelve = tax.summary.iter().find(|r| r.hsn_code == "3004" && r.gst_rate == 12.0).unwrap();
+        // 127.50 -> 113.84 + 6.83 + 6.83; 37.50 -> 33.48 + 2.01 + 2.01
+        assert_eq!(twelve.taxable_value, 147.32);
+        assert_eq!(twelve.cgst, 8.84);
+        assert_eq!(twelve.sgst, 8.84);
+
+        let five = tax.summary.iter().find(|r| r.gst_rate == 5.0).unwrap();
+        // 99.00 -> 2.36 each way, leaving 94.28 taxable
+        assert_eq!(five.cgst, 2.36);
+        assert_eq!(five.taxable_value, 94.28);
+
+        assert_eq!(tax.gross_total, 276.35);
+        assert_eq!(tax.round_off, -0.35);
+        assert_eq!(tax.grand_total, 276.0);
+    }
+
+    #[test]
+    fn round_off_goes_up_from_half_a_rupee() {
+        let tax = compute_invoice_tax(&[line("3004", 0.0, 10.50, 1, false)], SupplyType::IntraState);
+        assert_eq!(tax.round_off, 0.5);
+        assert_eq!(tax.grand_total, 11.0);
+    }
+
+    #[test]
+    fn place_of_supply_decides_igst() {
+        assert_eq!(supply_type(Some("27"), None), SupplyType::IntraState);
+        assert_eq!(supply_type(Some("27"), Some("27")), SupplyType::IntraState);
+        assert_eq!(supply_type(Some("27"), Some("29")), SupplyType::InterState);
+        assert_eq!(supply_type(None, Some("29")), SupplyType::IntraState);
+        assert_eq!(state_code_from_gstin("27AAPFU0939F1ZV").as_deref(), Some("27"));
+        assert_eq!(state_code_from_gstin("MH"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_gst_rates() {
+        assert!(validate_gst_rate(12.0).is_ok());
+        assert!(validate_gst_rate(40.0).is_ok());
+        assert!(validate_gst_rate(7.0).is_err());
+    }
+}
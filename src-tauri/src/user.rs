@@ -1,16 +1,28 @@
 //src-tauri/src/user.rs
+use crate::dates;
+use crate::error::AppError;
 use crate::model::User;
+use crate::pdf::JpegImage;
+use crate::repository::{PasswordKind, UserRepository};
+use crate::settings::clean_invoice_prefix;
 use bcrypt::{hash, verify, DEFAULT_COST};
-use mongodb::bson::doc;
 use mongodb::bson::oid::ObjectId;
-use mongodb::Collection;
+use mongodb::{Collection, Database};
 use serde_json::json;
-use chrono::{DateTime, Utc, Duration};
+use chrono::{Utc, Duration};
 use rand::Rng;
 use crate::utils::send_otp_email;
 
+// bcrypt at full cost takes seconds in a debug build
+const HASH_COST: u32 = if cfg!(test) { 4 } else { DEFAULT_COST };
+
+pub(crate) fn users_collection(db: &Database) -> Collection<User> {
+    db.collection("users")
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn signup_user(
-    user_collection: &Collection<User>,
+    users: &dyn UserRepository,
     username: &str,
     name: &str,
     mobile: &str,
@@ -19,30 +31,19 @@ pub async fn signup_user(
     password_doc: &str,
     password_pharma: &str,
     email: &str,
-) -> Result<(), String> {
-    // Hash the passwords
-    let password_hash_doc = hash(password_doc, DEFAULT_COST).map_err(|e| e.to_string())?;
-    let password_hash_pharma = hash(password_pharma, DEFAULT_COST).map_err(|e| e.to_string())?;
-
+) -> Result<(), AppError> {
     // Check for existing username and email
-    let existing_user = user_collection
-        .find_one(doc! { "username": username }, None)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?;
-
-    if existing_user.is_some() {
-        return Err("Username is already taken".to_string());
+    if users.find_user_by_username(username).await?.is_some() {
+        return Err(AppError::conflict("Username is already taken"));
     }
-
-    let existing_email = user_collection
-        .find_one(doc! { "email": email }, None)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?;
-
-    if existing_email.is_some() {
-        return Err("Email is already registered".to_string());
+    if users.find_user_by_email(email).await?.is_some() {
+        return Err(AppError::conflict("Email is already registered"));
     }
 
+    // Hash the passwords
+    let password_hash_doc = hash(password_doc, HASH_COST).map_err(AppError::internal)?;
+    let password_hash_pharma = hash(password_pharma, HASH_COST).map_err(AppError::internal)?;
+
     // Create the user object
     let user = User {
         id: None,
@@ -56,39 +57,36 @@ pub async fn signup_user(
         email: email.to_string(),
         otp: None,
         otp_expiry: None,
+        registration_number: None,
+        gstin: None,
+        state_code: None,
+        invoice_prefix: None,
+        upi_id: None,
+        logo_path: None,
+        letterhead: None,
+        settings: Default::default(),
     };
 
-    // Insert the user into the database
-    user_collection
-        .insert_one(user, None)
-        .await
-        .map_err(|e| format!("Failed to create user: {}", e))?;
-
-    Ok(())
+    Ok(users.insert_user(user).await?)
 }
 
 
 pub async fn login_user(
-    user_collection: &Collection<User>,
+    users: &dyn UserRepository,
     username: &str,
     password: &str,
     role: &str,
-) -> Result<String, String> {
-    let user_doc = user_collection
-        .find_one(doc! { "username": username }, None)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if let Some(user) = user_doc {
-        let password_hash = if role == "Doctor" {
-            &user.password_hash_doc
-        } else {
-            &user.password_hash_pharma
+) -> Result<String, AppError> {
+    if let Some(user) = users.find_user_by_username(username).await? {
+        let password_hash = match PasswordKind::from_role(role) {
+            PasswordKind::Doctor => &user.password_hash_doc,
+            PasswordKind::Pharmacy => &user.password_hash_pharma,
         };
 
-        if verify(password, password_hash).map_err(|e| e.to_string())? {
+        // An unreadable stored hash is treated as a wrong password
+        if verify(password, password_hash).unwrap_or(false) {
             let user_response = json!({
-                "userId": user.id.unwrap_or_else(|| ObjectId::new()).to_string(),
+                "userId": user.id.unwrap_or_else(ObjectId::new).to_string(),
                 "hospital": user.hospital,
                 "phone": user.mobile,
                 "address": user.address,
@@ -98,76 +96,223 @@ pub async fn login_user(
         }
     }
 
-    Err("Invalid username or password".to_string())
+    Err(AppError::unauthorized("Invalid username or password"))
 }
 
-pub async fn send_otp(user_collection: &Collection<User>, email: &str) -> Result<(), String> {
-    // Check if the email exists
-    let user = user_collection
-        .find_one(doc! { "email": email }, None)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    // if user.is_none() {
-    //     return Err("Email not registered".to_string());
-    // }
-
-    // Generate numeric OTP
+/// Stores a fresh six-digit OTP, valid for ten minutes, and returns it.
+pub async fn issue_otp(users: &dyn UserRepository, email: &str) -> Result<String, AppError> {
     let otp_code: String = rand::thread_rng()
         .sample_iter(rand::distributions::Uniform::from(0..10))
         .take(6)
         .map(|n| n.to_string())
         .collect();
-    let otp_expiry = Utc::now() + Duration::minutes(10);
-
-    // Update the user with OTP details
-    user_collection
-        .update_one(
-            doc! { "email": email },
-            doc! { "$set": { "otp": &otp_code, "otp_expiry": otp_expiry.to_rfc3339() } },
-            None,
-        )
-        .await
-        .map_err(|e| e.to_string())?;
+    let otp_expiry = dates::from_utc(Utc::now() + Duration::minutes(10));
+
+    users.set_otp(email, Some((&otp_code, otp_expiry))).await?;
+    Ok(otp_code)
+}
+
+pub async fn send_otp(users: &dyn UserRepository, email: &str) -> Result<(), AppError> {
+    let otp_code = issue_otp(users, email).await?;
 
     // Send OTP email
     send_otp_email(email, &otp_code)
         .await
-        .map_err(|e| format!("Failed to send OTP: {}", e))?;
+        .map_err(|e| AppError::email(format!("Failed to send OTP: {}", e)))?;
 
     Ok(())
 }
 
-pub async fn validate_otp(
-    user_collection: &Collection<User>,
+pub async fn validate_otp(users: &dyn UserRepository, email: &str, otp: &str) -> Result<(), AppError> {
+    if let Some(user) = users.find_user_by_email(email).await? {
+        if let (Some(stored_otp), Some(expiry)) = (user.otp, user.otp_expiry) {
+            if Utc::now() < dates::to_utc(expiry) && stored_otp == otp {
+                // Clear OTP after successful validation
+                users.set_otp(email, None).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    Err(AppError::unauthorized("Invalid or expired OTP"))
+}
+
+/// Sets a new password for the doctor or pharmacy login once the emailed
+/// OTP checks out.
+pub async fn reset_password(
+    users: &dyn UserRepository,
     email: &str,
     otp: &str,
-) -> Result<(), String> {
-    let user = user_collection
-        .find_one(doc! { "email": email }, None)
-        .await
-        .map_err(|e| e.to_string())?;
+    new_password: &str,
+    role: &str,
+) -> Result<(), AppError> {
+    validate_otp(users, email, otp).await?;
+    let password_hash = hash(new_password, HASH_COST).map_err(AppError::internal)?;
+    Ok(users.set_password_hash(email, PasswordKind::from_role(role), &password_hash).await?)
+}
 
-    if let Some(user) = user {
-        if let (Some(stored_otp), Some(expiry)) = (user.otp, user.otp_expiry) {
-            let expiry_date = DateTime::parse_from_rfc3339(&expiry)
-                .map_err(|e| format!("Failed to parse expiry date: {}", e))?;
+/// Profile fields a hospital can edit; `None` leaves a field unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileChanges {
+    pub name: Option<String>,
+    pub mobile: Option<String>,
+    pub address: Option<String>,
+    pub hospital: Option<String>,
+    pub registration_number: Option<String>,
+    pub gstin: Option<String>,
+    pub state_code: Option<String>,
+    pub invoice_prefix: Option<String>,
+    pub upi_id: Option<String>,
+    pub logo_path: Option<String>,
+    pub letterhead: Option<String>,
+}
 
-            if Utc::now() < expiry_date.with_timezone(&Utc) && stored_otp == otp {
-                // Clear OTP after successful validation
-                user_collection
-                    .update_one(
-                        doc! { "email": email },
-                        doc! { "$unset": { "otp": "", "otp_expiry": "" } },
-                        None,
-                    )
-                    .await
-                    .map_err(|e| e.to_string())?;
+pub async fn update_profile(
+    users: &dyn UserRepository,
+    hospital_id: &str,
+    changes: ProfileChanges,
+) -> Result<(), AppError> {
+    let mut user = find_hospital(users, hospital_id).await?;
 
-                return Ok(());
-            }
+    let invoice_prefix = changes.invoice_prefix.as_deref().map(clean_invoice_prefix).transpose()?;
+
+    if let Some(path) = changes.logo_path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        JpegImage::load(std::path::Path::new(path)).map_err(|e| AppError::invalid("logo_path", &e))?;
+    }
+
+    let trim = |value: Option<String>| value.map(|v| v.trim().to_string());
+    let mut changed = false;
+    let mut set = |field: &mut String, value: Option<String>| {
+        if let Some(value) = trim(value) {
+            *field = value;
+            changed = true;
+        }
+    };
+    set(&mut user.name, changes.name);
+    set(&mut user.mobile, changes.mobile);
+    set(&mut user.address, changes.address);
+    set(&mut user.hospital, changes.hospital);
+
+    for (field, value) in [
+        (&mut user.registration_number, changes.registration_number),
+        (&mut user.gstin, changes.gstin.map(|g| g.trim().to_uppercase())),
+        (&mut user.state_code, changes.state_code),
+        (&mut user.invoice_prefix, invoice_prefix),
+        (&mut user.upi_id, changes.upi_id),
+        (&mut user.logo_path, changes.logo_path),
+        (&mut user.letterhead, changes.letterhead),
+    ] {
+        if let Some(value) = trim(value) {
+            *field = Some(value);
+            changed = true;
         }
     }
 
-    Err("Invalid or expired OTP".to_string())
+    if !changed {
+        return Err(AppError::validation("No fields to update."));
+    }
+    Ok(users.replace_user(&user).await?)
+}
+
+/// Loads a hospital's profile by the `userId` handed out at login.
+pub async fn find_hospital(users: &dyn UserRepository, hospital_id: &str) -> Result<User, AppError> {
+    let object_id = ObjectId::parse_str(hospital_id).map_err(|_| AppError::invalid("hospital_id", "Invalid hospital ID"))?;
+    users
+        .find_user_by_id(object_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Hospital not found"))
+}
+
+/// Checks an admin override. The doctor login is the admin of a hospital
+/// account; the pharmacy login is the counter staff.
+pub fn verify_admin_password(hospital: &User, password: Option<&str>) -> Result<(), AppError> {
+    let password = password
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| AppError::forbidden("Admin password is required."))?;
+    if verify(password, &hospital.password_hash_doc).unwrap_or(false) {
+        Ok(())
+    } else {
+        Err(AppError::forbidden("Invalid admin password"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryStore;
+
+    async fn store_with_account() -> MemoryStore {
+        let store = MemoryStore::default();
+        signup_user(&store, "city", "Dr Rao", "9800000000", "City Clinic", "MG Road", "doc-pass", "pharma-pass", "rao@example.com")
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn signup_rejects_duplicates_and_login_checks_the_role() {
+        let store = store_with_account().await;
+        let taken = signup_user(&store, "city", "X", "1", "H", "A", "p", "p", "other@example.com").await;
+        assert_eq!(taken.unwrap_err(), AppError::conflict("Username is already taken"));
+        let registered = signup_user(&store, "other", "X", "1", "H", "A", "p", "p", "rao@example.com").await;
+        assert_eq!(registered.unwrap_err(), AppError::conflict("Email is already registered"));
+
+        assert!(login_user(&store, "city", "doc-pass", "Doctor").await.unwrap().contains("City Clinic"));
+        assert!(login_user(&store, "city", "pharma-pass", "Pharmacy").await.is_ok());
+        assert_eq!(login_user(&store, "city", "pharma-pass", "Doctor").await.unwrap_err().code(), "unauthorized");
+        assert!(login_user(&store, "nobody", "doc-pass", "Doctor").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn otp_is_single_use_and_expires() {
+        let store = store_with_account().await;
+        let otp = issue_otp(&store, "rao@example.com").await.unwrap();
+        assert!(validate_otp(&store, "rao@example.com", "000000x").await.is_err());
+        validate_otp(&store, "rao@example.com", &otp).await.unwrap();
+        assert!(validate_otp(&store, "rao@example.com", &otp).await.is_err());
+
+        let expired = dates::from_utc(Utc::now() - Duration::minutes(1));
+        store.set_otp("rao@example.com", Some(("123456", expired))).await.unwrap();
+        assert_eq!(
+            validate_otp(&store, "rao@example.com", "123456").await.unwrap_err(),
+            AppError::unauthorized("Invalid or expired OTP")
+        );
+    }
+
+    #[tokio::test]
+    async fn reset_changes_only_the_chosen_login() {
+        let store = store_with_account().await;
+        let otp = issue_otp(&store, "rao@example.com").await.unwrap();
+        reset_password(&store, "rao@example.com", &otp, "new-pharma", "Pharmacy").await.unwrap();
+
+        assert!(login_user(&store, "city", "new-pharma", "Pharmacy").await.is_ok());
+        assert!(login_user(&store, "city", "doc-pass", "Doctor").await.is_ok());
+        assert!(reset_password(&store, "rao@example.com", &otp, "again", "Doctor").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn profile_update_validates_the_invoice_prefix() {
+        let store = store_with_account().await;
+        let id = store.find_user_by_username("city").await.unwrap().unwrap().id.unwrap().to_hex();
+
+        assert_eq!(
+            update_profile(&store, &id, ProfileChanges::default()).await.unwrap_err(),
+            AppError::validation("No fields to update.")
+        );
+        let bad = ProfileChanges { invoice_prefix: Some("CITY/1".to_string()), ..Default::default() };
+        match update_profile(&store, &id, bad).await.unwrap_err() {
+            AppError::Validation { fields, .. } => assert_eq!(fields[0].field, "invoice_prefix"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let changes = ProfileChanges {
+            invoice_prefix: Some(" city ".to_string()),
+            gstin: Some("29abcde1234f1z5".to_string()),
+            ..Default::default()
+        };
+        update_profile(&store, &id, changes).await.unwrap();
+        let hospital = find_hospital(&store, &id).await.unwrap();
+        assert_eq!(hospital.invoice_prefix.as_deref(), Some("CITY"));
+        assert_eq!(hospital.gstin.as_deref(), Some("29ABCDE1234F1Z5"));
+    }
 }
@@ -2,6 +2,7 @@ import React, { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { TextField, Button, Box, Typography } from "@mui/material";
 import { toast } from "sonner";
+import { errorCode, errorMessage } from "../errors";
 
 interface BackendMedicine {
   _id?: { $oid: string };
@@ -28,6 +29,7 @@ const Appointment: React.FC = () => {
     mobile: "",
     disease: "",
     precautions: "",
+    doctor: "",
   });
 
   const [medicineSearch, setMedicineSearch] = useState("");
@@ -141,6 +143,7 @@ const Appointment: React.FC = () => {
         mobile: patient.mobile,
         disease: patient.disease || null,
         precautions: patient.precautions || null,
+        doctorName: patient.doctor || null,
         medicines: selectedMedicines.map(({ id, quantity }) => ({
           id,
           quantity,
@@ -149,12 +152,16 @@ const Appointment: React.FC = () => {
       });
 
       toast.success("Appointment saved successfully!");
-      setPatient({ name: "", mobile: "", disease: "", precautions: "" });
+      setPatient({ name: "", mobile: "", disease: "", precautions: "", doctor: "" });
       setSelectedMedicines([]);
       setMedicineSearch("");
       setSearchResults([]);
-    } catch (error: any) {
-      toast.error(`Failed to save appointment: ${error.message}`);
+    } catch (error) {
+      if (errorCode(error) === "validation") {
+        toast.error(errorMessage(error));
+      } else {
+        toast.error(`Failed to save appointment: ${errorMessage(error)}`);
+      }
       console.error("Error saving appointment:", error);
     }
   };
@@ -193,6 +200,15 @@ const Appointment: React.FC = () => {
           className="mb-4"
           fullWidth
         />
+        <TextField
+          label="Doctor"
+          variant="outlined"
+          name="doctor"
+          value={patient.doctor}
+          onChange={handleInputChange}
+          className="mb-4"
+          fullWidth
+        />
         <TextField
           label="Precautions"
           variant="outlined"
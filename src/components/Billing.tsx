@@ -6,6 +6,7 @@ import { invoke } from "@tauri-apps/api/core";
 import debounce from "lodash.debounce";
 import BillingSummary from "./BillingSummary";
 import { printBill } from "../hooks/printBill";
+import { errorMessage } from "../errors";
 import { Typography } from "@mui/material";
 
 interface Props {
@@ -49,7 +50,7 @@ const Billing: React.FC<Props> = ({ location }) => {  // const location = useLoc
   >([]);
   const [customerName, setCustomerName] = useState("");
   const [openDialog, setOpenDialog] = useState(false);
-  const [billingId] = useState(Math.floor(Math.random() * 100000));
+  const [billingId, setBillingId] = useState("");
 
   const hospitalName: string = localStorage.getItem("hospital") ?? "";
   const hospitalPhone: string = localStorage.getItem("phone") ?? "";
@@ -195,13 +196,19 @@ const Billing: React.FC<Props> = ({ location }) => {  // const location = useLoc
     }
 
     try {
-      for (const item of selectedMedicines) {
-        await invoke("reduce_batch", {
-          id: item.medicine.id,
-          batchNumber: item.medicine.batchNumber,
-          quantity: item.quantity,
-        });
-      }
+      // Stock is reduced and the invoice number assigned together on the backend
+      const invoice: { invoice_number: string } = await invoke("create_invoice", {
+        items: selectedMedicines.map(({ medicine, quantity }) => ({
+          id: medicine.id,
+          quantity,
+        })),
+        customerName,
+        customerMobile: null,
+        appointmentId: location?.state?.appointmentId ?? null,
+        placeOfSupply: null,
+        hospitalId: localStorage.getItem("userId"),
+      });
+      setBillingId(invoice.invoice_number);
       console.log("selected Medicines: ", selectedMedicines);
 
       toast.success("Purchase confirmed, and inventory updated!");
@@ -209,33 +216,24 @@ const Billing: React.FC<Props> = ({ location }) => {  // const location = useLoc
       // setSelectedMedicines([]); // Clear after successful update
     } catch (error) {
       console.error("Error updating inventory:", error);
-      toast.error("Failed to update inventory. Please try again.");
+      toast.error(`Failed to confirm purchase: ${errorMessage(error)}`);
     }
   };
 
   // Print the bill
-  const handlePrintBill = () => {
-    const today = new Date();
-    const billingDate = today.toLocaleDateString("en-US");
-
-    console.log("selected Medicines: ", selectedMedicines);
-
-    printBill(
-      selectedMedicines,
-      customerName,
-      billingId,
-      billingDate,
-      patientDetails?.disease || "",
-      patientDetails?.precautions || "",
-      hospitalName,
-      hospitalAddress,
-      hospitalPhone
-    );
+  const handlePrintBill = async () => {
+    try {
+      const path = await printBill(billingId);
+      toast.success(`Invoice saved to ${path}`);
+    } catch (error) {
+      console.error("Error generating invoice PDF:", error);
+      toast.error(`Failed to generate invoice: ${errorMessage(error)}`);
+      return;
+    }
     setSelectedMedicines([]);
-  setCustomerName(""); // Clear customer name
-  setPatientDetails(null); // Clear patient details including disease and precautions
-  setOpenDialog(false); // Close the dialog
-  toast.success("Bill printed successfully!");
+    setCustomerName(""); // Clear customer name
+    setPatientDetails(null); // Clear patient details including disease and precautions
+    setOpenDialog(false); // Close the dialog
   };
 
   const handleCloseDialog = () => {
@@ -15,6 +15,7 @@ import {
 } from "@mui/material";
 import { useNavigate } from "react-router-dom";
 import { toast } from "sonner";
+import { errorCode, errorMessage } from "../errors";
 import { invoke } from "@tauri-apps/api/core";
 import { useAuth } from "../context/AuthContext";
 
@@ -63,9 +64,13 @@ const LoginPage: React.FC = () => {
       toast.success("Login successful!");
       login(); // Update auth context
       navigate("/"); // Redirect to the homepage
-    } catch (error: any) {
+    } catch (error) {
       console.error("Login Error:", error);
-      toast.error("Invalid username or password.");
+      if (errorCode(error) === "unauthorized") {
+        toast.error("Invalid username or password.");
+      } else {
+        toast.error(`Login failed: ${errorMessage(error)}`);
+      }
     }
   };
 
@@ -80,9 +85,9 @@ const LoginPage: React.FC = () => {
         await invoke("forgot_password", { email });
         toast.success("OTP sent to your email.");
         setStep("otp");
-      } catch (error: any) {
+      } catch (error) {
         console.error("Error sending OTP:", error);
-        toast.error("Failed to send OTP. Please try again.");
+        toast.error(`Failed to send OTP: ${errorMessage(error)}`);
       }
     } else if (step === "otp") {
       if (!otp || !newPassword) {
@@ -95,9 +100,13 @@ const LoginPage: React.FC = () => {
         toast.success("Password reset successful! You can now log in.");
         setShowForgotPassword(false);
         setStep("email");
-      } catch (error: any) {
+      } catch (error) {
         console.error("Error resetting password:", error);
-        toast.error("Failed to reset password. Please check your details.");
+        if (errorCode(error) === "unauthorized") {
+          toast.error("Invalid or expired OTP. Please check your details.");
+        } else {
+          toast.error(`Failed to reset password: ${errorMessage(error)}`);
+        }
       }
     }
   };
@@ -9,6 +9,7 @@ import {
 import QRCode from "react-qr-code";
 import { useNavigate } from "react-router-dom";
 import { toast } from "sonner";
+import { errorCode, errorMessage } from "../errors";
 import { invoke } from "@tauri-apps/api/core";
 import Lottie from "react-lottie";
 import step1Animation from "./animations/growth.json";
@@ -72,8 +73,12 @@ const SignupPage: React.FC = () => {
        });
       setOtpSent(true);
       toast.success("OTP sent to your email!");
-    } catch (error: any) {
-      toast.error(`Failed to send OTP: ${error.message || error}`);
+    } catch (error) {
+      if (errorCode(error) === "conflict") {
+        toast.error(errorMessage(error)); // username or email already registered
+      } else {
+        toast.error(`Failed to send OTP: ${errorMessage(error)}`);
+      }
     }
   };
 
@@ -97,8 +102,12 @@ const SignupPage: React.FC = () => {
       setOtpVerified(true);
       toast.success("OTP verified successfully!");
       handleNextStep();
-    } catch (error: any) {
-      toast.error(`Invalid or expired OTP: ${error.message || error}`);
+    } catch (error) {
+      if (errorCode(error) === "unauthorized") {
+        toast.error("Invalid or expired OTP. Please check it or request a new one.");
+      } else {
+        toast.error(`Could not verify OTP: ${errorMessage(error)}`);
+      }
     }
   };
 
@@ -145,9 +154,13 @@ const SignupPage: React.FC = () => {
       });
       toast.success("Account created successfully!");
       setStep(4);
-    } catch (error: any) {
+    } catch (error) {
       console.error("Signup error:", error);
-      toast.error(`Signup failed: ${error.message || error}`);
+      if (errorCode(error) === "conflict") {
+        toast.error(errorMessage(error));
+      } else {
+        toast.error(`Signup failed: ${errorMessage(error)}`);
+      }
     }
   };
   
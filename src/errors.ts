@@ -0,0 +1,33 @@
+// Errors from Tauri commands. Newer commands reject with a structured
+// AppError (see src-tauri/src/error.rs); older ones still reject with a
+// plain string message.
+export type ErrorCode =
+  | "not_found"
+  | "validation"
+  | "conflict"
+  | "unauthorized"
+  | "forbidden"
+  | "insufficient_stock"
+  | "database"
+  | "email"
+  | "internal";
+
+export type AppError = {
+  code: ErrorCode;
+  message: string;
+  fields?: { field: string; message: string }[];
+};
+
+const isAppError = (error: unknown): error is AppError =>
+  typeof error === "object" && error !== null && "code" in error && "message" in error;
+
+// The message to show, whatever shape the command rejected with.
+export const errorMessage = (error: unknown): string => {
+  if (isAppError(error)) return error.message;
+  if (error instanceof Error) return error.message;
+  return String(error);
+};
+
+// The AppError code, or undefined for plain string errors.
+export const errorCode = (error: unknown): ErrorCode | undefined =>
+  isAppError(error) ? error.code : undefined;